@@ -0,0 +1,67 @@
+use {
+    crate::{db::Db, health::record_db_outcome, tx_tracker::SentTransaction},
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+};
+
+// Actions are only logged when a DB is configured, otherwise they would pile up
+static ACTION_LOG_RECORDING: AtomicBool = AtomicBool::new(false);
+
+// Completed keeper transactions waiting to be written to the DB (flushed by the core loop)
+static PENDING_ACTION_LOG_ENTRIES: LazyLock<Mutex<Vec<ActionLogEntry>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
+// Outcome of a keeper transaction, searchable with the history subcommand (support cases)
+#[derive(Debug, Clone)]
+pub struct ActionLogEntry {
+    pub signature: String,
+    pub action: String,
+    // The main account targeted by the action (Staking, UserStaking or Pool account)
+    pub account: String,
+    // Wallet owning the UserStaking account, for the actions done on behalf of a user
+    pub owner: Option<String>,
+    pub staking: String,
+    // confirmed, failed or dropped
+    pub outcome: String,
+    pub sent_at: i64,
+    pub completed_at: i64,
+}
+
+pub fn enable_action_log_recording() {
+    ACTION_LOG_RECORDING.store(true, Ordering::Relaxed);
+}
+
+pub fn record_action_outcome(sent_transaction: &SentTransaction, outcome: &'static str) {
+    if ACTION_LOG_RECORDING.load(Ordering::Relaxed) {
+        PENDING_ACTION_LOG_ENTRIES
+            .lock()
+            .unwrap()
+            .push(ActionLogEntry {
+                signature: sent_transaction.signature.to_string(),
+                action: sent_transaction.action.as_str().to_owned(),
+                account: sent_transaction.account.to_string(),
+                owner: sent_transaction.owner.map(|owner| owner.to_string()),
+                staking: sent_transaction.staking.to_owned(),
+                outcome: outcome.to_owned(),
+                sent_at: sent_transaction.sent_at,
+                completed_at: chrono::Utc::now().timestamp(),
+            });
+    }
+}
+
+// Write the logged actions to the DB - kept for the next flush if the write fails
+pub async fn flush_action_log(db: &Db) {
+    let entries = std::mem::take(&mut *PENDING_ACTION_LOG_ENTRIES.lock().unwrap());
+    if entries.is_empty() {
+        return;
+    }
+
+    let result = db.insert_action_log_entries(&entries).await;
+    record_db_outcome(result.is_ok());
+    if let Err(e) = result {
+        log::warn!("   <> Failed to record the keeper actions: {:?}", e);
+        PENDING_ACTION_LOG_ENTRIES.lock().unwrap().extend(entries);
+    }
+}
@@ -0,0 +1,171 @@
+use {
+    crate::{
+        metrics::{ACTIONS_ABANDONED, PREFLIGHT_FAILURES},
+        preflight::{PreflightFailure, PreflightFailureKind},
+        quarantine::record_account_failure,
+        tx_tracker::KeeperAction,
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::collections::HashMap,
+};
+
+// Backoff between failed attempts of a given action: 5s, 10s, 20s... capped at 10 min
+const RETRY_BACKOFF_BASE_SECONDS: i64 = 5;
+const RETRY_BACKOFF_MAX_SECONDS: i64 = 600;
+// Past this number of consecutive failures, the action is abandoned until its account changes onchain
+const MAX_ATTEMPTS: u32 = 5;
+
+// An action the keeper performs on a given account (locked_stake_id for the finalization of a specific locked stake)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ActionId {
+    pub action: KeeperAction,
+    pub account: Pubkey,
+    pub locked_stake_id: Option<u64>,
+}
+
+impl ActionId {
+    pub fn new(action: KeeperAction, account: Pubkey) -> Self {
+        Self {
+            action,
+            account,
+            locked_stake_id: None,
+        }
+    }
+
+    pub fn finalize_locked_stake(account: Pubkey, locked_stake_id: u64) -> Self {
+        Self {
+            action: KeeperAction::FinalizeLockedStake,
+            account,
+            locked_stake_id: Some(locked_stake_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionStatus {
+    Retrying,
+    // Terminal: no more attempts until the account is updated
+    Abandoned,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ActionAttempts {
+    pub attempts: u32,
+    pub next_attempt_time: i64,
+    pub status: ActionStatus,
+}
+
+// Failed actions are tracked here, the handlers errors don't restart the connection loop anymore
+#[derive(Debug, Default)]
+pub struct ActionRetries {
+    actions: HashMap<ActionId, ActionAttempts>,
+}
+
+impl ActionRetries {
+    // Actions currently backing off or abandoned
+    pub fn tracked_actions_count(&self) -> usize {
+        self.actions.len()
+    }
+
+    // Whether the action can be attempted now (never failed, or its backoff expired)
+    pub fn is_ready(&self, action_id: &ActionId, current_time: i64) -> bool {
+        match self.actions.get(action_id) {
+            None => true,
+            Some(attempts) => {
+                attempts.status == ActionStatus::Retrying
+                    && current_time >= attempts.next_attempt_time
+            }
+        }
+    }
+
+    // When the action can be attempted next (now if it never failed), None once abandoned
+    pub fn next_attempt_time(&self, action_id: &ActionId, current_time: i64) -> Option<i64> {
+        match self.actions.get(action_id) {
+            None => Some(current_time),
+            Some(attempts) => (attempts.status == ActionStatus::Retrying)
+                .then_some(attempts.next_attempt_time.max(current_time)),
+        }
+    }
+
+    pub fn record_success(&mut self, action_id: &ActionId) {
+        self.actions.remove(action_id);
+    }
+
+    pub fn record_failure(
+        &mut self,
+        action_id: &ActionId,
+        current_time: i64,
+        error: &anyhow::Error,
+        // Staking label of the metrics (see metrics.rs)
+        staking: &'static str,
+    ) -> ActionAttempts {
+        let attempts = self.actions.entry(*action_id).or_insert(ActionAttempts {
+            attempts: 0,
+            next_attempt_time: current_time,
+            status: ActionStatus::Retrying,
+        });
+        attempts.attempts += 1;
+        let backoff = RETRY_BACKOFF_BASE_SECONDS
+            .saturating_mul(1 << (attempts.attempts - 1).min(16))
+            .min(RETRY_BACKOFF_MAX_SECONDS);
+        attempts.next_attempt_time = current_time + backoff;
+
+        // A failed simulation tells whether retrying makes sense
+        let preflight_failure = error.downcast_ref::<PreflightFailure>();
+        let preflight_failure_kind =
+            preflight_failure.map(|preflight_failure| preflight_failure.kind);
+        if let Some(kind) = preflight_failure_kind {
+            PREFLIGHT_FAILURES
+                .with_label_values(&[action_id.action.as_str(), kind.as_str()])
+                .inc();
+        }
+        // Only the errors raised by the program count towards the quarantine of the account, not the keeper ones (payer
+        // out of SOL...)
+        if preflight_failure.is_some_and(|preflight_failure| preflight_failure.program_error) {
+            record_account_failure(action_id, current_time, error);
+        }
+
+        // Round resolution is critical to the protocol, it's never abandoned
+        if (attempts.attempts >= MAX_ATTEMPTS
+            || preflight_failure_kind == Some(PreflightFailureKind::Skip))
+            && action_id.action != KeeperAction::ResolveStakingRound
+        {
+            attempts.status = ActionStatus::Abandoned;
+            ACTIONS_ABANDONED
+                .with_label_values(&[action_id.action.as_str(), staking])
+                .inc();
+            log::error!(
+                "  <> {} for account {} abandoned after {} attempts: {}",
+                action_id.action.as_str(),
+                action_id.account,
+                attempts.attempts,
+                error
+            );
+        } else if preflight_failure_kind == Some(PreflightFailureKind::Alert) {
+            log::error!(
+                "  <*> {} for account {} needs attention (attempt {}, next attempt in {}s): {}",
+                action_id.action.as_str(),
+                action_id.account,
+                attempts.attempts,
+                backoff,
+                error
+            );
+        } else {
+            log::error!(
+                "  <> {} for account {} failed (attempt {}, next attempt in {}s): {}",
+                action_id.action.as_str(),
+                action_id.account,
+                attempts.attempts,
+                backoff,
+                error
+            );
+        }
+        *attempts
+    }
+
+    // The account changed onchain, its failed actions get a fresh start
+    pub fn reset_account(&mut self, account: &Pubkey) {
+        self.actions
+            .retain(|action_id, _| action_id.account != *account);
+    }
+}
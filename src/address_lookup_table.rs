@@ -0,0 +1,326 @@
+use {
+    crate::{
+        rate_limiter::rpc_call,
+        token_accounts::{init_mint_token_programs, reward_token_mint, token_program_id},
+    },
+    adrena_abi::{
+        get_governing_token_holding_pda, get_staking_pda, get_transfer_authority_pda,
+        ADRENA_GOVERNANCE_REALM_CONFIG_ID, ADRENA_GOVERNANCE_REALM_ID,
+        ADRENA_GOVERNANCE_SHADOW_TOKEN_MINT, ADX_MINT, ALP_MINT, CORTEX_ID, GENESIS_LOCK_ID,
+        GOVERNANCE_PROGRAM_ID, MAIN_POOL_ID,
+    },
+    anchor_client::solana_sdk::signer::keypair::read_keypair_file,
+    serde::{Deserialize, Serialize},
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{
+        address_lookup_table::{
+            instruction::{create_lookup_table, extend_lookup_table},
+            state::AddressLookupTable,
+        },
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_program,
+        transaction::Transaction,
+    },
+    std::str::FromStr,
+};
+
+// Addresses added per extend transaction, well within the packet size
+const EXTEND_CHUNK_SIZE: usize = 20;
+
+// Written by `alt create`, read by `alt extend` and `alt show` - only recorded for the operators (and a future switch to
+// v0 transactions), the keeper sends legacy transactions and never reads it
+#[derive(Debug, Serialize, Deserialize)]
+struct AltConfig {
+    address_lookup_table: String,
+}
+
+// The accounts every keeper instruction passes whatever the user (resolve, claim, finalize, AUM update), labeled for
+// `alt show` - the per user accounts (UserStaking account, owner token accounts...) have nothing to gain from a table
+fn keeper_static_accounts() -> Vec<(String, Pubkey)> {
+    let mut accounts: Vec<(String, Pubkey)> = vec![
+        ("adrena program".to_owned(), adrena_abi::ID),
+        ("cortex".to_owned(), CORTEX_ID),
+        ("main pool".to_owned(), MAIN_POOL_ID),
+        ("genesis lock".to_owned(), GENESIS_LOCK_ID),
+        (
+            "transfer authority".to_owned(),
+            get_transfer_authority_pda().0,
+        ),
+    ];
+    for (staking, staked_token_mint) in [("adx", ADX_MINT), ("alp", ALP_MINT)] {
+        let staking_account_key = get_staking_pda(&staked_token_mint).0;
+        accounts.extend([
+            (format!("{} staking", staking), staking_account_key),
+            (
+                format!("{} staked token vault", staking),
+                adrena_abi::pda::get_staking_staked_token_vault_pda(&staking_account_key).0,
+            ),
+            (
+                format!("{} reward token vault", staking),
+                adrena_abi::pda::get_staking_reward_token_vault_pda(&staking_account_key).0,
+            ),
+            (
+                format!("{} lm reward token vault", staking),
+                adrena_abi::pda::get_staking_lm_reward_token_vault_pda(&staking_account_key).0,
+            ),
+        ]);
+    }
+    accounts.extend([
+        ("governance program".to_owned(), GOVERNANCE_PROGRAM_ID),
+        ("governance realm".to_owned(), ADRENA_GOVERNANCE_REALM_ID),
+        (
+            "governance realm config".to_owned(),
+            ADRENA_GOVERNANCE_REALM_CONFIG_ID,
+        ),
+        (
+            "governance shadow token mint".to_owned(),
+            ADRENA_GOVERNANCE_SHADOW_TOKEN_MINT,
+        ),
+        (
+            "governance governing token holding".to_owned(),
+            get_governing_token_holding_pda(
+                &ADRENA_GOVERNANCE_REALM_ID,
+                &ADRENA_GOVERNANCE_SHADOW_TOKEN_MINT,
+            ),
+        ),
+        ("adx mint".to_owned(), ADX_MINT),
+        ("alp mint".to_owned(), ALP_MINT),
+        ("reward mint".to_owned(), reward_token_mint()),
+        ("system program".to_owned(), system_program::ID),
+        (
+            "reward token program".to_owned(),
+            token_program_id(&reward_token_mint()),
+        ),
+        ("lm token program".to_owned(), token_program_id(&ADX_MINT)),
+    ]);
+    // The reward and LM mints usually share their token program
+    let mut seen: Vec<Pubkey> = vec![];
+    accounts.retain(|(_, account)| {
+        let first = !seen.contains(account);
+        seen.push(*account);
+        first
+    });
+    accounts
+}
+
+fn read_alt_config(config_path: &str) -> anyhow::Result<Pubkey> {
+    let config = std::fs::read_to_string(config_path).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to read {} (run `alt create` first, or pass --address): {:?}",
+            config_path,
+            e
+        )
+    })?;
+    let config: AltConfig = serde_json::from_str(&config)?;
+    Ok(Pubkey::from_str(&config.address_lookup_table)?)
+}
+
+fn write_alt_config(config_path: &str, address_lookup_table: &Pubkey) -> anyhow::Result<()> {
+    let config = AltConfig {
+        address_lookup_table: address_lookup_table.to_string(),
+    };
+    std::fs::write(config_path, serde_json::to_string_pretty(&config)?)?;
+    Ok(())
+}
+
+async fn rpc_client_with_mints(endpoint: String) -> RpcClient {
+    let rpc_client = RpcClient::new_with_commitment(endpoint, CommitmentConfig::confirmed());
+    // The token programs of the mints are part of the table
+    init_mint_token_programs(&rpc_client).await;
+    rpc_client
+}
+
+async fn send_instruction(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    instruction: Instruction,
+) -> anyhow::Result<()> {
+    let recent_blockhash = rpc_call(|| rpc_client.get_latest_blockhash()).await?;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[payer],
+        recent_blockhash,
+    );
+    let signature = rpc_call(|| rpc_client.send_and_confirm_transaction(&transaction)).await?;
+    log::info!("  <> TX confirmed: {}", signature);
+    Ok(())
+}
+
+async fn fetch_table_addresses(
+    rpc_client: &RpcClient,
+    address_lookup_table: &Pubkey,
+) -> anyhow::Result<(Option<Pubkey>, u64, Vec<Pubkey>)> {
+    let data = rpc_call(|| rpc_client.get_account_data(address_lookup_table)).await?;
+    let table = AddressLookupTable::deserialize(&data).map_err(|e| {
+        anyhow::anyhow!(
+            "{} is not an address lookup table: {:?}",
+            address_lookup_table,
+            e
+        )
+    })?;
+    Ok((
+        table.meta.authority,
+        table.meta.deactivation_slot,
+        table.addresses.to_vec(),
+    ))
+}
+
+// Add the keeper accounts missing from the table, a few at a time - returns the number added
+async fn extend_with_missing_accounts(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    address_lookup_table: &Pubkey,
+    addresses: &[Pubkey],
+) -> anyhow::Result<usize> {
+    let missing: Vec<Pubkey> = keeper_static_accounts()
+        .into_iter()
+        .map(|(_, account)| account)
+        .filter(|account| !addresses.contains(account))
+        .collect();
+    for chunk in missing.chunks(EXTEND_CHUNK_SIZE) {
+        send_instruction(
+            rpc_client,
+            payer,
+            extend_lookup_table(
+                *address_lookup_table,
+                payer.pubkey(),
+                Some(payer.pubkey()),
+                chunk.to_vec(),
+            ),
+        )
+        .await?;
+    }
+    Ok(missing.len())
+}
+
+// Create the table (the payer being its authority), fill it with the keeper accounts and record its address
+pub async fn create_alt(
+    endpoint: String,
+    payer_keypair_path: &str,
+    config_path: &str,
+    force: bool,
+) -> anyhow::Result<()> {
+    // The recorded table would be lost track of (its rent only comes back by closing it)
+    if !force && std::path::Path::new(config_path).exists() {
+        return Err(anyhow::anyhow!(
+            "{} already records an address lookup table (`alt extend` completes it, --force creates a new one)",
+            config_path
+        ));
+    }
+    let payer = read_keypair_file(payer_keypair_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read the payer keypair: {:?}", e))?;
+    let rpc_client = rpc_client_with_mints(endpoint).await;
+
+    // The table address derives from a recent slot
+    let recent_slot =
+        rpc_call(|| rpc_client.get_slot_with_commitment(CommitmentConfig::finalized())).await?;
+    let (create_instruction, address_lookup_table) =
+        create_lookup_table(payer.pubkey(), payer.pubkey(), recent_slot);
+    send_instruction(&rpc_client, &payer, create_instruction).await?;
+    // Recorded right away, `alt extend` completes the table if the extends below fail
+    write_alt_config(config_path, &address_lookup_table)?;
+    log::info!(
+        "  <> Address lookup table created: {} (recorded in {})",
+        address_lookup_table,
+        config_path
+    );
+
+    let added =
+        extend_with_missing_accounts(&rpc_client, &payer, &address_lookup_table, &[]).await?;
+    log::info!(
+        "  <> {} accounts added to address lookup table {}",
+        added,
+        address_lookup_table
+    );
+    Ok(())
+}
+
+// Add the keeper accounts the table doesn't have yet (e.g. after a reward mint change)
+pub async fn extend_alt(
+    endpoint: String,
+    payer_keypair_path: &str,
+    config_path: &str,
+    address: Option<Pubkey>,
+) -> anyhow::Result<()> {
+    let address_lookup_table = match address {
+        Some(address) => address,
+        None => read_alt_config(config_path)?,
+    };
+    let payer = read_keypair_file(payer_keypair_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read the payer keypair: {:?}", e))?;
+    let rpc_client = rpc_client_with_mints(endpoint).await;
+
+    let (authority, _, addresses) =
+        fetch_table_addresses(&rpc_client, &address_lookup_table).await?;
+    if authority != Some(payer.pubkey()) {
+        return Err(anyhow::anyhow!(
+            "The payer is not the authority of address lookup table {} (authority: {:?})",
+            address_lookup_table,
+            authority
+        ));
+    }
+
+    let added =
+        extend_with_missing_accounts(&rpc_client, &payer, &address_lookup_table, &addresses)
+            .await?;
+    if address.is_some() {
+        write_alt_config(config_path, &address_lookup_table)?;
+    }
+    log::info!(
+        "  <> {} accounts added to address lookup table {}",
+        added,
+        address_lookup_table
+    );
+    Ok(())
+}
+
+// Print the table content, with the keeper accounts it doesn't have
+pub async fn show_alt(
+    endpoint: String,
+    config_path: &str,
+    address: Option<Pubkey>,
+) -> anyhow::Result<()> {
+    let address_lookup_table = match address {
+        Some(address) => address,
+        None => read_alt_config(config_path)?,
+    };
+    let rpc_client = rpc_client_with_mints(endpoint).await;
+
+    let (authority, deactivation_slot, addresses) =
+        fetch_table_addresses(&rpc_client, &address_lookup_table).await?;
+    let keeper_accounts = keeper_static_accounts();
+
+    println!("Address lookup table {}", address_lookup_table);
+    match authority {
+        Some(authority) => println!("  authority: {}", authority),
+        None => println!("  authority: none (frozen)"),
+    }
+    if deactivation_slot != u64::MAX {
+        println!("  deactivated at slot {}", deactivation_slot);
+    }
+    println!();
+    for (index, address) in addresses.iter().enumerate() {
+        let label = keeper_accounts
+            .iter()
+            .find(|(_, account)| account == address)
+            .map_or("-", |(label, _)| label.as_str());
+        println!("{:>3}  {:<44}  {}", index, address, label);
+    }
+
+    let missing: Vec<&(String, Pubkey)> = keeper_accounts
+        .iter()
+        .filter(|(_, account)| !addresses.contains(account))
+        .collect();
+    if !missing.is_empty() {
+        println!();
+        println!("Missing keeper accounts (`alt extend` adds them):");
+        for (label, account) in missing {
+            println!("     {:<44}  {}", account, label);
+        }
+    }
+    Ok(())
+}
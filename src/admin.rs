@@ -0,0 +1,129 @@
+use {
+    crate::{
+        handlers::finalize_locked_stake,
+        priority_fees::{PriorityFeeProvider, RpcPriorityFeeProvider},
+        rate_limiter::rpc_call,
+        user_staking_layout::decode_user_staking,
+        PendingTransactionsThreadSafe, MEAN_PRIORITY_FEE_PERCENTILE_CLAIM_STAKES,
+    },
+    adrena_abi::{StakingType, ADX_MINT, ALP_MINT},
+    anchor_client::{solana_sdk::signer::keypair::read_keypair_file, Client, Cluster},
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+    std::{collections::HashMap, sync::Arc},
+    tokio::sync::RwLock,
+};
+
+// Finalize a locked stake before its end (early exit, the owner pays the early exit fee) - for support cases
+// Dry run unless `execute` is set: the locked stake is checked and the transaction is only simulated
+pub async fn finalize_locked_stake_early_exit(
+    endpoint: String,
+    payer_keypair_path: &str,
+    owner_pubkey: &Pubkey,
+    user_staking_account_key: &Pubkey,
+    locked_stake_id: u64,
+    execute: bool,
+) -> anyhow::Result<()> {
+    let payer = read_keypair_file(payer_keypair_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read the payer keypair: {:?}", e))?;
+    let client = Client::new_with_options(
+        Cluster::Custom(endpoint.clone(), endpoint),
+        Arc::new(payer),
+        CommitmentConfig::confirmed(),
+    );
+    let program = client.program(adrena_abi::ID)?;
+    let rpc_client = program.rpc();
+
+    let data = rpc_call(|| rpc_client.get_account_data(user_staking_account_key)).await?;
+    let user_staking_account = decode_user_staking(&data)?;
+    let Some(locked_stake) = user_staking_account
+        .locked_stakes
+        .iter()
+        .find(|locked_stake| locked_stake.id == locked_stake_id && locked_stake.amount != 0)
+    else {
+        return Err(anyhow::anyhow!(
+            "No active locked stake {} in UserStaking account {}",
+            locked_stake_id,
+            user_staking_account_key
+        ));
+    };
+    if locked_stake.resolved != 0 {
+        return Err(anyhow::anyhow!(
+            "Locked stake {} of UserStaking account {} is already finalized",
+            locked_stake_id,
+            user_staking_account_key
+        ));
+    }
+    let staked_token_mint = match user_staking_account.get_staking_type() {
+        StakingType::LM => ADX_MINT,
+        StakingType::LP => ALP_MINT,
+    };
+
+    log::info!(
+        "  <> Early exit of locked stake {} (amount {}, ends at {}) - UserStaking account {} (owner {})",
+        locked_stake_id,
+        locked_stake.amount,
+        locked_stake.end_time,
+        user_staking_account_key,
+        owner_pubkey
+    );
+    if !execute {
+        log::info!("  <> Dry run - pass --execute to send the transaction");
+        return Ok(());
+    }
+
+    // Cluster-wide fee on the endpoint, whatever the keeper priority fee source
+    let priority_fee = RpcPriorityFeeProvider::new(rpc_client.url())
+        .priority_fee(MEAN_PRIORITY_FEE_PERCENTILE_CLAIM_STAKES, &[])
+        .await
+        .unwrap_or_default();
+    // The handler simulates the transaction first and bails out if it fails
+    let pending_transactions: PendingTransactionsThreadSafe = Arc::new(RwLock::new(HashMap::new()));
+    finalize_locked_stake(
+        user_staking_account_key,
+        owner_pubkey,
+        &program,
+        &pending_transactions,
+        priority_fee,
+        &staked_token_mint,
+        locked_stake_id,
+        true,
+    )
+    .await
+    .map_err(|e| match e {
+        backoff::Error::Permanent(e) | backoff::Error::Transient { err: e, .. } => e,
+    })?;
+
+    for signature in pending_transactions.read().await.keys() {
+        log::info!("  <> Early exit TX sent: {}", signature);
+    }
+    Ok(())
+}
+
+// Clear the quarantine and backoff of a UserStaking account on a running keeper (admin route of its HTTP API) and have it
+// claimed right away - for support cases, once the underlying issue is fixed (e.g. the owner recreated their token account)
+pub async fn release_quarantine(
+    api_url: &str,
+    admin_token: &str,
+    user_staking_account_key: &Pubkey,
+) -> anyhow::Result<()> {
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/quarantine/{}/release",
+            api_url.trim_end_matches('/'),
+            user_staking_account_key
+        ))
+        .bearer_auth(admin_token)
+        .send()
+        .await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(anyhow::anyhow!(
+            "The keeper rejected the release ({}): {}",
+            status,
+            body
+        ));
+    }
+    log::info!("  <> {}", body);
+    Ok(())
+}
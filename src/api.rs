@@ -0,0 +1,188 @@
+use {
+    crate::{
+        health::{evaluate_health, HealthReport, HealthState},
+        metrics,
+        next_actions::{next_actions, NextActions},
+        quarantine::{quarantined_accounts, release_quarantine},
+        reward_rates::{compute_staking_reward_rates, StakingRewardRates},
+        state_manager::StateManager,
+        update_caches::{claim_jitter_seconds, reschedule_claim},
+        ActionRetriesThreadSafe, FinalizeLockedStakesCacheThreadSafe,
+        UserStakingClaimCacheThreadSafe,
+    },
+    adrena_abi::Pubkey,
+    axum::{
+        extract::{Path, Query, State},
+        http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+        routing::{get, post},
+        Json, Router,
+    },
+    serde::{Deserialize, Serialize},
+    std::str::FromStr,
+};
+
+// Keeper state read by the HTTP API handlers
+#[derive(Clone)]
+pub struct ApiState {
+    pub state_manager: StateManager,
+    pub claim_cache: UserStakingClaimCacheThreadSafe,
+    pub finalize_locked_stakes_cache: FinalizeLockedStakesCacheThreadSafe,
+    pub action_retries: ActionRetriesThreadSafe,
+    // Bearer token of the admin routes, not served without one
+    pub admin_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NextActionsQuery {
+    user_staking_account: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReleaseQuarantineResponse {
+    pub user_staking_account: String,
+    // Whether the account was quarantined (its backoff state is cleared either way)
+    pub was_quarantined: bool,
+    // Whether a claim was made due right away (the account has stakes)
+    pub claim_scheduled: bool,
+}
+
+// HTTP API exposing the keeper metrics (Prometheus text format on /metrics), the staking yearly rewards per token (JSON on /reward-rates),
+// the keeper health (JSON on /health, 503 when unhealthy), the scheduled keeper actions (JSON on /next-actions)
+// and the quarantined UserStaking accounts with their last error (JSON on /quarantine)
+// With an admin token, POST /quarantine/<user_staking_account>/release clears the quarantine of an account
+pub async fn serve(api_addr: String, api_state: ApiState) -> anyhow::Result<()> {
+    let mut app = Router::new()
+        .route("/metrics", get(|| async { metrics::encode() }))
+        .route("/reward-rates", get(get_reward_rates))
+        .route("/health", get(get_health))
+        .route("/next-actions", get(get_next_actions))
+        .route(
+            "/quarantine",
+            get(|| async { Json(quarantined_accounts()) }),
+        );
+    if api_state.admin_token.is_some() {
+        app = app.route(
+            "/quarantine/:user_staking_account/release",
+            post(post_release_quarantine),
+        );
+    }
+    let app = app.with_state(api_state);
+
+    let listener = tokio::net::TcpListener::bind(&api_addr).await?;
+    log::info!("  <> HTTP API listening on {}", api_addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn get_health() -> (StatusCode, Json<HealthReport>) {
+    let report = evaluate_health();
+    let status = match report.state {
+        HealthState::Unhealthy => StatusCode::SERVICE_UNAVAILABLE,
+        HealthState::Healthy | HealthState::Degraded => StatusCode::OK,
+    };
+    (status, Json(report))
+}
+
+async fn get_reward_rates(State(api_state): State<ApiState>) -> Json<Vec<StakingRewardRates>> {
+    Json(
+        api_state
+            .state_manager
+            .call(|state| {
+                state
+                    .staking_accounts
+                    .iter()
+                    .filter_map(|(key, staking_account)| {
+                        compute_staking_reward_rates(key, staking_account)
+                    })
+                    .collect()
+            })
+            .await
+            .unwrap_or_default(),
+    )
+}
+
+// ?user_staking_account=<pubkey> restricts the claims and finalizations to one UserStaking account
+async fn get_next_actions(
+    State(api_state): State<ApiState>,
+    Query(query): Query<NextActionsQuery>,
+) -> Result<Json<NextActions>, (StatusCode, String)> {
+    let user_staking_account = query
+        .user_staking_account
+        .as_deref()
+        .map(Pubkey::from_str)
+        .transpose()
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid UserStaking account: {}", e),
+            )
+        })?;
+
+    Ok(Json(
+        next_actions(
+            &api_state.state_manager,
+            &api_state.claim_cache,
+            &api_state.finalize_locked_stakes_cache,
+            user_staking_account.as_ref(),
+        )
+        .await,
+    ))
+}
+
+// Clear the quarantine and the backoff state of a UserStaking account, and make its claim due right away (picked by the
+// next claim check) - for support, once the underlying issue is fixed (e.g. the owner recreated their token account)
+async fn post_release_quarantine(
+    State(api_state): State<ApiState>,
+    Path(user_staking_account): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ReleaseQuarantineResponse>, (StatusCode, String)> {
+    let authorized = api_state.admin_token.as_deref().is_some_and(|admin_token| {
+        headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            == Some(admin_token)
+    });
+    if !authorized {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_owned()));
+    }
+    let user_staking_account_key = Pubkey::from_str(&user_staking_account).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid UserStaking account: {}", e),
+        )
+    })?;
+
+    let was_quarantined = release_quarantine(&user_staking_account_key);
+    api_state
+        .action_retries
+        .write()
+        .await
+        .reset_account(&user_staking_account_key);
+    // The jitter is added back by the claim check
+    let claim_time =
+        chrono::Utc::now().timestamp() - claim_jitter_seconds(&user_staking_account_key);
+    let claim_scheduled = reschedule_claim(
+        &api_state.state_manager,
+        &api_state.claim_cache,
+        &user_staking_account_key,
+        |claim_times| claim_times.due_at(claim_time),
+    )
+    .await
+    .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+    log::info!(
+        "  <> Quarantine and backoff of UserStaking account {} cleared by the admin API{}",
+        user_staking_account_key,
+        if claim_scheduled {
+            " - claiming it"
+        } else {
+            ""
+        }
+    );
+
+    Ok(Json(ReleaseQuarantineResponse {
+        user_staking_account,
+        was_quarantined,
+        claim_scheduled,
+    }))
+}
@@ -0,0 +1,86 @@
+use {
+    crate::rate_limiter::{rpc_call, throttle_rpc},
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{clock::MAX_PROCESSING_AGE, commitment_config::CommitmentConfig, hash::Hash},
+    std::{
+        sync::{LazyLock, Mutex},
+        time::Duration,
+    },
+    tokio::time::interval,
+};
+
+// ~20 slots, a blockhash stays valid for 150 blocks so the cached one always has plenty of life left
+const BLOCKHASH_REFRESH_INTERVAL: Duration = Duration::from_secs(8);
+// Past this age (refresh task failing or not started yet), the blockhash is fetched on demand
+const BLOCKHASH_MAX_AGE_SECONDS: i64 = 30;
+
+// Latest blockhash shared by every transaction build, refreshed in the background
+static LATEST_BLOCKHASH: LazyLock<Mutex<Option<CachedBlockhash>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy)]
+pub struct CachedBlockhash {
+    pub blockhash: Hash,
+    // Last block height at which a transaction using the blockhash can land
+    pub last_valid_block_height: u64,
+    pub fetched_at: i64,
+}
+
+async fn fetch_latest_blockhash(rpc_client: &RpcClient) -> anyhow::Result<CachedBlockhash> {
+    let (blockhash, last_valid_block_height) =
+        rpc_call(|| rpc_client.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed()))
+            .await?;
+
+    let cached_blockhash = CachedBlockhash {
+        blockhash,
+        last_valid_block_height,
+        fetched_at: chrono::Utc::now().timestamp(),
+    };
+    *LATEST_BLOCKHASH.lock().unwrap() = Some(cached_blockhash);
+    Ok(cached_blockhash)
+}
+
+// Periodically refresh the shared blockhash, so bursts of transaction builds don't each fetch it
+pub async fn refresh_blockhash_cache(rpc_client: RpcClient) {
+    let mut refresh_interval = interval(BLOCKHASH_REFRESH_INTERVAL);
+    loop {
+        refresh_interval.tick().await;
+
+        throttle_rpc().await;
+        if let Err(e) = fetch_latest_blockhash(&rpc_client).await {
+            log::warn!("   <> Failed to refresh the latest blockhash: {:?}", e);
+        }
+    }
+}
+
+// The cached blockhash, or a freshly fetched one if the cache is empty or stale
+pub async fn latest_blockhash(rpc_client: &RpcClient) -> anyhow::Result<CachedBlockhash> {
+    let cached_blockhash = *LATEST_BLOCKHASH.lock().unwrap();
+    match cached_blockhash {
+        Some(cached_blockhash)
+            if chrono::Utc::now().timestamp() - cached_blockhash.fetched_at
+                <= BLOCKHASH_MAX_AGE_SECONDS =>
+        {
+            Ok(cached_blockhash)
+        }
+        _ => {
+            throttle_rpc().await;
+            fetch_latest_blockhash(rpc_client).await
+        }
+    }
+}
+
+// Whether a transaction built with a blockhash valid until `last_valid_block_height` can no longer land
+// The current block height is estimated from the cached blockhash (valid for MAX_PROCESSING_AGE blocks once produced)
+// Unknown (empty cache) means not expired
+pub fn is_blockhash_expired(last_valid_block_height: u64) -> bool {
+    LATEST_BLOCKHASH
+        .lock()
+        .unwrap()
+        .is_some_and(|cached_blockhash| {
+            cached_blockhash
+                .last_valid_block_height
+                .saturating_sub(MAX_PROCESSING_AGE as u64)
+                > last_valid_block_height
+        })
+}
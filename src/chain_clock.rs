@@ -0,0 +1,37 @@
+// Without slot progress for this long, the chain (or the stream) is considered halted
+const CHAIN_HALT_THRESHOLD_SECONDS: i64 = 10;
+
+// Tracks the chain head through the slot updates of the Geyser stream
+#[derive(Debug, Default)]
+pub struct ChainClock {
+    pub slot: u64,
+    // Wall-clock time at which the slot was received
+    pub slot_received_at: i64,
+}
+
+impl ChainClock {
+    pub fn update(&mut self, slot: u64) {
+        if slot > self.slot {
+            self.slot = slot;
+            self.slot_received_at = chrono::Utc::now().timestamp();
+        }
+    }
+
+    pub fn seconds_since_last_slot(&self) -> i64 {
+        chrono::Utc::now().timestamp() - self.slot_received_at
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.slot != 0 && self.seconds_since_last_slot() > CHAIN_HALT_THRESHOLD_SECONDS
+    }
+
+    // Current time as seen by the chain: the onchain clock doesn't move while no slot is produced,
+    // so we stop at the last slot time when halted (avoids firing resolves that can only fail)
+    pub fn now(&self) -> i64 {
+        if self.is_halted() {
+            self.slot_received_at
+        } else {
+            chrono::Utc::now().timestamp()
+        }
+    }
+}
@@ -1,65 +1,219 @@
 use {
     crate::{process_stream_message::process_stream_message, update_caches::update_claim_cache},
+    action_log::{enable_action_log_recording, flush_action_log},
+    action_retries::{ActionId, ActionRetries},
     adrena_abi::{
-        Discriminator, Staking, StakingType, UserStaking, ADX_MINT, ALP_MINT,
-        ROUND_MIN_DURATION_SECONDS,
+        get_staking_pda, Cortex, Discriminator, GenesisLock, Staking, StakingType, UserStaking,
+        ADX_MINT, ALP_MINT, CORTEX_ID, GENESIS_LOCK_ID, ROUND_MIN_DURATION_SECONDS,
     },
     anchor_client::{solana_sdk::signer::keypair::read_keypair_file, Client, Cluster, Program},
-    backoff::{future::retry, ExponentialBackoff},
-    clap::Parser,
-    futures::{StreamExt, TryFutureExt},
-    handlers::ClaimStakeOutcome,
-    openssl::ssl::{SslConnector, SslMethod},
-    postgres_openssl::MakeTlsConnector,
-    priority_fees::fetch_mean_priority_fee,
-    solana_client::rpc_filter::{Memcmp, RpcFilterType},
-    solana_sdk::{pubkey::Pubkey, signature::Keypair},
-    std::{collections::HashMap, env, str::FromStr, sync::Arc, time::Duration},
+    api::ApiState,
+    backoff::{backoff::Backoff, future::retry, ExponentialBackoff, ExponentialBackoffBuilder},
+    blockhash_cache::refresh_blockhash_cache,
+    chain_clock::ChainClock,
+    clap::{Parser, Subcommand},
+    compute_units::{init_cu_limits, CuLimits},
+    dashmap::DashMap,
+    db::{Db, DbBackend},
+    events::init_event_sink,
+    fee_budget::{init_daily_fee_budget, is_daily_fee_budget_exhausted},
+    fee_escalation::{init_fee_escalation, init_overdue_round_escalation, is_round_overdue},
+    futures::{
+        channel::mpsc::SendError, stream::BoxStream, FutureExt, Sink, SinkExt, StreamExt,
+        TryFutureExt,
+    },
+    genesis_lock::{genesis_campaign_end_time, is_genesis_campaign_active},
+    handlers::{
+        prepare_finalize_locked_stake, prepare_update_pool_aum, single_batch_claim,
+        ClaimStakeOutcome, CombinedClaim,
+    },
+    health::{
+        init_health, monitor_health, record_db_outcome, record_evaluation, record_stream_message,
+    },
+    instruction_packer::{
+        clear_due_instructions, is_queued, pack_instructions, queue_due_instruction,
+        requeue_due_instructions, requeue_due_instructions_alone, send_instruction_pack,
+        take_due_instructions, take_failed_packed_actions,
+    },
+    logging::{init_logging, log_summaries, LogFile, LogRotation},
+    maintenance::{
+        are_transactions_suspended, init_maintenance_windows, update_protocol_pause,
+        MaintenanceWindow,
+    },
+    metrics::{
+        staking_label, LockHoldTimer, NO_STAKING_LABEL, STAKING_ROUND_OVERDUE_SECONDS,
+        USER_STAKING_ACCOUNTS_MISSING_STAKING_TYPE,
+    },
+    monitoring::{monitor_index_sizes, monitor_referrer_reward_vault, monitor_slot_lag},
+    owner_cache::{cached_owner, flush_learned_owners, refresh_owner_cache},
+    pending_rewards::{estimate_pending_rewards, unclaimed_resolved_rounds, PendingRewards},
+    priority_fees::{
+        fetch_reference_priority_fee, init_priority_fees, new_priority_fee_provider,
+        PriorityFeeSource,
+    },
+    program_upgrade::{init_program_deploy_slot, program_data_address, take_resync_pending},
+    pubsub_fallback::subscribe_programs,
+    quarantine::{flush_quarantine, init_quarantine, is_quarantined, load_quarantine},
+    rate_limiter::{init_rpc_rate_limiter, rpc_call},
+    reward_rates::{staking_name, update_staking_reward_rates_metrics},
+    role::{current_role, init_role, Role},
+    schedule::TaskSchedule,
+    solana_client::{
+        nonblocking::rpc_client::RpcClient,
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        native_token::sol_to_lamports,
+        pubkey::Pubkey,
+        signature::{Keypair, Signature},
+    },
+    staked_amounts::compute_staked_amounts,
+    state_manager::StateManager,
+    state_snapshot::load_state_snapshot,
+    std::{
+        cmp::Reverse,
+        collections::{HashMap, HashSet},
+        pin::Pin,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    },
+    token_accounts::{init_mint_token_programs, init_reward_token_mint},
     tokio::{
+        signal::unix::{signal, SignalKind},
         sync::{Mutex, RwLock},
         task::JoinHandle,
-        time::{interval, timeout},
+        time::{interval, interval_at, timeout, Instant},
     },
     tonic::transport::channel::ClientTlsConfig,
+    transaction_costs::{enable_transaction_costs_recording, flush_transaction_costs},
+    tx_submitters::{
+        init_tx_submitters, FanoutTxSubmitter, HeliusSenderTxSubmitter, JitoTxSubmitter,
+        RpcTxSubmitter, TxSubmitter, TxSubmitterBackend,
+    },
+    tx_tracker::{
+        confirm_pending_transactions_with_signature_subscribe, has_in_flight_capacity,
+        init_in_flight_limit, is_pending, poll_pending_transactions,
+        rebroadcast_pending_transactions, KeeperAction, SentTransaction,
+    },
     update_caches::{
-        update_finalize_locked_stakes_cache, update_staking_round_next_resolve_time_cache,
+        claim_due_time, claim_jitter_seconds, init_claim_jitter_window, init_claim_thresholds,
+        pop_due_claims, requeue_failed_claim, reschedule_claim, schedule_claim, schedule_claims,
+        schedule_forced_claims, update_finalize_locked_stakes_cache,
+        update_staking_round_next_resolve_time_cache, ClaimTimes,
+    },
+    update_indexes::{
+        check_account_layouts, deserialize_user_staking_sliced, fetch_staking_accounts,
+        fetch_user_staking_accounts_data_sliced,
     },
+    user_staking_index::UserStakingIndex,
+    warm_start::{
+        flush_user_staking_accounts, init_warm_start, is_warm_start_enabled,
+        save_stream_checkpoint, store_scanned_user_staking_accounts, warm_start_indexes,
+    },
+    watchdog::{notify_ready, run_systemd_watchdog},
+    webhooks::{init_claim_webhook, init_round_webhook},
     yellowstone_grpc_client::{GeyserGrpcClient, Interceptor},
     yellowstone_grpc_proto::{
         geyser::{
             SubscribeRequest, SubscribeRequestFilterAccountsFilter,
-            SubscribeRequestFilterAccountsFilterMemcmp,
+            SubscribeRequestFilterAccountsFilterMemcmp, SubscribeUpdate,
         },
         prelude::{
             subscribe_request_filter_accounts_filter::Filter as AccountsFilterDataOneof,
             subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof,
-            CommitmentLevel, SubscribeRequestFilterAccounts,
+            CommitmentLevel, SubscribeRequestFilterAccounts, SubscribeRequestFilterSlots,
+            SubscribeRequestFilterTransactions,
         },
     },
 };
 use solana_sdk::instruction::AccountMeta;
-use crate::handlers::update_pool_aum;
 use adrena_abi::Custody;
 use adrena_abi::Pool;
 
 type AccountFilterMap = HashMap<String, SubscribeRequestFilterAccounts>;
+type TransactionFilterMap = HashMap<String, SubscribeRequestFilterTransactions>;
+type SlotFilterMap = HashMap<String, SubscribeRequestFilterSlots>;
+// Either the Geyser subscription or the WebSocket PubSub fallback
+type SubscribeTx = Pin<Box<dyn Sink<SubscribeRequest, Error = SendError> + Send>>;
+type UpdateStream = BoxStream<'static, Result<SubscribeUpdate, tonic::Status>>;
 
-type IndexedStakingAccountsThreadSafe = Arc<RwLock<HashMap<Pubkey, Staking>>>;
-type IndexedUserStakingAccountsThreadSafe = Arc<RwLock<HashMap<Pubkey, UserStaking>>>;
-type IndexedCustodiesThreadSafe = Arc<RwLock<HashMap<Pubkey, Custody>>>;
-// Cache the claim time of the oldest locked stake for each user staking account - This is used to determine when we should trigger the next auto claim
-// If none, no auto claim is needed
-type UserStakingClaimCacheThreadSafe = Arc<RwLock<HashMap<Pubkey, Option<i64>>>>;
-// Cache the time of next execution for the resolve staking round task, keyed by Staking account pda
-type StakingRoundNextResolveTimeCacheThreadSafe = Arc<RwLock<HashMap<Pubkey, i64>>>;
+// The UserStaking accounts and the caches derived from them are sharded maps (tens of thousands of accounts), the stream
+// updates and the cache rebuilds don't serialize behind a single lock - the UserStaking accounts are split by staking type on top
+type IndexedUserStakingAccountsThreadSafe = Arc<UserStakingIndex>;
+// The GenesisLock account, used to know whether the genesis campaign locked stakes can be claimed
+type IndexedGenesisLockThreadSafe = Arc<RwLock<Option<GenesisLock>>>;
+// Cache the claim times of the oldest locked stake and of the liquid stake for each user staking account - This is used to determine when we should trigger the next auto claim
+// Accounts without stakes are not in the cache, no auto claim is needed
+type UserStakingClaimCacheThreadSafe = Arc<DashMap<Pubkey, ClaimTimes>>;
+// Failed keeper actions (resolve, claim, finalize, update aum) - Used to back off between attempts and give up on the hopeless ones
+type ActionRetriesThreadSafe = Arc<RwLock<ActionRetries>>;
 // Cache the list of UserStaking accounts and their stake ids/ time at which it may be finalized
-type FinalizeLockedStakesCacheThreadSafe = Arc<RwLock<HashMap<Pubkey, HashMap<u64, i64>>>>;
+type FinalizeLockedStakesCacheThreadSafe = Arc<DashMap<Pubkey, HashMap<u64, i64>>>;
+// Transactions sent by the keeper and waiting for confirmation, keyed by signature
+type PendingTransactionsThreadSafe = Arc<RwLock<HashMap<Signature, SentTransaction>>>;
+// Slot of the last update received from the stream
+type LastStreamSlotThreadSafe = Arc<AtomicU64>;
+// Chain head tracked through the stream slot updates
+type ChainClockThreadSafe = Arc<RwLock<ChainClock>>;
 
+pub mod action_log;
+pub mod action_retries;
+pub mod address_lookup_table;
+pub mod admin;
+pub mod api;
+pub mod blockhash_cache;
+pub mod chain_clock;
+pub mod compute_units;
+pub mod cost_report;
+pub mod db;
+pub mod events;
+pub mod fee_budget;
+pub mod fee_escalation;
+pub mod genesis_lock;
 pub mod handlers;
+pub mod health;
+pub mod history;
+pub mod inspect;
+pub mod instruction_packer;
+pub mod logging;
+pub mod maintenance;
+pub mod metrics;
+pub mod monitoring;
+pub mod next_actions;
+pub mod owner_cache;
+pub mod pending_rewards;
+pub mod preflight;
 pub mod priority_fees;
 pub mod process_stream_message;
+pub mod program_upgrade;
+pub mod pubsub_fallback;
+pub mod pushgateway;
+pub mod quarantine;
+pub mod rate_limiter;
+pub mod reward_rates;
+pub mod role;
+pub mod schedule;
+pub mod stake_transactions;
+pub mod staked_amounts;
+pub mod state_manager;
+pub mod state_snapshot;
+pub mod statsd;
+pub mod token_accounts;
+pub mod transaction_costs;
+pub mod tx_submitters;
+pub mod tx_tracker;
 pub mod update_caches;
 pub mod update_indexes;
+pub mod user_staking_index;
+pub mod user_staking_layout;
+pub mod warm_start;
+pub mod watchdog;
+pub mod webhooks;
 
 const DEFAULT_ENDPOINT: &str = "http://127.0.0.1:10000";
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
@@ -67,12 +221,84 @@ const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 const MEAN_PRIORITY_FEE_PERCENTILE_RESOLVE_STAKING_ROUND: u64 = 3500; // 35th
 const MEAN_PRIORITY_FEE_PERCENTILE_CLAIM_STAKES: u64 = 3500; // 35th
 const PRIORITY_FEE_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
-pub const RESOLVE_STAKING_ROUND_CU_LIMIT: u32 = 400_000;
-pub const UPDATE_AUM_CU_LIMIT: u32 = 100_000;
+// µLamports / cu - 0.001 SOL of priority fee for a 1M CU claim
+const DEFAULT_MAX_PRIORITY_FEE: u64 = 1_000_000;
+const DEFAULT_FEE_ESCALATION_MULTIPLIER: f64 = 1.5;
+// A round still unresolved 15 minutes after its end is alerted, and its resolves bid twice the fetched price
+const DEFAULT_OVERDUE_ROUND_ALERT_MINUTES: i64 = 15;
+const DEFAULT_OVERDUE_ROUND_FEE_MULTIPLIER: f64 = 2.0;
+const DEFAULT_RPC_QPS: u32 = 40;
+const DEFAULT_RPC_BURST: u32 = 10;
+const DEFAULT_GEYSER_FALLBACK_AFTER_SECONDS: i64 = 120;
+// Well within the slots usually kept by the Geyser endpoints for the replays (~150 per minute)
+const DEFAULT_MAX_RESUME_DOWNTIME_SECONDS: i64 = 60;
+// Period of the sends of the queued finalizations and AUM updates (see instruction_packer.rs)
+const INSTRUCTION_PACKS_INTERVAL: Duration = Duration::from_secs(2);
+// Period of the stream checkpoint saves, the resumed stream replays up to that many seconds twice
+const STREAM_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_IN_FLIGHT_TRANSACTIONS: usize = 50;
+const DEFAULT_REBROADCAST_INTERVAL_SECONDS: u64 = 5;
+const DEFAULT_REBROADCAST_QPS: u32 = 10;
+// Stream messages processed in a row before the periodic tasks get a chance to run (bursts)
+const MAX_STREAM_MESSAGES_PER_BATCH: usize = 500;
+// Reconnect backoff, same as the backoff crate defaults
+const DEFAULT_RECONNECT_INITIAL_INTERVAL_MS: u64 = 500;
+const DEFAULT_RECONNECT_MULTIPLIER: f64 = 1.5;
+const DEFAULT_RECONNECT_MAX_INTERVAL_SECONDS: u64 = 60;
+const DEFAULT_RECONNECT_MAX_ELAPSED_SECONDS: u64 = 15 * 60;
+// While in degraded mode (WebSocket PubSub), periodically try to get back to Geyser
+const GEYSER_RETRY_INTERVAL: Duration = Duration::from_secs(300);
+// Program accounts size (discriminator + data), used to let the Geyser server discard the other accounts cheaply
+const STAKING_ACCOUNT_SIZE: u64 = (8 + std::mem::size_of::<Staking>()) as u64;
+const USER_STAKING_ACCOUNT_SIZE: u64 = (8 + std::mem::size_of::<UserStaking>()) as u64;
+// ~1 min
+const DEFAULT_MAX_SLOT_LAG: u64 = 150;
+// Starting CU limits, refined from the CU consumed by the landed transactions (see compute_units.rs)
+const DEFAULT_RESOLVE_STAKING_ROUND_CU_LIMIT: u32 = 400_000;
+const DEFAULT_UPDATE_POOL_AUM_CU_LIMIT: u32 = 100_000;
+// Max CU for a claim transaction - heavy accounts are split across several transactions to stay below
+const DEFAULT_CLAIM_STAKES_CU_LIMIT: u32 = 1_000_000;
+// Limit of the finalization simulations, the sent transactions request the simulated CU
+const DEFAULT_FINALIZE_LOCKED_STAKE_CU_LIMIT: u32 = 1_000_000;
+// Heap requested by the claims of heavy accounts (max allowed, the default heap is 32KB)
+const DEFAULT_CLAIM_HEAP_FRAME_BYTES: u32 = 256 * 1024;
+// Spread the auto claims over 6 hours past the threshold
+const DEFAULT_CLAIM_JITTER_WINDOW_SECONDS: i64 = 6 * 3_600;
+// Reached before the attempts after which an action is abandoned until its account changes (see action_retries.rs)
+const DEFAULT_QUARANTINE_AFTER_FAILURES: u32 = 3;
+// A week, long enough for the support to look into it
+const DEFAULT_QUARANTINE_EXPIRY_HOURS: i64 = 7 * 24;
+// Actions printed by the history subcommand unless --limit is set
+const DEFAULT_HISTORY_LIMIT: i64 = 100;
+// Address lookup table recorded by the alt subcommands unless --alt-config is set
+const DEFAULT_ALT_CONFIG_PATH: &str = "address_lookup_table.json";
+// Reload the owner mapping every 10 minutes, the new accounts in between are looked up one by one
+const DEFAULT_OWNER_CACHE_REFRESH_SECONDS: u64 = 600;
+const DEFAULT_JITO_BLOCK_ENGINE_URL: &str = "https://mainnet.block-engine.jito.wtf";
+// Above the 1,000 lamports minimum accepted by the block engine
+const DEFAULT_JITO_TIP_LAMPORTS: u64 = 10_000;
+const DEFAULT_HELIUS_SENDER_URL: &str = "https://sender.helius-rpc.com/fast";
+// Minimum tip required by Helius Sender (0.0002 SOL)
+const DEFAULT_HELIUS_SENDER_TIP_LAMPORTS: u64 = 200_000;
+const DEFAULT_LOG_MAX_SIZE_MB: usize = 100;
+// A week of daily log files
+const DEFAULT_LOG_RETENTION: usize = 7;
+const DEFAULT_LOG_DEDUP_WINDOW_SECONDS: u64 = 60;
+const DEFAULT_PUSHGATEWAY_JOB: &str = "mrsablier";
+const DEFAULT_PUSHGATEWAY_INTERVAL_SECONDS: u64 = 15;
+const DEFAULT_STATSD_INTERVAL_SECONDS: u64 = 10;
+const DEFAULT_KAFKA_TOPIC: &str = "mrsablier-events";
+const DEFAULT_NATS_SUBJECT: &str = "mrsablier.events";
+
+// Once a resolve is sent, wait this long for the Staking account update to come back over the stream before resending
+pub const RESOLVE_STAKING_ROUND_GRACE_PERIOD_SECONDS: i64 = 60;
 
-// The threshold to trigger a claim of the stakes for a UserStaking account - we can store up to 32 rounds data per account, we do so to avoid loosing rewards
+// The default threshold to trigger a claim of the stakes for a UserStaking account (locked and liquid) - we can store up to 32 rounds data per account, we do so to avoid loosing rewards
 pub const AUTO_CLAIM_THRESHOLD_SECONDS: i64 = ROUND_MIN_DURATION_SECONDS * 20; // this means that we will claim ~5 days if the user has not claim during that time
 
+// Claim regardless of the elapsed time once that many resolved rounds are unclaimed - leaves a few rounds of margin for the claim to land
+const FORCE_CLAIM_UNCLAIMED_ROUNDS: usize = 28;
+
 #[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
 enum ArgsCommitment {
     #[default]
@@ -81,6 +307,16 @@ enum ArgsCommitment {
     Finalized,
 }
 
+impl From<ArgsCommitment> for CommitmentConfig {
+    fn from(commitment: ArgsCommitment) -> Self {
+        match commitment {
+            ArgsCommitment::Processed => CommitmentConfig::processed(),
+            ArgsCommitment::Confirmed => CommitmentConfig::confirmed(),
+            ArgsCommitment::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+}
+
 impl From<ArgsCommitment> for CommitmentLevel {
     fn from(commitment: ArgsCommitment) -> Self {
         match commitment {
@@ -91,9 +327,109 @@ impl From<ArgsCommitment> for CommitmentLevel {
     }
 }
 
+#[derive(Debug, Clone, Subcommand)]
+enum Command {
+    /// Create, extend or print the address lookup table of the static Adrena accounts used by the keeper, then exit
+    Alt {
+        #[clap(subcommand)]
+        command: AltCommand,
+    },
+    /// Dump the indexed Staking/UserStaking accounts and the caches to a JSON file, then exit
+    ExportState {
+        /// Path of the JSON file to write
+        #[clap(long)]
+        output: String,
+    },
+    /// Finalize a locked stake before its end (early exit) for a support case, then exit - dry run without --execute
+    FinalizeEarlyExit {
+        /// Wallet owning the UserStaking account
+        #[clap(long)]
+        owner: Pubkey,
+        /// UserStaking account holding the locked stake
+        #[clap(long)]
+        user_staking: Pubkey,
+        /// Id of the locked stake to finalize
+        #[clap(long)]
+        locked_stake_id: u64,
+        /// Send the transaction (otherwise the locked stake is only checked)
+        #[clap(long)]
+        execute: bool,
+    },
+    /// Print the keeper actions logged in the DB (signature, outcome) matching the filters, most recent first, then exit
+    History {
+        /// Wallet, or account targeted by the action (UserStaking, Staking or Pool account)
+        #[clap(long)]
+        user: Option<Pubkey>,
+        /// Staking side: adx (LM) or alp (LP)
+        #[clap(long, value_parser = ["adx", "alp"])]
+        staking: Option<String>,
+        /// Action: resolve_staking_round, claim_stakes, finalize_locked_stake or update_pool_aum
+        #[clap(long, value_parser = ["resolve_staking_round", "claim_stakes", "finalize_locked_stake", "update_pool_aum"])]
+        action: Option<String>,
+        /// First day (UTC), YYYY-MM-DD
+        #[clap(long)]
+        since: Option<chrono::NaiveDate>,
+        /// Last day (UTC, included), YYYY-MM-DD
+        #[clap(long)]
+        until: Option<chrono::NaiveDate>,
+        /// Max number of actions printed
+        #[clap(long, default_value_t = DEFAULT_HISTORY_LIMIT)]
+        limit: i64,
+    },
+    /// Print the stakes of a wallet (locked/liquid, unclaimed resolved rounds, estimated claimable rewards), then exit
+    Inspect {
+        /// Wallet owning the UserStaking accounts
+        #[clap(long)]
+        owner: Pubkey,
+    },
+    /// Clear the quarantine and backoff of a UserStaking account on a running keeper and claim it right away, then exit
+    ReleaseQuarantine {
+        /// UserStaking account to release
+        #[clap(long)]
+        user_staking: Pubkey,
+        /// Base URL of the keeper HTTP API (--api-addr of the running keeper), e.g. http://127.0.0.1:9100
+        #[clap(long)]
+        api_url: String,
+    },
+    /// Print the fees paid by the keeper transactions recorded in the DB, by day and action, then exit
+    ReportCosts {
+        /// First day (UTC) of the report, YYYY-MM-DD
+        #[clap(long)]
+        since: chrono::NaiveDate,
+        /// Path of a CSV file to also export the report to
+        #[clap(long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum AltCommand {
+    /// Create the table (the payer being its authority) with the keeper accounts, and record its address in --alt-config
+    Create {
+        /// Create a new table even though --alt-config already records one (overwritten)
+        #[clap(long)]
+        force: bool,
+    },
+    /// Add the keeper accounts missing from the table (e.g. after a reward mint change)
+    Extend {
+        /// Table to extend (then recorded in --alt-config), the one recorded in --alt-config by default
+        #[clap(long)]
+        address: Option<Pubkey>,
+    },
+    /// Print the addresses of the table and the keeper accounts missing from it
+    Show {
+        /// Table to print, the one recorded in --alt-config by default
+        #[clap(long)]
+        address: Option<Pubkey>,
+    },
+}
+
 #[derive(Debug, Clone, Parser)]
 #[clap(author, version, about)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     #[clap(short, long, default_value_t = String::from(DEFAULT_ENDPOINT))]
     /// Service endpoint
     endpoint: String,
@@ -105,13 +441,305 @@ struct Args {
     #[clap(long)]
     commitment: Option<ArgsCommitment>,
 
-    /// Path to the payer keypair
+    /// Path to the payer keypair - read again on SIGHUP, to rotate the keypair without restarting
     #[clap(long)]
     payer_keypair: String,
 
-    /// DB Url
+    /// DB Url (Postgres) or file path (SQLite) - required unless the role is resolves
+    #[clap(long)]
+    db_string: Option<String>,
+
+    /// Workload of this instance: claims (claims and finalizations, needs the DB), resolves (round resolution and AUM updates) or all
+    #[clap(long, value_enum, default_value_t = Role::All)]
+    role: Role,
+
+    /// DB backend holding the UserStaking accounts owners: postgres or sqlite
+    #[clap(long, value_enum, default_value_t = DbBackend::Postgres)]
+    db_backend: DbBackend,
+
+    /// Optional Redis Url, used as a cache in front of the DB owner lookups
+    #[clap(long)]
+    redis_url: Option<String>,
+
+    /// Period (seconds) at which the in-memory UserStaking account -> owner mapping is reloaded from the DB
+    #[clap(long, default_value_t = DEFAULT_OWNER_CACHE_REFRESH_SECONDS)]
+    owner_cache_refresh_seconds: u64,
+
+    /// Max number of RPC calls per second (0 to disable the rate limiter)
+    #[clap(long, default_value_t = DEFAULT_RPC_QPS)]
+    rpc_qps: u32,
+
+    /// Number of RPC calls that can be issued in a burst before being throttled
+    #[clap(long, default_value_t = DEFAULT_RPC_BURST)]
+    rpc_burst: u32,
+
+    /// Address to serve the HTTP API (/metrics, /reward-rates, /health, /next-actions, /quarantine) on, e.g. 0.0.0.0:9100 - disabled if not set
+    #[clap(long)]
+    api_addr: Option<String>,
+
+    /// Bearer token of the HTTP API admin routes (POST /quarantine/<account>/release) - not served if not set
+    #[clap(long)]
+    api_admin_token: Option<String>,
+
+    /// JSON file recording the address lookup table of the keeper accounts, only read by the alt subcommands (not used by the keeper yet)
+    #[clap(long, default_value_t = String::from(DEFAULT_ALT_CONFIG_PATH))]
+    alt_config: String,
+
+    /// File the logs are also written to (besides stderr), rotated and pruned - disabled if not set
+    #[clap(long)]
+    log_file: Option<String>,
+
+    /// Log file rotation: size (--log-max-size-mb), hourly or daily
+    #[clap(long, value_enum, default_value_t = LogRotation::Daily)]
+    log_rotation: LogRotation,
+
+    /// Size (MB) of the log file triggering a rotation with --log-rotation size
+    #[clap(long, default_value_t = DEFAULT_LOG_MAX_SIZE_MB)]
+    log_max_size_mb: usize,
+
+    /// Number of rotated log files kept, the oldest are deleted
+    #[clap(long, default_value_t = DEFAULT_LOG_RETENTION)]
+    log_retention: usize,
+
+    /// Window (seconds) within which the identical log lines are collapsed, and period of the stream updates summaries (0 to disable both)
+    #[clap(long, default_value_t = DEFAULT_LOG_DEDUP_WINDOW_SECONDS)]
+    log_dedup_window_seconds: u64,
+
+    /// Prometheus Pushgateway Url the metrics are pushed to (when they can't be scraped), e.g. http://pushgateway:9091 - disabled if not set
+    #[clap(long)]
+    pushgateway_url: Option<String>,
+
+    /// Job label of the metrics pushed to the Pushgateway
+    #[clap(long, default_value = DEFAULT_PUSHGATEWAY_JOB)]
+    pushgateway_job: String,
+
+    /// Instance label of the metrics pushed to the Pushgateway (set it when several keepers push to the same one)
+    #[clap(long)]
+    pushgateway_instance: Option<String>,
+
+    /// Interval (seconds) at which the metrics are pushed to the Pushgateway
+    #[clap(long, default_value_t = DEFAULT_PUSHGATEWAY_INTERVAL_SECONDS)]
+    pushgateway_interval_seconds: u64,
+
+    /// StatsD / DogStatsD agent address the metrics are also emitted to, e.g. 127.0.0.1:8125 - disabled if not set
+    #[clap(long)]
+    statsd_addr: Option<String>,
+
+    /// Interval (seconds) at which the metrics are flushed to the StatsD agent
+    #[clap(long, default_value_t = DEFAULT_STATSD_INTERVAL_SECONDS)]
+    statsd_interval_seconds: u64,
+
+    /// Number of slots the Geyser stream can fall behind the RPC chain head before alerting
+    #[clap(long, default_value_t = DEFAULT_MAX_SLOT_LAG)]
+    max_slot_lag: u64,
+
+    /// Priority fee source: rpc (getRecentPrioritizationFees), helius (getPriorityFeeEstimate) or triton (getRecentPrioritizationFees percentiles)
+    #[clap(long, value_enum, default_value_t = PriorityFeeSource::Rpc)]
+    priority_fee_source: PriorityFeeSource,
+
+    /// Helius RPC Url (with api key) used for the priority fee estimates - defaults to the endpoint
+    #[clap(long)]
+    helius_rpc_url: Option<String>,
+
+    /// Triton RPC Url used for the priority fee percentiles - defaults to the endpoint
+    #[clap(long)]
+    triton_rpc_url: Option<String>,
+
+    /// Solana WebSocket endpoint, used with programSubscribe when the Geyser endpoint stays down - disabled if not set
+    #[clap(long)]
+    ws_endpoint: Option<String>,
+
+    /// Number of seconds the Geyser endpoint must be down before falling back to the WebSocket endpoint
+    #[clap(long, default_value_t = DEFAULT_GEYSER_FALLBACK_AFTER_SECONDS)]
+    geyser_fallback_after_seconds: i64,
+
+    /// Also confirm the keeper transactions through signatureSubscribe on the WebSocket endpoint (requires --ws-endpoint)
+    #[clap(long)]
+    signature_subscribe: bool,
+
+    /// Path to a snapshot written by export-state, used to seed the indexes at startup (only the delta is fetched)
+    #[clap(long)]
+    state_snapshot: Option<String>,
+
+    /// Store the UserStaking accounts in the DB as updates arrive and seed the indexes from it at startup (only the delta is fetched)
+    #[clap(long)]
+    warm_start: bool,
+
+    /// With --warm-start, longest downtime after which the stored UserStaking accounts are indexed as is (the stream replays the changes since the last processed slot) instead of fetching the new and closed ones first (0 to always fetch them)
+    #[clap(long, default_value_t = DEFAULT_MAX_RESUME_DOWNTIME_SECONDS)]
+    max_resume_downtime_seconds: i64,
+
+    /// Url receiving a POST (wallet, UserStaking account, signature, claimed amounts) after each successful auto-claim
+    #[clap(long)]
+    claim_webhook_url: Option<String>,
+
+    /// Url receiving a POST (Staking account, staking side, round index, timestamps, signature) each time a staking round is resolved
+    #[clap(long)]
+    round_webhook_url: Option<String>,
+
+    /// Kafka brokers (comma separated) receiving the keeper events (rounds resolved, claims, finalizations, indexed/closed accounts)
+    #[clap(long)]
+    kafka_brokers: Option<String>,
+
+    /// Kafka topic of the keeper events
+    #[clap(long, default_value_t = String::from(DEFAULT_KAFKA_TOPIC))]
+    kafka_topic: String,
+
+    /// NATS server url receiving the keeper events, lighter-weight alternative to Kafka (exclusive with --kafka-brokers)
+    #[clap(long)]
+    nats_url: Option<String>,
+
+    /// NATS subject prefix of the keeper events, each event is published on <prefix>.<event type>
+    #[clap(long, default_value_t = String::from(DEFAULT_NATS_SUBJECT))]
+    nats_subject: String,
+
+    /// Alert (error log) when the referrer reward token vault holds more than this amount of reward tokens
+    #[clap(long)]
+    referrer_vault_alert_threshold: Option<f64>,
+
+    /// Mint of the staking rewards (fee redistribution), USDC if not set - Follows a protocol change of reward token without a release
+    #[clap(long)]
+    reward_mint: Option<Pubkey>,
+
+    /// Max number of keeper transactions waiting for confirmation, the due actions are queued past it (0 to disable)
+    #[clap(long, default_value_t = DEFAULT_MAX_IN_FLIGHT_TRANSACTIONS)]
+    max_in_flight_transactions: usize,
+
+    /// Interval (seconds) at which the pending keeper transactions are sent again while their blockhash is valid (0 to disable)
+    #[clap(long, default_value_t = DEFAULT_REBROADCAST_INTERVAL_SECONDS)]
+    rebroadcast_interval_seconds: u64,
+
+    /// Additional RPC endpoint the pending keeper transactions are rebroadcast through (repeatable)
+    #[clap(long)]
+    rebroadcast_endpoint: Vec<String>,
+
+    /// Max number of rebroadcasts per second, across the endpoints - apart from --rpc-qps, the rebroadcasts never delay the other RPC calls (0 to disable the rate limiter)
+    #[clap(long, default_value_t = DEFAULT_REBROADCAST_QPS)]
+    rebroadcast_qps: u32,
+
+    /// Transaction submission backend: rpc (the endpoint), fanout (the endpoint and every --submit-endpoint), jito (block engine) or helius-sender
+    #[clap(long, value_enum, default_value_t = TxSubmitterBackend::Rpc)]
+    tx_submitter: TxSubmitterBackend,
+
+    /// Additional RPC endpoint the keeper transactions are sent through with --tx-submitter fanout (repeatable)
+    #[clap(long)]
+    submit_endpoint: Vec<String>,
+
+    /// Jito block engine Url used with --tx-submitter jito
+    #[clap(long, default_value = DEFAULT_JITO_BLOCK_ENGINE_URL)]
+    jito_block_engine_url: String,
+
+    /// Tip (lamports) added to each keeper transaction sent through Jito
+    #[clap(long, default_value_t = DEFAULT_JITO_TIP_LAMPORTS)]
+    jito_tip_lamports: u64,
+
+    /// Helius Sender Url used with the helius-sender submission backend (pick the region closest to the keeper)
+    #[clap(long, default_value = DEFAULT_HELIUS_SENDER_URL)]
+    helius_sender_url: String,
+
+    /// Tip (lamports) added to each keeper transaction sent through Helius Sender
+    #[clap(long, default_value_t = DEFAULT_HELIUS_SENDER_TIP_LAMPORTS)]
+    helius_sender_tip_lamports: u64,
+
+    /// Submission backend of the resolves, e.g. helius-sender to land them right at the round boundaries - defaults to --tx-submitter
+    #[clap(long, value_enum)]
+    resolve_staking_round_tx_submitter: Option<TxSubmitterBackend>,
+
+    /// Submission backend of the claims - defaults to --tx-submitter
+    #[clap(long, value_enum)]
+    claim_stakes_tx_submitter: Option<TxSubmitterBackend>,
+
+    /// Submission backend of the finalizations - defaults to --tx-submitter
+    #[clap(long, value_enum)]
+    finalize_locked_stake_tx_submitter: Option<TxSubmitterBackend>,
+
+    /// Submission backend of the AUM updates - defaults to --tx-submitter
+    #[clap(long, value_enum)]
+    update_pool_aum_tx_submitter: Option<TxSubmitterBackend>,
+
+    /// Max fees (base + priority) in SOL spent per UTC day - once reached, claims stop until the next day (resolves continue)
+    #[clap(long)]
+    daily_fee_budget_sol: Option<f64>,
+
+    /// Hard cap on the compute unit price (µLamports / cu) attached to the keeper transactions, whatever the fetched fees
+    #[clap(long, default_value_t = DEFAULT_MAX_PRIORITY_FEE)]
+    max_priority_fee: u64,
+
+    /// Compute unit price multiplier applied for each transaction of an action dropped in a row, capped by --max-priority-fee (1 to disable)
+    #[clap(long, default_value_t = DEFAULT_FEE_ESCALATION_MULTIPLIER)]
+    fee_escalation_multiplier: f64,
+
+    /// Minutes past the end of a staking round after which it is alerted as overdue if still unresolved
+    #[clap(long, default_value_t = DEFAULT_OVERDUE_ROUND_ALERT_MINUTES)]
+    overdue_round_alert_minutes: i64,
+
+    /// Compute unit price multiplier applied to the resolves of the overdue staking rounds, capped by --max-priority-fee (1 to disable)
+    #[clap(long, default_value_t = DEFAULT_OVERDUE_ROUND_FEE_MULTIPLIER)]
+    overdue_round_fee_multiplier: f64,
+
+    /// Starting CU limit of the resolve transactions (refined from the CU consumed by the landed ones)
+    #[clap(long, default_value_t = DEFAULT_RESOLVE_STAKING_ROUND_CU_LIMIT)]
+    resolve_staking_round_cu_limit: u32,
+
+    /// Max CU of a claim transaction, heavy UserStaking accounts are split across several transactions to stay below
+    #[clap(long, default_value_t = DEFAULT_CLAIM_STAKES_CU_LIMIT)]
+    claim_stakes_cu_limit: u32,
+
+    /// CU limit of the finalization simulations
+    #[clap(long, default_value_t = DEFAULT_FINALIZE_LOCKED_STAKE_CU_LIMIT)]
+    finalize_locked_stake_cu_limit: u32,
+
+    /// Starting CU limit of the AUM update transactions (refined from the CU consumed by the landed ones)
+    #[clap(long, default_value_t = DEFAULT_UPDATE_POOL_AUM_CU_LIMIT)]
+    update_pool_aum_cu_limit: u32,
+
+    /// Heap frame size (bytes, multiple of 1024) requested by the claims of heavy UserStaking accounts (0 to disable)
+    #[clap(long, default_value_t = DEFAULT_CLAIM_HEAP_FRAME_BYTES)]
+    claim_heap_frame_bytes: u32,
+
+    /// Time (seconds) after the oldest locked stake claim at which a UserStaking account is auto claimed
+    #[clap(long, default_value_t = AUTO_CLAIM_THRESHOLD_SECONDS)]
+    locked_claim_threshold_seconds: i64,
+
+    /// Time (seconds) after the liquid stake claim at which a UserStaking account is auto claimed
+    #[clap(long, default_value_t = AUTO_CLAIM_THRESHOLD_SECONDS)]
+    liquid_claim_threshold_seconds: i64,
+
+    /// Window (seconds) over which the auto claims are spread past the threshold, by a deterministic per account delay (0 to disable)
+    #[clap(long, default_value_t = DEFAULT_CLAIM_JITTER_WINDOW_SECONDS)]
+    claim_jitter_window_seconds: i64,
+
+    /// Consecutive program errors of the claims / finalizations of a UserStaking account after which it is quarantined (skipped until cleared or expired, 0 to disable)
+    #[clap(long, default_value_t = DEFAULT_QUARANTINE_AFTER_FAILURES)]
+    quarantine_after_failures: u32,
+
+    /// Hours after which a quarantined UserStaking account is released on its own (0 to keep it until cleared)
+    #[clap(long, default_value_t = DEFAULT_QUARANTINE_EXPIRY_HOURS)]
+    quarantine_expiry_hours: i64,
+
+    /// Cron expression (UTC, with seconds: `sec min hour day month weekday`) scheduling the pool AUM updates instead of the 5 minutes interval, e.g. "0 5 0 * * *" for 00:05 daily
+    #[clap(long, value_parser = cron::Schedule::from_str)]
+    update_pool_aum_cron: Option<cron::Schedule>,
+
+    /// Window during which no transaction is sent (indexing continues), as `<start>/<end>` RFC 3339 - repeat for several windows
     #[clap(long)]
-    db_string: String,
+    maintenance_window: Vec<MaintenanceWindow>,
+
+    /// First delay (milliseconds) before reconnecting after a connection failure
+    #[clap(long, default_value_t = DEFAULT_RECONNECT_INITIAL_INTERVAL_MS)]
+    reconnect_initial_interval_ms: u64,
+
+    /// Factor applied to the reconnect delay after each consecutive failure
+    #[clap(long, default_value_t = DEFAULT_RECONNECT_MULTIPLIER)]
+    reconnect_multiplier: f64,
+
+    /// Cap (seconds) on the delay between two reconnect attempts
+    #[clap(long, default_value_t = DEFAULT_RECONNECT_MAX_INTERVAL_SECONDS)]
+    reconnect_max_interval_seconds: u64,
+
+    /// Time (seconds) spent reconnecting before the keeper gives up and exits (0 to retry forever)
+    #[clap(long, default_value_t = DEFAULT_RECONNECT_MAX_ELAPSED_SECONDS)]
+    reconnect_max_elapsed_seconds: u64,
 }
 
 impl Args {
@@ -129,6 +757,92 @@ impl Args {
             .await
             .map_err(Into::into)
     }
+
+    // RPC queried by the priority fees provider
+    fn priority_fee_rpc_url(&self) -> String {
+        let rpc_url = match self.priority_fee_source {
+            PriorityFeeSource::Rpc => None,
+            PriorityFeeSource::Helius => self.helius_rpc_url.as_ref(),
+            PriorityFeeSource::Triton => self.triton_rpc_url.as_ref(),
+        };
+        rpc_url.unwrap_or(&self.endpoint).clone()
+    }
+
+    fn new_tx_submitter(&self, backend: TxSubmitterBackend) -> Arc<dyn TxSubmitter> {
+        match backend {
+            TxSubmitterBackend::Rpc => Arc::new(RpcTxSubmitter::new(self.endpoint.clone())),
+            TxSubmitterBackend::Fanout => Arc::new(FanoutTxSubmitter::new(
+                std::iter::once(&self.endpoint)
+                    .chain(self.submit_endpoint.iter())
+                    .cloned()
+                    .collect(),
+            )),
+            TxSubmitterBackend::Jito => Arc::new(JitoTxSubmitter::new(
+                self.jito_block_engine_url.clone(),
+                self.jito_tip_lamports,
+            )),
+            TxSubmitterBackend::HeliusSender => Arc::new(HeliusSenderTxSubmitter::new(
+                self.helius_sender_url.clone(),
+                self.helius_sender_tip_lamports,
+            )),
+        }
+    }
+
+    // Backends delivering the keeper transactions: the default one, and the ones selected for specific actions
+    fn tx_submitters(
+        &self,
+    ) -> (
+        Arc<dyn TxSubmitter>,
+        HashMap<KeeperAction, Arc<dyn TxSubmitter>>,
+    ) {
+        let per_action = [
+            (
+                KeeperAction::ResolveStakingRound,
+                self.resolve_staking_round_tx_submitter,
+            ),
+            (KeeperAction::ClaimStakes, self.claim_stakes_tx_submitter),
+            (
+                KeeperAction::FinalizeLockedStake,
+                self.finalize_locked_stake_tx_submitter,
+            ),
+            (
+                KeeperAction::UpdatePoolAum,
+                self.update_pool_aum_tx_submitter,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(action, backend)| Some((action, self.new_tx_submitter(backend?))))
+        .collect();
+        (self.new_tx_submitter(self.tx_submitter), per_action)
+    }
+
+    // Program client signing with the payer keypair currently at --payer-keypair
+    fn program(&self) -> anyhow::Result<Program<Arc<Keypair>>> {
+        let payer = read_keypair_file(&self.payer_keypair).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to read the payer keypair {}: {}",
+                self.payer_keypair,
+                e
+            )
+        })?;
+        let client = Client::new(
+            Cluster::Custom(self.endpoint.clone(), self.endpoint.clone()),
+            Arc::new(payer),
+        );
+        Ok(client.program(adrena_abi::ID)?)
+    }
+
+    fn reconnect_backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoffBuilder::new()
+            .with_initial_interval(Duration::from_millis(self.reconnect_initial_interval_ms))
+            .with_multiplier(self.reconnect_multiplier)
+            .with_max_interval(Duration::from_secs(self.reconnect_max_interval_seconds))
+            .with_max_elapsed_time(
+                Some(Duration::from_secs(self.reconnect_max_elapsed_seconds))
+                    .filter(|max_elapsed_time| !max_elapsed_time.is_zero()),
+            )
+            .build()
+    }
 }
 
 async fn generate_accounts_filter_map(
@@ -148,23 +862,63 @@ async fn generate_accounts_filter_map(
             },
         )),
     };
+    let staking_filter_datasize = SubscribeRequestFilterAccountsFilter {
+        filter: Some(AccountsFilterDataOneof::Datasize(STAKING_ACCOUNT_SIZE)),
+    };
     let staking_owner = vec![adrena_abi::ID.to_string()];
     accounts_filter_map.insert(
         "staking_create_update".to_owned(),
         SubscribeRequestFilterAccounts {
             account: vec![],
             owner: staking_owner,
-            filters: vec![staking_filter_discriminator],
+            filters: vec![staking_filter_discriminator, staking_filter_datasize],
+            nonempty_txn_signature: None,
         },
     );
     // We don't monitor Staking accounts for close events - These are ever lasting accounts
 
+    // The GenesisLock account - We monitor it to know when the genesis campaign is over
+    accounts_filter_map.insert(
+        "genesis_lock_update".to_owned(),
+        SubscribeRequestFilterAccounts {
+            account: vec![GENESIS_LOCK_ID.to_string()],
+            owner: vec![],
+            filters: vec![],
+            nonempty_txn_signature: None,
+        },
+    );
+
+    // The Cortex account - We monitor it to stop sending transactions while the protocol is paused
+    accounts_filter_map.insert(
+        "cortex_update".to_owned(),
+        SubscribeRequestFilterAccounts {
+            account: vec![CORTEX_ID.to_string()],
+            owner: vec![],
+            filters: vec![],
+            nonempty_txn_signature: None,
+        },
+    );
+
+    // The ProgramData account of the Adrena program - We monitor it to resync everything when the program is upgraded
+    accounts_filter_map.insert(
+        "program_upgrade".to_owned(),
+        SubscribeRequestFilterAccounts {
+            account: vec![program_data_address().to_string()],
+            owner: vec![],
+            filters: vec![],
+            nonempty_txn_signature: None,
+        },
+    );
+
+    // The UserStaking accounts are only indexed by the instances doing the claims
+    if !current_role().handles_claims() {
+        return accounts_filter_map;
+    }
+
     // Retrieve the existing user staking accounts keys - they are monitored for close events
     let existing_user_staking_accounts_keys: Vec<String> = indexed_user_staking_accounts
-        .read()
-        .await
-        .keys()
-        .map(|p| p.to_string())
+        .iter()
+        .map(|entry| entry.key().to_string())
         .collect();
     // User staking accounts (will catch new user staking accounts created and modified user staking accounts)
     let user_staking_filter_discriminator = SubscribeRequestFilterAccountsFilter {
@@ -177,13 +931,20 @@ async fn generate_accounts_filter_map(
             },
         )),
     };
+    let user_staking_filter_datasize = SubscribeRequestFilterAccountsFilter {
+        filter: Some(AccountsFilterDataOneof::Datasize(USER_STAKING_ACCOUNT_SIZE)),
+    };
     let user_staking_owner = vec![adrena_abi::ID.to_string()];
     accounts_filter_map.insert(
         "user_staking_create_update".to_owned(),
         SubscribeRequestFilterAccounts {
             account: vec![],
             owner: user_staking_owner,
-            filters: vec![user_staking_filter_discriminator],
+            filters: vec![
+                user_staking_filter_discriminator,
+                user_staking_filter_datasize,
+            ],
+            nonempty_txn_signature: None,
         },
     );
 
@@ -194,51 +955,357 @@ async fn generate_accounts_filter_map(
             account: existing_user_staking_accounts_keys,
             owner: vec![],
             filters: vec![],
+            nonempty_txn_signature: None,
         },
     );
 
     accounts_filter_map
 }
 
+// Transactions signed by the keeper, used to confirm them without polling the RPC
+// and, for the claims, the transactions of the Staking accounts (the owners of the new UserStaking accounts are learned from the stakes)
+fn generate_transactions_filter_map(keeper_pubkey: &Pubkey) -> TransactionFilterMap {
+    let mut transactions_filter_map: TransactionFilterMap = HashMap::new();
+    transactions_filter_map.insert(
+        "keeper_transactions".to_owned(),
+        SubscribeRequestFilterTransactions {
+            vote: Some(false),
+            // Both successful and failed transactions
+            failed: None,
+            account_include: vec![keeper_pubkey.to_string()],
+            ..Default::default()
+        },
+    );
+    if current_role().handles_claims() {
+        transactions_filter_map.insert(
+            "stake_transactions".to_owned(),
+            SubscribeRequestFilterTransactions {
+                vote: Some(false),
+                failed: Some(false),
+                account_include: vec![
+                    get_staking_pda(&ADX_MINT).0.to_string(),
+                    get_staking_pda(&ALP_MINT).0.to_string(),
+                ],
+                ..Default::default()
+            },
+        );
+    }
+    transactions_filter_map
+}
+
+// Slot updates, used to track the chain head
+fn generate_slots_filter_map() -> SlotFilterMap {
+    let mut slots_filter_map: SlotFilterMap = HashMap::new();
+    slots_filter_map.insert(
+        "chain_head".to_owned(),
+        SubscribeRequestFilterSlots {
+            // Only the slots reaching the subscription commitment level
+            filter_by_commitment: Some(true),
+            ..Default::default()
+        },
+    );
+    slots_filter_map
+}
+
+// The whole subscription: a new request replaces the previous one, commitment included - only the initial one replays
+async fn generate_subscribe_request(
+    indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+    keeper_pubkey: &Pubkey,
+    commitment: Option<CommitmentLevel>,
+    from_slot: Option<u64>,
+) -> SubscribeRequest {
+    SubscribeRequest {
+        accounts: generate_accounts_filter_map(indexed_user_staking_accounts).await,
+        transactions: generate_transactions_filter_map(keeper_pubkey),
+        slots: generate_slots_filter_map(),
+        commitment: commitment.map(|c| c.into()),
+        from_slot,
+        ..Default::default()
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    env::set_var(
-        env_logger::DEFAULT_FILTER_ENV,
-        env::var_os(env_logger::DEFAULT_FILTER_ENV).unwrap_or_else(|| "info".into()),
+    let args = Args::parse();
+    init_logging(
+        args.log_file.clone().map(|path| LogFile {
+            path,
+            rotation: args.log_rotation,
+            max_size_mb: args.log_max_size_mb,
+            retention: args.log_retention,
+        }),
+        Duration::from_secs(args.log_dedup_window_seconds),
     );
-    env_logger::init();
+    init_rpc_rate_limiter(args.rpc_qps, args.rpc_burst);
+    init_priority_fees(
+        new_priority_fee_provider(args.priority_fee_source, args.priority_fee_rpc_url()),
+        args.max_priority_fee,
+    );
+    let (default_tx_submitter, per_action_tx_submitters) = args.tx_submitters();
+    init_tx_submitters(default_tx_submitter, per_action_tx_submitters);
 
-    let args = Args::parse();
+    if let Some(Command::Alt { command }) = &args.command {
+        // The reward mint is part of the table
+        init_reward_token_mint(args.reward_mint);
+        return match command {
+            AltCommand::Create { force } => {
+                address_lookup_table::create_alt(
+                    args.endpoint.clone(),
+                    &args.payer_keypair,
+                    &args.alt_config,
+                    *force,
+                )
+                .await
+            }
+            AltCommand::Extend { address } => {
+                address_lookup_table::extend_alt(
+                    args.endpoint.clone(),
+                    &args.payer_keypair,
+                    &args.alt_config,
+                    *address,
+                )
+                .await
+            }
+            AltCommand::Show { address } => {
+                address_lookup_table::show_alt(args.endpoint.clone(), &args.alt_config, *address)
+                    .await
+            }
+        };
+    }
+
+    if let Some(Command::ExportState { output }) = &args.command {
+        return state_snapshot::export_state(
+            args.endpoint.clone(),
+            args.commitment.unwrap_or_default().into(),
+            output,
+        )
+        .await;
+    }
+
+    if let Some(Command::FinalizeEarlyExit {
+        owner,
+        user_staking,
+        locked_stake_id,
+        execute,
+    }) = &args.command
+    {
+        return admin::finalize_locked_stake_early_exit(
+            args.endpoint.clone(),
+            &args.payer_keypair,
+            owner,
+            user_staking,
+            *locked_stake_id,
+            *execute,
+        )
+        .await;
+    }
+
+    if let Some(Command::Inspect { owner }) = &args.command {
+        return inspect::inspect_owner(args.endpoint.clone(), owner).await;
+    }
+
+    if let Some(Command::ReleaseQuarantine {
+        user_staking,
+        api_url,
+    }) = &args.command
+    {
+        let admin_token = args.api_admin_token.as_deref().ok_or_else(|| {
+            anyhow::anyhow!("--api-admin-token is required by release-quarantine")
+        })?;
+        return admin::release_quarantine(api_url, admin_token, user_staking).await;
+    }
+
+    if let Some(Command::ReportCosts { since, output }) = &args.command {
+        let db_string = args
+            .db_string
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("--db-string is required by report-costs"))?;
+        return cost_report::report_costs(args.db_backend, db_string, *since, output.as_deref())
+            .await;
+    }
+
+    if let Some(Command::History {
+        user,
+        staking,
+        action,
+        since,
+        until,
+        limit,
+    }) = &args.command
+    {
+        let db_string = args
+            .db_string
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("--db-string is required by history"))?;
+        return history::print_history(
+            args.db_backend,
+            db_string,
+            *user,
+            staking.clone(),
+            action.clone(),
+            *since,
+            *until,
+            *limit,
+        )
+        .await;
+    }
+
+    if args.role.handles_claims() && args.db_string.is_none() {
+        return Err(anyhow::anyhow!(
+            "--db-string is required by the claims (role {:?})",
+            args.role
+        ));
+    }
+
+    if args.db_string.is_some() {
+        enable_transaction_costs_recording();
+        enable_action_log_recording();
+    }
+
+    if args.signature_subscribe && args.ws_endpoint.is_none() {
+        return Err(anyhow::anyhow!(
+            "--signature-subscribe requires --ws-endpoint"
+        ));
+    }
+    init_role(args.role);
+    init_maintenance_windows(args.maintenance_window.clone());
+
+    metrics::register();
+    init_claim_webhook(args.claim_webhook_url.clone());
+    init_round_webhook(args.round_webhook_url.clone());
+    init_event_sink(
+        args.kafka_brokers.clone(),
+        args.kafka_topic.clone(),
+        args.nats_url.clone(),
+        args.nats_subject.clone(),
+    )
+    .await?;
+    init_in_flight_limit(args.max_in_flight_transactions);
+    init_cu_limits(CuLimits {
+        resolve_staking_round: args.resolve_staking_round_cu_limit,
+        claim_stakes: args.claim_stakes_cu_limit,
+        finalize_locked_stake: args.finalize_locked_stake_cu_limit,
+        update_pool_aum: args.update_pool_aum_cu_limit,
+    });
+    init_reward_token_mint(args.reward_mint);
+    init_daily_fee_budget(args.daily_fee_budget_sol.map(sol_to_lamports));
+    init_fee_escalation(args.fee_escalation_multiplier, args.max_priority_fee);
+    init_overdue_round_escalation(
+        args.overdue_round_alert_minutes * 60,
+        args.overdue_round_fee_multiplier,
+    );
+    init_claim_jitter_window(args.claim_jitter_window_seconds);
+    init_quarantine(
+        args.quarantine_after_failures,
+        args.quarantine_expiry_hours * 3_600,
+    );
+    init_claim_thresholds(
+        args.locked_claim_threshold_seconds,
+        args.liquid_claim_threshold_seconds,
+    );
+    init_warm_start(args.warm_start, args.max_resume_downtime_seconds);
     let zero_attempts = Arc::new(Mutex::new(true));
 
-    // The array of indexed Staking accounts (these are the top level ADX and ALP staking "config" accounts)
-    let indexed_staking_accounts: IndexedStakingAccountsThreadSafe =
-        Arc::new(RwLock::new(HashMap::new()));
+    // Owns the Staking accounts, the custodies, the UserStaking accounts missing their staking type and the resolve schedule
+    let state_manager = StateManager::spawn();
     // The array of indexed Locked Staking accounts (these are the users locked stakes, mixing ADX and ALP)
     let indexed_user_staking_accounts: IndexedUserStakingAccountsThreadSafe =
-        Arc::new(RwLock::new(HashMap::new()));
-    // The array of indexed custodies - These are not directly observed, but are needed for instructions and to keep track of which price update v2 accounts are observed
-    let indexed_custodies: IndexedCustodiesThreadSafe = Arc::new(RwLock::new(HashMap::new()));
-    let claim_cache: UserStakingClaimCacheThreadSafe = Arc::new(RwLock::new(HashMap::new()));
-    let staking_round_next_resolve_time_cache: StakingRoundNextResolveTimeCacheThreadSafe =
-        Arc::new(RwLock::new(HashMap::new()));
+        Arc::new(UserStakingIndex::default());
+    let indexed_genesis_lock: IndexedGenesisLockThreadSafe = Arc::new(RwLock::new(None));
+    let claim_cache: UserStakingClaimCacheThreadSafe = Arc::new(DashMap::new());
+    let action_retries: ActionRetriesThreadSafe = Arc::new(RwLock::new(ActionRetries::default()));
     let finalize_locked_stakes_cache: FinalizeLockedStakesCacheThreadSafe =
-        Arc::new(RwLock::new(HashMap::new()));
+        Arc::new(DashMap::new());
+    let pending_transactions: PendingTransactionsThreadSafe = Arc::new(RwLock::new(HashMap::new()));
+    let last_stream_slot: LastStreamSlotThreadSafe = Arc::new(AtomicU64::new(0));
+    let chain_clock: ChainClockThreadSafe = Arc::new(RwLock::new(ChainClock::default()));
+    // Time of the first failed Geyser connection since the last successful one
+    let geyser_down_since: Arc<Mutex<Option<i64>>> = Arc::new(Mutex::new(None));
+    // Set when the Geyser endpoint couldn't replay the missed updates (slot no longer available), the next reconnection refetches everything
+    let replay_rejected = Arc::new(AtomicBool::new(false));
+    // Set once a connection gets to the core loop, the reconnection backoff then starts over
+    let connected = Arc::new(AtomicBool::new(false));
+    let reconnect_backoff = ReconnectBackoff {
+        backoff: args.reconnect_backoff(),
+        connected: Arc::clone(&connected),
+    };
+
+    if let Some(api_addr) = args.api_addr.clone() {
+        let api_state = ApiState {
+            state_manager: state_manager.clone(),
+            claim_cache: Arc::clone(&claim_cache),
+            finalize_locked_stakes_cache: Arc::clone(&finalize_locked_stakes_cache),
+            action_retries: Arc::clone(&action_retries),
+            admin_token: args.api_admin_token.clone(),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = api::serve(api_addr, api_state).await {
+                log::error!("HTTP API error: {:?}", e);
+            }
+        });
+    }
+
+    if let Some(pushgateway_url) = args.pushgateway_url.clone() {
+        tokio::spawn(pushgateway::push_metrics(
+            pushgateway_url,
+            args.pushgateway_job.clone(),
+            args.pushgateway_instance.clone(),
+            Duration::from_secs(args.pushgateway_interval_seconds.max(1)),
+        ));
+    }
+
+    if let Some(statsd_addr) = args.statsd_addr.clone() {
+        tokio::spawn(statsd::export_statsd(
+            statsd_addr,
+            Duration::from_secs(args.statsd_interval_seconds.max(1)),
+        ));
+    }
+
+    // Summaries of the per stream message logs (trace level), and of the collapsed identical lines
+    if args.log_dedup_window_seconds > 0 {
+        tokio::spawn(log_summaries(Duration::from_secs(
+            args.log_dedup_window_seconds,
+        )));
+    }
+
+    // Run across the reconnections, a keeper stuck reconnecting is unhealthy too
+    init_health();
+    tokio::spawn(monitor_health());
+    tokio::spawn(run_systemd_watchdog());
 
-    // The default exponential backoff strategy intervals:
+    // Registered once for the whole run: without a handler, a SIGHUP terminates the process (e.g. while reconnecting)
+    let payer_keypair_reload_signal = Arc::new(Mutex::new(signal(SignalKind::hangup())?));
+
+    // With the default reconnect settings, the exponential backoff strategy intervals (randomized by +/- 50%):
     // [500ms, 750ms, 1.125s, 1.6875s, 2.53125s, 3.796875s, 5.6953125s,
-    // 8.5s, 12.8s, 19.2s, 28.8s, 43.2s, 64.8s, 97s, ... ]
-    retry(ExponentialBackoff::default(), move || {
+    // 8.5s, 12.8s, 19.2s, 28.8s, 43.2s, 60s, 60s, ... ] for up to 15 min of consecutive failures
+    let mut run_connection = move || {
         let args = args.clone();
+        let connected = Arc::clone(&connected);
         let zero_attempts = Arc::clone(&zero_attempts);
-        let indexed_staking_accounts = Arc::clone(&indexed_staking_accounts);
+        let state_manager = state_manager.clone();
         let indexed_user_staking_accounts = Arc::clone(&indexed_user_staking_accounts);
-        let indexed_custodies = Arc::clone(&indexed_custodies);
+        let indexed_genesis_lock = Arc::clone(&indexed_genesis_lock);
         let claim_cache = Arc::clone(&claim_cache);
-        let staking_round_next_resolve_time_cache = Arc::clone(&staking_round_next_resolve_time_cache);
+        let action_retries = Arc::clone(&action_retries);
         let finalize_locked_stakes_cache = Arc::clone(&finalize_locked_stakes_cache);
-        let mut periodical_priority_fees_fetching_task: Option<JoinHandle<Result<(), backoff::Error<anyhow::Error>>>> = None;
+        let pending_transactions = Arc::clone(&pending_transactions);
+        let last_stream_slot = Arc::clone(&last_stream_slot);
+        let chain_clock = Arc::clone(&chain_clock);
+        let geyser_down_since = Arc::clone(&geyser_down_since);
+        let replay_rejected = Arc::clone(&replay_rejected);
+        let payer_keypair_reload_signal = Arc::clone(&payer_keypair_reload_signal);
+        let mut periodical_priority_fees_fetching_task: Option<
+            JoinHandle<Result<(), backoff::Error<anyhow::Error>>>,
+        > = None;
         let mut db_connection_task: Option<JoinHandle<()>> = None;
+        let mut transactions_confirmation_task: Option<JoinHandle<()>> = None;
+        let mut slot_lag_monitoring_task: Option<JoinHandle<()>> = None;
+        let mut index_sizes_monitoring_task: Option<JoinHandle<()>> = None;
+        let mut referrer_reward_vault_monitoring_task: Option<JoinHandle<()>> = None;
+        let mut blockhash_refresh_task: Option<JoinHandle<()>> = None;
+        let mut signature_subscribe_task: Option<JoinHandle<()>> = None;
+        let mut rebroadcast_task: Option<JoinHandle<()>> = None;
 
         async move {
             // In case it errored out, abort the fee task (will be recreated)
@@ -248,8 +1315,30 @@ async fn main() -> anyhow::Result<()> {
             if let Some(t) = db_connection_task.take() {
                 t.abort();
             }
+            if let Some(t) = transactions_confirmation_task.take() {
+                t.abort();
+            }
+            if let Some(t) = slot_lag_monitoring_task.take() {
+                t.abort();
+            }
+            if let Some(t) = index_sizes_monitoring_task.take() {
+                t.abort();
+            }
+            if let Some(t) = referrer_reward_vault_monitoring_task.take() {
+                t.abort();
+            }
+            if let Some(t) = blockhash_refresh_task.take() {
+                t.abort();
+            }
+            if let Some(t) = signature_subscribe_task.take() {
+                t.abort();
+            }
+            if let Some(t) = rebroadcast_task.take() {
+                t.abort();
+            }
 
             let mut zero_attempts = zero_attempts.lock().await;
+            let is_first_attempt = *zero_attempts;
             if *zero_attempts {
                 *zero_attempts = false;
             } else {
@@ -258,43 +1347,136 @@ async fn main() -> anyhow::Result<()> {
             drop(zero_attempts);
 
             let commitment = args.get_commitment();
-            let mut grpc = args
-                .connect()
-                .await
-                .map_err(backoff::Error::transient)?;
+            // Failures are handled when opening the stream (may fall back to the WebSocket endpoint)
+            let grpc = args.connect().await;
 
-            let payer = read_keypair_file(args.payer_keypair.clone()).unwrap();
-            let payer = Arc::new(payer);
-            let client = Client::new(
-                Cluster::Custom(args.endpoint.clone(), args.endpoint.clone()),
-                Arc::clone(&payer),
-            );
-            let program = client
-                .program(adrena_abi::ID)
-                .map_err(|e| backoff::Error::transient(e.into()))?;
+            // Replaced when the payer keypair is reloaded (SIGHUP)
+            let mut program = args.program().map_err(backoff::Error::permanent)?;
             log::info!("  <> gRPC, RPC clients connected!");
 
-            // Connect to the DB that contains the table matching the UserStaking accounts to their owners (the onchain data doesn't contain the owner)
-            // Create an SSL connector
-            let builder = SslConnector::builder(SslMethod::tls()).unwrap();
-            let connector = MakeTlsConnector::new(builder.build());
-            let (db, db_connection) = tokio_postgres::connect(&args.db_string, connector).await.map_err(|e| backoff::Error::transient(e.into()))?;
-            // Open a connection to the DB
+            // Side thread refreshing the blockhash shared by the transaction builds
             #[allow(unused_assignments)]
             {
-                db_connection_task = Some(tokio::spawn(async move {
-                    if let Err(e) = db_connection.await {
-                        log::error!("connection error: {}", e);
-                    }
-                }));
+                let rpc_client = RpcClient::new_with_commitment(args.endpoint.clone(), CommitmentConfig::confirmed());
+                blockhash_refresh_task = Some(tokio::spawn(refresh_blockhash_cache(rpc_client)));
+            }
+
+            // Side thread checking the outcome of the transactions sent by the keeper
+            #[allow(unused_assignments)]
+            {
+                let rpc_client = RpcClient::new_with_commitment(args.endpoint.clone(), CommitmentConfig::confirmed());
+                let pending_transactions = Arc::clone(&pending_transactions);
+                transactions_confirmation_task = Some(tokio::spawn(poll_pending_transactions(rpc_client, pending_transactions)));
+            }
+
+            // Side thread sending the pending transactions again until they land or expire
+            #[allow(unused_assignments)]
+            {
+                if args.rebroadcast_interval_seconds != 0 {
+                    let rpc_clients = std::iter::once(&args.endpoint)
+                        .chain(args.rebroadcast_endpoint.iter())
+                        .map(|endpoint| RpcClient::new_with_commitment(endpoint.clone(), CommitmentConfig::confirmed()))
+                        .collect();
+                    let pending_transactions = Arc::clone(&pending_transactions);
+                    rebroadcast_task = Some(tokio::spawn(rebroadcast_pending_transactions(
+                        rpc_clients,
+                        pending_transactions,
+                        Duration::from_secs(args.rebroadcast_interval_seconds),
+                        args.rebroadcast_qps,
+                    )));
+                }
+            }
+
+            // Side thread confirming the keeper transactions as soon as they land, through signatureSubscribe
+            #[allow(unused_assignments)]
+            {
+                if let (true, Some(ws_endpoint)) = (args.signature_subscribe, &args.ws_endpoint) {
+                    let rpc_client = RpcClient::new_with_commitment(args.endpoint.clone(), CommitmentConfig::confirmed());
+                    let pending_transactions = Arc::clone(&pending_transactions);
+                    signature_subscribe_task = Some(tokio::spawn(confirm_pending_transactions_with_signature_subscribe(ws_endpoint.clone(), rpc_client, pending_transactions)));
+                }
+            }
+
+            // Side thread measuring how far behind the chain head the stream is
+            #[allow(unused_assignments)]
+            {
+                let rpc_client = RpcClient::new_with_commitment(args.endpoint.clone(), CommitmentConfig::processed());
+                let last_stream_slot = Arc::clone(&last_stream_slot);
+                let chain_clock = Arc::clone(&chain_clock);
+                slot_lag_monitoring_task = Some(tokio::spawn(monitor_slot_lag(rpc_client, last_stream_slot, chain_clock, args.max_slot_lag)));
             }
 
-            // Fetched once
-            let pool = program
-                .account::<Pool>(adrena_abi::MAIN_POOL_ID)
+            // Side thread exporting the size of the indexes and caches
+            #[allow(unused_assignments)]
+            {
+                index_sizes_monitoring_task = Some(tokio::spawn(monitor_index_sizes(
+                    state_manager.clone(),
+                    Arc::clone(&indexed_user_staking_accounts),
+                    Arc::clone(&claim_cache),
+                    Arc::clone(&finalize_locked_stakes_cache),
+                    Arc::clone(&action_retries),
+                    Arc::clone(&pending_transactions),
+                )));
+            }
+
+            // Side thread watching the referral rewards waiting in the referrer reward token vault
+            #[allow(unused_assignments)]
+            {
+                let rpc_client = RpcClient::new_with_commitment(args.endpoint.clone(), CommitmentConfig::confirmed());
+                referrer_reward_vault_monitoring_task = Some(tokio::spawn(monitor_referrer_reward_vault(rpc_client, args.referrer_vault_alert_threshold)));
+            }
+
+            // Connect to the DB that contains the table matching the UserStaking accounts to their owners (the onchain data doesn't contain the owner)
+            // Optional in the resolves role, the resolved rounds history is then not recorded
+            let role = args.role;
+            let db = match &args.db_string {
+                Some(db_string) => {
+                    let (db, db_connection_handle) = Db::connect(
+                        args.db_backend,
+                        db_string,
+                        args.redis_url.as_deref(),
+                    )
+                    .await?;
+                    #[allow(unused_assignments)]
+                    {
+                        db_connection_task = db_connection_handle;
+                    }
+                    // Shared with the owner mapping reloads, run apart from the core loop
+                    Some(Arc::new(db))
+                }
+                None => None,
+            };
+
+            // Fetched once - The AUM updates iterate all the pools, the protocol may add more than the main one
+            let pool_filters = vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &Pool::DISCRIMINATOR))];
+            let pools = rpc_call(|| program.accounts::<Pool>(pool_filters.clone()))
                 .await
                 .map_err(|e| backoff::Error::transient(e.into()))?;
+            log::info!("  <> # of pools indexed: {}", pools.len());
+
+            // Kept up to date by the stream - the claims still work without it (the program rejects the genesis ones)
+            match rpc_call(|| program.account::<GenesisLock>(GENESIS_LOCK_ID)).await {
+                Ok(genesis_lock) => {
+                    log::info!(
+                        "  <> Genesis lock indexed (campaign end: {})",
+                        genesis_campaign_end_time(&genesis_lock)
+                    );
+                    *indexed_genesis_lock.write().await = Some(genesis_lock);
+                }
+                Err(e) => log::warn!("  <> Failed to fetch the genesis lock: {:?}", e),
+            }
+
+            // Kept up to date by the stream - if it can't be fetched, the keeper assumes the protocol is not paused
+            match rpc_call(|| program.account::<Cortex>(CORTEX_ID)).await {
+                Ok(cortex) => update_protocol_pause(&cortex),
+                Err(e) => log::warn!("  <> Failed to fetch the cortex: {:?}", e),
+            }
+
+            // Deployment slot of the program, the stream updates of the ProgramData account are compared to it
+            init_program_deploy_slot(&program.rpc()).await;
 
+            // Token program (SPL Token or Token-2022) of the reward and LM mints, for the ATAs and the staking instructions
+            init_mint_token_programs(&program.rpc()).await;
 
             // ////////////////////////////////////////////////////////////////
             log::info!("0 - Retrieving and indexing existing custodies...");
@@ -304,57 +1486,146 @@ async fn main() -> anyhow::Result<()> {
                     &Custody::DISCRIMINATOR,
                 ));
                 let filters = vec![custody_pda_filter];
-                let existing_custodies_accounts = program
-                    .accounts::<Custody>(filters)
+                let existing_custodies_accounts = rpc_call(|| program.accounts::<Custody>(filters.clone()))
                     .await
                     .map_err(|e| backoff::Error::transient(e.into()))?;
                 // Extend the indexed custodies map with the existing custodies
-                indexed_custodies.write().await.extend(existing_custodies_accounts);
+                let custodies_count = state_manager
+                    .call(move |state| {
+                        state.custodies.extend(existing_custodies_accounts);
+                        state.custodies.len()
+                    })
+                    .await
+                    .map_err(backoff::Error::permanent)?;
                 log::info!(
                     "  <> # of existing custodies parsed and loaded: {}",
-                    indexed_custodies.read().await.len()
+                    custodies_count
                 );
             }
             // ////////////////////////////////////////////////////////////////
 
             // ////////////////////////////////////////////////////////////////
             log::info!("1 - Retrieving and indexing all Staking andUserStaking accounts...");
-            {
-                // Staking accounts
-                {
-                let staking_pda_filter = RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
-                    0,
-                    Staking::DISCRIMINATOR,
-                ));
-                let filters = vec![staking_pda_filter];
-                let existing_staking_accounts = program
-                    .accounts::<Staking>(filters)
-                    .await
-                    .map_err(|e| backoff::Error::transient(e.into()))?;
+            // The program has been upgraded: start over from the chain, the accounts no longer decodable must not linger in the indexes
+            let resync = take_resync_pending();
+            // Reconnection: the Geyser endpoint replays the updates missed since the last processed slot (from_slot), the indexes
+            // are kept as is - refetch everything after a program upgrade, a rejected replay, or when the endpoint is down
+            let replay_rejected_previously = replay_rejected.swap(false, Ordering::Relaxed);
+            let mut replay_from_slot = match last_stream_slot.load(Ordering::Relaxed) {
+                0 => None,
+                _ if is_first_attempt || resync || replay_rejected_previously || grpc.is_err() => None,
+                last_processed_slot => Some(last_processed_slot),
+            };
+            // The snapshot and the warm start are only used at startup (the warm start may resume from the stream checkpoint)
+            let mut indexes_seeded = replay_from_slot.is_some();
+            if let Some(last_processed_slot) = replay_from_slot {
+                log::info!("  <> Keeping the indexes, replaying the updates since slot {}", last_processed_slot);
+            }
+            // Same after a rejected replay, the accounts closed in the meantime wouldn't be dropped by the stream
+            if resync || replay_rejected_previously {
+                state_manager.call(|state| state.staking_accounts.clear()).await.map_err(backoff::Error::permanent)?;
+                indexed_user_staking_accounts.clear();
+                claim_cache.clear();
+                finalize_locked_stakes_cache.clear();
+                clear_due_instructions();
+            }
+            // Only with Geyser: the accounts modified since the snapshot are refreshed by replaying the updates from its slot
+            if let (Some(state_snapshot), true) = (&args.state_snapshot, is_first_attempt && role.handles_claims() && grpc.is_ok()) {
+                let rpc_client = RpcClient::new_with_commitment(args.endpoint.clone(), args.commitment.unwrap_or_default().into());
+                match load_state_snapshot(
+                    state_snapshot,
+                    &rpc_client,
+                    args.commitment.unwrap_or_default().into(),
+                    &state_manager,
+                    &indexed_user_staking_accounts,
+                )
+                .await
                 {
-                    let mut indexed_staking_accounts = indexed_staking_accounts.write().await;
+                    Ok(snapshot_slot) => {
+                        indexes_seeded = true;
+                        // A rejected replay (snapshot older than what the endpoint keeps) refetches all accounts
+                        replay_from_slot = Some(snapshot_slot);
+                        log::info!(
+                            "  <> # of Staking / UserStaking accounts loaded from the state snapshot: {} / {}",
+                            state_manager.call(|state| state.staking_accounts.len()).await.map_err(backoff::Error::permanent)?,
+                            indexed_user_staking_accounts.len()
+                        );
+                    }
+                    Err(e) => log::warn!("  <> Failed to load the state snapshot, fetching all accounts: {:?}", e),
+                }
+            }
+            // Bulk load the owners, the claims only query the DB for the accounts created since
+            if let Some(db) = db.as_deref().filter(|_| role.handles_claims()) {
+                refresh_owner_cache(db).await;
+                load_quarantine(db).await;
+            }
 
-                    indexed_staking_accounts.extend(existing_staking_accounts);
+            let warm_start_db = db
+                .as_deref()
+                .filter(|_| !indexes_seeded && is_first_attempt && is_warm_start_enabled() && role.handles_claims() && grpc.is_ok());
+            if let Some(db) = warm_start_db {
+                let rpc_client = RpcClient::new_with_commitment(args.endpoint.clone(), args.commitment.unwrap_or_default().into());
+                match warm_start_indexes(
+                    db,
+                    &rpc_client,
+                    args.commitment.unwrap_or_default().into(),
+                    &state_manager,
+                    &indexed_user_staking_accounts,
+                )
+                .await
+                {
+                    Ok(checkpoint_slot) => {
+                        indexes_seeded = true;
+                        // Same as the snapshot, a rejected replay refetches all accounts
+                        replay_from_slot = Some(checkpoint_slot);
+                        log::info!(
+                            "  <> # of Staking / UserStaking accounts loaded from the DB: {} / {}",
+                            state_manager.call(|state| state.staking_accounts.len()).await.map_err(backoff::Error::permanent)?,
+                            indexed_user_staking_accounts.len()
+                        );
+                    }
+                    Err(e) => log::warn!("  <> Failed to warm start from the DB, fetching all accounts: {:?}", e),
                 }
+            }
+            if !indexes_seeded {
+                // Staking accounts
+                {
+                let existing_staking_accounts = fetch_staking_accounts(&program.rpc())
+                    .await
+                    .map_err(backoff::Error::transient)?;
+                let staking_accounts_count = state_manager
+                    .call(move |state| {
+                        state.staking_accounts.extend(existing_staking_accounts);
+                        state.staking_accounts.len()
+                    })
+                    .await
+                    .map_err(backoff::Error::permanent)?;
                 log::info!(
                     "  <> # of existing Staking accounts parsed and loaded: {}",
-                    indexed_staking_accounts.read().await.len()
+                    staking_accounts_count
                 );
                 }
 
-                // User staking accounts
-                {
-                    let existing_user_staking_accounts = program
-                        .accounts::<UserStaking>(vec![])
+                // User staking accounts - Not needed to resolve the rounds
+                if role.handles_claims() {
+                    let mut existing_user_staking_accounts_data = fetch_user_staking_accounts_data_sliced(&program.rpc())
                         .await
-                        .map_err(|e| backoff::Error::transient(e.into()))?;
+                        .map_err(backoff::Error::transient)?;
+                    let existing_user_staking_accounts = existing_user_staking_accounts_data
+                        .iter()
+                        .map(|(key, data)| Ok((*key, deserialize_user_staking_sliced(data)?)))
+                        .collect::<anyhow::Result<HashMap<Pubkey, UserStaking>>>()
+                        .map_err(backoff::Error::transient)?;
                     {
-                        let mut indexed_user_staking_accounts = indexed_user_staking_accounts.write().await;
-
-                        // filter out the accounts that have no staking type defined yet
-                        let existing_user_staking_accounts_len = existing_user_staking_accounts.len();
-                        let existing_user_staking_accounts_with_staking_type: HashMap<Pubkey, UserStaking> = existing_user_staking_accounts.into_iter().filter(|a| a.1.staking_type != 0).collect();
-                        log::info!("  <> # of existing UserStaking accounts w/o staking type defined filtered out: {}", existing_user_staking_accounts_len - existing_user_staking_accounts_with_staking_type.len());
+                        // filter out the accounts that have no staking type defined yet - they are kept aside until their type is set
+                        let (existing_user_staking_accounts_with_staking_type, existing_user_staking_accounts_missing_staking_type): (HashMap<Pubkey, UserStaking>, HashMap<Pubkey, UserStaking>) = existing_user_staking_accounts.into_iter().partition(|a| a.1.staking_type != 0);
+                        log::info!("  <> # of existing UserStaking accounts w/o staking type defined filtered out: {}", existing_user_staking_accounts_missing_staking_type.len());
+                        let user_staking_accounts_missing_staking_type: HashSet<Pubkey> = existing_user_staking_accounts_missing_staking_type.into_keys().collect();
+                        USER_STAKING_ACCOUNTS_MISSING_STAKING_TYPE.set(user_staking_accounts_missing_staking_type.len() as i64);
+                        state_manager
+                            .call(move |state| state.user_staking_accounts_missing_staking_type = user_staking_accounts_missing_staking_type)
+                            .await
+                            .map_err(backoff::Error::permanent)?;
 
                         // DEBUG helper
                         // let target_account = Pubkey::from_str("").unwrap(); 
@@ -368,19 +1639,55 @@ async fn main() -> anyhow::Result<()> {
                     }
                     log::info!(
                         "  <> # of existing UserStaking accounts parsed and loaded: {}",
-                        indexed_user_staking_accounts.read().await.len()
+                        indexed_user_staking_accounts.len()
                     );
+
+                    // Only the indexed accounts are stored for the next warm start
+                    if let (Some(db), true) = (db.as_deref(), is_warm_start_enabled()) {
+                        existing_user_staking_accounts_data.retain(|(key, _)| indexed_user_staking_accounts.contains_key(key));
+                        store_scanned_user_staking_accounts(db, &existing_user_staking_accounts_data).await;
+                    }
                 }
+            }
 
-                // Update for current Staking accounts
-                update_staking_round_next_resolve_time_cache(&staking_round_next_resolve_time_cache, &indexed_staking_accounts).await;
+            if resync {
+                if let Err(e) = check_account_layouts(&program.rpc()).await {
+                    log::warn!("  <> Failed to check the accounts layout: {:?}", e);
+                }
+            }
 
-                // Update for current UserStaking accounts
-                update_claim_cache(&claim_cache, &indexed_user_staking_accounts).await;
+            // Build the caches from the indexed accounts (fetched or loaded from the state snapshot)
+            {
+                // Update for current Staking accounts
+                update_staking_round_next_resolve_time_cache(&state_manager).await.map_err(backoff::Error::permanent)?;
+                state_manager
+                    .call(|state| {
+                        for (staking_account_key, staking_account) in state.staking_accounts.iter() {
+                            update_staking_reward_rates_metrics(staking_account_key, staking_account);
+                        }
+                    })
+                    .await
+                    .map_err(backoff::Error::permanent)?;
 
-                // Update for current UserStaking accounts that need to be finalized
-                update_finalize_locked_stakes_cache(&finalize_locked_stakes_cache, &indexed_user_staking_accounts).await;
+                // Update for current UserStaking accounts (empty index in the resolves role)
+                if role.handles_claims() {
+                    update_claim_cache(&state_manager, &claim_cache, &indexed_user_staking_accounts).await.map_err(backoff::Error::permanent)?;
+                    compute_staked_amounts(&indexed_user_staking_accounts);
+                    // The accounts already past the unclaimed resolved rounds limit
+                    let (lm_staking_account, lp_staking_account) = state_manager
+                        .call(|state| (state.staking_account(StakingType::LM), state.staking_account(StakingType::LP)))
+                        .await
+                        .map_err(backoff::Error::permanent)?;
+                    if let Some(lm_staking_account) = lm_staking_account {
+                        schedule_forced_claims(&state_manager, &claim_cache, indexed_user_staking_accounts.shard(StakingType::LM), &lm_staking_account).await.map_err(backoff::Error::permanent)?;
+                    }
+                    if let Some(lp_staking_account) = lp_staking_account {
+                        schedule_forced_claims(&state_manager, &claim_cache, indexed_user_staking_accounts.shard(StakingType::LP), &lp_staking_account).await.map_err(backoff::Error::permanent)?;
+                    }
 
+                    // Update for current UserStaking accounts that need to be finalized
+                    update_finalize_locked_stakes_cache(&finalize_locked_stakes_cache, &indexed_user_staking_accounts);
+                }
             }
             // ////////////////////////////////////////////////////////////////
 
@@ -389,23 +1696,50 @@ async fn main() -> anyhow::Result<()> {
             // to inform the server about the accounts we are interested in observing changes to
             // ////////////////////////////////////////////////////////////////
             log::info!("2 - Generate subscription request and open stream...");
-            let accounts_filter_map =
-                generate_accounts_filter_map(&indexed_user_staking_accounts).await;
-            log::info!("  <> Account filter map initialized");
-            let (mut subscribe_tx, mut stream) = {
-                let request = SubscribeRequest {
-                    ping: None,// Some(SubscribeRequestPing { id: 1 }),
-                    accounts: accounts_filter_map,
-                    commitment: commitment.map(|c| c.into()),
-                    ..Default::default()
-                };
+            let (mut subscribe_tx, mut stream, degraded_mode): (SubscribeTx, UpdateStream, bool) = {
+                let request = generate_subscribe_request(
+                    &indexed_user_staking_accounts,
+                    &program.payer(),
+                    commitment,
+                    replay_from_slot,
+                ).await;
                 log::debug!("  <> Sending subscription request: {:?}", request);
-                let (subscribe_tx, stream) = grpc
-                    .subscribe_with_request(Some(request))
-                    .await
-                    .map_err(|e| backoff::Error::transient(e.into()))?;
-                log::info!("  <> stream opened");
-                (subscribe_tx, stream)
+                let geyser_subscription = match grpc {
+                    Ok(mut grpc) => grpc.subscribe_with_request(Some(request)).await.map_err(Into::into),
+                    Err(e) => Err(e),
+                };
+                match geyser_subscription {
+                    Ok((subscribe_tx, stream)) => {
+                        *geyser_down_since.lock().await = None;
+                        log::info!("  <> stream opened");
+                        (Box::pin(subscribe_tx), stream.boxed(), false)
+                    }
+                    Err(e) => {
+                        // The indexes weren't refetched, the next attempt does it (and may fall back to the WebSocket endpoint)
+                        if replay_from_slot.is_some() {
+                            replay_rejected.store(true, Ordering::Relaxed);
+                            return Err(backoff::Error::transient(e));
+                        }
+                        let current_time = chrono::Utc::now().timestamp();
+                        let down_since = *geyser_down_since.lock().await.get_or_insert(current_time);
+                        match &args.ws_endpoint {
+                            Some(ws_endpoint) if current_time - down_since >= args.geyser_fallback_after_seconds => {
+                                log::warn!(
+                                    "  <> Geyser endpoint down for {}s ({:?}) - falling back to WebSocket PubSub (degraded mode)",
+                                    current_time - down_since,
+                                    e
+                                );
+                                let (subscribe_tx, stream) = subscribe_programs(
+                                    ws_endpoint.clone(),
+                                    args.commitment.unwrap_or_default().into(),
+                                )
+                                .await?;
+                                (Box::pin(subscribe_tx), stream, true)
+                            }
+                            _ => return Err(backoff::Error::transient(e)),
+                        }
+                    }
+                }
             };
 
 
@@ -421,13 +1755,21 @@ async fn main() -> anyhow::Result<()> {
             periodical_priority_fees_fetching_task = Some({
                 let median_priority_fee_low = Arc::clone(&median_priority_fee_low);
                 let median_priority_fee_high = Arc::clone(&median_priority_fee_high);
+                let state_manager = state_manager.clone();
                 tokio::spawn(async move {
                     let mut fee_refresh_interval = interval(PRIORITY_FEE_REFRESH_INTERVAL);
                     loop {
                         fee_refresh_interval.tick().await;
-                        if let Ok(fee_high) =
-                            fetch_mean_priority_fee(&client, MEAN_PRIORITY_FEE_PERCENTILE_RESOLVE_STAKING_ROUND).await
+                        let staking_account_keys: Vec<Pubkey> = match state_manager
+                            .call(|state| state.staking_accounts.keys().cloned().collect())
+                            .await
                         {
+                            Ok(staking_account_keys) => staking_account_keys,
+                            Err(e) => return Err(backoff::Error::permanent(e)),
+                        };
+                        let fee_high = fetch_reference_priority_fee(MEAN_PRIORITY_FEE_PERCENTILE_RESOLVE_STAKING_ROUND, &staking_account_keys).await;
+                        let fee_low = fetch_reference_priority_fee(MEAN_PRIORITY_FEE_PERCENTILE_CLAIM_STAKES, &staking_account_keys).await;
+                        if let Ok(fee_high) = fee_high {
                             let mut fee_lock = median_priority_fee_high.lock().await;
                             *fee_lock = fee_high;
                             log::debug!(
@@ -435,9 +1777,7 @@ async fn main() -> anyhow::Result<()> {
                                 fee_high
                             );
                         }
-                        if let Ok(fee_low) =
-                            fetch_mean_priority_fee(&client, MEAN_PRIORITY_FEE_PERCENTILE_CLAIM_STAKES).await
-                        {
+                        if let Ok(fee_low) = fee_low {
                             let mut fee_lock = median_priority_fee_low.lock().await;
                             *fee_lock = fee_low;
                             log::debug!(
@@ -450,37 +1790,67 @@ async fn main() -> anyhow::Result<()> {
                 });
             }
 
-            let mut custodies_accounts: Vec<AccountMeta> = vec![];
-            let mut custodies_oracle_accounts: Vec<AccountMeta> = vec![];
-            let mut custodies_trade_oracle_accounts: Vec<AccountMeta> = vec![];
-            for key in pool.custodies.iter() {
-                if key != &Pubkey::default() {
-                    
-                    custodies_accounts.push(AccountMeta {
-                        pubkey: key.clone(),
-                        is_signer: false,
-                        is_writable: false,
-                    });
-                    
-                    let oracle_key = indexed_custodies.read().await[key].oracle;
-                    custodies_oracle_accounts.push(AccountMeta {
-                        pubkey: oracle_key,
-                        is_signer: false,
-                        is_writable: false,
-                    });
-                    
-                    let trade_oracle_key = indexed_custodies.read().await[key].trade_oracle;
-                    if trade_oracle_key != oracle_key {
-                        custodies_trade_oracle_accounts.push(AccountMeta {
-                            pubkey: trade_oracle_key,
-                            is_signer: false,
-                            is_writable: false,
-                        });
-                    }
+            let pools_remaining_accounts: Vec<(Pubkey, Vec<AccountMeta>)> = {
+                let indexed_custodies = state_manager.call(|state| state.custodies.clone()).await.map_err(backoff::Error::permanent)?;
+                pools
+                    .iter()
+                    .map(|(pool_key, pool)| (*pool_key, pool_aum_remaining_accounts(pool, &indexed_custodies)))
+                    .collect()
+            };
+
+            // ////////////////////////////////////////////////////////////////
+            // CATCH-UP
+            //
+            // The rounds that ended and the claims that became due while the keeper
+            // was down (or reconnecting) are sent right away, before the stream is read
+            // Not when replaying from a slot: the indexes are only current once the
+            // replayed updates are processed, the periodic checks take over then
+            // ////////////////////////////////////////////////////////////////
+            if replay_from_slot.is_none() {
+                log::info!("4 - Catch-up pass on the overdue resolves and claims...");
+                // Don't wait for the priority fees task, the overdue actions shouldn't go out without a fee
+                let staking_account_keys: Vec<Pubkey> = state_manager
+                    .call(|state| state.staking_accounts.keys().cloned().collect())
+                    .await
+                    .map_err(backoff::Error::permanent)?;
+                if let Ok(fee_high) = fetch_reference_priority_fee(MEAN_PRIORITY_FEE_PERCENTILE_RESOLVE_STAKING_ROUND, &staking_account_keys).await {
+                    *median_priority_fee_high.lock().await = fee_high;
+                }
+                if let Ok(fee_low) = fetch_reference_priority_fee(MEAN_PRIORITY_FEE_PERCENTILE_CLAIM_STAKES, &staking_account_keys).await {
+                    *median_priority_fee_low.lock().await = fee_low;
                 }
-            }
 
-            let remaining_accounts = [custodies_accounts, custodies_oracle_accounts, custodies_trade_oracle_accounts].concat();
+                // The resolves first, the claims sent after them normally land after them and include the resolved rounds
+                if role.handles_resolves() {
+                    process_resolve_staking_rounds(
+                        &state_manager,
+                        &claim_cache,
+                        &indexed_user_staking_accounts,
+                        &action_retries,
+                        &chain_clock,
+                        &program,
+                        &pending_transactions,
+                        *median_priority_fee_high.lock().await,
+                    )
+                    .await;
+                }
+                if role.handles_claims() {
+                    process_claim_stakes(
+                        &claim_cache,
+                        db.as_deref().expect("The claims require a DB (checked at startup)"),
+                        &state_manager,
+                        &indexed_user_staking_accounts,
+                        &indexed_genesis_lock,
+                        &action_retries,
+                        &program,
+                        &pending_transactions,
+                        *median_priority_fee_low.lock().await,
+                        args.claim_heap_frame_bytes,
+                    )
+                    .await?;
+                }
+                record_evaluation();
+            }
 
             // ////////////////////////////////////////////////////////////////
             // CORE LOOP
@@ -490,61 +1860,205 @@ async fn main() -> anyhow::Result<()> {
             // liquidation/sl/tp conditions on the already indexed positions if 
             // coming from the position accounts, we update the indexed positions map
             // ////////////////////////////////////////////////////////////////
-            log::info!("4 - Start core loop: processing gRPC stream...");
+            log::info!("5 - Start core loop: processing gRPC stream...");
+            connected.store(true, Ordering::Relaxed);
+            notify_ready();
             // Create intervals for each task
-            let mut resolve_staking_rounds_interval = interval(Duration::from_secs(1));
-            let mut claim_stakes_interval = interval(Duration::from_secs(20));
+            // The catch-up pass just sent the due resolves and claims, first check after a period (their transactions are
+            // still pending, they would be sent twice)
+            let first_check = |period: Duration| {
+                if replay_from_slot.is_none() { Instant::now() + period } else { Instant::now() }
+            };
+            let resolve_staking_rounds_period = Duration::from_secs(1);
+            let mut resolve_staking_rounds_interval =
+                interval_at(first_check(resolve_staking_rounds_period), resolve_staking_rounds_period);
+            let claim_stakes_period = Duration::from_secs(20);
+            let mut claim_stakes_interval = interval_at(first_check(claim_stakes_period), claim_stakes_period);
             let mut finalize_locked_stakes_interval = interval(Duration::from_secs(20));
-            let mut update_pool_aum_interval = interval(Duration::from_secs(300));
+            let mut instruction_packs_interval = interval(INSTRUCTION_PACKS_INTERVAL);
+            let mut update_pool_aum_schedule =
+                TaskSchedule::new(Duration::from_secs(300), args.update_pool_aum_cron.clone());
+            let mut record_transaction_costs_interval = interval(Duration::from_secs(30));
+            // Just loaded, first refresh after a period
+            let owner_cache_refresh_period = Duration::from_secs(args.owner_cache_refresh_seconds.max(1));
+            let mut owner_cache_refresh_interval =
+                interval_at(Instant::now() + owner_cache_refresh_period, owner_cache_refresh_period);
+            // The reload in progress, if any
+            let mut owner_cache_refresh: Option<JoinHandle<()>> = None;
+            let mut write_back_owners_interval = interval(Duration::from_secs(30));
+            let mut geyser_retry_interval = interval(GEYSER_RETRY_INTERVAL);
+            let mut stream_checkpoint_interval = interval(STREAM_CHECKPOINT_INTERVAL);
+            // The first tick completes immediately
+            geyser_retry_interval.tick().await;
+            // Held by the single connection running at a time
+            let mut payer_keypair_reload_signal = payer_keypair_reload_signal.lock().await;
+            // The endpoint reports a replay it can't serve (slot older than the updates it keeps) as a stream error, before any update
+            let mut replay_pending = replay_from_slot.is_some();
 
             loop {
                 tokio::select! {
-                    _ = resolve_staking_rounds_interval.tick() => {
+                    _ = geyser_retry_interval.tick(), if degraded_mode => {
+                        log::info!("Leaving degraded mode, retrying Geyser");
+                        break;
+                    },
+                    // Key rotation: sign with the new keypair from now on, without reopening the stream nor reindexing
+                    _ = payer_keypair_reload_signal.recv() => {
+                        match args.program() {
+                            Ok(new_program) => {
+                                log::info!(
+                                    "  <*> Payer keypair reloaded: {} -> {}",
+                                    program.payer(),
+                                    new_program.payer()
+                                );
+                                program = new_program;
+                                // Confirm the transactions of the new payer from the stream, the pending ones of the previous payer are polled
+                                let request = generate_subscribe_request(
+                                    &indexed_user_staking_accounts,
+                                    &program.payer(),
+                                    commitment,
+                                    None,
+                                ).await;
+                                subscribe_tx
+                                    .send(request)
+                                    .await
+                                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                            }
+                            Err(e) => log::error!(
+                                "  <*> Failed to reload the payer keypair, still signing with {}: {:?}",
+                                program.payer(),
+                                e
+                            ),
+                        }
+                    },
+                    _ = resolve_staking_rounds_interval.tick(), if role.handles_resolves() => {
                         process_resolve_staking_rounds(
-                            &staking_round_next_resolve_time_cache,
+                            &state_manager,
+                            &claim_cache,
+                            &indexed_user_staking_accounts,
+                            &action_retries,
+                            &chain_clock,
                             &program,
+                            &pending_transactions,
                             *median_priority_fee_high.lock().await,
-                        ).await?;
+                        ).await;
+                        record_evaluation();
                     },
-                    _ = claim_stakes_interval.tick() => {
+                    _ = claim_stakes_interval.tick(), if role.handles_claims() => {
                         process_claim_stakes(
                             &claim_cache,
-                            &db,
+                            db.as_deref().expect("The claims require a DB (checked at startup)"),
+                            &state_manager,
                             &indexed_user_staking_accounts,
+                            &indexed_genesis_lock,
+                            &action_retries,
                             &program,
+                            &pending_transactions,
                             *median_priority_fee_low.lock().await,
+                            args.claim_heap_frame_bytes,
                         ).await?;
+                        record_evaluation();
                     },
-                    _ = finalize_locked_stakes_interval.tick() => {
+                    _ = finalize_locked_stakes_interval.tick(), if role.handles_claims() => {
                         process_finalize_locked_stakes(
                             &finalize_locked_stakes_cache,
                             &indexed_user_staking_accounts,
-                            &db,
+                            db.as_deref().expect("The claims require a DB (checked at startup)"),
+                            &action_retries,
                             &program,
+                            &pending_transactions,
                             *median_priority_fee_low.lock().await,
                         ).await?;
                     },
-                    _ = update_pool_aum_interval.tick() => {
-                        update_pool_aum(
+                    _ = update_pool_aum_schedule.tick(), if role.handles_resolves() => {
+                        process_update_pool_aum(
+                            &action_retries,
                             &program,
                             *median_priority_fee_low.lock().await,
-                            remaining_accounts.clone(),
-                        ).await?;
+                            &pools_remaining_accounts,
+                        ).await;
+                    },
+                    _ = instruction_packs_interval.tick() => {
+                        process_instruction_packs(&action_retries, &program, &pending_transactions).await;
+                    },
+                    _ = record_transaction_costs_interval.tick(), if db.is_some() => {
+                        let db = db.as_deref().expect("Checked by the guard");
+                        flush_transaction_costs(db).await;
+                        flush_action_log(db).await;
+                    },
+                    // The whole table is loaded apart, the mapping is swapped in once loaded (skipped while the previous reload runs)
+                    _ = owner_cache_refresh_interval.tick(), if db.is_some() && role.handles_claims() => {
+                        if owner_cache_refresh.as_ref().map_or(true, |refresh| refresh.is_finished()) {
+                            let db = Arc::clone(db.as_ref().expect("Checked by the guard"));
+                            owner_cache_refresh = Some(tokio::spawn(async move { refresh_owner_cache(&db).await }));
+                        }
+                    },
+                    _ = write_back_owners_interval.tick(), if db.is_some() => {
+                        let db = db.as_deref().expect("Checked by the guard");
+                        flush_learned_owners(db).await;
+                        flush_quarantine(db).await;
+                    },
+                    _ = stream_checkpoint_interval.tick(), if db.is_some() => {
+                        let db = db.as_deref().expect("Checked by the guard");
+                        let last_processed_slot = last_stream_slot.load(Ordering::Relaxed);
+                        // The checkpoint is only saved once the accounts changed up to it are stored
+                        // Not in degraded mode, the WebSocket slots are not bound to the commitment of the account updates
+                        if flush_user_staking_accounts(db).await && !degraded_mode {
+                            save_stream_checkpoint(Some(db), last_processed_slot).await;
+                        }
                     },
                     // Handle incoming messages with a timeout
                     result = timeout(Duration::from_secs(11), stream.next()) => {
                         match result {
                             Ok(Some(Ok(msg))) => {
-                                // Process the message
-                                process_stream_message(
-                                    Ok(msg),
-                                    &indexed_staking_accounts,
-                                    &indexed_user_staking_accounts,
-                                    &claim_cache,
-                                    &finalize_locked_stakes_cache,
-                                    &staking_round_next_resolve_time_cache,
-                                    &mut subscribe_tx,
-                                ).await?;
+                                replay_pending = false;
+                                // Drain the messages already received (bursts) before going back to the periodic tasks
+                                let mut next_msg = Some(msg);
+                                let mut batch_size = 0;
+                                let mut stream_closed = false;
+                                while let Some(msg) = next_msg.take() {
+                                    // Process the message
+                                    process_stream_message(
+                                        Ok(msg),
+                                        &state_manager,
+                                        &indexed_user_staking_accounts,
+                                        &indexed_genesis_lock,
+                                        &claim_cache,
+                                        &finalize_locked_stakes_cache,
+                                        &last_stream_slot,
+                                        &chain_clock,
+                                        &pending_transactions,
+                                        &action_retries,
+                                        &program.payer(),
+                                        commitment,
+                                        db.as_deref(),
+                                        &mut subscribe_tx,
+                                    ).await?;
+                                    record_stream_message();
+
+                                    batch_size += 1;
+                                    if batch_size >= MAX_STREAM_MESSAGES_PER_BATCH {
+                                        break;
+                                    }
+                                    match stream.next().now_or_never() {
+                                        Some(Some(Ok(msg))) => next_msg = Some(msg),
+                                        Some(Some(Err(e))) => log::warn!("Error receiving message: {:?}", e),
+                                        Some(None) => stream_closed = true,
+                                        // Nothing more received yet
+                                        None => {}
+                                    }
+                                }
+                                if stream_closed {
+                                    log::warn!("Stream closed by server - restarting connection");
+                                    break;
+                                }
+                            },
+                            Ok(Some(Err(e))) if replay_pending => {
+                                replay_rejected.store(true, Ordering::Relaxed);
+                                return Err(backoff::Error::transient(anyhow::anyhow!(
+                                    "Replay from slot {} rejected, refetching all accounts: {:?}",
+                                    replay_from_slot.unwrap_or_default(),
+                                    e
+                                )));
                             },
                             Ok(Some(Err(e))) => {
                                 log::warn!("Error receiving message: {:?}", e);
@@ -564,121 +2078,575 @@ async fn main() -> anyhow::Result<()> {
             Ok::<(), backoff::Error<anyhow::Error>>(())
         }
         .inspect_err(|error| log::error!("failed to connect: {error}"))
-    })
-    .await
-    .map_err(Into::into)
+    };
+
+    // A connection ending without error (stream closed by the server, leaving the degraded mode) reconnects right away
+    loop {
+        retry(reconnect_backoff.clone(), &mut run_connection).await?;
+    }
+}
+
+// Reconnection backoff started over once a connection gets to the core loop: the max elapsed time bounds the consecutive
+// failed reconnections, not the uptime
+#[derive(Clone)]
+struct ReconnectBackoff {
+    backoff: ExponentialBackoff,
+    connected: Arc<AtomicBool>,
 }
 
+impl Backoff for ReconnectBackoff {
+    fn reset(&mut self) {
+        self.backoff.reset();
+    }
+
+    fn next_backoff(&mut self) -> Option<Duration> {
+        if self.connected.swap(false, Ordering::Relaxed) {
+            self.backoff.reset();
+        }
+        self.backoff.next_backoff()
+    }
+}
+
+// Resolves are critical and not subject to the in-flight transactions limit (a handful of Staking accounts)
 async fn process_resolve_staking_rounds(
-    staking_round_next_resolve_time_cache: &StakingRoundNextResolveTimeCacheThreadSafe,
+    state_manager: &StateManager,
+    claim_cache: &UserStakingClaimCacheThreadSafe,
+    indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+    action_retries: &ActionRetriesThreadSafe,
+    chain_clock: &ChainClockThreadSafe,
     program: &Program<Arc<Keypair>>,
+    pending_transactions: &PendingTransactionsThreadSafe,
     median_priority_fee: u64,
-) -> Result<(), backoff::Error<anyhow::Error>> {
-    let current_time = chrono::Utc::now().timestamp();
-    let cache = staking_round_next_resolve_time_cache.read().await;
+) {
+    if are_transactions_suspended() {
+        return;
+    }
+
+    // The round end is checked against the onchain clock, which stops while the chain is halted
+    let current_time = chain_clock.read().await.now();
+    // Pop the due resolves (the state manager keeps applying the stream updates during the sends), along with the end of
+    // the current rounds for the overdue gauges
+    let due_resolves_and_round_end_times = state_manager
+        .call(move |state| {
+            let round_end_times: Vec<(Pubkey, Option<i64>)> = state
+                .next_resolve_times
+                .keys()
+                .map(|staking_account_key| {
+                    (
+                        *staking_account_key,
+                        state.round_end_time(staking_account_key),
+                    )
+                })
+                .collect();
+            (state.pop_due_resolves(current_time), round_end_times)
+        })
+        .await;
+    let (due_resolves, round_end_times) = match due_resolves_and_round_end_times {
+        Ok(due_resolves_and_round_end_times) => due_resolves_and_round_end_times,
+        Err(e) => {
+            log::error!("   <> Failed to pop the due resolves: {:?}", e);
+            return;
+        }
+    };
+    let overdue_seconds_of = |round_end_time: Option<i64>| {
+        round_end_time.map_or(0, |round_end_time| (current_time - round_end_time).max(0))
+    };
+    for (staking_account_key, round_end_time) in round_end_times {
+        STAKING_ROUND_OVERDUE_SECONDS
+            .with_label_values(&[staking_name(&staking_account_key).unwrap_or(NO_STAKING_LABEL)])
+            .set(overdue_seconds_of(round_end_time));
+    }
+
+    // The action retries are only locked for each check and record, not across the simulations and sends
+    for (staking_account_key, next_resolve_time, round_end_time) in due_resolves {
+        // Whatever happens below, the resolve goes back in the queue - unless a Staking account update already rescheduled it
+        let reschedule = move |rescheduled_time: i64| async move {
+            let rescheduled = state_manager
+                .call(move |state| {
+                    state.reschedule_resolve(
+                        &staking_account_key,
+                        next_resolve_time,
+                        rescheduled_time,
+                    )
+                })
+                .await;
+            if let Err(e) = rescheduled {
+                log::error!(
+                    "   <> Failed to reschedule the resolve of {}: {:?}",
+                    staking_account_key,
+                    e
+                );
+            }
+        };
+
+        let action_id = ActionId::new(KeeperAction::ResolveStakingRound, staking_account_key);
+        // Back off if the previous attempts for this Staking account failed
+        if !action_retries
+            .read()
+            .await
+            .is_ready(&action_id, current_time)
+        {
+            reschedule(next_resolve_time).await;
+            continue;
+        }
+
+        // Idempotency guard: the round may already have been resolved (by another keeper or a previous send) while
+        // the cache wasn't refreshed yet - the current round of the indexed account must be over to be resolved
+        if let Some(round_end_time) = round_end_time {
+            if current_time < round_end_time {
+                log::info!(
+                    "  <> Staking round of {} already resolved (current round started at {}) - skipping resolve",
+                    staking_account_key,
+                    round_end_time - ROUND_MIN_DURATION_SECONDS
+                );
+                reschedule(round_end_time).await;
+                continue;
+            }
+        }
 
-    for (staking_account_key, next_resolve_time) in cache.iter() {
-        if current_time >= *next_resolve_time {
-            if let Err(e) = handlers::resolve_staking_round::resolve_staking_round(
+        // The sends keep failing or not landing: rewards accrual is blocked for all the stakers, bid more
+        let overdue_seconds = overdue_seconds_of(round_end_time);
+        let overdue_round = is_round_overdue(overdue_seconds);
+        if overdue_round {
+            log::error!(
+                "  <*> Staking round of {} still unresolved {} minutes after its end - escalating the priority fee",
                 staking_account_key,
-                program,
-                median_priority_fee,
+                overdue_seconds / 60
+            );
+        }
+
+        // A due claim of the same staking side goes along when this instance also does the claims
+        let combined_claim = if current_role().handles_claims() {
+            take_combined_claim(
+                state_manager,
+                claim_cache,
+                indexed_user_staking_accounts,
+                action_retries,
+                &staking_account_key,
+                current_time,
+            )
+            .await
+        } else {
+            None
+        };
+
+        let outcome = handlers::resolve_staking_round::resolve_staking_round(
+            &staking_account_key,
+            program,
+            pending_transactions,
+            median_priority_fee,
+            overdue_round,
+            combined_claim.as_ref(),
+        )
+        .await;
+        if let Some(combined_claim) = &combined_claim {
+            // Sent or not, checked again by the next claim loop (in case the transaction doesn't land)
+            if matches!(outcome, Ok(true)) {
+                action_retries.write().await.record_success(&ActionId::new(
+                    KeeperAction::ClaimStakes,
+                    combined_claim.user_staking_account_key,
+                ));
+            }
+            if let Err(e) = schedule_claim(
+                state_manager,
+                combined_claim.user_staking_account_key,
+                current_time,
             )
             .await
             {
-                log::error!("Error resolving staking round: {}", e);
+                log::error!(
+                    "   <> Failed to queue the claim of {}: {:?}",
+                    combined_claim.user_staking_account_key,
+                    e
+                );
+            }
+        }
+        match outcome {
+            Ok(_) => {
+                action_retries.write().await.record_success(&action_id);
+                // Optimistically push the next resolve time, the Staking account update will overwrite it once the round is resolved
+                reschedule(current_time + RESOLVE_STAKING_ROUND_GRACE_PERIOD_SECONDS).await;
+            }
+            Err(e) => {
+                action_retries.write().await.record_failure(
+                    &action_id,
+                    current_time,
+                    &handler_error(e),
+                    staking_name(&staking_account_key).unwrap_or(NO_STAKING_LABEL),
+                );
+                // Popped again by the next checks, attempted once its backoff expires
+                reschedule(next_resolve_time).await;
             }
         }
     }
-    Ok(())
+}
+
+// The first due claim of the staking side of the Staking account that can go in its resolve transaction: owner known
+// without querying the DB, locked stakes claimable in a single transaction - the claims needing more go through
+// process_claim_stakes
+async fn take_combined_claim(
+    state_manager: &StateManager,
+    claim_cache: &UserStakingClaimCacheThreadSafe,
+    indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+    action_retries: &ActionRetriesThreadSafe,
+    staking_account_key: &Pubkey,
+    current_time: i64,
+) -> Option<CombinedClaim> {
+    let staking_type = if *staking_account_key == get_staking_pda(&ADX_MINT).0 {
+        StakingType::LM
+    } else if *staking_account_key == get_staking_pda(&ALP_MINT).0 {
+        StakingType::LP
+    } else {
+        return None;
+    };
+    let user_staking_accounts = indexed_user_staking_accounts.shard(staking_type);
+
+    let due_claims = match pop_due_claims(state_manager, current_time).await {
+        Ok(due_claims) => due_claims,
+        Err(e) => {
+            log::error!("   <> Failed to pop the due claims: {:?}", e);
+            return None;
+        }
+    };
+    // The first one fitting, the other due claims stay queued
+    let mut combined_claim = None;
+    let mut still_due: Vec<(Pubkey, i64)> = vec![];
+    let action_retries = action_retries.read().await;
+    let select = |user_staking_account_key: &Pubkey| {
+        let claim_times = *claim_cache.get(user_staking_account_key)?;
+        // Queued before a reschedule, or forced by the unclaimed rounds (left to process_claim_stakes)
+        if current_time < claim_due_time(user_staking_account_key, &claim_times)? {
+            return None;
+        }
+        let action_id = ActionId::new(KeeperAction::ClaimStakes, *user_staking_account_key);
+        if is_quarantined(user_staking_account_key)
+            || !action_retries.is_ready(&action_id, current_time)
+        {
+            return None;
+        }
+        let owner_pubkey = cached_owner(user_staking_account_key)?;
+        let user_staking_account = *user_staking_accounts.get(user_staking_account_key)?;
+        Some(CombinedClaim {
+            user_staking_account_key: *user_staking_account_key,
+            owner_pubkey,
+            locked_stake_indexes: single_batch_claim(&user_staking_account)?,
+        })
+    };
+    for user_staking_account_key in due_claims {
+        if combined_claim.is_none() {
+            combined_claim = select(&user_staking_account_key);
+            if combined_claim.is_some() {
+                continue;
+            }
+        }
+        still_due.push((user_staking_account_key, current_time));
+    }
+    drop(action_retries);
+
+    if let Err(e) = schedule_claims(state_manager, still_due).await {
+        log::error!("   <> Failed to queue the due claims: {:?}", e);
+    }
+    combined_claim
 }
 
 pub async fn process_claim_stakes(
     claim_cache: &UserStakingClaimCacheThreadSafe,
-    db: &tokio_postgres::Client,
+    db: &Db,
+    state_manager: &StateManager,
     indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+    indexed_genesis_lock: &IndexedGenesisLockThreadSafe,
+    action_retries: &ActionRetriesThreadSafe,
     program: &Program<Arc<Keypair>>,
+    pending_transactions: &PendingTransactionsThreadSafe,
     median_priority_fee: u64,
+    claim_heap_frame_bytes: u32,
 ) -> Result<(), backoff::Error<anyhow::Error>> {
+    if are_transactions_suspended() {
+        return Ok(());
+    }
+
+    // Claims are not critical, they wait for the next day once the fee budget is spent
+    if is_daily_fee_budget_exhausted() {
+        log::debug!("Daily fee budget exhausted - skipping claims");
+        return Ok(());
+    }
+
     let current_time = chrono::Utc::now().timestamp();
-    let mut claim_cache = claim_cache.write().await;
+    // Snapshot what the claims depend on, no lock is held across the DB queries and transaction sends (the stream keeps updating the caches meanwhile)
+    let genesis_lock = *indexed_genesis_lock.read().await;
+    let genesis_campaign_active = is_genesis_campaign_active(genesis_lock.as_ref(), current_time);
+    let (lm_staking_account, lp_staking_account) = state_manager
+        .call(|state| {
+            (
+                state.staking_account(StakingType::LM),
+                state.staking_account(StakingType::LP),
+            )
+        })
+        .await
+        .map_err(backoff::Error::permanent)?;
+    // Only the queued accounts are checked (due by time, or forced by the resolved rounds), not the whole claim cache
+    // Estimate the rewards each claim would collect (unknown if the Staking account is not indexed)
+    let popped_claims = pop_due_claims(state_manager, current_time)
+        .await
+        .map_err(backoff::Error::permanent)?;
+    // The popped claims checked too early, or backing off, are queued again at their time
+    let mut requeued_claims: Vec<(Pubkey, i64)> = vec![];
+    let mut due_claims: Vec<(Pubkey, ActionId, Option<PendingRewards>)> = {
+        let action_retries = action_retries.read().await;
+        let _action_retries_hold = LockHoldTimer::start("action_retries");
+        popped_claims
+            .into_iter()
+            .filter_map(|user_staking_account_key| {
+                // Closed or without stakes since it was queued
+                let claim_times = *claim_cache.get(&user_staking_account_key)?;
+                // No stake, nothing to claim
+                let last_claim_time = claim_times.oldest()?;
+                let next_claim_time = claim_times.next_claim_time()?;
+                let user_staking_account =
+                    indexed_user_staking_accounts.get(&user_staking_account_key);
+                let staking_account = user_staking_account.as_ref().and_then(|user_staking_account| {
+                    match user_staking_account.get_staking_type() {
+                        StakingType::LM => lm_staking_account.as_ref(),
+                        StakingType::LP => lp_staking_account.as_ref(),
+                    }
+                });
+
+                // Whichever of the locked and liquid stakes reaches its threshold first
+                let due_time = next_claim_time + claim_jitter_seconds(&user_staking_account_key);
+                let due_by_time = current_time >= due_time;
+                // Rounds resolving faster than ROUND_MIN_DURATION_SECONDS fill the 32 rounds buffer before the threshold
+                let unclaimed_rounds = user_staking_account
+                    .as_ref()
+                    .zip(staking_account)
+                    .map_or(0, |(user_staking_account, staking_account)| {
+                        unclaimed_resolved_rounds(user_staking_account, staking_account)
+                    });
+                // At most once per round, so the accounts skipped (nothing to claim) or postponed aren't retried every loop
+                let forced_time = last_claim_time + ROUND_MIN_DURATION_SECONDS;
+                let due_by_rounds =
+                    unclaimed_rounds >= FORCE_CLAIM_UNCLAIMED_ROUNDS && current_time >= forced_time;
+                // Rescheduled since it was queued (or queued by a retry), checked again once due
+                if !due_by_time && !due_by_rounds {
+                    let requeue_time = if unclaimed_rounds >= FORCE_CLAIM_UNCLAIMED_ROUNDS {
+                        due_time.min(forced_time)
+                    } else {
+                        due_time
+                    };
+                    requeued_claims.push((user_staking_account_key, requeue_time));
+                    return None;
+                }
+
+                // Released through the API, which queues the claim again
+                if is_quarantined(&user_staking_account_key) {
+                    return None;
+                }
+                // Back off if the previous attempts for this UserStaking account failed - the abandoned claims wait for an account update
+                let action_id = ActionId::new(KeeperAction::ClaimStakes, user_staking_account_key);
+                if !action_retries.is_ready(&action_id, current_time) {
+                    if let Some(next_attempt_time) =
+                        action_retries.next_attempt_time(&action_id, current_time)
+                    {
+                        requeued_claims.push((user_staking_account_key, next_attempt_time));
+                    }
+                    return None;
+                }
+
+                if !due_by_time {
+                    log::info!(
+                        "  <> {} unclaimed resolved rounds for UserStaking account {} - forcing claim",
+                        unclaimed_rounds,
+                        user_staking_account_key
+                    );
+                }
+                let pending_rewards = user_staking_account.as_ref().zip(staking_account).map(
+                    |(user_staking_account, staking_account)| {
+                        estimate_pending_rewards(user_staking_account, staking_account)
+                    },
+                );
+                Some((user_staking_account_key, action_id, pending_rewards))
+            })
+            .collect()
+    };
+    schedule_claims(state_manager, requeued_claims)
+        .await
+        .map_err(backoff::Error::permanent)?;
+    // Serve the largest pending rewards first, the dust accounts wait when more claims are due than can be sent
+    due_claims.sort_by_key(|(_, _, pending_rewards)| Reverse(pending_rewards.unwrap_or_default()));
 
     // Process a max of 10 claims per loop
     let max_claims_per_loop = 10;
     let mut claim_count = 0;
-    let claim_cache_shallow_copy = claim_cache.clone();
 
-    for (user_staking_account_key, last_claim_time) in claim_cache_shallow_copy
-        .iter()
-        .filter(|(_, last_claim_time)| last_claim_time.is_some())
-    // If the last claim time is None, it means the user has no stake and we should not claim for them
+    // The due claims not processed by this loop stay due
+    let requeue_remaining_claims =
+        |remaining_claims: &[(Pubkey, ActionId, Option<PendingRewards>)]| {
+            schedule_claims(
+                state_manager,
+                remaining_claims
+                    .iter()
+                    .map(|(user_staking_account_key, _, _)| {
+                        (*user_staking_account_key, current_time)
+                    })
+                    .collect(),
+            )
+        };
+
+    for (claim_index, (user_staking_account_key, action_id, pending_rewards)) in
+        due_claims.iter().enumerate()
     {
+        // Nothing accrued since the last claim: no transaction (nor priority fee) for it, checked again after the threshold
+        if pending_rewards.is_some_and(|pending_rewards| pending_rewards.is_zero()) {
+            log::info!(
+                "  <> No pending rewards for UserStaking account {} - skipping claim",
+                user_staking_account_key
+            );
+            reschedule_claim(
+                state_manager,
+                claim_cache,
+                user_staking_account_key,
+                |claim_times| claim_times.claimed_at(current_time),
+            )
+            .await
+            .map_err(backoff::Error::permanent)?;
+            continue;
+        }
+
         if claim_count >= max_claims_per_loop {
             log::info!("Batch size reached - stopping claim processing until next loop");
+            requeue_remaining_claims(&due_claims[claim_index..])
+                .await
+                .map_err(backoff::Error::permanent)?;
+            break;
+        }
+        if !has_in_flight_capacity(pending_transactions).await {
+            log::info!(
+                "Too many transactions in flight - queuing the remaining claims until next loop"
+            );
+            requeue_remaining_claims(&due_claims[claim_index..])
+                .await
+                .map_err(backoff::Error::permanent)?;
             break;
         }
-        if current_time >= last_claim_time.unwrap() + AUTO_CLAIM_THRESHOLD_SECONDS {
-            // retrieve the owner of the UserStaking account
-            if let Some(owner_pubkey) = get_owner_pubkey(db, user_staking_account_key).await? {
-                // Retrieve the UserStaking account
-                let indexed_user_staking_accounts_read = indexed_user_staking_accounts.read().await;
-                let user_staking_account = indexed_user_staking_accounts_read
-                    .get(user_staking_account_key)
-                    .expect("UserStaking account not found in the indexed user staking accounts");
-
-                // Retrieve the staked token mint - Which might not be defined for some account as it was a late addition to the program.
-                let staked_token_mint = match user_staking_account.get_staking_type() {
-                    StakingType::LM => ADX_MINT,
-                    StakingType::LP => ALP_MINT,
-                };
 
-                let has_stake = user_staking_account
-                    .locked_stakes
-                    .iter()
-                    .any(|ls| ls.amount != 0)
-                    || user_staking_account.liquid_stake.amount != 0;
-
-                if has_stake {
-                    let outcome = handlers::claim_stakes(
-                        user_staking_account_key,
-                        &owner_pubkey,
-                        program,
-                        median_priority_fee,
-                        &staked_token_mint,
-                    )
-                    .await
-                    .map_err(|e| backoff::Error::transient(anyhow::anyhow!(e)))?;
+        // retrieve the owner of the UserStaking account
+        let owner_pubkey = db.get_owner_pubkey(user_staking_account_key).await;
+        record_db_outcome(owner_pubkey.is_ok());
+        if let Some(owner_pubkey) = owner_pubkey? {
+            // Retrieve the UserStaking account - It may have been closed since the snapshot
+            let Some(user_staking_account) = indexed_user_staking_accounts
+                .get(user_staking_account_key)
+                .map(|user_staking_account| *user_staking_account)
+            else {
+                continue;
+            };
 
-                    match outcome {
-                        ClaimStakeOutcome::Success => {
-                            // Do nothing, the on-chain account modification will update the cache from the message filtering
-                        }
-                        ClaimStakeOutcome::NoRewardTokens => {
-                            // On chain account won't be updated here, so we have to update the cache manually
-                            claim_cache.insert(
-                                *user_staking_account_key,
-                                Some(current_time + AUTO_CLAIM_THRESHOLD_SECONDS),
-                            );
-                        }
-                        ClaimStakeOutcome::Error(e) => {
-                            return Err(backoff::Error::transient(anyhow::anyhow!(e)));
-                        }
+            // Retrieve the staked token mint - Which might not be defined for some account as it was a late addition to the program.
+            let staked_token_mint = match user_staking_account.get_staking_type() {
+                StakingType::LM => ADX_MINT,
+                StakingType::LP => ALP_MINT,
+            };
+
+            let has_stake = user_staking_account
+                .locked_stakes
+                .iter()
+                .any(|ls| ls.amount != 0)
+                || user_staking_account.liquid_stake.amount != 0;
+
+            if has_stake {
+                let outcome = handlers::claim_stakes(
+                    user_staking_account_key,
+                    &user_staking_account,
+                    &owner_pubkey,
+                    program,
+                    pending_transactions,
+                    median_priority_fee,
+                    &staked_token_mint,
+                    claim_heap_frame_bytes,
+                    genesis_campaign_active,
+                )
+                .await;
+
+                match outcome {
+                    Ok(ClaimStakeOutcome::Success) => {
+                        // Do nothing, the on-chain account modification will update the cache from the message filtering
+                        // Checked again by the next loop meanwhile, in case the transaction doesn't land
+                        action_retries.write().await.record_success(action_id);
+                        schedule_claim(state_manager, *user_staking_account_key, current_time)
+                            .await
+                            .map_err(backoff::Error::permanent)?;
+                    }
+                    Ok(ClaimStakeOutcome::NoRewardTokens) => {
+                        // On chain account won't be updated here, so we have to update the cache manually
+                        action_retries.write().await.record_success(action_id);
+                        reschedule_claim(
+                            state_manager,
+                            claim_cache,
+                            user_staking_account_key,
+                            |claim_times| {
+                                claim_times.claimed_at(current_time + AUTO_CLAIM_THRESHOLD_SECONDS)
+                            },
+                        )
+                        .await
+                        .map_err(backoff::Error::permanent)?;
+                    }
+                    Ok(ClaimStakeOutcome::GenesisLocked(_)) => {
+                        // Nothing to retry before the end of the genesis campaign
+                        action_retries.write().await.record_success(action_id);
+                        let retry_time = genesis_lock
+                            .as_ref()
+                            .map(genesis_campaign_end_time)
+                            .filter(|end_time| *end_time > current_time)
+                            .unwrap_or(current_time + ROUND_MIN_DURATION_SECONDS);
+                        reschedule_claim(
+                            state_manager,
+                            claim_cache,
+                            user_staking_account_key,
+                            |claim_times| claim_times.due_at(retry_time),
+                        )
+                        .await
+                        .map_err(backoff::Error::permanent)?;
+                    }
+                    Ok(ClaimStakeOutcome::Error(e)) => {
+                        let attempts = action_retries.write().await.record_failure(
+                            action_id,
+                            current_time,
+                            &e,
+                            staking_label(&staked_token_mint),
+                        );
+                        requeue_failed_claim(state_manager, user_staking_account_key, &attempts)
+                            .await
+                            .map_err(backoff::Error::permanent)?;
+                    }
+                    Err(e) => {
+                        let attempts = action_retries.write().await.record_failure(
+                            action_id,
+                            current_time,
+                            &handler_error(e),
+                            staking_label(&staked_token_mint),
+                        );
+                        requeue_failed_claim(state_manager, user_staking_account_key, &attempts)
+                            .await
+                            .map_err(backoff::Error::permanent)?;
                     }
                 }
-                claim_count += 1;
             } else {
-                log::warn!(
-                    "No owner found in DB for UserStaking account: {} - Skipping claim",
-                    user_staking_account_key
-                );
-
-                // Remove the user without owner in db for now, will be reprocessed when the owner is found
+                // All the stakes were withdrawn, nothing to claim until new ones appear (account update)
                 claim_cache.remove(user_staking_account_key);
-                log::warn!(
-                    "Removed UserStaking account from claim cache: {} - will be reprocessed when his account updates",
-                    user_staking_account_key
-                );
+                continue;
             }
+            claim_count += 1;
+        } else {
+            log::warn!(
+                "No owner found in DB for UserStaking account: {} - Skipping claim",
+                user_staking_account_key
+            );
+
+            // Remove the user without owner in db for now, will be reprocessed when the owner is found
+            claim_cache.remove(user_staking_account_key);
+            log::warn!(
+                "Removed UserStaking account from claim cache: {} - will be reprocessed when his account updates",
+                user_staking_account_key
+            );
         }
     }
     Ok(())
@@ -687,38 +2655,97 @@ pub async fn process_claim_stakes(
 async fn process_finalize_locked_stakes(
     finalize_locked_stakes_cache: &FinalizeLockedStakesCacheThreadSafe,
     indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
-    db: &tokio_postgres::Client,
+    db: &Db,
+    action_retries: &ActionRetriesThreadSafe,
     program: &Program<Arc<Keypair>>,
+    pending_transactions: &PendingTransactionsThreadSafe,
     median_priority_fee: u64,
 ) -> Result<(), backoff::Error<anyhow::Error>> {
+    if are_transactions_suspended() {
+        return Ok(());
+    }
+
     let current_time = chrono::Utc::now().timestamp();
-    let finalize_locked_stakes_cache = finalize_locked_stakes_cache.read().await;
-
-    for (user_staking_account_key, locked_stakes) in finalize_locked_stakes_cache.iter() {
-        for (stake_resolution_thread_id, end_time) in locked_stakes.iter() {
-            if current_time >= *end_time {
-                if let Some(owner_pubkey) = get_owner_pubkey(db, user_staking_account_key).await? {
-                    let indexed_user_staking_accounts_read =
-                        indexed_user_staking_accounts.read().await;
-                    let user_staking_account = indexed_user_staking_accounts_read
-                        .get(user_staking_account_key)
-                        .expect(
-                            "UserStaking account not found in the indexed user staking accounts",
-                        );
-                    let staked_token_mint = match user_staking_account.get_staking_type() {
-                        StakingType::LM => ADX_MINT,
-                        StakingType::LP => ALP_MINT,
-                    };
-                    handlers::finalize_locked_stake(
-                        user_staking_account_key,
-                        &owner_pubkey,
-                        program,
-                        median_priority_fee,
-                        &staked_token_mint,
-                        *stake_resolution_thread_id,
-                    )
-                    .await
-                    .map_err(|e| backoff::Error::transient(anyhow::anyhow!(e)))?;
+    // Snapshot the locked stakes due for finalization, no lock is held across the DB queries and transaction sends
+    let due_locked_stakes: Vec<(Pubkey, u64, ActionId)> = {
+        let action_retries = action_retries.read().await;
+        let _action_retries_hold = LockHoldTimer::start("action_retries");
+        finalize_locked_stakes_cache
+            .iter()
+            .flat_map(|entry| {
+                let user_staking_account_key = *entry.key();
+                entry
+                    .value()
+                    .iter()
+                    .filter(|(_, end_time)| current_time >= **end_time)
+                    .map(|(stake_resolution_thread_id, _)| {
+                        (
+                            user_staking_account_key,
+                            *stake_resolution_thread_id,
+                            ActionId::finalize_locked_stake(
+                                user_staking_account_key,
+                                *stake_resolution_thread_id,
+                            ),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            // Back off if the previous attempts for this locked stake failed
+            .filter(|(user_staking_account_key, _, action_id)| {
+                action_retries.is_ready(action_id, current_time)
+                    && !is_quarantined(user_staking_account_key)
+            })
+            .collect()
+    };
+
+    for (user_staking_account_key, stake_resolution_thread_id, action_id) in
+        due_locked_stakes.iter()
+    {
+        // Already simulated and waiting to be sent, or sent and waiting to land
+        if is_queued(action_id) || is_pending(pending_transactions, action_id).await {
+            continue;
+        }
+        if !has_in_flight_capacity(pending_transactions).await {
+            log::info!("Too many transactions in flight - queuing the remaining finalizations until next loop");
+            return Ok(());
+        }
+
+        let owner_pubkey = db.get_owner_pubkey(user_staking_account_key).await;
+        record_db_outcome(owner_pubkey.is_ok());
+        if let Some(owner_pubkey) = owner_pubkey? {
+            // The UserStaking account may have been closed since the snapshot
+            let Some(user_staking_account) = indexed_user_staking_accounts
+                .get(user_staking_account_key)
+                .map(|user_staking_account| *user_staking_account)
+            else {
+                continue;
+            };
+            let staked_token_mint = match user_staking_account.get_staking_type() {
+                StakingType::LM => ADX_MINT,
+                StakingType::LP => ALP_MINT,
+            };
+            let outcome = prepare_finalize_locked_stake(
+                user_staking_account_key,
+                &owner_pubkey,
+                program,
+                median_priority_fee,
+                &staked_token_mint,
+                *stake_resolution_thread_id,
+                false,
+            )
+            .await;
+            // Sent (and recorded) with the other due actions by process_instruction_packs
+            match outcome {
+                Ok(due_instruction) => {
+                    queue_due_instruction(due_instruction);
+                }
+                Err(e) => {
+                    action_retries.write().await.record_failure(
+                        action_id,
+                        current_time,
+                        &handler_error(e),
+                        staking_label(&staked_token_mint),
+                    );
                 }
             }
         }
@@ -726,27 +2753,156 @@ async fn process_finalize_locked_stakes(
     Ok(())
 }
 
-async fn get_owner_pubkey(
-    db: &tokio_postgres::Client,
-    user_staking_account_key: &Pubkey,
-) -> Result<Option<Pubkey>, backoff::Error<anyhow::Error>> {
-    let rows = db
-        .query(
-            "SELECT user_pubkey FROM ref_user_staking WHERE user_staking_pubkey = $1::TEXT",
-            &[&user_staking_account_key.to_string()],
-        )
-        .await
-        .map_err(|e| backoff::Error::transient(e.into()))?;
+// The custodies of the pool, then their oracles and their trade oracles (when different)
+fn pool_aum_remaining_accounts(
+    pool: &Pool,
+    indexed_custodies: &HashMap<Pubkey, Custody>,
+) -> Vec<AccountMeta> {
+    let mut custodies_accounts: Vec<AccountMeta> = vec![];
+    let mut custodies_oracle_accounts: Vec<AccountMeta> = vec![];
+    let mut custodies_trade_oracle_accounts: Vec<AccountMeta> = vec![];
+    for key in pool.custodies.iter() {
+        if key != &Pubkey::default() {
+            custodies_accounts.push(AccountMeta {
+                pubkey: *key,
+                is_signer: false,
+                is_writable: false,
+            });
 
-    if let Some(row) = rows.first() {
-        Ok(Some(
-            Pubkey::from_str(row.get::<_, String>(0).as_str()).expect("Invalid pubkey"),
-        ))
-    } else {
-        log::debug!(
-            "No owner found in DB for UserStaking account: {}",
-            user_staking_account_key
-        );
-        Ok(None)
+            let oracle_key = indexed_custodies[key].oracle;
+            custodies_oracle_accounts.push(AccountMeta {
+                pubkey: oracle_key,
+                is_signer: false,
+                is_writable: false,
+            });
+
+            let trade_oracle_key = indexed_custodies[key].trade_oracle;
+            if trade_oracle_key != oracle_key {
+                custodies_trade_oracle_accounts.push(AccountMeta {
+                    pubkey: trade_oracle_key,
+                    is_signer: false,
+                    is_writable: false,
+                });
+            }
+        }
+    }
+
+    [
+        custodies_accounts,
+        custodies_oracle_accounts,
+        custodies_trade_oracle_accounts,
+    ]
+    .concat()
+}
+
+async fn process_update_pool_aum(
+    action_retries: &ActionRetriesThreadSafe,
+    program: &Program<Arc<Keypair>>,
+    median_priority_fee: u64,
+    pools_remaining_accounts: &[(Pubkey, Vec<AccountMeta>)],
+) {
+    if are_transactions_suspended() {
+        return;
+    }
+
+    let current_time = chrono::Utc::now().timestamp();
+    // Checked under a short read lock, not held while the updates are prepared
+    let due_pools: Vec<&(Pubkey, Vec<AccountMeta>)> = {
+        let action_retries = action_retries.read().await;
+        pools_remaining_accounts
+            .iter()
+            .filter(|(pool_key, _)| {
+                let action_id = ActionId::new(KeeperAction::UpdatePoolAum, *pool_key);
+                !is_queued(&action_id) && action_retries.is_ready(&action_id, current_time)
+            })
+            .collect()
+    };
+    for (pool_key, remaining_accounts) in due_pools {
+        // Sent (and recorded) with the other due actions by process_instruction_packs
+        queue_due_instruction(prepare_update_pool_aum(
+            program,
+            median_priority_fee,
+            pool_key,
+            remaining_accounts.clone(),
+        ));
+    }
+}
+
+// Send the actions queued by the handlers (finalizations, AUM updates), packed in as few transactions as possible -
+// busy periods (many locked stakes ending together) cost fewer transactions and fees
+async fn process_instruction_packs(
+    action_retries: &ActionRetriesThreadSafe,
+    program: &Program<Arc<Keypair>>,
+    pending_transactions: &PendingTransactionsThreadSafe,
+) {
+    let current_time = chrono::Utc::now().timestamp();
+    // The packed transactions that failed onchain (sent with skip_preflight), their actions back off and go alone next
+    let failed_packed_actions = take_failed_packed_actions();
+    if !failed_packed_actions.is_empty() {
+        let failure = anyhow::anyhow!("Packed transaction failed onchain");
+        let mut action_retries = action_retries.write().await;
+        for (action_id, staking) in failed_packed_actions {
+            action_retries.record_failure(&action_id, current_time, &failure, staking);
+        }
+    }
+
+    if are_transactions_suspended() {
+        return;
+    }
+    let due_instructions = take_due_instructions();
+    if due_instructions.is_empty() {
+        return;
+    }
+
+    let packs = pack_instructions(&program.payer(), due_instructions);
+    for (index, pack) in packs.iter().enumerate() {
+        if !has_in_flight_capacity(pending_transactions).await {
+            log::info!(
+                "Too many transactions in flight - queuing the remaining actions until next loop"
+            );
+            requeue_due_instructions(
+                packs[index..]
+                    .iter()
+                    .flat_map(|pack| pack.due_instructions.iter().cloned())
+                    .collect(),
+            );
+            return;
+        }
+
+        let outcome = send_instruction_pack(program, pending_transactions, pack).await;
+        let mut action_retries = action_retries.write().await;
+        match outcome {
+            Ok(()) => {
+                for due_instruction in pack.due_instructions.iter() {
+                    action_retries.record_success(&due_instruction.action_id);
+                }
+            }
+            Err(e) if pack.due_instructions.len() > 1 => {
+                // Counted once: the failing action is unknown, each one is sent on its own next, its failure recorded
+                // against it only (a program error of one must not back off nor quarantine the others)
+                log::warn!(
+                    "   <> Failed to send the {} packed actions, sending them one by one: {:?}",
+                    pack.due_instructions.len(),
+                    handler_error(e)
+                );
+                requeue_due_instructions_alone(pack.due_instructions.clone());
+            }
+            Err(e) => {
+                let due_instruction = &pack.due_instructions[0];
+                action_retries.record_failure(
+                    &due_instruction.action_id,
+                    current_time,
+                    &handler_error(e),
+                    due_instruction.staking,
+                );
+            }
+        }
+    }
+}
+
+// The handlers still return backoff errors, the action retries only keep the underlying error
+fn handler_error(error: backoff::Error<anyhow::Error>) -> anyhow::Error {
+    match error {
+        backoff::Error::Permanent(e) | backoff::Error::Transient { err: e, .. } => e,
     }
 }
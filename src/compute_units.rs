@@ -0,0 +1,109 @@
+use {
+    crate::{
+        tx_tracker::KeeperAction, DEFAULT_CLAIM_STAKES_CU_LIMIT,
+        DEFAULT_FINALIZE_LOCKED_STAKE_CU_LIMIT, DEFAULT_RESOLVE_STAKING_ROUND_CU_LIMIT,
+        DEFAULT_UPDATE_POOL_AUM_CU_LIMIT,
+    },
+    std::{
+        collections::{HashMap, VecDeque},
+        sync::{LazyLock, Mutex, OnceLock},
+    },
+};
+
+// Number of landed transactions kept per action to compute the percentiles
+const CU_OBSERVATIONS_WINDOW: usize = 200;
+// Below this number of observations, the static CU limits are used
+const CU_OBSERVATIONS_MIN_SAMPLES: usize = 10;
+const CU_LIMIT_PERCENTILE: f64 = 0.99;
+// Margin applied on top of the observed percentile
+const CU_LIMIT_MARGIN: f64 = 1.1;
+// Max compute units a transaction can request
+pub const MAX_CU_LIMIT: u32 = 1_400_000;
+
+// Static CU limits of each action, set once at startup so they can be re-tuned after a program upgrade without a release
+static CU_LIMITS: OnceLock<CuLimits> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy)]
+pub struct CuLimits {
+    pub resolve_staking_round: u32,
+    pub claim_stakes: u32,
+    pub finalize_locked_stake: u32,
+    pub update_pool_aum: u32,
+}
+
+impl Default for CuLimits {
+    fn default() -> Self {
+        Self {
+            resolve_staking_round: DEFAULT_RESOLVE_STAKING_ROUND_CU_LIMIT,
+            claim_stakes: DEFAULT_CLAIM_STAKES_CU_LIMIT,
+            finalize_locked_stake: DEFAULT_FINALIZE_LOCKED_STAKE_CU_LIMIT,
+            update_pool_aum: DEFAULT_UPDATE_POOL_AUM_CU_LIMIT,
+        }
+    }
+}
+
+pub fn init_cu_limits(cu_limits: CuLimits) {
+    let cu_limits = CuLimits {
+        resolve_staking_round: cu_limits.resolve_staking_round.min(MAX_CU_LIMIT),
+        claim_stakes: cu_limits.claim_stakes.min(MAX_CU_LIMIT),
+        finalize_locked_stake: cu_limits.finalize_locked_stake.min(MAX_CU_LIMIT),
+        update_pool_aum: cu_limits.update_pool_aum.min(MAX_CU_LIMIT),
+    };
+    log::info!("  <> CU limits: {:?}", cu_limits);
+    CU_LIMITS.set(cu_limits).ok();
+}
+
+// Configured limit of the action, regardless of the observed consumption
+pub fn static_cu_limit(action: KeeperAction) -> u32 {
+    let cu_limits = CU_LIMITS.get().copied().unwrap_or_default();
+    match action {
+        KeeperAction::ResolveStakingRound => cu_limits.resolve_staking_round,
+        KeeperAction::ClaimStakes => cu_limits.claim_stakes,
+        KeeperAction::FinalizeLockedStake => cu_limits.finalize_locked_stake,
+        KeeperAction::UpdatePoolAum => cu_limits.update_pool_aum,
+    }
+}
+
+// Compute units consumed by the last landed transactions of each action
+static UNITS_CONSUMED: LazyLock<Mutex<HashMap<KeeperAction, VecDeque<u64>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Called by the confirmation task for each landed transaction
+pub fn record_units_consumed(action: KeeperAction, units_consumed: u64) {
+    let mut units_consumed_per_action = UNITS_CONSUMED.lock().unwrap();
+    let observations = units_consumed_per_action.entry(action).or_default();
+    if observations.len() == CU_OBSERVATIONS_WINDOW {
+        observations.pop_front();
+    }
+    observations.push_back(units_consumed);
+}
+
+// Called for each landed transaction that ran out of compute units: only the landed ones that fit feed the observations,
+// so the limit could otherwise only go down - start over from the static limit
+pub fn record_cu_exceeded(action: KeeperAction) {
+    log::warn!(
+        "   <> {} TX exceeded its CU limit - back to the static limit ({})",
+        action.as_str(),
+        static_cu_limit(action)
+    );
+    UNITS_CONSUMED.lock().unwrap().remove(&action);
+}
+
+// CU limit for the next transaction of the given action, based on the observed consumption
+// Falls back to the static limit until enough transactions have landed
+pub fn compute_unit_limit(action: KeeperAction) -> u32 {
+    let units_consumed_per_action = UNITS_CONSUMED.lock().unwrap();
+    let Some(observations) = units_consumed_per_action.get(&action) else {
+        return static_cu_limit(action);
+    };
+    if observations.len() < CU_OBSERVATIONS_MIN_SAMPLES {
+        return static_cu_limit(action);
+    }
+
+    let mut sorted_observations: Vec<u64> = observations.iter().copied().collect();
+    sorted_observations.sort_unstable();
+    let index = ((sorted_observations.len() - 1) as f64 * CU_LIMIT_PERCENTILE).ceil() as usize;
+    let limit = (sorted_observations[index] as f64 * CU_LIMIT_MARGIN) as u32;
+
+    limit.min(MAX_CU_LIMIT)
+}
@@ -0,0 +1,91 @@
+use {
+    crate::db::{Db, DbBackend},
+    chrono::NaiveDate,
+    solana_sdk::native_token::lamports_to_sol,
+    std::{collections::BTreeMap, fmt::Write},
+};
+
+// Print the fees paid by the keeper since the given day (UTC), by day and action - optionally exported as CSV
+pub async fn report_costs(
+    db_backend: DbBackend,
+    db_string: &str,
+    since: NaiveDate,
+    output_path: Option<&str>,
+) -> anyhow::Result<()> {
+    let (db, db_connection_task) = Db::connect(db_backend, db_string, None)
+        .await
+        .map_err(|e| match e {
+            backoff::Error::Permanent(e) | backoff::Error::Transient { err: e, .. } => e,
+        })?;
+
+    let since_timestamp = since
+        .and_hms_opt(0, 0, 0)
+        .expect("Midnight is a valid time")
+        .and_utc()
+        .timestamp();
+    let daily_transaction_costs = db
+        .get_daily_transaction_costs(since_timestamp)
+        .await
+        .map_err(|e| match e {
+            backoff::Error::Permanent(e) | backoff::Error::Transient { err: e, .. } => e,
+        })?;
+    if let Some(db_connection_task) = db_connection_task {
+        db_connection_task.abort();
+    }
+
+    println!("Keeper transaction costs since {} (UTC)", since);
+    println!();
+    println!(
+        "{:<12} {:<24} {:>12} {:>16}",
+        "day", "action", "transactions", "fees (SOL)"
+    );
+    let mut totals: BTreeMap<&str, (i64, i64)> = BTreeMap::new();
+    for costs in daily_transaction_costs.iter() {
+        println!(
+            "{:<12} {:<24} {:>12} {:>16.9}",
+            costs.day,
+            costs.action,
+            costs.transactions,
+            lamports_to_sol(costs.fee_lamports as u64)
+        );
+        let total = totals.entry(costs.action.as_str()).or_default();
+        total.0 += costs.transactions;
+        total.1 += costs.fee_lamports;
+    }
+    println!();
+    for (action, (transactions, fee_lamports)) in totals.iter() {
+        println!(
+            "{:<12} {:<24} {:>12} {:>16.9}",
+            "total",
+            action,
+            transactions,
+            lamports_to_sol(*fee_lamports as u64)
+        );
+    }
+    let (transactions, fee_lamports) = totals
+        .values()
+        .fold((0, 0), |(transactions, fee_lamports), total| {
+            (transactions + total.0, fee_lamports + total.1)
+        });
+    println!(
+        "{:<12} {:<24} {:>12} {:>16.9}",
+        "total",
+        "all",
+        transactions,
+        lamports_to_sol(fee_lamports as u64)
+    );
+
+    if let Some(output_path) = output_path {
+        let mut csv = String::from("day,action,transactions,fee_lamports\n");
+        for costs in daily_transaction_costs.iter() {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                costs.day, costs.action, costs.transactions, costs.fee_lamports
+            )?;
+        }
+        std::fs::write(output_path, csv)?;
+        log::info!("  <> Transaction costs exported to {}", output_path);
+    }
+    Ok(())
+}
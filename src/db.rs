@@ -0,0 +1,1204 @@
+use {
+    crate::{
+        action_log::ActionLogEntry,
+        owner_cache::{cache_owner, cached_owner},
+        quarantine::QuarantinedAccount,
+        transaction_costs::TransactionCost,
+    },
+    openssl::ssl::{SslConnector, SslMethod},
+    postgres_openssl::MakeTlsConnector,
+    redis::{aio::MultiplexedConnection, AsyncCommands},
+    rusqlite::OptionalExtension,
+    serde::Serialize,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::HashMap,
+        str::FromStr,
+        sync::{Arc, Mutex},
+    },
+    tokio::task::JoinHandle,
+};
+
+// Prefix of the Redis keys caching the UserStaking account -> owner mapping
+const REDIS_OWNER_KEY_PREFIX: &str = "ref_user_staking:";
+
+// History of the resolved staking rounds (analytics), one row per Staking account and round
+const CREATE_STAKING_ROUNDS_TABLE: &str = "CREATE TABLE IF NOT EXISTS staking_rounds (
+    staking_pubkey TEXT NOT NULL,
+    start_time BIGINT NOT NULL,
+    end_time BIGINT NOT NULL,
+    rate BIGINT NOT NULL,
+    total_stake BIGINT NOT NULL,
+    total_claim BIGINT NOT NULL,
+    lm_rate BIGINT NOT NULL,
+    lm_total_stake BIGINT NOT NULL,
+    lm_total_claim BIGINT NOT NULL,
+    resolved_slot BIGINT NOT NULL,
+    PRIMARY KEY (staking_pubkey, start_time)
+);";
+
+// UserStaking accounts data as last seen by the keeper, used to warm start the indexes
+// (the startup scan only stores the bytes the keeper reads, the stream updates store the full accounts)
+const CREATE_USER_STAKING_ACCOUNTS_TABLE: &str =
+    "CREATE TABLE IF NOT EXISTS user_staking_accounts (
+    user_staking_pubkey TEXT PRIMARY KEY,
+    data BYTEA NOT NULL,
+    updated_at BIGINT NOT NULL
+);";
+// Fees paid by the landed keeper transactions (cost accounting), one row per transaction
+const CREATE_TRANSACTION_COSTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS transaction_costs (
+    signature TEXT PRIMARY KEY,
+    action TEXT NOT NULL,
+    account TEXT NOT NULL,
+    fee_lamports BIGINT NOT NULL,
+    landed_at BIGINT NOT NULL
+);";
+// Outcome of each keeper transaction (see action_log.rs), searched by the history subcommand
+const CREATE_ACTION_LOG_TABLE: &str = "CREATE TABLE IF NOT EXISTS keeper_actions (
+    signature TEXT PRIMARY KEY,
+    action TEXT NOT NULL,
+    account TEXT NOT NULL,
+    owner TEXT,
+    staking TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    sent_at BIGINT NOT NULL,
+    completed_at BIGINT NOT NULL
+);";
+// UserStaking accounts quarantined after too many consecutive failed actions (see quarantine.rs)
+const CREATE_QUARANTINED_ACCOUNTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS quarantined_accounts (
+    user_staking_pubkey TEXT PRIMARY KEY,
+    failures BIGINT NOT NULL,
+    last_error TEXT NOT NULL,
+    quarantined_at BIGINT NOT NULL
+);";
+// Highest slot processed from the stream, saved along the stored UserStaking accounts (see warm_start.rs)
+const CREATE_STREAM_CHECKPOINT_TABLE: &str = "CREATE TABLE IF NOT EXISTS stream_checkpoint (
+    name TEXT PRIMARY KEY,
+    slot BIGINT NOT NULL,
+    saved_at BIGINT NOT NULL
+);";
+// Name of the stream_checkpoint row of the keeper
+const STREAM_CHECKPOINT_NAME: &str = "last_processed_slot";
+// Rows written per query when storing many UserStaking accounts
+const USER_STAKING_ACCOUNTS_WRITE_CHUNK_SIZE: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum DbBackend {
+    #[default]
+    Postgres,
+    Sqlite,
+}
+
+// The SQL DB contains the table matching the UserStaking accounts to their owners (the onchain data doesn't contain the owner)
+enum SqlDb {
+    Postgres(tokio_postgres::Client),
+    // rusqlite is sync, queries are run on the blocking thread pool
+    Sqlite(Arc<Mutex<rusqlite::Connection>>),
+}
+
+// A staking round as recorded once resolved (amounts in native units)
+#[derive(Debug, Serialize)]
+pub struct ResolvedStakingRound {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub rate: u64,
+    pub total_stake: u64,
+    pub total_claim: u64,
+    pub lm_rate: u64,
+    pub lm_total_stake: u64,
+    pub lm_total_claim: u64,
+    pub resolved_slot: u64,
+}
+
+// Fees paid by the keeper transactions of an action over a UTC day
+#[derive(Debug)]
+pub struct DailyTransactionCosts {
+    // YYYY-MM-DD
+    pub day: String,
+    pub action: String,
+    pub transactions: i64,
+    pub fee_lamports: i64,
+}
+
+// Criteria of the history subcommand, None matches everything
+#[derive(Debug)]
+pub struct ActionLogFilter {
+    // Wallet or targeted account (UserStaking, Staking, Pool)
+    pub user: Option<String>,
+    pub staking: Option<String>,
+    pub action: Option<String>,
+    // Completion time range, [since, until[
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: i64,
+}
+
+// A UserStaking account as stored for the warm start
+#[derive(Debug)]
+pub struct StoredUserStakingAccount {
+    pub key: Pubkey,
+    pub data: Vec<u8>,
+    pub updated_at: i64,
+}
+
+pub struct Db {
+    sql: SqlDb,
+    // Optional cache in front of the SQL lookups, populated as mappings are learned from the SQL DB
+    redis: Option<MultiplexedConnection>,
+}
+
+impl Db {
+    // Returns the DB and, for Postgres, the task driving the connection (to be aborted on reconnect)
+    pub async fn connect(
+        backend: DbBackend,
+        db_string: &str,
+        redis_url: Option<&str>,
+    ) -> Result<(Db, Option<JoinHandle<()>>), backoff::Error<anyhow::Error>> {
+        let (sql, db_connection_task) = match backend {
+            DbBackend::Postgres => {
+                // Create an SSL connector
+                let builder = SslConnector::builder(SslMethod::tls()).unwrap();
+                let connector = MakeTlsConnector::new(builder.build());
+                let (db, db_connection) = tokio_postgres::connect(db_string, connector)
+                    .await
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                // Open a connection to the DB
+                let db_connection_task = tokio::spawn(async move {
+                    if let Err(e) = db_connection.await {
+                        log::error!("connection error: {}", e);
+                    }
+                });
+                // Not fatal, the analytics are a side feature
+                if let Err(e) = db.batch_execute(CREATE_STAKING_ROUNDS_TABLE).await {
+                    log::warn!("   <> Failed to create the staking_rounds table: {:?}", e);
+                }
+                // Not fatal either, the keeper falls back to the full scan
+                if let Err(e) = db.batch_execute(CREATE_USER_STAKING_ACCOUNTS_TABLE).await {
+                    log::warn!(
+                        "   <> Failed to create the user_staking_accounts table: {:?}",
+                        e
+                    );
+                }
+                // Not fatal, the costs are not recorded
+                if let Err(e) = db.batch_execute(CREATE_TRANSACTION_COSTS_TABLE).await {
+                    log::warn!(
+                        "   <> Failed to create the transaction_costs table: {:?}",
+                        e
+                    );
+                }
+                // Not fatal, the actions are not logged
+                if let Err(e) = db.batch_execute(CREATE_ACTION_LOG_TABLE).await {
+                    log::warn!("   <> Failed to create the keeper_actions table: {:?}", e);
+                }
+                // Not fatal, the quarantine doesn't survive the restarts
+                if let Err(e) = db.batch_execute(CREATE_QUARANTINED_ACCOUNTS_TABLE).await {
+                    log::warn!(
+                        "   <> Failed to create the quarantined_accounts table: {:?}",
+                        e
+                    );
+                }
+                // Not fatal, the restarts refetch the accounts
+                if let Err(e) = db.batch_execute(CREATE_STREAM_CHECKPOINT_TABLE).await {
+                    log::warn!(
+                        "   <> Failed to create the stream_checkpoint table: {:?}",
+                        e
+                    );
+                }
+                (SqlDb::Postgres(db), Some(db_connection_task))
+            }
+            DbBackend::Sqlite => {
+                let connection = rusqlite::Connection::open(db_string)
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                connection
+                    .execute_batch(
+                        "CREATE TABLE IF NOT EXISTS ref_user_staking (
+                            user_staking_pubkey TEXT PRIMARY KEY,
+                            user_pubkey TEXT NOT NULL
+                        );",
+                    )
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                connection
+                    .execute_batch(CREATE_STAKING_ROUNDS_TABLE)
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                connection
+                    .execute_batch(CREATE_USER_STAKING_ACCOUNTS_TABLE)
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                connection
+                    .execute_batch(CREATE_TRANSACTION_COSTS_TABLE)
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                connection
+                    .execute_batch(CREATE_ACTION_LOG_TABLE)
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                connection
+                    .execute_batch(CREATE_QUARANTINED_ACCOUNTS_TABLE)
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                connection
+                    .execute_batch(CREATE_STREAM_CHECKPOINT_TABLE)
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                (SqlDb::Sqlite(Arc::new(Mutex::new(connection))), None)
+            }
+        };
+
+        let redis = match redis_url {
+            Some(redis_url) => {
+                let client = redis::Client::open(redis_url)
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                let connection = client
+                    .get_multiplexed_async_connection()
+                    .await
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                log::info!("  <> Redis owner cache connected");
+                Some(connection)
+            }
+            None => None,
+        };
+
+        Ok((Db { sql, redis }, db_connection_task))
+    }
+
+    pub async fn get_owner_pubkey(
+        &self,
+        user_staking_account_key: &Pubkey,
+    ) -> Result<Option<Pubkey>, backoff::Error<anyhow::Error>> {
+        if let Some(owner_pubkey) = cached_owner(user_staking_account_key) {
+            return Ok(Some(owner_pubkey));
+        }
+        if let Some(owner_pubkey) = self.get_cached_owner_pubkey(user_staking_account_key).await {
+            cache_owner(user_staking_account_key, &owner_pubkey);
+            return Ok(Some(owner_pubkey));
+        }
+
+        let owner = match &self.sql {
+            SqlDb::Postgres(db) => {
+                let rows = db
+                    .query(
+                        "SELECT user_pubkey FROM ref_user_staking WHERE user_staking_pubkey = $1::TEXT",
+                        &[&user_staking_account_key.to_string()],
+                    )
+                    .await
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                rows.first().map(|row| row.get::<_, String>(0))
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                let user_staking_account_key = user_staking_account_key.to_string();
+                tokio::task::spawn_blocking(move || {
+                    connection
+                        .lock()
+                        .expect("SQLite connection lock poisoned")
+                        .query_row(
+                            "SELECT user_pubkey FROM ref_user_staking WHERE user_staking_pubkey = ?1",
+                            [user_staking_account_key],
+                            |row| row.get::<_, String>(0),
+                        )
+                        .optional()
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?
+            }
+        };
+
+        if let Some(owner) = owner {
+            let owner_pubkey = Pubkey::from_str(owner.as_str()).expect("Invalid pubkey");
+            // Write through so the next lookup doesn't hit the SQL DB
+            cache_owner(user_staking_account_key, &owner_pubkey);
+            self.cache_owner_pubkey(user_staking_account_key, &owner_pubkey)
+                .await;
+            Ok(Some(owner_pubkey))
+        } else {
+            log::debug!(
+                "No owner found in DB for UserStaking account: {}",
+                user_staking_account_key
+            );
+            Ok(None)
+        }
+    }
+
+    // The whole UserStaking account -> owner mapping (see owner_cache.rs)
+    pub async fn load_owner_pubkeys(
+        &self,
+    ) -> Result<HashMap<Pubkey, Pubkey>, backoff::Error<anyhow::Error>> {
+        let rows: Vec<(String, String)> = match &self.sql {
+            SqlDb::Postgres(db) => db
+                .query(
+                    "SELECT user_staking_pubkey, user_pubkey FROM ref_user_staking",
+                    &[],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .iter()
+                .map(|row| (row.get(0), row.get(1)))
+                .collect(),
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    let connection = connection.lock().expect("SQLite connection lock poisoned");
+                    let mut statement = connection
+                        .prepare("SELECT user_staking_pubkey, user_pubkey FROM ref_user_staking")?;
+                    let rows = statement
+                        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                        .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+                    Ok::<_, rusqlite::Error>(rows)
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?
+            }
+        };
+
+        // Skip the malformed rows rather than failing the whole load, they are looked up (and rejected) one by one
+        Ok(rows
+            .into_iter()
+            .filter_map(|(user_staking_account_key, owner)| {
+                Some((
+                    Pubkey::from_str(user_staking_account_key.as_str()).ok()?,
+                    Pubkey::from_str(owner.as_str()).ok()?,
+                ))
+            })
+            .collect())
+    }
+
+    // Owners learned by the keeper - the existing mappings are left untouched
+    pub async fn insert_owner_pubkeys(
+        &self,
+        owners: &[(Pubkey, Pubkey)],
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        let rows: Vec<(String, String)> = owners
+            .iter()
+            .map(|(user_staking_account_key, owner_pubkey)| {
+                (
+                    user_staking_account_key.to_string(),
+                    owner_pubkey.to_string(),
+                )
+            })
+            .collect();
+
+        match &self.sql {
+            SqlDb::Postgres(db) => {
+                let user_staking_account_keys: Vec<&str> =
+                    rows.iter().map(|row| row.0.as_str()).collect();
+                let owner_pubkeys: Vec<&str> = rows.iter().map(|row| row.1.as_str()).collect();
+                // The table is maintained by the indexer, don't rely on a unique constraint on user_staking_pubkey
+                db.execute(
+                    "INSERT INTO ref_user_staking (user_staking_pubkey, user_pubkey)
+                    SELECT learned.user_staking_pubkey, learned.user_pubkey
+                    FROM UNNEST($1::TEXT[], $2::TEXT[]) AS learned (user_staking_pubkey, user_pubkey)
+                    WHERE NOT EXISTS (
+                        SELECT 1 FROM ref_user_staking WHERE ref_user_staking.user_staking_pubkey = learned.user_staking_pubkey
+                    )
+                    ON CONFLICT DO NOTHING",
+                    &[&user_staking_account_keys, &owner_pubkeys],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    let mut connection =
+                        connection.lock().expect("SQLite connection lock poisoned");
+                    let transaction = connection.transaction()?;
+                    {
+                        let mut statement = transaction.prepare_cached(
+                            "INSERT INTO ref_user_staking (user_staking_pubkey, user_pubkey)
+                            VALUES (?1, ?2) ON CONFLICT DO NOTHING",
+                        )?;
+                        for (user_staking_account_key, owner_pubkey) in rows.iter() {
+                            statement.execute([user_staking_account_key, owner_pubkey])?;
+                        }
+                    }
+                    transaction.commit()
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+        }
+
+        for (user_staking_account_key, owner_pubkey) in owners.iter() {
+            self.cache_owner_pubkey(user_staking_account_key, owner_pubkey)
+                .await;
+        }
+        Ok(())
+    }
+
+    pub async fn insert_resolved_staking_round(
+        &self,
+        staking_account_key: &Pubkey,
+        round: &ResolvedStakingRound,
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        let staking_account_key = staking_account_key.to_string();
+        // Amounts are stored as BIGINT, they fit in an i64
+        let values: [i64; 9] = [
+            round.start_time,
+            round.end_time,
+            round.rate as i64,
+            round.total_stake as i64,
+            round.total_claim as i64,
+            round.lm_rate as i64,
+            round.lm_total_stake as i64,
+            round.lm_total_claim as i64,
+            round.resolved_slot as i64,
+        ];
+
+        match &self.sql {
+            SqlDb::Postgres(db) => {
+                db.execute(
+                    "INSERT INTO staking_rounds (staking_pubkey, start_time, end_time, rate, total_stake, total_claim, lm_rate, lm_total_stake, lm_total_claim, resolved_slot)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT DO NOTHING",
+                    &[
+                        &staking_account_key,
+                        &values[0],
+                        &values[1],
+                        &values[2],
+                        &values[3],
+                        &values[4],
+                        &values[5],
+                        &values[6],
+                        &values[7],
+                        &values[8],
+                    ],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    connection
+                        .lock()
+                        .expect("SQLite connection lock poisoned")
+                        .execute(
+                            "INSERT INTO staking_rounds (staking_pubkey, start_time, end_time, rate, total_stake, total_claim, lm_rate, lm_total_stake, lm_total_claim, resolved_slot)
+                            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10) ON CONFLICT DO NOTHING",
+                            rusqlite::params![
+                                staking_account_key,
+                                values[0],
+                                values[1],
+                                values[2],
+                                values[3],
+                                values[4],
+                                values[5],
+                                values[6],
+                                values[7],
+                                values[8],
+                            ],
+                        )
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn insert_transaction_costs(
+        &self,
+        transaction_costs: &[TransactionCost],
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        let rows: Vec<(String, &'static str, String, i64, i64)> = transaction_costs
+            .iter()
+            .map(|transaction_cost| {
+                (
+                    transaction_cost.signature.to_string(),
+                    transaction_cost.action.as_str(),
+                    transaction_cost.account.to_string(),
+                    transaction_cost.fee_lamports as i64,
+                    transaction_cost.landed_at,
+                )
+            })
+            .collect();
+
+        match &self.sql {
+            SqlDb::Postgres(db) => {
+                let signatures: Vec<&str> = rows.iter().map(|row| row.0.as_str()).collect();
+                let actions: Vec<&str> = rows.iter().map(|row| row.1).collect();
+                let accounts: Vec<&str> = rows.iter().map(|row| row.2.as_str()).collect();
+                let fees: Vec<i64> = rows.iter().map(|row| row.3).collect();
+                let landed_at: Vec<i64> = rows.iter().map(|row| row.4).collect();
+                db.execute(
+                    "INSERT INTO transaction_costs (signature, action, account, fee_lamports, landed_at)
+                    SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::TEXT[], $4::BIGINT[], $5::BIGINT[])
+                    ON CONFLICT DO NOTHING",
+                    &[&signatures, &actions, &accounts, &fees, &landed_at],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    let mut connection =
+                        connection.lock().expect("SQLite connection lock poisoned");
+                    let transaction = connection.transaction()?;
+                    {
+                        let mut statement = transaction.prepare_cached(
+                            "INSERT INTO transaction_costs (signature, action, account, fee_lamports, landed_at)
+                            VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT DO NOTHING",
+                        )?;
+                        for (signature, action, account, fee_lamports, landed_at) in rows.iter() {
+                            statement.execute(rusqlite::params![
+                                signature,
+                                action,
+                                account,
+                                fee_lamports,
+                                landed_at
+                            ])?;
+                        }
+                    }
+                    transaction.commit()
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn insert_action_log_entries(
+        &self,
+        entries: &[ActionLogEntry],
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        match &self.sql {
+            SqlDb::Postgres(db) => {
+                let signatures: Vec<&str> = entries
+                    .iter()
+                    .map(|entry| entry.signature.as_str())
+                    .collect();
+                let actions: Vec<&str> =
+                    entries.iter().map(|entry| entry.action.as_str()).collect();
+                let accounts: Vec<&str> =
+                    entries.iter().map(|entry| entry.account.as_str()).collect();
+                let owners: Vec<Option<&str>> =
+                    entries.iter().map(|entry| entry.owner.as_deref()).collect();
+                let stakings: Vec<&str> =
+                    entries.iter().map(|entry| entry.staking.as_str()).collect();
+                let outcomes: Vec<&str> =
+                    entries.iter().map(|entry| entry.outcome.as_str()).collect();
+                let sent_at: Vec<i64> = entries.iter().map(|entry| entry.sent_at).collect();
+                let completed_at: Vec<i64> =
+                    entries.iter().map(|entry| entry.completed_at).collect();
+                db.execute(
+                    "INSERT INTO keeper_actions (signature, action, account, owner, staking, outcome, sent_at, completed_at)
+                    SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::TEXT[], $7::BIGINT[], $8::BIGINT[])
+                    ON CONFLICT DO NOTHING",
+                    &[&signatures, &actions, &accounts, &owners, &stakings, &outcomes, &sent_at, &completed_at],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                let entries = entries.to_vec();
+                tokio::task::spawn_blocking(move || {
+                    let mut connection =
+                        connection.lock().expect("SQLite connection lock poisoned");
+                    let transaction = connection.transaction()?;
+                    {
+                        let mut statement = transaction.prepare_cached(
+                            "INSERT INTO keeper_actions (signature, action, account, owner, staking, outcome, sent_at, completed_at)
+                            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) ON CONFLICT DO NOTHING",
+                        )?;
+                        for entry in entries.iter() {
+                            statement.execute(rusqlite::params![
+                                entry.signature,
+                                entry.action,
+                                entry.account,
+                                entry.owner,
+                                entry.staking,
+                                entry.outcome,
+                                entry.sent_at,
+                                entry.completed_at
+                            ])?;
+                        }
+                    }
+                    transaction.commit()
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Logged keeper actions matching the filter, most recent first
+    pub async fn search_action_log(
+        &self,
+        filter: &ActionLogFilter,
+    ) -> Result<Vec<ActionLogEntry>, backoff::Error<anyhow::Error>> {
+        type Row = (
+            String,
+            String,
+            String,
+            Option<String>,
+            String,
+            String,
+            i64,
+            i64,
+        );
+        let rows: Vec<Row> = match &self.sql {
+            SqlDb::Postgres(db) => db
+                .query(
+                    "SELECT signature, action, account, owner, staking, outcome, sent_at, completed_at FROM keeper_actions
+                    WHERE ($1::TEXT IS NULL OR owner = $1 OR account = $1)
+                    AND ($2::TEXT IS NULL OR staking = $2)
+                    AND ($3::TEXT IS NULL OR action = $3)
+                    AND ($4::BIGINT IS NULL OR completed_at >= $4)
+                    AND ($5::BIGINT IS NULL OR completed_at < $5)
+                    ORDER BY completed_at DESC LIMIT $6",
+                    &[
+                        &filter.user,
+                        &filter.staking,
+                        &filter.action,
+                        &filter.since,
+                        &filter.until,
+                        &filter.limit,
+                    ],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .iter()
+                .map(|row| {
+                    (
+                        row.get(0),
+                        row.get(1),
+                        row.get(2),
+                        row.get(3),
+                        row.get(4),
+                        row.get(5),
+                        row.get(6),
+                        row.get(7),
+                    )
+                })
+                .collect(),
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                let params = (
+                    filter.user.clone(),
+                    filter.staking.clone(),
+                    filter.action.clone(),
+                    filter.since,
+                    filter.until,
+                    filter.limit,
+                );
+                tokio::task::spawn_blocking(move || {
+                    let connection = connection.lock().expect("SQLite connection lock poisoned");
+                    let mut statement = connection.prepare(
+                        "SELECT signature, action, account, owner, staking, outcome, sent_at, completed_at FROM keeper_actions
+                        WHERE (?1 IS NULL OR owner = ?1 OR account = ?1)
+                        AND (?2 IS NULL OR staking = ?2)
+                        AND (?3 IS NULL OR action = ?3)
+                        AND (?4 IS NULL OR completed_at >= ?4)
+                        AND (?5 IS NULL OR completed_at < ?5)
+                        ORDER BY completed_at DESC LIMIT ?6",
+                    )?;
+                    let rows = statement
+                        .query_map(params, |row| {
+                            Ok((
+                                row.get(0)?,
+                                row.get(1)?,
+                                row.get(2)?,
+                                row.get(3)?,
+                                row.get(4)?,
+                                row.get(5)?,
+                                row.get(6)?,
+                                row.get(7)?,
+                            ))
+                        })?
+                        .collect::<rusqlite::Result<Vec<Row>>>()?;
+                    Ok::<_, rusqlite::Error>(rows)
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?
+            }
+        };
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(signature, action, account, owner, staking, outcome, sent_at, completed_at)| {
+                    ActionLogEntry {
+                        signature,
+                        action,
+                        account,
+                        owner,
+                        staking,
+                        outcome,
+                        sent_at,
+                        completed_at,
+                    }
+                },
+            )
+            .collect())
+    }
+
+    // Recorded transaction costs since the given time, aggregated by UTC day and action
+    pub async fn get_daily_transaction_costs(
+        &self,
+        since: i64,
+    ) -> Result<Vec<DailyTransactionCosts>, backoff::Error<anyhow::Error>> {
+        let rows: Vec<(String, String, i64, i64)> = match &self.sql {
+            SqlDb::Postgres(db) => db
+                .query(
+                    "SELECT to_char(to_timestamp(landed_at) AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS day, action, COUNT(*), SUM(fee_lamports)::BIGINT
+                    FROM transaction_costs WHERE landed_at >= $1 GROUP BY day, action ORDER BY day, action",
+                    &[&since],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .iter()
+                .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+                .collect(),
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    let connection = connection.lock().expect("SQLite connection lock poisoned");
+                    let mut statement = connection.prepare(
+                        "SELECT strftime('%Y-%m-%d', landed_at, 'unixepoch') AS day, action, COUNT(*), SUM(fee_lamports)
+                        FROM transaction_costs WHERE landed_at >= ?1 GROUP BY day, action ORDER BY day, action",
+                    )?;
+                    let rows = statement
+                        .query_map([since], |row| {
+                            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                        })?
+                        .collect::<rusqlite::Result<Vec<(String, String, i64, i64)>>>()?;
+                    Ok::<_, rusqlite::Error>(rows)
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?
+            }
+        };
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(day, action, transactions, fee_lamports)| DailyTransactionCosts {
+                    day,
+                    action,
+                    transactions,
+                    fee_lamports,
+                },
+            )
+            .collect())
+    }
+
+    // Insert or replace the stored UserStaking accounts, all stamped with the given time
+    pub async fn upsert_user_staking_accounts(
+        &self,
+        user_staking_accounts: &[(Pubkey, Vec<u8>)],
+        updated_at: i64,
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        for chunk in user_staking_accounts.chunks(USER_STAKING_ACCOUNTS_WRITE_CHUNK_SIZE) {
+            match &self.sql {
+                SqlDb::Postgres(db) => {
+                    let keys: Vec<String> = chunk.iter().map(|(key, _)| key.to_string()).collect();
+                    let data: Vec<&[u8]> = chunk.iter().map(|(_, data)| data.as_slice()).collect();
+                    let updated_at = vec![updated_at; chunk.len()];
+                    db.execute(
+                        "INSERT INTO user_staking_accounts (user_staking_pubkey, data, updated_at)
+                        SELECT * FROM UNNEST($1::TEXT[], $2::BYTEA[], $3::BIGINT[])
+                        ON CONFLICT (user_staking_pubkey) DO UPDATE SET data = EXCLUDED.data, updated_at = EXCLUDED.updated_at",
+                        &[&keys, &data, &updated_at],
+                    )
+                    .await
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                }
+                SqlDb::Sqlite(connection) => {
+                    let connection = Arc::clone(connection);
+                    let rows: Vec<(String, Vec<u8>)> = chunk
+                        .iter()
+                        .map(|(key, data)| (key.to_string(), data.clone()))
+                        .collect();
+                    tokio::task::spawn_blocking(move || {
+                        let mut connection =
+                            connection.lock().expect("SQLite connection lock poisoned");
+                        let transaction = connection.transaction()?;
+                        {
+                            let mut statement = transaction.prepare_cached(
+                                "INSERT INTO user_staking_accounts (user_staking_pubkey, data, updated_at) VALUES (?1, ?2, ?3)
+                                ON CONFLICT (user_staking_pubkey) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+                            )?;
+                            for (key, data) in rows.iter() {
+                                statement.execute(rusqlite::params![key, data, updated_at])?;
+                            }
+                        }
+                        transaction.commit()
+                    })
+                    .await
+                    .map_err(|e| backoff::Error::transient(e.into()))?
+                    .map_err(|e| backoff::Error::transient(e.into()))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn delete_user_staking_account(
+        &self,
+        user_staking_account_key: &Pubkey,
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        let user_staking_account_key = user_staking_account_key.to_string();
+        match &self.sql {
+            SqlDb::Postgres(db) => {
+                db.execute(
+                    "DELETE FROM user_staking_accounts WHERE user_staking_pubkey = $1::TEXT",
+                    &[&user_staking_account_key],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    connection
+                        .lock()
+                        .expect("SQLite connection lock poisoned")
+                        .execute(
+                            "DELETE FROM user_staking_accounts WHERE user_staking_pubkey = ?1",
+                            [user_staking_account_key],
+                        )
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Drop the UserStaking accounts not stored since the given time (closed while the keeper was not watching)
+    pub async fn prune_user_staking_accounts(
+        &self,
+        updated_before: i64,
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        match &self.sql {
+            SqlDb::Postgres(db) => {
+                db.execute(
+                    "DELETE FROM user_staking_accounts WHERE updated_at < $1",
+                    &[&updated_before],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    connection
+                        .lock()
+                        .expect("SQLite connection lock poisoned")
+                        .execute(
+                            "DELETE FROM user_staking_accounts WHERE updated_at < ?1",
+                            [updated_before],
+                        )
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn load_user_staking_accounts(
+        &self,
+    ) -> Result<Vec<StoredUserStakingAccount>, backoff::Error<anyhow::Error>> {
+        let rows: Vec<(String, Vec<u8>, i64)> = match &self.sql {
+            SqlDb::Postgres(db) => db
+                .query(
+                    "SELECT user_staking_pubkey, data, updated_at FROM user_staking_accounts",
+                    &[],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .iter()
+                .map(|row| (row.get(0), row.get(1), row.get(2)))
+                .collect(),
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    let connection = connection.lock().expect("SQLite connection lock poisoned");
+                    let mut statement = connection.prepare(
+                        "SELECT user_staking_pubkey, data, updated_at FROM user_staking_accounts",
+                    )?;
+                    let rows = statement
+                        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                        .collect::<rusqlite::Result<Vec<(String, Vec<u8>, i64)>>>()?;
+                    Ok::<_, rusqlite::Error>(rows)
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?
+            }
+        };
+
+        Ok(rows
+            .into_iter()
+            .map(|(key, data, updated_at)| StoredUserStakingAccount {
+                key: Pubkey::from_str(key.as_str()).expect("Invalid pubkey"),
+                data,
+                updated_at,
+            })
+            .collect())
+    }
+
+    pub async fn upsert_stream_checkpoint(
+        &self,
+        slot: u64,
+        saved_at: i64,
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        let slot = slot as i64;
+        match &self.sql {
+            SqlDb::Postgres(db) => {
+                db.execute(
+                    "INSERT INTO stream_checkpoint (name, slot, saved_at) VALUES ($1, $2, $3)
+                    ON CONFLICT (name) DO UPDATE SET slot = EXCLUDED.slot, saved_at = EXCLUDED.saved_at",
+                    &[&STREAM_CHECKPOINT_NAME, &slot, &saved_at],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    connection
+                        .lock()
+                        .expect("SQLite connection lock poisoned")
+                        .execute(
+                            "INSERT INTO stream_checkpoint (name, slot, saved_at) VALUES (?1, ?2, ?3)
+                            ON CONFLICT (name) DO UPDATE SET slot = excluded.slot, saved_at = excluded.saved_at",
+                            rusqlite::params![STREAM_CHECKPOINT_NAME, slot, saved_at],
+                        )
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // The last saved slot and when it was saved, None if never saved
+    pub async fn load_stream_checkpoint(
+        &self,
+    ) -> Result<Option<(u64, i64)>, backoff::Error<anyhow::Error>> {
+        let checkpoint: Option<(i64, i64)> = match &self.sql {
+            SqlDb::Postgres(db) => db
+                .query_opt(
+                    "SELECT slot, saved_at FROM stream_checkpoint WHERE name = $1",
+                    &[&STREAM_CHECKPOINT_NAME],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map(|row| (row.get(0), row.get(1))),
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    connection
+                        .lock()
+                        .expect("SQLite connection lock poisoned")
+                        .query_row(
+                            "SELECT slot, saved_at FROM stream_checkpoint WHERE name = ?1",
+                            [STREAM_CHECKPOINT_NAME],
+                            |row| Ok((row.get(0)?, row.get(1)?)),
+                        )
+                        .optional()
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?
+            }
+        };
+        Ok(checkpoint.map(|(slot, saved_at)| (slot as u64, saved_at)))
+    }
+
+    // Insert or replace the quarantined accounts
+    pub async fn upsert_quarantined_accounts(
+        &self,
+        accounts: &[(Pubkey, QuarantinedAccount)],
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        let rows: Vec<(String, i64, String, i64)> = accounts
+            .iter()
+            .map(|(key, account)| {
+                (
+                    key.to_string(),
+                    account.failures as i64,
+                    account.last_error.clone(),
+                    account.quarantined_at,
+                )
+            })
+            .collect();
+
+        match &self.sql {
+            SqlDb::Postgres(db) => {
+                let keys: Vec<&str> = rows.iter().map(|row| row.0.as_str()).collect();
+                let failures: Vec<i64> = rows.iter().map(|row| row.1).collect();
+                let last_errors: Vec<&str> = rows.iter().map(|row| row.2.as_str()).collect();
+                let quarantined_at: Vec<i64> = rows.iter().map(|row| row.3).collect();
+                db.execute(
+                    "INSERT INTO quarantined_accounts (user_staking_pubkey, failures, last_error, quarantined_at)
+                    SELECT * FROM UNNEST($1::TEXT[], $2::BIGINT[], $3::TEXT[], $4::BIGINT[])
+                    ON CONFLICT (user_staking_pubkey) DO UPDATE SET failures = EXCLUDED.failures, last_error = EXCLUDED.last_error, quarantined_at = EXCLUDED.quarantined_at",
+                    &[&keys, &failures, &last_errors, &quarantined_at],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    let mut connection =
+                        connection.lock().expect("SQLite connection lock poisoned");
+                    let transaction = connection.transaction()?;
+                    {
+                        let mut statement = transaction.prepare_cached(
+                            "INSERT INTO quarantined_accounts (user_staking_pubkey, failures, last_error, quarantined_at) VALUES (?1, ?2, ?3, ?4)
+                            ON CONFLICT (user_staking_pubkey) DO UPDATE SET failures = excluded.failures, last_error = excluded.last_error, quarantined_at = excluded.quarantined_at",
+                        )?;
+                        for (key, failures, last_error, quarantined_at) in rows.iter() {
+                            statement.execute(rusqlite::params![
+                                key,
+                                failures,
+                                last_error,
+                                quarantined_at
+                            ])?;
+                        }
+                    }
+                    transaction.commit()
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn delete_quarantined_accounts(
+        &self,
+        user_staking_account_keys: &[Pubkey],
+    ) -> Result<(), backoff::Error<anyhow::Error>> {
+        let keys: Vec<String> = user_staking_account_keys
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        match &self.sql {
+            SqlDb::Postgres(db) => {
+                db.execute(
+                    "DELETE FROM quarantined_accounts WHERE user_staking_pubkey = ANY($1::TEXT[])",
+                    &[&keys],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    let mut connection =
+                        connection.lock().expect("SQLite connection lock poisoned");
+                    let transaction = connection.transaction()?;
+                    {
+                        let mut statement = transaction.prepare_cached(
+                            "DELETE FROM quarantined_accounts WHERE user_staking_pubkey = ?1",
+                        )?;
+                        for key in keys.iter() {
+                            statement.execute([key])?;
+                        }
+                    }
+                    transaction.commit()
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn load_quarantined_accounts(
+        &self,
+    ) -> Result<HashMap<Pubkey, QuarantinedAccount>, backoff::Error<anyhow::Error>> {
+        let rows: Vec<(String, i64, String, i64)> = match &self.sql {
+            SqlDb::Postgres(db) => db
+                .query(
+                    "SELECT user_staking_pubkey, failures, last_error, quarantined_at FROM quarantined_accounts",
+                    &[],
+                )
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .iter()
+                .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+                .collect(),
+            SqlDb::Sqlite(connection) => {
+                let connection = Arc::clone(connection);
+                tokio::task::spawn_blocking(move || {
+                    let connection = connection.lock().expect("SQLite connection lock poisoned");
+                    let mut statement = connection.prepare(
+                        "SELECT user_staking_pubkey, failures, last_error, quarantined_at FROM quarantined_accounts",
+                    )?;
+                    let rows = statement
+                        .query_map([], |row| {
+                            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                        })?
+                        .collect::<rusqlite::Result<Vec<(String, i64, String, i64)>>>()?;
+                    Ok::<_, rusqlite::Error>(rows)
+                })
+                .await
+                .map_err(|e| backoff::Error::transient(e.into()))?
+                .map_err(|e| backoff::Error::transient(e.into()))?
+            }
+        };
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(key, failures, last_error, quarantined_at)| {
+                Some((
+                    Pubkey::from_str(key.as_str()).ok()?,
+                    QuarantinedAccount {
+                        failures: failures as u32,
+                        last_error,
+                        quarantined_at,
+                    },
+                ))
+            })
+            .collect())
+    }
+
+    // Redis errors are not fatal, we fall back to the SQL DB
+    async fn get_cached_owner_pubkey(&self, user_staking_account_key: &Pubkey) -> Option<Pubkey> {
+        let mut redis = self.redis.clone()?;
+        match redis
+            .get::<_, Option<String>>(format!(
+                "{}{}",
+                REDIS_OWNER_KEY_PREFIX, user_staking_account_key
+            ))
+            .await
+        {
+            Ok(owner) => owner.and_then(|owner| Pubkey::from_str(owner.as_str()).ok()),
+            Err(e) => {
+                log::warn!("   <> Redis owner lookup failed: {:?}", e);
+                None
+            }
+        }
+    }
+
+    async fn cache_owner_pubkey(&self, user_staking_account_key: &Pubkey, owner_pubkey: &Pubkey) {
+        let Some(mut redis) = self.redis.clone() else {
+            return;
+        };
+        if let Err(e) = redis
+            .set::<_, _, ()>(
+                format!("{}{}", REDIS_OWNER_KEY_PREFIX, user_staking_account_key),
+                owner_pubkey.to_string(),
+            )
+            .await
+        {
+            log::warn!("   <> Redis owner write failed: {:?}", e);
+        }
+    }
+}
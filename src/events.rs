@@ -0,0 +1,219 @@
+use {
+    crate::{db::ResolvedStakingRound, webhooks::ClaimedAmount},
+    async_nats::ToSubject,
+    rdkafka::{
+        producer::{FutureProducer, FutureRecord},
+        ClientConfig,
+    },
+    serde::Serialize,
+    std::{sync::OnceLock, time::Duration},
+    tokio::sync::mpsc::{self, error::TrySendError},
+};
+
+// How long the producer keeps retrying an event before dropping it
+const KAFKA_MESSAGE_TIMEOUT_MS: &str = "30000";
+// Events waiting for the publisher, the new ones are dropped beyond (sink down or too slow)
+const EVENT_QUEUE_CAPACITY: usize = 10_000;
+
+// Set once at startup - No queue means the events are not published
+static EVENT_QUEUE: OnceLock<mpsc::Sender<QueuedEvent>> = OnceLock::new();
+
+enum EventSink {
+    Kafka {
+        producer: FutureProducer,
+        topic: String,
+    },
+    // Published on <subject>.<event type>, consumers subscribe to the types they need
+    Nats {
+        client: async_nats::Client,
+        subject: String,
+    },
+}
+
+// The keeper activity published to the downstream services (amounts in native units)
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeeperEvent {
+    RoundResolved {
+        staking_account: String,
+        round: ResolvedStakingRound,
+    },
+    ClaimExecuted {
+        user_staking_account: String,
+        wallet: String,
+        signature: String,
+        claimed_amounts: Vec<ClaimedAmount>,
+    },
+    StakeFinalized {
+        user_staking_account: String,
+        signature: String,
+    },
+    AccountIndexed {
+        user_staking_account: String,
+        slot: u64,
+    },
+    AccountClosed {
+        user_staking_account: String,
+        slot: u64,
+    },
+    AccountQuarantined {
+        user_staking_account: String,
+        failures: u32,
+        last_error: String,
+    },
+}
+
+impl KeeperEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            KeeperEvent::RoundResolved { .. } => "round_resolved",
+            KeeperEvent::ClaimExecuted { .. } => "claim_executed",
+            KeeperEvent::StakeFinalized { .. } => "stake_finalized",
+            KeeperEvent::AccountIndexed { .. } => "account_indexed",
+            KeeperEvent::AccountClosed { .. } => "account_closed",
+            KeeperEvent::AccountQuarantined { .. } => "account_quarantined",
+        }
+    }
+
+    // Events of the same account share a key, so they keep their order within a partition
+    fn key(&self) -> &str {
+        match self {
+            KeeperEvent::RoundResolved {
+                staking_account, ..
+            } => staking_account,
+            KeeperEvent::ClaimExecuted {
+                user_staking_account,
+                ..
+            }
+            | KeeperEvent::StakeFinalized {
+                user_staking_account,
+                ..
+            }
+            | KeeperEvent::AccountIndexed {
+                user_staking_account,
+                ..
+            }
+            | KeeperEvent::AccountClosed {
+                user_staking_account,
+                ..
+            }
+            | KeeperEvent::AccountQuarantined {
+                user_staking_account,
+                ..
+            } => user_staking_account,
+        }
+    }
+}
+
+// An event serialized by publish_event, waiting for the publisher task
+struct QueuedEvent {
+    name: &'static str,
+    key: String,
+    payload: String,
+}
+
+#[derive(Debug, Serialize)]
+struct EventEnvelope<'a> {
+    // Unix timestamp (seconds) at which the keeper emitted the event
+    emitted_at: i64,
+    #[serde(flatten)]
+    event: &'a KeeperEvent,
+}
+
+// Kafka or NATS, a single sink publishes the keeper events
+pub async fn init_event_sink(
+    kafka_brokers: Option<String>,
+    kafka_topic: String,
+    nats_url: Option<String>,
+    nats_subject: String,
+) -> anyhow::Result<()> {
+    let sink = match (kafka_brokers, nats_url) {
+        (None, None) => return Ok(()),
+        (Some(_), Some(_)) => {
+            return Err(anyhow::anyhow!(
+                "Kafka and NATS events are mutually exclusive, configure a single sink"
+            ));
+        }
+        (Some(brokers), None) => {
+            let producer: FutureProducer = ClientConfig::new()
+                .set("bootstrap.servers", &brokers)
+                .set("message.timeout.ms", KAFKA_MESSAGE_TIMEOUT_MS)
+                .create()?;
+            log::info!("  <> Kafka events enabled (topic {})", kafka_topic);
+            EventSink::Kafka {
+                producer,
+                topic: kafka_topic,
+            }
+        }
+        (None, Some(url)) => {
+            let client = async_nats::connect(url).await?;
+            log::info!("  <> NATS events enabled (subject {}.*)", nats_subject);
+            EventSink::Nats {
+                client,
+                subject: nats_subject,
+            }
+        }
+    };
+    let (queue, receiver) = mpsc::channel(EVENT_QUEUE_CAPACITY);
+    EVENT_QUEUE
+        .set(queue)
+        .map_err(|_| anyhow::anyhow!("Event sink already initialized"))?;
+    tokio::spawn(run_event_publisher(sink, receiver));
+    Ok(())
+}
+
+// Publish the event to the configured sink (fire and forget, a failure never blocks the keeper)
+// Queued for the publisher task, the events are published in the order they are emitted
+pub fn publish_event(event: KeeperEvent) {
+    let Some(queue) = EVENT_QUEUE.get() else {
+        return;
+    };
+
+    let envelope = EventEnvelope {
+        emitted_at: chrono::Utc::now().timestamp(),
+        event: &event,
+    };
+    let payload = match serde_json::to_string(&envelope) {
+        Ok(payload) => payload,
+        Err(e) => {
+            log::warn!("   <> Failed to serialize the keeper event: {:?}", e);
+            return;
+        }
+    };
+
+    let queued_event = QueuedEvent {
+        name: event.name(),
+        key: event.key().to_owned(),
+        payload,
+    };
+    match queue.try_send(queued_event) {
+        Ok(()) => {}
+        Err(TrySendError::Full(queued_event)) => log::warn!(
+            "   <> Keeper events queue full, {} event dropped",
+            queued_event.name
+        ),
+        Err(TrySendError::Closed(_)) => {}
+    }
+}
+
+// Single publisher: an event is sent once the previous one is
+async fn run_event_publisher(sink: EventSink, mut receiver: mpsc::Receiver<QueuedEvent>) {
+    while let Some(queued_event) = receiver.recv().await {
+        match &sink {
+            EventSink::Kafka { producer, topic } => {
+                let record = FutureRecord::to(topic)
+                    .key(&queued_event.key)
+                    .payload(&queued_event.payload);
+                if let Err((e, _)) = producer.send(record, Duration::from_secs(0)).await {
+                    log::warn!("   <> Failed to publish the keeper event to Kafka: {:?}", e);
+                }
+            }
+            EventSink::Nats { client, subject } => {
+                let subject = format!("{}.{}", subject, queued_event.name).to_subject();
+                if let Err(e) = client.publish(subject, queued_event.payload.into()).await {
+                    log::warn!("   <> Failed to publish the keeper event to NATS: {:?}", e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use {
+    crate::metrics::FEES_PAID_TODAY,
+    std::sync::{LazyLock, Mutex, OnceLock},
+};
+
+// Signature fee - keeper transactions are only signed by the payer
+const BASE_FEE_LAMPORTS: u64 = 5_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// Max fees (base + priority + tips) the keeper can spend per UTC day on non-critical actions, set once at startup
+// If never set, there is no budget
+static DAILY_FEE_BUDGET_LAMPORTS: OnceLock<u64> = OnceLock::new();
+
+static DAILY_FEES: LazyLock<Mutex<DailyFees>> = LazyLock::new(|| Mutex::new(DailyFees::default()));
+
+#[derive(Debug, Default)]
+struct DailyFees {
+    // Days since epoch (UTC)
+    day: i64,
+    lamports: u64,
+    budget_exhausted_alerted: bool,
+}
+
+impl DailyFees {
+    // Start over when a new day begins
+    fn roll(&mut self, current_time: i64) {
+        let day = current_time / SECONDS_PER_DAY;
+        if day != self.day {
+            *self = DailyFees {
+                day,
+                ..Default::default()
+            };
+            FEES_PAID_TODAY.set(0);
+        }
+    }
+}
+
+pub fn init_daily_fee_budget(daily_fee_budget_lamports: Option<u64>) {
+    if let Some(daily_fee_budget_lamports) = daily_fee_budget_lamports {
+        let _ = DAILY_FEE_BUDGET_LAMPORTS.set(daily_fee_budget_lamports);
+    }
+}
+
+// Called for each landed keeper transaction (successful or not, both pay their fees)
+pub fn record_fee_paid(priority_fee_lamports: u64, tip_lamports: u64) {
+    let mut daily_fees = DAILY_FEES.lock().unwrap();
+    daily_fees.roll(chrono::Utc::now().timestamp());
+    daily_fees.lamports += BASE_FEE_LAMPORTS + priority_fee_lamports + tip_lamports;
+    FEES_PAID_TODAY.set(daily_fees.lamports as i64);
+
+    if let Some(&budget) = DAILY_FEE_BUDGET_LAMPORTS.get() {
+        if daily_fees.lamports >= budget && !daily_fees.budget_exhausted_alerted {
+            daily_fees.budget_exhausted_alerted = true;
+            log::error!(
+                "  <> Daily fee budget exhausted ({} / {} lamports) - claims are paused until tomorrow (UTC), resolves continue",
+                daily_fees.lamports,
+                budget
+            );
+        }
+    }
+}
+
+// Whether the non-critical actions (claims) must stop for today
+pub fn is_daily_fee_budget_exhausted() -> bool {
+    let Some(&budget) = DAILY_FEE_BUDGET_LAMPORTS.get() else {
+        return false;
+    };
+    let mut daily_fees = DAILY_FEES.lock().unwrap();
+    daily_fees.roll(chrono::Utc::now().timestamp());
+    daily_fees.lamports >= budget
+}
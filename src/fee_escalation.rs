@@ -0,0 +1,115 @@
+use {
+    crate::tx_tracker::KeeperAction,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::HashMap,
+        sync::{LazyLock, Mutex, OnceLock},
+    },
+};
+
+// Set once at startup - if never set, the actions are always sent at the fetched price
+static FEE_ESCALATION: OnceLock<FeeEscalation> = OnceLock::new();
+
+// Set once at startup - if never set, the overdue rounds are neither alerted nor bid more
+static OVERDUE_ROUND_ESCALATION: OnceLock<OverdueRoundEscalation> = OnceLock::new();
+
+// Number of consecutive transactions of each action (per account) that never landed
+static DROPPED_TRANSACTIONS: LazyLock<Mutex<HashMap<(KeeperAction, Pubkey), u32>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy)]
+struct FeeEscalation {
+    // Applied to the compute unit price for each transaction of the action dropped in a row
+    multiplier: f64,
+    // Cap on the escalated compute unit price (µLamports / cu)
+    max_priority_fee: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OverdueRoundEscalation {
+    // Delay past the end of a staking round after which it is considered overdue
+    after_seconds: i64,
+    // Applied to the compute unit price of the resolves of an overdue round
+    multiplier: f64,
+}
+
+pub fn init_fee_escalation(multiplier: f64, max_priority_fee: u64) {
+    let _ = FEE_ESCALATION.set(FeeEscalation {
+        multiplier: multiplier.max(1.0),
+        max_priority_fee,
+    });
+}
+
+pub fn init_overdue_round_escalation(after_seconds: i64, multiplier: f64) {
+    let _ = OVERDUE_ROUND_ESCALATION.set(OverdueRoundEscalation {
+        after_seconds,
+        multiplier: multiplier.max(1.0),
+    });
+}
+
+// An unresolved round blocks the rewards accrual of every staker of its Staking account
+pub fn is_round_overdue(overdue_seconds: i64) -> bool {
+    OVERDUE_ROUND_ESCALATION
+        .get()
+        .is_some_and(|escalation| overdue_seconds > escalation.after_seconds)
+}
+
+// Compute unit price of the resolve of an overdue round: the fetched one multiplied, capped by the max priority fee
+pub fn overdue_round_priority_fee(priority_fee: u64) -> u64 {
+    let Some(escalation) = OVERDUE_ROUND_ESCALATION.get() else {
+        return priority_fee;
+    };
+    let escalated_priority_fee = (priority_fee as f64 * escalation.multiplier) as u64;
+    let escalated_priority_fee = FEE_ESCALATION
+        .get()
+        .map_or(escalated_priority_fee, |fee_escalation| {
+            escalated_priority_fee.min(fee_escalation.max_priority_fee)
+        });
+    escalated_priority_fee.max(priority_fee)
+}
+
+// The transaction never landed, the next one of the action bids more
+pub fn record_transaction_dropped(action: KeeperAction, account: &Pubkey) {
+    *DROPPED_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .entry((action, *account))
+        .or_insert(0) += 1;
+}
+
+// The transaction landed (successfully or not), the action is back to the fetched price
+pub fn record_transaction_landed(action: KeeperAction, account: &Pubkey) {
+    DROPPED_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .remove(&(action, *account));
+}
+
+// Compute unit price of the action: the fetched one, multiplied for each previous transaction that didn't land
+pub fn escalated_priority_fee(action: KeeperAction, account: &Pubkey, priority_fee: u64) -> u64 {
+    let Some(fee_escalation) = FEE_ESCALATION.get() else {
+        return priority_fee;
+    };
+    let Some(&dropped_transactions) = DROPPED_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .get(&(action, *account))
+    else {
+        return priority_fee;
+    };
+
+    let escalated_priority_fee =
+        (priority_fee as f64 * fee_escalation.multiplier.powi(dropped_transactions as i32)) as u64;
+    let escalated_priority_fee = escalated_priority_fee.min(fee_escalation.max_priority_fee);
+    if escalated_priority_fee > priority_fee {
+        log::info!(
+            "   <> {} of {} dropped {} time(s) - priority fee raised to {} µLamports / cu",
+            action.as_str(),
+            account,
+            dropped_transactions,
+            escalated_priority_fee
+        );
+    }
+    // Never below the fetched price, even if above the cap (the fetched price is already capped)
+    escalated_priority_fee.max(priority_fee)
+}
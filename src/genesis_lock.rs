@@ -0,0 +1,24 @@
+use adrena_abi::{GenesisLock, LockedStake};
+
+// Logged by the program when a claim is rejected because of the genesis lock (anchor error names start with "Genesis")
+const GENESIS_LOCK_ERROR_LOG: &str = "Error Code: Genesis";
+
+// The genesis campaign locked stakes are only claimable once the campaign is over
+pub fn genesis_campaign_end_time(genesis_lock: &GenesisLock) -> i64 {
+    genesis_lock.campaign_start_date + genesis_lock.campaign_duration
+}
+
+pub fn is_genesis_campaign_active(genesis_lock: Option<&GenesisLock>, current_time: i64) -> bool {
+    genesis_lock.is_some_and(|genesis_lock| current_time < genesis_campaign_end_time(genesis_lock))
+}
+
+pub fn is_genesis_locked_stake(locked_stake: &LockedStake) -> bool {
+    locked_stake.is_genesis != 0
+}
+
+// Genesis lock error raised by the program in the simulation logs, if any
+pub fn find_genesis_lock_error(simulation_logs: &[String]) -> Option<&String> {
+    simulation_logs
+        .iter()
+        .find(|line| line.contains(GENESIS_LOCK_ERROR_LOG))
+}
@@ -1,28 +1,64 @@
 use {
-    crate::handlers::create_claim_stakes_ix,
+    crate::{
+        blockhash_cache::latest_blockhash,
+        compute_units::static_cu_limit,
+        fee_escalation::escalated_priority_fee,
+        genesis_lock::{find_genesis_lock_error, is_genesis_locked_stake},
+        handlers::create_claim_stakes_ix,
+        metrics::staking_label,
+        preflight::{classify_simulation_failure, is_cu_exceeded, is_missing_account_error},
+        priority_fees::{write_locked_accounts, write_locked_priority_fee},
+        rate_limiter::rpc_call,
+        token_accounts::{
+            invalidate_owner_token_accounts, reward_token_mint, token_program_id,
+            validate_owner_token_accounts,
+        },
+        tx_submitters::{submit_transaction, tip_instruction},
+        tx_tracker::{track_transaction, KeeperAction, SentTransaction},
+        PendingTransactionsThreadSafe,
+    },
     adrena_abi::{
         get_staking_lm_reward_token_vault_pda, get_staking_pda, get_staking_reward_token_vault_pda,
-        get_transfer_authority_pda, ADX_MINT, SPL_TOKEN_PROGRAM_ID, USDC_MINT,
+        get_transfer_authority_pda, UserStaking, ADX_MINT, ROUND_MIN_DURATION_SECONDS,
     },
     anchor_client::Program,
-    solana_client::rpc_config::RpcSendTransactionConfig,
     solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey, signature::Keypair},
     spl_associated_token_account::instruction::create_associated_token_account_idempotent,
-    std::sync::Arc,
+    std::{collections::VecDeque, sync::Arc},
 };
 
+// Rough compute cost estimates used to split claims before simulating them
+// - fixed cost of the transaction (ATAs creation, accounts loading, liquid stake)
+const CLAIM_STAKES_BASE_CU_ESTIMATE: u64 = 120_000;
+// - fixed cost per locked stake claimed
+const CLAIM_STAKES_CU_PER_LOCKED_STAKE_ESTIMATE: u64 = 15_000;
+// - cost per resolved round a stake has to go through since its last claim
+const CLAIM_STAKES_CU_PER_ROUND_ESTIMATE: u64 = 2_500;
+// The UserStaking account keeps track of up to 32 resolved rounds
+const MAX_RESOLVED_ROUNDS: i64 = 32;
+// From this number of locked stakes going through all the resolved rounds, the claim runs out of the default heap
+const HEAP_FRAME_MIN_LOCKED_STAKES: usize = 8;
+// Logged by the runtime when the program runs out of heap
+const MEMORY_ALLOCATION_FAILED_LOG: &str = "memory allocation failed";
+
 pub enum ClaimStakeOutcome {
     Success,
     NoRewardTokens,
+    // Rejected by the program because of the genesis lock, not a keeper failure
+    GenesisLocked(String),
     Error(anyhow::Error),
 }
 
 pub async fn claim_stakes(
     user_staking_account_key: &Pubkey,
+    user_staking_account: &UserStaking,
     owner_pubkey: &Pubkey,
     program: &Program<Arc<Keypair>>,
+    pending_transactions: &PendingTransactionsThreadSafe,
     median_priority_fee: u64,
     staked_token_mint: &Pubkey,
+    heap_frame_bytes: u32,
+    genesis_campaign_active: bool,
 ) -> Result<ClaimStakeOutcome, backoff::Error<anyhow::Error>> {
     log::info!(
         "  <*> Claiming stakes for UserStaking account {:#?} (owner: {:#?} staked token: {:#?})",
@@ -30,17 +66,57 @@ pub async fn claim_stakes(
         owner_pubkey,
         staked_token_mint
     );
+
     let transfer_authority_pda = get_transfer_authority_pda().0;
     let staking_pda = get_staking_pda(staked_token_mint).0;
 
     let staking_reward_token_vault_pda = get_staking_reward_token_vault_pda(&staking_pda).0;
     let staking_lm_reward_token_vault_pda = get_staking_lm_reward_token_vault_pda(&staking_pda).0;
 
-    // First attempt to claim all stakes - if simulation fails, we will slowly reduce
-    let mut remaining_indices: Vec<u8> = (0..32).collect();
+    // Priced on the recent fees of the shared accounts the claims write-lock (Staking account, vaults), the same for
+    // every batch and every user of the staking
+    let (_, claim_stakes_accounts) = create_claim_stakes_ix(
+        &program.payer(),
+        owner_pubkey,
+        transfer_authority_pda,
+        &staking_pda,
+        user_staking_account_key,
+        &staking_reward_token_vault_pda,
+        &staking_lm_reward_token_vault_pda,
+        None,
+    );
+    let median_priority_fee = write_locked_priority_fee(
+        &write_locked_accounts(&claim_stakes_accounts, &staking_pda),
+        median_priority_fee,
+    )
+    .await;
+    // Bid more if the previous transactions of the action didn't land
+    let median_priority_fee = escalated_priority_fee(
+        KeeperAction::ClaimStakes,
+        user_staking_account_key,
+        median_priority_fee,
+    );
+
+    // Only claim the active locked stakes, split in batches based on their estimated compute cost
+    // The genesis locked stakes are left aside until the end of the genesis campaign
+    let mut pending_batches = split_claim_in_batches(user_staking_account, genesis_campaign_active);
+
+    // If a batch simulation exceeds the CU limit, we will slowly reduce it
     let mut postponed_indices: Vec<u8> = vec![];
     let mut is_empty_claim = false;
-    while !remaining_indices.is_empty() || !postponed_indices.is_empty() {
+    // Once a batch needs a bigger heap, the following ones (same account) request it too
+    let mut request_heap_frame = false;
+    // The token accounts creation is left out of the transactions once they are known to exist
+    let mut owner_token_accounts =
+        validate_owner_token_accounts(&program.rpc(), owner_pubkey).await;
+    while let Some(mut remaining_indices) = pending_batches.pop_front() {
+        log::info!(
+            "   <> Claiming locked stakes {:?} ({} batch(es) left)",
+            remaining_indices,
+            pending_batches.len()
+        );
+        request_heap_frame = heap_frame_bytes != 0
+            && (request_heap_frame || is_heavy_batch(user_staking_account, &remaining_indices));
         let (claim_stakes_params, claim_stakes_accounts) = create_claim_stakes_ix(
             &program.payer(),
             owner_pubkey,
@@ -54,28 +130,43 @@ pub async fn claim_stakes(
 
         let rpc_client = program.rpc();
 
-        let tx_simulation = program
+        let recent_blockhash = latest_blockhash(&rpc_client).await.map_err(|e| {
+            log::error!("   <> Failed to fetch the latest blockhash: {:?}", e);
+            backoff::Error::transient(e)
+        })?;
+
+        let mut request = program
             .request()
             .instruction(ComputeBudgetInstruction::set_compute_unit_price(
                 median_priority_fee,
             ))
-            .instruction(ComputeBudgetInstruction::set_compute_unit_limit(1_000_000))
-            .instruction(create_associated_token_account_idempotent(
-                &program.payer(),
-                owner_pubkey,
-                &ADX_MINT,
-                &SPL_TOKEN_PROGRAM_ID,
-            ))
-            .instruction(create_associated_token_account_idempotent(
-                &program.payer(),
-                owner_pubkey,
-                &USDC_MINT,
-                &SPL_TOKEN_PROGRAM_ID,
-            ))
+            .instruction(ComputeBudgetInstruction::set_compute_unit_limit(
+                static_cu_limit(KeeperAction::ClaimStakes),
+            ));
+        if request_heap_frame {
+            request = request.instruction(ComputeBudgetInstruction::request_heap_frame(
+                heap_frame_bytes,
+            ));
+        }
+        if !owner_token_accounts.exist {
+            request = request
+                .instruction(create_associated_token_account_idempotent(
+                    &program.payer(),
+                    owner_pubkey,
+                    &ADX_MINT,
+                    &token_program_id(&ADX_MINT),
+                ))
+                .instruction(create_associated_token_account_idempotent(
+                    &program.payer(),
+                    owner_pubkey,
+                    &reward_token_mint(),
+                    &token_program_id(&reward_token_mint()),
+                ));
+        }
+        let tx_simulation = request
             .args(claim_stakes_params)
             .accounts(claim_stakes_accounts)
-            .signed_transaction()
-            .await
+            .signed_transaction_with_blockhash(recent_blockhash.blockhash)
             .map_err(|e| {
                 log::error!(
                     "   <> Simulation Transaction generation failed with error: {:?}",
@@ -86,7 +177,7 @@ pub async fn claim_stakes(
 
         let mut simulation_attempts = 0;
         let simulation = loop {
-            match rpc_client.simulate_transaction(&tx_simulation).await {
+            match rpc_call(|| rpc_client.simulate_transaction(&tx_simulation)).await {
                 Ok(simulation) => break simulation,
                 Err(e) => {
                     if e.to_string().contains("BlockhashNotFound") {
@@ -105,9 +196,59 @@ pub async fn claim_stakes(
             }
         };
 
+        let simulation_logs = simulation.value.logs.unwrap_or(vec![]);
+
+        // Out of heap: retry the batch with a bigger heap frame
+        if simulation_logs
+            .iter()
+            .any(|line| line.contains(MEMORY_ALLOCATION_FAILED_LOG))
+        {
+            if request_heap_frame || heap_frame_bytes == 0 {
+                return Err(backoff::Error::transient(anyhow::anyhow!(
+                    "   <> Claim simulation ran out of heap (heap frame requested: {})",
+                    request_heap_frame
+                )));
+            }
+            log::info!("   <> Simulation ran out of heap - retrying with a heap frame");
+            request_heap_frame = true;
+            pending_batches.push_front(remaining_indices);
+            continue;
+        }
+
+        if let Some(genesis_lock_error) = find_genesis_lock_error(&simulation_logs) {
+            log::warn!(
+                "   <> Claim rejected by the genesis lock: {}",
+                genesis_lock_error
+            );
+            return Ok(ClaimStakeOutcome::GenesisLocked(genesis_lock_error.clone()));
+        }
+
+        // The token accounts may have been closed since they were validated: retry the batch creating them
+        if owner_token_accounts.exist
+            && simulation
+                .value
+                .err
+                .as_ref()
+                .is_some_and(is_missing_account_error)
+        {
+            log::info!("   <> Token account missing - retrying with the token accounts creation");
+            invalidate_owner_token_accounts(owner_pubkey);
+            owner_token_accounts.exist = false;
+            pending_batches.push_front(remaining_indices);
+            continue;
+        }
+
+        // Out of compute units: the batch is too heavy, split below like one exceeding the limit
+        let cu_exceeded = simulation.value.err.as_ref().is_some_and(is_cu_exceeded);
+        if let Some(simulation_error) = simulation.value.err.as_ref().filter(|_| !cu_exceeded) {
+            let preflight_failure = classify_simulation_failure(simulation_error, &simulation_logs);
+            log::warn!("   <> Claim {}", preflight_failure);
+            return Err(backoff::Error::permanent(preflight_failure.into()));
+        }
+
         let simulated_cu = simulation.value.units_consumed.unwrap_or(0);
 
-        if simulated_cu == 0 {
+        if simulated_cu == 0 && !cu_exceeded {
             log::warn!(
                 "   <> CU consumed: {} - Seems that the simulation cannot be performed due to low sol balance OR that the state is not updated yet (postpone)",
                 simulated_cu
@@ -115,14 +256,16 @@ pub async fn claim_stakes(
             break;
         }
 
-        // If CU exceeds 1 million, reduce the number of indices (we use 500k instead of 1.4m cause it's more likely to land - eventually lower that further)
-        if simulated_cu >= 1_000_000 {
+        // If CU exceeds the limit, reduce the number of indices (we use 1m instead of 1.4m cause it's more likely to land - eventually lower that further)
+        if cu_exceeded || simulated_cu >= static_cu_limit(KeeperAction::ClaimStakes) as u64 {
             log::info!(
                 "   <> CU consumed: {} - too high, postponing locked stake and retrying",
                 simulated_cu
             );
-            if let Some(index) = remaining_indices.pop() {
-                postponed_indices.push(index);
+            // A single locked stake exceeding the limit cannot be split further
+            if remaining_indices.len() > 1 {
+                postponed_indices.push(remaining_indices.pop().unwrap());
+                pending_batches.push_front(remaining_indices);
             } else {
                 log::warn!("   <> No more indices to pop from remaining_indices");
                 return Err(backoff::Error::transient(anyhow::anyhow!(
@@ -134,7 +277,6 @@ pub async fn claim_stakes(
             // log::info!("   <> CU consumed: {}", simulated_cu);
         }
 
-        let simulation_logs = simulation.value.logs.unwrap_or(vec![]);
         println!("   <> Simulation logs: {:?}", simulation_logs);
 
         is_empty_claim = simulation_logs
@@ -156,44 +298,51 @@ pub async fn claim_stakes(
             Some(&remaining_indices),
         );
 
-        let tx = program
+        let compute_unit_limit = (simulated_cu as f64 * 1.02) as u32; // +2% for any jitter due to find_pda calls
+
+        let mut request = program
             .request()
             .instruction(ComputeBudgetInstruction::set_compute_unit_price(
                 median_priority_fee,
             ))
             .instruction(ComputeBudgetInstruction::set_compute_unit_limit(
-                (simulated_cu as f64 * 1.02) as u32, // +2% for any jitter due to find_pda calls
-            ))
-            .instruction(create_associated_token_account_idempotent(
-                &program.payer(),
-                owner_pubkey,
-                &ADX_MINT,
-                &SPL_TOKEN_PROGRAM_ID,
-            ))
-            .instruction(create_associated_token_account_idempotent(
-                &program.payer(),
-                owner_pubkey,
-                &USDC_MINT,
-                &SPL_TOKEN_PROGRAM_ID,
-            ))
+                compute_unit_limit,
+            ));
+        if request_heap_frame {
+            request = request.instruction(ComputeBudgetInstruction::request_heap_frame(
+                heap_frame_bytes,
+            ));
+        }
+        // Required by the submission backend (e.g. Jito tip)
+        if let Some(tip_instruction) = tip_instruction(KeeperAction::ClaimStakes, &program.payer())
+        {
+            request = request.instruction(tip_instruction);
+        }
+        if !owner_token_accounts.exist {
+            request = request
+                .instruction(create_associated_token_account_idempotent(
+                    &program.payer(),
+                    owner_pubkey,
+                    &ADX_MINT,
+                    &token_program_id(&ADX_MINT),
+                ))
+                .instruction(create_associated_token_account_idempotent(
+                    &program.payer(),
+                    owner_pubkey,
+                    &reward_token_mint(),
+                    &token_program_id(&reward_token_mint()),
+                ));
+        }
+        let tx = request
             .args(claim_stakes_params)
             .accounts(claim_stakes_accounts)
-            .signed_transaction()
-            .await
+            .signed_transaction_with_blockhash(recent_blockhash.blockhash)
             .map_err(|e| {
                 log::error!("   <> Transaction generation failed with error: {:?}", e);
                 backoff::Error::transient(e.into())
             })?;
 
-        let tx_hash = rpc_client
-            .send_transaction_with_config(
-                &tx,
-                RpcSendTransactionConfig {
-                    skip_preflight: true,
-                    max_retries: Some(0),
-                    ..Default::default()
-                },
-            )
+        let tx_hash = submit_transaction(KeeperAction::ClaimStakes, &rpc_client, &tx)
             .await
             .map_err(|e| {
                 log::error!("   <> Transaction sending failed with error: {:?}", e);
@@ -202,9 +351,26 @@ pub async fn claim_stakes(
 
         log::info!("   <> TX sent: {:#?}", tx_hash.to_string());
 
-        // Reset remaining indices and move postponed indices to remaining
-        remaining_indices = postponed_indices;
-        postponed_indices = vec![];
+        track_transaction(
+            pending_transactions,
+            SentTransaction::new(
+                tx_hash,
+                KeeperAction::ClaimStakes,
+                *user_staking_account_key,
+                median_priority_fee,
+                compute_unit_limit,
+            )
+            .with_last_valid_block_height(recent_blockhash.last_valid_block_height)
+            .with_transaction(tx)
+            .with_owner(*owner_pubkey)
+            .with_staking(staking_label(staked_token_mint)),
+        )
+        .await;
+
+        // Postponed indices are claimed in the next transaction
+        if !postponed_indices.is_empty() {
+            pending_batches.push_front(std::mem::take(&mut postponed_indices));
+        }
     }
 
     if is_empty_claim {
@@ -213,3 +379,77 @@ pub async fn claim_stakes(
         Ok(ClaimStakeOutcome::Success)
     }
 }
+
+// Locked stakes to claim if the claim fits in a single transaction with the default heap, None if it has to be split
+// The genesis locked stakes are included, the program rejects the claim while the genesis campaign is active
+pub fn single_batch_claim(user_staking_account: &UserStaking) -> Option<Vec<u8>> {
+    let mut batches = split_claim_in_batches(user_staking_account, false);
+    let batch = batches.pop_front()?;
+    (batches.is_empty() && !is_heavy_batch(user_staking_account, &batch)).then_some(batch)
+}
+
+// Estimate the number of resolved rounds to go through since the last claim
+fn rounds_since_claim(claim_time: i64, current_time: i64) -> u64 {
+    ((current_time - claim_time) / ROUND_MIN_DURATION_SECONDS).clamp(0, MAX_RESOLVED_ROUNDS) as u64
+}
+
+// Claiming many locked stakes that went through all the resolved rounds exceeds the default 32KB heap
+fn is_heavy_batch(user_staking_account: &UserStaking, locked_stakes_indices: &[u8]) -> bool {
+    let current_time = chrono::Utc::now().timestamp();
+    let locked_stakes_at_max_rounds = locked_stakes_indices
+        .iter()
+        .filter_map(|index| user_staking_account.locked_stakes.get(*index as usize))
+        .filter(|locked_stake| {
+            rounds_since_claim(locked_stake.claim_time, current_time) == MAX_RESOLVED_ROUNDS as u64
+        })
+        .count();
+    locked_stakes_at_max_rounds >= HEAP_FRAME_MIN_LOCKED_STAKES
+}
+
+// Greedily pack the active locked stakes indexes in batches whose estimated compute cost fits in the claim CU limit
+fn split_claim_in_batches(
+    user_staking_account: &UserStaking,
+    genesis_campaign_active: bool,
+) -> VecDeque<Vec<u8>> {
+    let current_time = chrono::Utc::now().timestamp();
+    let claim_stakes_cu_limit = static_cu_limit(KeeperAction::ClaimStakes) as u64;
+    // Each transaction pays the fixed cost and (at worst) the liquid stake rounds
+    let batch_base_cu = CLAIM_STAKES_BASE_CU_ESTIMATE
+        + rounds_since_claim(user_staking_account.liquid_stake.claim_time, current_time)
+            * CLAIM_STAKES_CU_PER_ROUND_ESTIMATE;
+
+    let mut batches: VecDeque<Vec<u8>> = VecDeque::new();
+    let mut current_batch: Vec<u8> = vec![];
+    let mut current_batch_cu = batch_base_cu;
+
+    for (index, locked_stake) in user_staking_account.locked_stakes.iter().enumerate() {
+        if locked_stake.amount == 0
+            || (genesis_campaign_active && is_genesis_locked_stake(locked_stake))
+        {
+            continue;
+        }
+        let locked_stake_cu = CLAIM_STAKES_CU_PER_LOCKED_STAKE_ESTIMATE
+            + rounds_since_claim(locked_stake.claim_time, current_time)
+                * CLAIM_STAKES_CU_PER_ROUND_ESTIMATE;
+
+        if !current_batch.is_empty() && current_batch_cu + locked_stake_cu > claim_stakes_cu_limit {
+            batches.push_back(std::mem::take(&mut current_batch));
+            current_batch_cu = batch_base_cu;
+        }
+        current_batch.push(index as u8);
+        current_batch_cu += locked_stake_cu;
+    }
+
+    // Also covers accounts without locked stakes, only the liquid stake is claimed
+    if !current_batch.is_empty() || batches.is_empty() {
+        batches.push_back(current_batch);
+    }
+
+    if batches.len() > 1 {
+        log::info!(
+            "   <> Claim split in {} transactions based on estimated compute cost",
+            batches.len()
+        );
+    }
+    batches
+}
@@ -1,9 +1,11 @@
 use {
+    crate::token_accounts::{
+        get_owner_token_accounts, reward_token_mint, staking_token_program_id,
+    },
     adrena_abi::{
         ADRENA_GOVERNANCE_REALM_CONFIG_ID, ADRENA_GOVERNANCE_REALM_ID,
         ADRENA_GOVERNANCE_SHADOW_TOKEN_MINT, ADX_MINT, CORTEX_ID, GENESIS_LOCK_ID,
-        GOVERNANCE_PROGRAM_ID, MAIN_POOL_ID, SPL_ASSOCIATED_TOKEN_PROGRAM_ID, SPL_TOKEN_PROGRAM_ID,
-        USDC_MINT,
+        GOVERNANCE_PROGRAM_ID, MAIN_POOL_ID,
     },
     solana_sdk::{pubkey::Pubkey, system_program},
 };
@@ -30,10 +32,10 @@ pub fn create_resolve_staking_round_ix(
         staking: *staking_pda,
         cortex: CORTEX_ID,
         lm_token_mint: ADX_MINT,
-        fee_redistribution_mint: USDC_MINT,
+        fee_redistribution_mint: reward_token_mint(),
         adrena_program: adrena_abi::ID,
         system_program: system_program::ID,
-        token_program: SPL_TOKEN_PROGRAM_ID,
+        token_program: staking_token_program_id(),
     };
     let accounts = resolve_staking_round;
     (args, accounts)
@@ -52,24 +54,7 @@ pub fn create_claim_stakes_ix(
     adrena_abi::instruction::ClaimStakes,
     adrena_abi::accounts::ClaimStakes,
 ) {
-    let reward_token_account = Pubkey::find_program_address(
-        &[
-            &owner_pubkey.to_bytes(),
-            &SPL_TOKEN_PROGRAM_ID.to_bytes(),
-            &USDC_MINT.to_bytes(),
-        ],
-        &SPL_ASSOCIATED_TOKEN_PROGRAM_ID,
-    )
-    .0;
-    let lm_token_account = Pubkey::find_program_address(
-        &[
-            &owner_pubkey.to_bytes(),
-            &SPL_TOKEN_PROGRAM_ID.to_bytes(),
-            &ADX_MINT.to_bytes(),
-        ],
-        &SPL_ASSOCIATED_TOKEN_PROGRAM_ID,
-    )
-    .0;
+    let owner_token_accounts = get_owner_token_accounts(owner_pubkey);
 
     let args = adrena_abi::instruction::ClaimStakes {
         params: adrena_abi::types::ClaimStakesParams {
@@ -80,8 +65,8 @@ pub fn create_claim_stakes_ix(
         caller: *payer,
         payer: *payer,
         owner: *owner_pubkey,
-        reward_token_account,
-        lm_token_account,
+        reward_token_account: owner_token_accounts.reward_token_account,
+        lm_token_account: owner_token_accounts.lm_token_account,
         staking_reward_token_vault: *staking_reward_token_vault_pda,
         staking_lm_reward_token_vault: *staking_lm_reward_token_vault_pda,
         transfer_authority: transfer_authority_pda,
@@ -91,10 +76,10 @@ pub fn create_claim_stakes_ix(
         pool: MAIN_POOL_ID,
         genesis_lock: GENESIS_LOCK_ID,
         lm_token_mint: ADX_MINT,
-        fee_redistribution_mint: USDC_MINT,
+        fee_redistribution_mint: reward_token_mint(),
         adrena_program: adrena_abi::ID,
         system_program: system_program::ID,
-        token_program: SPL_TOKEN_PROGRAM_ID,
+        token_program: staking_token_program_id(),
     };
     (args, accounts)
 }
@@ -103,6 +88,7 @@ pub fn create_finalize_locked_stake_ix(
     payer: &Pubkey,
     owner_pubkey: &Pubkey,
     locked_stake_id: u64,
+    early_exit: bool,
     transfer_authority_pda: &Pubkey,
     staking_pda: &Pubkey,
     user_staking_account_pda: &Pubkey,
@@ -114,7 +100,7 @@ pub fn create_finalize_locked_stake_ix(
 ) {
     let args = adrena_abi::instruction::FinalizeLockedStake {
         params: adrena_abi::types::FinalizeLockedStakeParams {
-            early_exit: false,
+            early_exit,
             locked_stake_id,
         },
     };
@@ -134,7 +120,7 @@ pub fn create_finalize_locked_stake_ix(
         governance_program: GOVERNANCE_PROGRAM_ID,
         adrena_program: adrena_abi::ID,
         system_program: system_program::ID,
-        token_program: SPL_TOKEN_PROGRAM_ID,
+        token_program: staking_token_program_id(),
     };
     let accounts = finalize_locked_stake;
     (args, accounts)
@@ -142,6 +128,7 @@ pub fn create_finalize_locked_stake_ix(
 
 pub fn create_update_pool_aum_ix(
     payer: &Pubkey,
+    pool_pda: &Pubkey,
 ) -> (
     adrena_abi::instruction::UpdatePoolAum,
     adrena_abi::accounts::UpdatePoolAum,
@@ -150,7 +137,7 @@ pub fn create_update_pool_aum_ix(
     let accounts = adrena_abi::accounts::UpdatePoolAum {
         payer: *payer,
         cortex: CORTEX_ID,
-        pool: MAIN_POOL_ID,
+        pool: *pool_pda,
     };
     (args, accounts)
 }
@@ -1,31 +1,54 @@
 use {
-    crate::handlers::create_finalize_locked_stake_ix,
+    crate::{
+        action_retries::ActionId,
+        blockhash_cache::latest_blockhash,
+        compute_units::static_cu_limit,
+        fee_escalation::escalated_priority_fee,
+        handlers::create_finalize_locked_stake_ix,
+        instruction_packer::{send_instruction_pack, DueInstruction, InstructionPack},
+        metrics::staking_label,
+        preflight::classify_simulation_failure,
+        priority_fees::{write_locked_accounts, write_locked_priority_fee},
+        rate_limiter::rpc_call,
+        token_accounts::{get_owner_token_accounts, reward_token_mint, token_program_id},
+        tx_tracker::KeeperAction,
+        PendingTransactionsThreadSafe,
+    },
     adrena_abi::{
         get_governing_token_holding_pda, get_staking_pda, get_token_owner_record_pda,
         get_transfer_authority_pda, ADRENA_GOVERNANCE_REALM_ID,
-        ADRENA_GOVERNANCE_SHADOW_TOKEN_MINT, ADX_MINT, SPL_TOKEN_PROGRAM_ID, USDC_MINT,
+        ADRENA_GOVERNANCE_SHADOW_TOKEN_MINT, ADX_MINT,
+    },
+    anchor_client::{
+        anchor_lang::{InstructionData, ToAccountMetas},
+        Program,
+    },
+    solana_sdk::{
+        compute_budget::ComputeBudgetInstruction, instruction::Instruction, pubkey::Pubkey,
+        signature::Keypair,
     },
-    anchor_client::Program,
-    solana_client::rpc_config::RpcSendTransactionConfig,
-    solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey, signature::Keypair},
     spl_associated_token_account::instruction::create_associated_token_account_idempotent,
-    std::sync::Arc,
+    std::{sync::Arc, time::Instant},
 };
 
-pub async fn finalize_locked_stake(
+// Simulate the finalization and return it ready to be packed (see instruction_packer.rs)
+pub async fn prepare_finalize_locked_stake(
     user_staking_account_key: &Pubkey,
     owner_pubkey: &Pubkey,
     program: &Program<Arc<Keypair>>,
     median_priority_fee: u64,
     staked_token_mint: &Pubkey,
     locked_stake_id: u64,
-) -> Result<(), backoff::Error<anyhow::Error>> {
+    // Admin only (support cases), the keeper finalizes the locked stakes once they end
+    early_exit: bool,
+) -> Result<DueInstruction, backoff::Error<anyhow::Error>> {
     log::info!(
         "  <*> Finalizing locked stake for UserStaking account {:#?} (owner: {:#?} staked token: {:#?})",
         user_staking_account_key,
         owner_pubkey,
         staked_token_mint
     );
+
     let transfer_authority_pda = get_transfer_authority_pda().0;
     let staking_pda = get_staking_pda(staked_token_mint).0;
 
@@ -42,11 +65,17 @@ pub async fn finalize_locked_stake(
 
     let rpc_client = program.rpc();
 
+    let recent_blockhash = latest_blockhash(&rpc_client).await.map_err(|e| {
+        log::error!("   <> Failed to fetch the latest blockhash: {:?}", e);
+        backoff::Error::transient(e)
+    })?;
+
     let (finalize_locked_stake_params, finalize_locked_stake_accounts) =
         create_finalize_locked_stake_ix(
             &program.payer(),
             owner_pubkey,
             locked_stake_id,
+            early_exit,
             &transfer_authority_pda,
             &staking_pda,
             user_staking_account_key,
@@ -54,35 +83,58 @@ pub async fn finalize_locked_stake(
             &governance_governing_token_owner_record_pda,
         );
 
-    let tx_simulation = program
-        .request()
-        .instruction(ComputeBudgetInstruction::set_compute_unit_price(
-            median_priority_fee,
-        ))
-        .instruction(ComputeBudgetInstruction::set_compute_unit_limit(1_000_000))
-        .instruction(create_associated_token_account_idempotent(
+    // Priced on the recent fees of the shared accounts the finalization write-locks (Staking account, vaults)
+    let median_priority_fee = write_locked_priority_fee(
+        &write_locked_accounts(&finalize_locked_stake_accounts, &staking_pda),
+        median_priority_fee,
+    )
+    .await;
+    // Bid more if the previous transactions of the action didn't land
+    let median_priority_fee = escalated_priority_fee(
+        KeeperAction::FinalizeLockedStake,
+        user_staking_account_key,
+        median_priority_fee,
+    );
+
+    let instructions = vec![
+        create_associated_token_account_idempotent(
             &program.payer(),
             owner_pubkey,
             &ADX_MINT,
-            &SPL_TOKEN_PROGRAM_ID,
-        ))
-        .instruction(create_associated_token_account_idempotent(
+            &token_program_id(&ADX_MINT),
+        ),
+        create_associated_token_account_idempotent(
             &program.payer(),
             owner_pubkey,
-            &USDC_MINT,
-            &SPL_TOKEN_PROGRAM_ID,
+            &reward_token_mint(),
+            &token_program_id(&reward_token_mint()),
+        ),
+        Instruction {
+            program_id: adrena_abi::ID,
+            accounts: finalize_locked_stake_accounts.to_account_metas(None),
+            data: finalize_locked_stake_params.data(),
+        },
+    ];
+
+    let mut request = program
+        .request()
+        .instruction(ComputeBudgetInstruction::set_compute_unit_price(
+            median_priority_fee,
         ))
-        .args(finalize_locked_stake_params)
-        .accounts(finalize_locked_stake_accounts)
-        .signed_transaction()
-        .await
+        .instruction(ComputeBudgetInstruction::set_compute_unit_limit(
+            static_cu_limit(KeeperAction::FinalizeLockedStake),
+        ));
+    for instruction in instructions.iter() {
+        request = request.instruction(instruction.clone());
+    }
+    let tx_simulation = request
+        .signed_transaction_with_blockhash(recent_blockhash.blockhash)
         .map_err(|e| {
             log::error!("   <> Transaction generation failed with error: {:?}", e);
             backoff::Error::transient(e.into())
         })?;
 
-    let simulation = rpc_client
-        .simulate_transaction(&tx_simulation)
+    let simulation = rpc_call(|| rpc_client.simulate_transaction(&tx_simulation))
         .await
         .map_err(|e| {
             log::error!("   <> Simulation failed with error: {:?}", e);
@@ -90,6 +142,15 @@ pub async fn finalize_locked_stake(
         })?;
     // log::info!("Simulation result: {:?}", simulation);
 
+    if let Some(simulation_error) = &simulation.value.err {
+        let preflight_failure = classify_simulation_failure(
+            simulation_error,
+            simulation.value.logs.as_deref().unwrap_or_default(),
+        );
+        log::warn!("   <> Finalization {}", preflight_failure);
+        return Err(backoff::Error::permanent(preflight_failure.into()));
+    }
+
     let simulated_cu = simulation.value.units_consumed.unwrap_or(0);
 
     if simulated_cu == 0 {
@@ -103,69 +164,50 @@ pub async fn finalize_locked_stake(
     }
     // log::info!("CU consumed: {}", simulated_cu);
 
-    let (finalize_locked_stake_params, finalize_locked_stake_accounts) =
-        create_finalize_locked_stake_ix(
-            &program.payer(),
-            owner_pubkey,
-            locked_stake_id,
-            &transfer_authority_pda,
-            &staking_pda,
-            user_staking_account_key,
-            &governance_governing_token_holding_pda,
-            &governance_governing_token_owner_record_pda,
-        );
-
-    let tx = program
-        .request()
-        .instruction(ComputeBudgetInstruction::set_compute_unit_price(
-            median_priority_fee,
-        ))
-        .instruction(ComputeBudgetInstruction::set_compute_unit_limit(
-            (simulated_cu as f64 * 1.02) as u32, // +2% for any jitter due to find_pda calls
-        ))
-        .instruction(create_associated_token_account_idempotent(
-            &program.payer(),
-            owner_pubkey,
-            &ADX_MINT,
-            &SPL_TOKEN_PROGRAM_ID,
-        ))
-        .instruction(create_associated_token_account_idempotent(
-            &program.payer(),
-            owner_pubkey,
-            &USDC_MINT,
-            &SPL_TOKEN_PROGRAM_ID,
-        ))
-        .args(finalize_locked_stake_params)
-        .accounts(finalize_locked_stake_accounts)
-        .signed_transaction()
-        .await
-        .map_err(|e| {
-            log::error!("   <> Transaction generation failed with error: {:?}", e);
-            backoff::Error::transient(e.into())
-        })?;
-
-    let tx_hash = rpc_client
-        .send_transaction_with_config(
-            &tx,
-            RpcSendTransactionConfig {
-                skip_preflight: true,
-                max_retries: Some(0),
-                ..Default::default()
-            },
-        )
-        .await
-        .map_err(|e| {
-            log::error!("   <> Transaction sending failed with error: {:?}", e);
-            backoff::Error::transient(e.into())
-        })?;
+    let owner_token_accounts = get_owner_token_accounts(owner_pubkey);
+    Ok(DueInstruction {
+        action_id: ActionId::finalize_locked_stake(*user_staking_account_key, locked_stake_id),
+        staking: staking_label(staked_token_mint),
+        instructions,
+        compute_unit_limit: (simulated_cu as f64 * 1.02) as u32, // +2% for any jitter due to find_pda calls
+        priority_fee: median_priority_fee,
+        // Written for this owner only: the UserStaking account, the governance record and the token accounts
+        exclusive_accounts: vec![
+            *user_staking_account_key,
+            governance_governing_token_owner_record_pda,
+            owner_token_accounts.lm_token_account,
+            owner_token_accounts.reward_token_account,
+        ],
+        prepared_at: Instant::now(),
+        send_alone: false,
+    })
+}
 
-    log::info!(
-        "   <> Finalize locked stake for staking account {:#?} - TX sent: {:#?}",
+// Finalize a locked stake in its own transaction, right away (admin early exits)
+pub async fn finalize_locked_stake(
+    user_staking_account_key: &Pubkey,
+    owner_pubkey: &Pubkey,
+    program: &Program<Arc<Keypair>>,
+    pending_transactions: &PendingTransactionsThreadSafe,
+    median_priority_fee: u64,
+    staked_token_mint: &Pubkey,
+    locked_stake_id: u64,
+    early_exit: bool,
+) -> Result<(), backoff::Error<anyhow::Error>> {
+    let due_instruction = prepare_finalize_locked_stake(
         user_staking_account_key,
-        tx_hash.to_string(),
-    );
-
-    // TODO wait for confirmation and retry if needed
-
-    Ok(())
+        owner_pubkey,
+        program,
+        median_priority_fee,
+        staked_token_mint,
+        locked_stake_id,
+        early_exit,
+    )
+    .await?;
+    send_instruction_pack(
+        program,
+        pending_transactions,
+        &InstructionPack::single(due_instruction),
+    )
+    .await
 }
@@ -1,17 +1,50 @@
 use {
-    crate::{handlers::create_resolve_staking_round_ix, RESOLVE_STAKING_ROUND_CU_LIMIT},
-    adrena_abi::get_transfer_authority_pda,
-    anchor_client::Program,
-    solana_client::rpc_config::RpcSendTransactionConfig,
-    solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey, signature::Keypair},
+    crate::{
+        blockhash_cache::latest_blockhash,
+        compute_units::{compute_unit_limit, static_cu_limit, MAX_CU_LIMIT},
+        fee_escalation::{escalated_priority_fee, overdue_round_priority_fee},
+        handlers::{create_claim_stakes_ix, create_resolve_staking_round_ix},
+        metrics::NO_STAKING_LABEL,
+        priority_fees::{write_locked_accounts, write_locked_priority_fee},
+        rate_limiter::rpc_call,
+        reward_rates::staking_name,
+        tx_submitters::{submit_transaction, tip_instruction},
+        tx_tracker::{track_transaction, KeeperAction, SentTransaction},
+        PendingTransactionsThreadSafe,
+    },
+    adrena_abi::{
+        get_staking_lm_reward_token_vault_pda, get_staking_reward_token_vault_pda,
+        get_transfer_authority_pda,
+    },
+    anchor_client::{
+        anchor_lang::{InstructionData, ToAccountMetas},
+        Program,
+    },
+    solana_sdk::{
+        compute_budget::ComputeBudgetInstruction, hash::Hash, instruction::Instruction,
+        pubkey::Pubkey, signature::Keypair, transaction::Transaction,
+    },
     std::sync::Arc,
 };
 
+// A due claim sent in the resolve transaction, right after the ResolveStakingRound instruction: it collects the round
+// just resolved, and the keeper pays one set of fees for both
+#[derive(Debug)]
+pub struct CombinedClaim {
+    pub user_staking_account_key: Pubkey,
+    pub owner_pubkey: Pubkey,
+    pub locked_stake_indexes: Vec<u8>,
+}
+
 pub async fn resolve_staking_round(
     staking_account_key: &Pubkey,
     program: &Program<Arc<Keypair>>,
+    pending_transactions: &PendingTransactionsThreadSafe,
     median_priority_fee: u64,
-) -> Result<(), backoff::Error<anyhow::Error>> {
+    // The round is overdue, bid more than the accounts recent fees
+    overdue_round: bool,
+    combined_claim: Option<&CombinedClaim>,
+) -> Result<bool, backoff::Error<anyhow::Error>> {
     log::info!(
         "  <*> Resolving staking round for staking account {:#?}",
         staking_account_key
@@ -35,34 +68,89 @@ pub async fn resolve_staking_round(
             &staking_lm_reward_token_vault_pda,
         );
 
-    let tx = program
-        .request()
-        .instruction(ComputeBudgetInstruction::set_compute_unit_price(
+    let rpc_client = program.rpc();
+
+    // Priced on the recent fees of the accounts the resolve write-locks (Staking account, vaults)
+    let median_priority_fee = write_locked_priority_fee(
+        &write_locked_accounts(&resolve_staking_round_accounts, staking_account_key),
+        median_priority_fee,
+    )
+    .await;
+    let median_priority_fee = if overdue_round {
+        overdue_round_priority_fee(median_priority_fee)
+    } else {
+        median_priority_fee
+    };
+    // Bid more if the previous transactions of the action didn't land
+    let median_priority_fee = escalated_priority_fee(
+        KeeperAction::ResolveStakingRound,
+        staking_account_key,
+        median_priority_fee,
+    );
+
+    let recent_blockhash = latest_blockhash(&rpc_client).await.map_err(|e| {
+        log::error!("Failed to fetch the latest blockhash: {:?}", e);
+        backoff::Error::transient(e)
+    })?;
+
+    let mut instructions = vec![Instruction {
+        program_id: adrena_abi::ID,
+        accounts: resolve_staking_round_accounts.to_account_metas(None),
+        data: resolve_staking_round_params.data(),
+    }];
+    let mut compute_unit_limit = compute_unit_limit(KeeperAction::ResolveStakingRound);
+
+    // The claim only goes along if the combined transaction simulates fine, the resolve must not depend on it
+    let mut claim_included = false;
+    if let Some(combined_claim) = combined_claim {
+        let claim_stakes_instruction =
+            create_combined_claim_instruction(program, staking_account_key, combined_claim);
+        let combined_instructions = [instructions[0].clone(), claim_stakes_instruction.clone()];
+        let simulation_cu_limit =
+            (compute_unit_limit + static_cu_limit(KeeperAction::ClaimStakes)).min(MAX_CU_LIMIT);
+        let tx_simulation = build_transaction(
+            program,
             median_priority_fee,
-        ))
-        .instruction(ComputeBudgetInstruction::set_compute_unit_limit(
-            RESOLVE_STAKING_ROUND_CU_LIMIT,
-        ))
-        .args(resolve_staking_round_params)
-        .accounts(resolve_staking_round_accounts)
-        .signed_transaction()
-        .await
-        .map_err(|e| {
-            log::error!("Transaction generation failed with error: {:?}", e);
-            backoff::Error::transient(e.into())
-        })?;
+            simulation_cu_limit,
+            &combined_instructions,
+            recent_blockhash.blockhash,
+        )?;
+        match rpc_call(|| rpc_client.simulate_transaction(&tx_simulation)).await {
+            Ok(simulation)
+                if simulation.value.err.is_none()
+                    && simulation.value.units_consumed.unwrap_or(0) != 0 =>
+            {
+                let simulated_cu = simulation.value.units_consumed.unwrap_or(0);
+                // +2% for any jitter due to find_pda calls
+                compute_unit_limit = ((simulated_cu as f64 * 1.02) as u32).min(MAX_CU_LIMIT);
+                instructions.push(claim_stakes_instruction);
+                claim_included = true;
+                log::info!(
+                    "  <> Claim of UserStaking account {} combined with the resolve",
+                    combined_claim.user_staking_account_key
+                );
+            }
+            Ok(simulation) => log::info!(
+                "  <> Combined claim of UserStaking account {} failed simulation ({:?}) - resolving alone",
+                combined_claim.user_staking_account_key,
+                simulation.value.err
+            ),
+            Err(e) => log::warn!(
+                "   <> Combined claim simulation failed: {:?} - resolving alone",
+                e
+            ),
+        }
+    }
 
-    let rpc_client = program.rpc();
+    let tx = build_transaction(
+        program,
+        median_priority_fee,
+        compute_unit_limit,
+        &instructions,
+        recent_blockhash.blockhash,
+    )?;
 
-    let tx_hash = rpc_client
-        .send_transaction_with_config(
-            &tx,
-            RpcSendTransactionConfig {
-                skip_preflight: true,
-                max_retries: Some(0),
-                ..Default::default()
-            },
-        )
+    let tx_hash = submit_transaction(KeeperAction::ResolveStakingRound, &rpc_client, &tx)
         .await
         .map_err(|e| {
             log::error!("Transaction sending failed with error: {:?}", e);
@@ -75,7 +163,77 @@ pub async fn resolve_staking_round(
         tx_hash.to_string(),
     );
 
-    // TODO wait for confirmation and retry if needed
+    let mut sent_transaction = SentTransaction::new(
+        tx_hash,
+        KeeperAction::ResolveStakingRound,
+        *staking_account_key,
+        median_priority_fee,
+        compute_unit_limit,
+    )
+    .with_last_valid_block_height(recent_blockhash.last_valid_block_height)
+    .with_transaction(tx)
+    .with_staking(staking_name(staking_account_key).unwrap_or(NO_STAKING_LABEL));
+    if let Some(combined_claim) = combined_claim.filter(|_| claim_included) {
+        sent_transaction = sent_transaction.with_combined_claim(
+            combined_claim.user_staking_account_key,
+            combined_claim.owner_pubkey,
+        );
+    }
+    track_transaction(pending_transactions, sent_transaction).await;
+
+    Ok(claim_included)
+}
+
+fn create_combined_claim_instruction(
+    program: &Program<Arc<Keypair>>,
+    staking_account_key: &Pubkey,
+    combined_claim: &CombinedClaim,
+) -> Instruction {
+    let (claim_stakes_params, claim_stakes_accounts) = create_claim_stakes_ix(
+        &program.payer(),
+        &combined_claim.owner_pubkey,
+        get_transfer_authority_pda().0,
+        staking_account_key,
+        &combined_claim.user_staking_account_key,
+        &get_staking_reward_token_vault_pda(staking_account_key).0,
+        &get_staking_lm_reward_token_vault_pda(staking_account_key).0,
+        Some(&combined_claim.locked_stake_indexes),
+    );
+    Instruction {
+        program_id: adrena_abi::ID,
+        accounts: claim_stakes_accounts.to_account_metas(None),
+        data: claim_stakes_params.data(),
+    }
+}
 
-    Ok(())
+fn build_transaction(
+    program: &Program<Arc<Keypair>>,
+    median_priority_fee: u64,
+    compute_unit_limit: u32,
+    instructions: &[Instruction],
+    recent_blockhash: Hash,
+) -> Result<Transaction, backoff::Error<anyhow::Error>> {
+    let mut request = program
+        .request()
+        .instruction(ComputeBudgetInstruction::set_compute_unit_price(
+            median_priority_fee,
+        ))
+        .instruction(ComputeBudgetInstruction::set_compute_unit_limit(
+            compute_unit_limit,
+        ));
+    // Required by the submission backend (e.g. Jito tip)
+    if let Some(tip_instruction) =
+        tip_instruction(KeeperAction::ResolveStakingRound, &program.payer())
+    {
+        request = request.instruction(tip_instruction);
+    }
+    for instruction in instructions {
+        request = request.instruction(instruction.clone());
+    }
+    request
+        .signed_transaction_with_blockhash(recent_blockhash)
+        .map_err(|e| {
+            log::error!("Transaction generation failed with error: {:?}", e);
+            backoff::Error::transient(e.into())
+        })
 }
@@ -1,60 +1,54 @@
 use {
-    crate::{handlers::create_update_pool_aum_ix, UPDATE_AUM_CU_LIMIT},
-    anchor_client::Program,
-    solana_client::rpc_config::RpcSendTransactionConfig,
+    crate::{
+        action_retries::ActionId, compute_units::compute_unit_limit,
+        fee_escalation::escalated_priority_fee, handlers::create_update_pool_aum_ix,
+        instruction_packer::DueInstruction, metrics::NO_STAKING_LABEL, tx_tracker::KeeperAction,
+    },
+    anchor_client::{
+        anchor_lang::{InstructionData, ToAccountMetas},
+        Program,
+    },
     solana_sdk::{
-        compute_budget::ComputeBudgetInstruction, instruction::AccountMeta, signature::Keypair,
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        signature::Keypair,
     },
-    std::sync::Arc,
+    std::{sync::Arc, time::Instant},
 };
 
-pub async fn update_pool_aum(
+// The AUM update, ready to be packed (see instruction_packer.rs)
+pub fn prepare_update_pool_aum(
     program: &Program<Arc<Keypair>>,
     median_priority_fee: u64,
+    pool_key: &Pubkey,
     remaining_accounts: Vec<AccountMeta>,
-) -> Result<(), backoff::Error<anyhow::Error>> {
-    log::info!("  <*> Updating AUM");
-
-    let (update_pool_aum_params, update_pool_aum_accounts) =
-        create_update_pool_aum_ix(&program.payer());
-
-    let tx = program
-        .request()
-        .instruction(ComputeBudgetInstruction::set_compute_unit_price(
-            median_priority_fee,
-        ))
-        .instruction(ComputeBudgetInstruction::set_compute_unit_limit(
-            UPDATE_AUM_CU_LIMIT,
-        ))
-        .args(update_pool_aum_params)
-        .accounts(update_pool_aum_accounts)
-        // Remaining accounts
-        .accounts(remaining_accounts)
-        .signed_transaction()
-        .await
-        .map_err(|e| {
-            log::error!("   <> Transaction generation failed with error: {:?}", e);
-            backoff::Error::transient(e.into())
-        })?;
+) -> DueInstruction {
+    log::info!("  <*> Updating AUM of pool {:#?}", pool_key);
 
-    let rpc_client = program.rpc();
+    // Bid more if the previous transactions of the action didn't land
+    let median_priority_fee =
+        escalated_priority_fee(KeeperAction::UpdatePoolAum, pool_key, median_priority_fee);
 
-    let tx_hash = rpc_client
-        .send_transaction_with_config(
-            &tx,
-            RpcSendTransactionConfig {
-                skip_preflight: true,
-                max_retries: Some(0),
-                ..Default::default()
-            },
-        )
-        .await
-        .map_err(|e| {
-            log::error!("   <> Transaction sending failed with error: {:?}", e);
-            backoff::Error::transient(e.into())
-        })?;
+    let (update_pool_aum_params, update_pool_aum_accounts) =
+        create_update_pool_aum_ix(&program.payer(), pool_key);
 
-    log::info!("   <> TX sent: {:#?}", tx_hash.to_string());
+    let mut accounts = update_pool_aum_accounts.to_account_metas(None);
+    // Remaining accounts
+    accounts.extend(remaining_accounts);
 
-    Ok(())
+    DueInstruction {
+        action_id: ActionId::new(KeeperAction::UpdatePoolAum, *pool_key),
+        staking: NO_STAKING_LABEL,
+        instructions: vec![Instruction {
+            program_id: adrena_abi::ID,
+            accounts,
+            data: update_pool_aum_params.data(),
+        }],
+        compute_unit_limit: compute_unit_limit(KeeperAction::UpdatePoolAum),
+        priority_fee: median_priority_fee,
+        exclusive_accounts: vec![*pool_key],
+        prepared_at: Instant::now(),
+        // Not simulated (e.g. a stale oracle makes it fail): never packed, it would revert the finalizations along
+        send_alone: true,
+    }
 }
@@ -0,0 +1,179 @@
+use {
+    crate::metrics::HEALTH_STATE,
+    serde::Serialize,
+    std::{
+        sync::atomic::{AtomicI64, Ordering},
+        time::Duration,
+    },
+    tokio::time::interval,
+};
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+// Past this silence of the stream or the periodic evaluations, the core loop is considered wedged
+const CORE_LOOP_MAX_SILENCE_SECONDS: i64 = 60;
+// RPC / DB calls failing without a single success for that long
+const DEPENDENCY_MAX_FAILING_SECONDS: i64 = 60;
+// Transactions sent for that long without any landing
+const CONFIRMATION_MAX_SILENCE_SECONDS: i64 = 180;
+
+// Unix timestamps of the keeper signals, 0 until the first occurrence
+static STARTED_AT: AtomicI64 = AtomicI64::new(0);
+static LAST_STREAM_MESSAGE: AtomicI64 = AtomicI64::new(0);
+static LAST_EVALUATION: AtomicI64 = AtomicI64::new(0);
+static LAST_RPC_SUCCESS: AtomicI64 = AtomicI64::new(0);
+static LAST_RPC_FAILURE: AtomicI64 = AtomicI64::new(0);
+static LAST_DB_SUCCESS: AtomicI64 = AtomicI64::new(0);
+static LAST_DB_FAILURE: AtomicI64 = AtomicI64::new(0);
+static LAST_TRANSACTION_SENT: AtomicI64 = AtomicI64::new(0);
+static LAST_TRANSACTION_LANDED: AtomicI64 = AtomicI64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    Healthy,
+    // The keeper works but one of its dependencies is failing
+    Degraded,
+    // The keeper can't do its job: core loop wedged, or nothing can be sent nor confirmed
+    Unhealthy,
+}
+
+// Single view of the keeper health, shared by the health endpoint, the systemd watchdog and the alerting
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub state: HealthState,
+    pub reasons: Vec<&'static str>,
+    // Seconds since each signal last happened (None: never since startup)
+    pub seconds_since_stream_message: Option<i64>,
+    pub seconds_since_evaluation: Option<i64>,
+    pub seconds_since_rpc_success: Option<i64>,
+    pub seconds_since_db_success: Option<i64>,
+    pub seconds_since_transaction_landed: Option<i64>,
+}
+
+fn record(signal: &AtomicI64) {
+    signal.store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+}
+
+pub fn init_health() {
+    record(&STARTED_AT);
+}
+
+pub fn record_stream_message() {
+    record(&LAST_STREAM_MESSAGE);
+}
+
+// A periodic evaluation (resolves / claims) has completed
+pub fn record_evaluation() {
+    record(&LAST_EVALUATION);
+}
+
+pub fn record_rpc_outcome(success: bool) {
+    record(if success {
+        &LAST_RPC_SUCCESS
+    } else {
+        &LAST_RPC_FAILURE
+    });
+}
+
+pub fn record_db_outcome(success: bool) {
+    record(if success {
+        &LAST_DB_SUCCESS
+    } else {
+        &LAST_DB_FAILURE
+    });
+}
+
+pub fn record_sent_transaction() {
+    record(&LAST_TRANSACTION_SENT);
+}
+
+// Confirmed or failed onchain, both mean the keeper transactions get through
+pub fn record_landed_transaction() {
+    record(&LAST_TRANSACTION_LANDED);
+}
+
+fn seconds_since(signal: &AtomicI64, current_time: i64) -> Option<i64> {
+    match signal.load(Ordering::Relaxed) {
+        0 => None,
+        last => Some(current_time - last),
+    }
+}
+
+// Silence of a signal, counted from the startup if it never happened (grace period)
+fn silence(signal: &AtomicI64, current_time: i64) -> i64 {
+    let last = signal.load(Ordering::Relaxed);
+    current_time - last.max(STARTED_AT.load(Ordering::Relaxed))
+}
+
+fn is_failing(last_success: &AtomicI64, last_failure: &AtomicI64, current_time: i64) -> bool {
+    last_failure.load(Ordering::Relaxed) > last_success.load(Ordering::Relaxed)
+        && silence(last_success, current_time) > DEPENDENCY_MAX_FAILING_SECONDS
+}
+
+pub fn evaluate_health() -> HealthReport {
+    let current_time = chrono::Utc::now().timestamp();
+
+    let stream_silent = silence(&LAST_STREAM_MESSAGE, current_time) > CORE_LOOP_MAX_SILENCE_SECONDS;
+    let evaluations_stalled =
+        silence(&LAST_EVALUATION, current_time) > CORE_LOOP_MAX_SILENCE_SECONDS;
+    let rpc_failing = is_failing(&LAST_RPC_SUCCESS, &LAST_RPC_FAILURE, current_time);
+    let db_failing = is_failing(&LAST_DB_SUCCESS, &LAST_DB_FAILURE, current_time);
+    let confirmations_stalled = LAST_TRANSACTION_SENT.load(Ordering::Relaxed)
+        > LAST_TRANSACTION_LANDED.load(Ordering::Relaxed)
+        && silence(&LAST_TRANSACTION_LANDED, current_time) > CONFIRMATION_MAX_SILENCE_SECONDS;
+
+    let reasons: Vec<&'static str> = [
+        (stream_silent, "stream silent"),
+        (evaluations_stalled, "periodic evaluations stalled"),
+        (rpc_failing, "RPC failing"),
+        (db_failing, "DB failing"),
+        (confirmations_stalled, "transactions not landing"),
+    ]
+    .into_iter()
+    .filter_map(|(failing, reason)| failing.then_some(reason))
+    .collect();
+
+    // A failing RPC alone is survivable (the stream keeps the indexes up to date), not along with transactions not landing
+    let state = if stream_silent || evaluations_stalled || (rpc_failing && confirmations_stalled) {
+        HealthState::Unhealthy
+    } else if !reasons.is_empty() {
+        HealthState::Degraded
+    } else {
+        HealthState::Healthy
+    };
+
+    HealthReport {
+        state,
+        reasons,
+        seconds_since_stream_message: seconds_since(&LAST_STREAM_MESSAGE, current_time),
+        seconds_since_evaluation: seconds_since(&LAST_EVALUATION, current_time),
+        seconds_since_rpc_success: seconds_since(&LAST_RPC_SUCCESS, current_time),
+        seconds_since_db_success: seconds_since(&LAST_DB_SUCCESS, current_time),
+        seconds_since_transaction_landed: seconds_since(&LAST_TRANSACTION_LANDED, current_time),
+    }
+}
+
+// Periodically evaluate the health, export it and log its transitions
+pub async fn monitor_health() {
+    let mut check_interval = interval(HEALTH_CHECK_INTERVAL);
+    let mut previous_state = HealthState::Healthy;
+    loop {
+        check_interval.tick().await;
+
+        let health = evaluate_health();
+        HEALTH_STATE.set(health.state as i64);
+        if health.state == previous_state {
+            continue;
+        }
+        match health.state {
+            HealthState::Unhealthy => {
+                log::error!("  <*> Keeper unhealthy: {}", health.reasons.join(", "))
+            }
+            HealthState::Degraded => {
+                log::warn!("  <> Keeper degraded: {}", health.reasons.join(", "))
+            }
+            HealthState::Healthy => log::info!("  <> Keeper healthy again"),
+        }
+        previous_state = health.state;
+    }
+}
@@ -0,0 +1,69 @@
+use {
+    crate::db::{ActionLogFilter, Db, DbBackend},
+    chrono::{DateTime, NaiveDate},
+    solana_sdk::pubkey::Pubkey,
+};
+
+// Print the keeper actions matching the filters, most recent first - replaces grepping the logs for the support cases
+#[allow(clippy::too_many_arguments)]
+pub async fn print_history(
+    db_backend: DbBackend,
+    db_string: &str,
+    user: Option<Pubkey>,
+    staking: Option<String>,
+    action: Option<String>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    limit: i64,
+) -> anyhow::Result<()> {
+    let (db, db_connection_task) = Db::connect(db_backend, db_string, None)
+        .await
+        .map_err(|e| match e {
+            backoff::Error::Permanent(e) | backoff::Error::Transient { err: e, .. } => e,
+        })?;
+
+    let midnight = |day: NaiveDate| {
+        day.and_hms_opt(0, 0, 0)
+            .expect("Midnight is a valid time")
+            .and_utc()
+            .timestamp()
+    };
+    let filter = ActionLogFilter {
+        user: user.map(|user| user.to_string()),
+        staking,
+        action,
+        since: since.map(midnight),
+        // The until day is included
+        until: until.map(|until| midnight(until) + 86_400),
+        limit,
+    };
+    let entries = db.search_action_log(&filter).await.map_err(|e| match e {
+        backoff::Error::Permanent(e) | backoff::Error::Transient { err: e, .. } => e,
+    })?;
+    if let Some(db_connection_task) = db_connection_task {
+        db_connection_task.abort();
+    }
+
+    println!(
+        "{:<20} {:<22} {:<6} {:<10} {:<44} {:<44} {}",
+        "completed (UTC)", "action", "side", "outcome", "account", "owner", "signature"
+    );
+    for entry in entries.iter() {
+        let completed_at = DateTime::from_timestamp(entry.completed_at, 0)
+            .map(|completed_at| completed_at.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        println!(
+            "{:<20} {:<22} {:<6} {:<10} {:<44} {:<44} {}",
+            completed_at,
+            entry.action,
+            entry.staking,
+            entry.outcome,
+            entry.account,
+            entry.owner.as_deref().unwrap_or("-"),
+            entry.signature
+        );
+    }
+    println!();
+    println!("{} action(s) (limit {})", entries.len(), limit);
+    Ok(())
+}
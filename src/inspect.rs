@@ -0,0 +1,116 @@
+use {
+    crate::{
+        genesis_lock::is_genesis_locked_stake,
+        pending_rewards::{estimate_pending_rewards, unclaimed_resolved_rounds},
+        rate_limiter::rpc_call,
+        stake_transactions::get_user_staking_pda,
+        user_staking_layout::decode_user_staking,
+    },
+    adrena_abi::{get_staking_pda, AccountDeserialize, Staking, ADX_MINT, ALP_MINT},
+    chrono::DateTime,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+};
+
+// Staked tokens and rewards all have 6 decimals (see reward_rates.rs)
+const TOKEN_DECIMALS: i32 = 6;
+
+// Print the stakes of a wallet (both staking sides), the resolved rounds they haven't claimed and the rewards the
+// next claim should bring - replaces reading the accounts in an explorer for the support cases
+pub async fn inspect_owner(endpoint: String, owner: &Pubkey) -> anyhow::Result<()> {
+    let rpc_client = RpcClient::new_with_commitment(endpoint, CommitmentConfig::confirmed());
+    let current_time = chrono::Utc::now().timestamp();
+
+    println!("Owner {}", owner);
+    for (staking, staked_token_mint) in [("adx", ADX_MINT), ("alp", ALP_MINT)] {
+        let staking_account_key = get_staking_pda(&staked_token_mint).0;
+        let user_staking_account_key = get_user_staking_pda(owner, &staking_account_key);
+
+        let accounts = rpc_call(|| {
+            rpc_client.get_multiple_accounts(&[staking_account_key, user_staking_account_key])
+        })
+        .await?;
+        let (Some(staking_account), Some(user_staking_account)) = (&accounts[0], &accounts[1])
+        else {
+            println!();
+            println!("[{}] no UserStaking account", staking);
+            continue;
+        };
+        let staking_account = Staking::try_deserialize(&mut &staking_account.data[..])?;
+        let user_staking_account = decode_user_staking(&user_staking_account.data)?;
+
+        println!();
+        println!(
+            "[{}] UserStaking account {}",
+            staking, user_staking_account_key
+        );
+
+        let liquid_stake = &user_staking_account.liquid_stake;
+        println!(
+            "  Liquid stake: {} (last claim {})",
+            ui_amount(liquid_stake.amount),
+            format_time(liquid_stake.claim_time)
+        );
+
+        let locked_stakes: Vec<_> = user_staking_account
+            .locked_stakes
+            .iter()
+            .filter(|locked_stake| locked_stake.amount != 0)
+            .collect();
+        println!("  Locked stakes: {}", locked_stakes.len());
+        if !locked_stakes.is_empty() {
+            println!(
+                "    {:<6} {:>16} {:<20} {:<20} {:<10} {}",
+                "id", "amount", "end (UTC)", "last claim (UTC)", "state", "genesis"
+            );
+        }
+        for locked_stake in locked_stakes {
+            let state = if locked_stake.resolved != 0 {
+                "finalized"
+            } else if locked_stake.end_time <= current_time {
+                "ended"
+            } else {
+                "locked"
+            };
+            println!(
+                "    {:<6} {:>16} {:<20} {:<20} {:<10} {}",
+                locked_stake.id,
+                ui_amount(locked_stake.amount),
+                format_time(locked_stake.end_time),
+                format_time(locked_stake.claim_time),
+                state,
+                if is_genesis_locked_stake(locked_stake) {
+                    "yes"
+                } else {
+                    "no"
+                }
+            );
+        }
+
+        let pending_rewards = estimate_pending_rewards(&user_staking_account, &staking_account);
+        println!(
+            "  Resolved rounds not claimed yet: {} (the Staking account keeps the last {})",
+            unclaimed_resolved_rounds(&user_staking_account, &staking_account),
+            staking_account.resolved_staking_rounds.len()
+        );
+        println!(
+            "  Estimated claimable rewards: {} reward token, {} ADX (LM)",
+            ui_amount(pending_rewards.rewards),
+            ui_amount(pending_rewards.lm_rewards)
+        );
+    }
+    Ok(())
+}
+
+fn ui_amount(native_amount: u64) -> String {
+    format!("{:.6}", native_amount as f64 / 10f64.powi(TOKEN_DECIMALS))
+}
+
+fn format_time(timestamp: i64) -> String {
+    if timestamp == 0 {
+        return "-".to_string();
+    }
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
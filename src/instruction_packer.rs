@@ -0,0 +1,345 @@
+use {
+    crate::{
+        action_retries::ActionId,
+        blockhash_cache::latest_blockhash,
+        compute_units::MAX_CU_LIMIT,
+        tx_submitters::{same_tx_submitter, submit_transaction, tip_instruction},
+        tx_tracker::{track_transaction, KeeperAction, SentTransaction},
+        PendingTransactionsThreadSafe,
+    },
+    anchor_client::Program,
+    solana_sdk::{
+        clock::{DEFAULT_MS_PER_SLOT, MAX_PROCESSING_AGE},
+        compute_budget::ComputeBudgetInstruction,
+        instruction::Instruction,
+        message::Message,
+        packet::PACKET_DATA_SIZE,
+        pubkey::Pubkey,
+        signature::Keypair,
+    },
+    std::{
+        collections::HashSet,
+        sync::{Arc, LazyLock, Mutex},
+        time::{Duration, Instant},
+    },
+};
+
+// Accounts a transaction can lock (the lowest of the runtime limits, whatever the activated features)
+const MAX_TX_ACCOUNT_LOCKS: usize = 64;
+
+// One blockhash validity window: the actions queued for longer were simulated against a state long gone, the handlers
+// prepare them again on their next check
+const MAX_QUEUED_DURATION: Duration =
+    Duration::from_millis(MAX_PROCESSING_AGE as u64 * DEFAULT_MS_PER_SLOT);
+
+// Actions prepared by the handlers, waiting to be packed and sent by the core loop - only the simulated ones are packed
+static DUE_INSTRUCTIONS: LazyLock<Mutex<Vec<DueInstruction>>> =
+    LazyLock::new(|| Mutex::new(vec![]));
+
+// Actions of the packed transactions that failed (to be sent, or onchain): the failing one is unknown, each one is sent on
+// its own next - cleared once sent alone
+static SEND_ALONE: LazyLock<Mutex<HashSet<ActionId>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+// Actions of the packed transactions that failed onchain (with their staking label), their failure is recorded by the
+// next packing (see process_instruction_packs)
+static FAILED_PACKED_ACTIONS: LazyLock<Mutex<Vec<(ActionId, &'static str)>>> =
+    LazyLock::new(|| Mutex::new(vec![]));
+
+#[derive(Debug, Clone)]
+pub struct DueInstruction {
+    pub action_id: ActionId,
+    // Staking label of the metrics (see metrics.rs)
+    pub staking: &'static str,
+    // The instructions of the action, in order (e.g. the token accounts creations before the finalization)
+    pub instructions: Vec<Instruction>,
+    pub compute_unit_limit: u32,
+    // Compute unit price bid for the action (µLamports)
+    pub priority_fee: u64,
+    // Accounts written by this action only (its UserStaking account, the owner accounts...): two actions sharing one never
+    // go in the same transaction, one failing would revert the other - the shared ones (Staking account, vaults) are fine
+    pub exclusive_accounts: Vec<Pubkey>,
+    // When the action was prepared (and simulated)
+    pub prepared_at: Instant,
+    // Sent in a transaction of its own (e.g. its pack failed), its failure is recorded against it only
+    pub send_alone: bool,
+}
+
+// Actions sent in a single transaction - it lands or fails as a whole
+#[derive(Debug)]
+pub struct InstructionPack {
+    pub due_instructions: Vec<DueInstruction>,
+}
+
+impl InstructionPack {
+    pub fn single(due_instruction: DueInstruction) -> Self {
+        Self {
+            due_instructions: vec![due_instruction],
+        }
+    }
+
+    // The transaction is delivered by the backend of its first action
+    pub fn action(&self) -> KeeperAction {
+        self.due_instructions[0].action_id.action
+    }
+
+    pub fn compute_unit_limit(&self) -> u32 {
+        self.due_instructions
+            .iter()
+            .map(|due_instruction| due_instruction.compute_unit_limit)
+            .sum::<u32>()
+            .min(MAX_CU_LIMIT)
+    }
+
+    // The highest bid of the actions, none of them lands later than it would have alone
+    pub fn priority_fee(&self) -> u64 {
+        self.due_instructions
+            .iter()
+            .map(|due_instruction| due_instruction.priority_fee)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // The compute budget and tip instructions, then the instructions of each action
+    fn instructions(&self, payer: &Pubkey) -> Vec<Instruction> {
+        let mut instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_price(self.priority_fee()),
+            ComputeBudgetInstruction::set_compute_unit_limit(self.compute_unit_limit()),
+        ];
+        // Required by the submission backend (e.g. Jito tip)
+        instructions.extend(tip_instruction(self.action(), payer));
+        for due_instruction in self.due_instructions.iter() {
+            instructions.extend(due_instruction.instructions.iter().cloned());
+        }
+        instructions
+    }
+
+    fn accepts(&self, payer: &Pubkey, due_instruction: &DueInstruction) -> bool {
+        if self.due_instructions[0].send_alone || due_instruction.send_alone {
+            return false;
+        }
+        if !same_tx_submitter(self.action(), due_instruction.action_id.action) {
+            return false;
+        }
+        let conflicts = self.due_instructions.iter().any(|packed| {
+            packed
+                .exclusive_accounts
+                .iter()
+                .any(|account| due_instruction.exclusive_accounts.contains(account))
+        });
+        if conflicts {
+            return false;
+        }
+        let compute_unit_limit = self
+            .due_instructions
+            .iter()
+            .map(|packed| packed.compute_unit_limit)
+            .sum::<u32>()
+            + due_instruction.compute_unit_limit;
+        if compute_unit_limit > MAX_CU_LIMIT {
+            return false;
+        }
+
+        let mut instructions = self.instructions(payer);
+        instructions.extend(due_instruction.instructions.iter().cloned());
+        let message = Message::new(&instructions, Some(payer));
+        message.account_keys.len() <= MAX_TX_ACCOUNT_LOCKS
+            && transaction_size(&message) <= PACKET_DATA_SIZE
+    }
+}
+
+// Size of the signed transaction: the signatures (short vec length, a single byte below 128) then the message
+fn transaction_size(message: &Message) -> usize {
+    let message_size = bincode::serialized_size(message).map_or(usize::MAX, |size| size as usize);
+    (1 + message.header.num_required_signatures as usize * 64).saturating_add(message_size)
+}
+
+fn is_sent_alone(action_id: &ActionId) -> bool {
+    SEND_ALONE.lock().unwrap().contains(action_id)
+}
+
+// A packed transaction failed onchain (called by the transaction tracker)
+pub fn record_failed_pack(actions: Vec<(ActionId, &'static str)>) {
+    SEND_ALONE
+        .lock()
+        .unwrap()
+        .extend(actions.iter().map(|(action_id, _)| *action_id));
+    FAILED_PACKED_ACTIONS.lock().unwrap().extend(actions);
+}
+
+pub fn take_failed_packed_actions() -> Vec<(ActionId, &'static str)> {
+    std::mem::take(&mut *FAILED_PACKED_ACTIONS.lock().unwrap())
+}
+
+// Queue an action for the next packing - false if it's already queued
+pub fn queue_due_instruction(mut due_instruction: DueInstruction) -> bool {
+    due_instruction.send_alone |= is_sent_alone(&due_instruction.action_id);
+    let mut due_instructions = DUE_INSTRUCTIONS.lock().unwrap();
+    if due_instructions
+        .iter()
+        .any(|queued| queued.action_id == due_instruction.action_id)
+    {
+        return false;
+    }
+    due_instructions.push(due_instruction);
+    true
+}
+
+// The handlers skip preparing (and simulating) the actions still waiting to be sent
+pub fn is_queued(action_id: &ActionId) -> bool {
+    DUE_INSTRUCTIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|queued| queued.action_id == *action_id)
+}
+
+// The queued actions, without the ones prepared more than a blockhash validity window ago
+pub fn take_due_instructions() -> Vec<DueInstruction> {
+    let mut due_instructions = std::mem::take(&mut *DUE_INSTRUCTIONS.lock().unwrap());
+    due_instructions.retain(|due_instruction| {
+        let fresh = due_instruction.prepared_at.elapsed() <= MAX_QUEUED_DURATION;
+        if !fresh {
+            log::info!(
+                "  <> {} of account {} queued for too long - dropped, prepared again by the next check",
+                due_instruction.action_id.action.as_str(),
+                due_instruction.action_id.account
+            );
+        }
+        fresh
+    });
+    due_instructions
+}
+
+// The indexes are rebuilt from the chain (program upgrade), the queued actions were prepared from the previous ones
+pub fn clear_due_instructions() {
+    DUE_INSTRUCTIONS.lock().unwrap().clear();
+}
+
+// The pack could not be sent: its actions are sent on their own next, ahead of the ones queued since
+pub fn requeue_due_instructions_alone(due_instructions: Vec<DueInstruction>) {
+    SEND_ALONE.lock().unwrap().extend(
+        due_instructions
+            .iter()
+            .map(|due_instruction| due_instruction.action_id),
+    );
+    requeue_due_instructions(
+        due_instructions
+            .into_iter()
+            .map(|due_instruction| DueInstruction {
+                send_alone: true,
+                ..due_instruction
+            })
+            .collect(),
+    );
+}
+
+// Put back the actions not sent (no in-flight capacity left), ahead of the ones queued since
+pub fn requeue_due_instructions(due_instructions: Vec<DueInstruction>) {
+    let mut queued = DUE_INSTRUCTIONS.lock().unwrap();
+    let queued_since = std::mem::replace(&mut *queued, due_instructions);
+    for due_instruction in queued_since {
+        if !queued
+            .iter()
+            .any(|queued| queued.action_id == due_instruction.action_id)
+        {
+            queued.push(due_instruction);
+        }
+    }
+}
+
+// Greedy first fit in the queue order: each action goes in the first transaction it fits in (same submission backend,
+// no exclusive account in common, within the CU, account locks and packet size limits), or starts a new one
+// The claims are not packed: their outcome depends on the simulation of each one (no reward tokens, genesis lock...)
+// and the resolves take along the claim they can carry (see take_combined_claim)
+pub fn pack_instructions(
+    payer: &Pubkey,
+    due_instructions: Vec<DueInstruction>,
+) -> Vec<InstructionPack> {
+    let mut packs: Vec<InstructionPack> = vec![];
+    for due_instruction in due_instructions {
+        match packs
+            .iter()
+            .position(|pack| pack.accepts(payer, &due_instruction))
+        {
+            Some(index) => packs[index].due_instructions.push(due_instruction),
+            None => packs.push(InstructionPack::single(due_instruction)),
+        }
+    }
+    packs
+}
+
+pub async fn send_instruction_pack(
+    program: &Program<Arc<Keypair>>,
+    pending_transactions: &PendingTransactionsThreadSafe,
+    pack: &InstructionPack,
+) -> Result<(), backoff::Error<anyhow::Error>> {
+    let rpc_client = program.rpc();
+
+    let recent_blockhash = latest_blockhash(&rpc_client).await.map_err(|e| {
+        log::error!("   <> Failed to fetch the latest blockhash: {:?}", e);
+        backoff::Error::transient(e)
+    })?;
+
+    let mut request = program.request();
+    for instruction in pack.instructions(&program.payer()) {
+        request = request.instruction(instruction);
+    }
+    let tx = request
+        .signed_transaction_with_blockhash(recent_blockhash.blockhash)
+        .map_err(|e| {
+            log::error!("   <> Transaction generation failed with error: {:?}", e);
+            backoff::Error::transient(e.into())
+        })?;
+
+    let tx_hash = submit_transaction(pack.action(), &rpc_client, &tx)
+        .await
+        .map_err(|e| {
+            log::error!("   <> Transaction sending failed with error: {:?}", e);
+            backoff::Error::transient(e.into())
+        })?;
+
+    let first = &pack.due_instructions[0];
+    if pack.due_instructions.len() == 1 {
+        SEND_ALONE.lock().unwrap().remove(&first.action_id);
+    }
+    for due_instruction in pack.due_instructions.iter() {
+        log::info!(
+            "   <> {} of account {:#?} - TX sent: {:#?}",
+            due_instruction.action_id.action.as_str(),
+            due_instruction.action_id.account,
+            tx_hash.to_string(),
+        );
+    }
+    if pack.due_instructions.len() > 1 {
+        log::info!(
+            "  <> {} actions packed in TX {}",
+            pack.due_instructions.len(),
+            tx_hash
+        );
+    }
+
+    track_transaction(
+        pending_transactions,
+        SentTransaction::new(
+            tx_hash,
+            first.action_id.action,
+            first.action_id.account,
+            pack.priority_fee(),
+            pack.compute_unit_limit(),
+        )
+        .with_last_valid_block_height(recent_blockhash.last_valid_block_height)
+        .with_transaction(tx)
+        .with_staking(first.staking)
+        .with_locked_stake_id(first.action_id.locked_stake_id)
+        .with_packed(
+            pack.due_instructions[1..]
+                .iter()
+                .map(|due_instruction| (due_instruction.action_id, due_instruction.staking))
+                .collect(),
+        ),
+    )
+    .await;
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use {
+    file_rotate::{
+        compression::Compression,
+        suffix::{AppendTimestamp, FileLimit},
+        ContentLimit, FileRotate, TimeFrequency,
+    },
+    log::{Level, Log, Metadata, Record},
+    std::{
+        collections::{BTreeMap, HashMap},
+        env,
+        io::{self, Write},
+        sync::{LazyLock, Mutex, OnceLock},
+        time::{Duration, Instant},
+    },
+    tokio::time::interval,
+};
+
+// Set once at startup (init_logging)
+static LOGGER: OnceLock<DedupLogger> = OnceLock::new();
+
+// Number of per-message log lines demoted to trace since the last summary, per kind (e.g. "UserStaking account modified")
+static CHATTER: LazyLock<Mutex<BTreeMap<&'static str, u64>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+// Collapses the identical lines (same level, target and message) logged within the window: the first one is written,
+// the following ones are only counted and reported once the window is over
+struct DedupLogger {
+    inner: env_logger::Logger,
+    // Zero to disable
+    window: Duration,
+    recent: Mutex<HashMap<(Level, String, String), RecentLine>>,
+}
+
+struct RecentLine {
+    first_logged_at: Instant,
+    suppressed: u64,
+}
+
+impl DedupLogger {
+    fn log_repeated(&self, (level, target, message): &(Level, String, String), suppressed: u64) {
+        self.inner.log(
+            &Record::builder()
+                .level(*level)
+                .target(target)
+                .args(format_args!(
+                    "(repeated {} more times) {}",
+                    suppressed, message
+                ))
+                .build(),
+        );
+    }
+
+    // Report the lines whose window is over
+    fn flush_suppressed(&self) {
+        let expired: Vec<((Level, String, String), u64)> = {
+            let mut recent = self.recent.lock().unwrap();
+            let expired_keys: Vec<(Level, String, String)> = recent
+                .iter()
+                .filter(|(_, line)| line.first_logged_at.elapsed() >= self.window)
+                .map(|(key, _)| key.clone())
+                .collect();
+            expired_keys
+                .into_iter()
+                .filter_map(|key| {
+                    let line = recent.remove(&key)?;
+                    Some((key, line.suppressed))
+                })
+                .collect()
+        };
+        for (key, suppressed) in expired {
+            if suppressed > 0 {
+                self.log_repeated(&key, suppressed);
+            }
+        }
+    }
+}
+
+impl Log for DedupLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.enabled(record.metadata()) {
+            return;
+        }
+        if self.window.is_zero() {
+            self.inner.log(record);
+            return;
+        }
+
+        let key = (
+            record.level(),
+            record.target().to_string(),
+            record.args().to_string(),
+        );
+        let previous = {
+            let mut recent = self.recent.lock().unwrap();
+            match recent.get_mut(&key) {
+                Some(line) if line.first_logged_at.elapsed() < self.window => {
+                    line.suppressed += 1;
+                    return;
+                }
+                _ => recent.insert(
+                    key.clone(),
+                    RecentLine {
+                        first_logged_at: Instant::now(),
+                        suppressed: 0,
+                    },
+                ),
+            }
+        };
+        if let Some(previous) = previous.filter(|previous| previous.suppressed > 0) {
+            self.log_repeated(&key, previous.suppressed);
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum LogRotation {
+    // A new file once the current one reaches --log-max-size-mb
+    Size,
+    Hourly,
+    #[default]
+    Daily,
+}
+
+// Log file settings, for the deployments without a log shipper (bare VM)
+pub struct LogFile {
+    pub path: String,
+    pub rotation: LogRotation,
+    pub max_size_mb: usize,
+    // Number of rotated files kept besides the current one
+    pub retention: usize,
+}
+
+// Every line goes to stderr (as without a log file) and to the rotating file
+struct TeeWriter {
+    file: FileRotate<AppendTimestamp>,
+}
+
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stderr().write_all(buf)?;
+        self.file.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()?;
+        self.file.flush()
+    }
+}
+
+// RUST_LOG defaults to info
+pub fn init_logging(log_file: Option<LogFile>, dedup_window: Duration) {
+    env::set_var(
+        env_logger::DEFAULT_FILTER_ENV,
+        env::var_os(env_logger::DEFAULT_FILTER_ENV).unwrap_or_else(|| "info".into()),
+    );
+    let mut builder = env_logger::Builder::from_default_env();
+
+    if let Some(log_file) = log_file {
+        let content_limit = match log_file.rotation {
+            LogRotation::Size => ContentLimit::Bytes(log_file.max_size_mb.max(1) * 1024 * 1024),
+            LogRotation::Hourly => ContentLimit::Time(TimeFrequency::Hourly),
+            LogRotation::Daily => ContentLimit::Time(TimeFrequency::Daily),
+        };
+        let file = FileRotate::new(
+            &log_file.path,
+            AppendTimestamp::default(FileLimit::MaxFiles(log_file.retention)),
+            content_limit,
+            Compression::None,
+            #[cfg(unix)]
+            None,
+        );
+        builder.target(env_logger::Target::Pipe(Box::new(TeeWriter { file })));
+    }
+
+    let inner = builder.build();
+    let max_level = inner.filter();
+    let logger = LOGGER.get_or_init(|| DedupLogger {
+        inner,
+        window: dedup_window,
+        recent: Mutex::new(HashMap::new()),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+// Per stream message logs: written at trace level, and counted for the periodic summary (none when the window is 0)
+pub fn log_chatter(kind: &'static str, details: std::fmt::Arguments) {
+    log::trace!("{}: {}", kind, details);
+    if LOGGER.get().is_some_and(|logger| !logger.window.is_zero()) {
+        *CHATTER.lock().unwrap().entry(kind).or_insert(0) += 1;
+    }
+}
+
+// Periodically log the counts of the per-message lines demoted to trace, and the identical lines collapsed - only run
+// with a dedup window
+pub async fn log_summaries(summary_interval: Duration) {
+    let mut summary_ticker = interval(summary_interval);
+    // The first tick completes immediately
+    summary_ticker.tick().await;
+    loop {
+        summary_ticker.tick().await;
+
+        let chatter = std::mem::take(&mut *CHATTER.lock().unwrap());
+        if !chatter.is_empty() {
+            let counts: Vec<String> = chatter
+                .iter()
+                .map(|(kind, count)| format!("{}: {}", kind, count))
+                .collect();
+            log::info!(
+                "  <> Last {}s: {}",
+                summary_interval.as_secs(),
+                counts.join(", ")
+            );
+        }
+
+        if let Some(logger) = LOGGER.get() {
+            logger.flush_suppressed();
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use {
+    crate::metrics::PROTOCOL_PAUSED,
+    adrena_abi::Cortex,
+    chrono::{DateTime, Utc},
+    std::{
+        str::FromStr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            OnceLock,
+        },
+    },
+};
+
+// Set once at startup - if never set, the transactions are never suspended
+static MAINTENANCE_WINDOWS: OnceLock<Vec<MaintenanceWindow>> = OnceLock::new();
+
+// Whether the last check was within a window, to log the transitions only
+static IN_MAINTENANCE_WINDOW: AtomicBool = AtomicBool::new(false);
+
+// Pause flag of the Cortex account (indexed at startup, then kept up to date by the stream)
+static CORTEX_PAUSED: AtomicBool = AtomicBool::new(false);
+
+// Period (e.g. a program upgrade) during which the keeper keeps indexing but sends no transaction
+#[derive(Debug, Clone, Copy)]
+pub struct MaintenanceWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+// `<start>/<end>`, both RFC 3339 (e.g. 2024-11-05T14:00:00Z/2024-11-05T15:30:00Z)
+impl FromStr for MaintenanceWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("Expected <start>/<end>, got {}", s))?;
+        let maintenance_window = MaintenanceWindow {
+            start: DateTime::parse_from_rfc3339(start.trim())?.with_timezone(&Utc),
+            end: DateTime::parse_from_rfc3339(end.trim())?.with_timezone(&Utc),
+        };
+        if maintenance_window.end <= maintenance_window.start {
+            return Err(anyhow::anyhow!("The window ends before it starts: {}", s));
+        }
+        Ok(maintenance_window)
+    }
+}
+
+pub fn init_maintenance_windows(maintenance_windows: Vec<MaintenanceWindow>) {
+    let _ = MAINTENANCE_WINDOWS.set(maintenance_windows);
+}
+
+// Whether the transaction sends are suspended - the due actions are caught up once the window ends (or the protocol resumes)
+pub fn are_transactions_suspended() -> bool {
+    is_in_maintenance_window() || CORTEX_PAUSED.load(Ordering::Relaxed)
+}
+
+fn is_in_maintenance_window() -> bool {
+    let Some(maintenance_windows) = MAINTENANCE_WINDOWS.get() else {
+        return false;
+    };
+    let now = Utc::now();
+    let current_window = maintenance_windows
+        .iter()
+        .find(|maintenance_window| maintenance_window.start <= now && now < maintenance_window.end);
+
+    let was_in_maintenance_window =
+        IN_MAINTENANCE_WINDOW.swap(current_window.is_some(), Ordering::Relaxed);
+    match (current_window, was_in_maintenance_window) {
+        (Some(maintenance_window), false) => log::warn!(
+            "  <> Maintenance window until {} - transactions suspended, indexing continues",
+            maintenance_window.end
+        ),
+        (None, true) => log::info!("  <> Maintenance window over - resuming the transactions"),
+        _ => {}
+    }
+    current_window.is_some()
+}
+
+// While the protocol is paused onchain, every resolve and claim would fail
+pub fn update_protocol_pause(cortex: &Cortex) {
+    let paused = cortex.paused != 0;
+    PROTOCOL_PAUSED.set(paused as i64);
+
+    let was_paused = CORTEX_PAUSED.swap(paused, Ordering::Relaxed);
+    match (paused, was_paused) {
+        (true, false) => log::error!(
+            "  <*> Protocol paused onchain (Cortex) - transactions suspended until it resumes"
+        ),
+        (false, true) => log::info!("  <> Protocol resumed onchain - resuming the transactions"),
+        _ => {}
+    }
+}
@@ -0,0 +1,315 @@
+use {
+    adrena_abi::{ADX_MINT, ALP_MINT},
+    prometheus::{
+        register_gauge_vec, register_histogram_vec, register_int_counter, register_int_counter_vec,
+        register_int_gauge, register_int_gauge_vec, Encoder, GaugeVec, HistogramVec, IntCounter,
+        IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::{sync::LazyLock, time::Instant},
+};
+
+pub const NO_STAKING_LABEL: &str = "none";
+
+// Keeper outcomes, labeled by action (resolve_staking_round, claim_stakes, finalize_locked_stake, update_pool_aum)
+// and staking (adx for the LM side, alp for the LP side, none for the actions not tied to a staking like update_pool_aum)
+// The number of rounds resolved, claims executed and finalizations done are the confirmed transactions of each action
+pub static TRANSACTIONS_SENT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "mrsablier_transactions_sent_total",
+        "Number of transactions sent by the keeper",
+        &["action", "staking"]
+    )
+    .unwrap()
+});
+
+pub static TRANSACTIONS_CONFIRMED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "mrsablier_transactions_confirmed_total",
+        "Number of keeper transactions that landed successfully",
+        &["action", "staking"]
+    )
+    .unwrap()
+});
+
+pub static TRANSACTIONS_FAILED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "mrsablier_transactions_failed_total",
+        "Number of keeper transactions that landed with an error",
+        &["action", "staking"]
+    )
+    .unwrap()
+});
+
+pub static TRANSACTIONS_DROPPED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "mrsablier_transactions_dropped_total",
+        "Number of keeper transactions that never landed",
+        &["action", "staking"]
+    )
+    .unwrap()
+});
+
+pub static TRANSACTIONS_REBROADCAST: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "mrsablier_transactions_rebroadcast_total",
+        "Number of times a pending keeper transaction was sent again",
+        &["action", "staking"]
+    )
+    .unwrap()
+});
+
+pub static PRIORITY_FEES_PAID: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "mrsablier_priority_fees_paid_lamports_total",
+        "Priority fees paid by landed keeper transactions, in lamports",
+        &["action", "staking"]
+    )
+    .unwrap()
+});
+
+pub static ACTIONS_ABANDONED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "mrsablier_actions_abandoned_total",
+        "Number of keeper actions given up after too many failed attempts",
+        &["action", "staking"]
+    )
+    .unwrap()
+});
+
+pub static ACCOUNTS_QUARANTINED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "mrsablier_accounts_quarantined_total",
+        "Number of UserStaking accounts quarantined after too many consecutive failed actions"
+    )
+    .unwrap()
+});
+
+pub static QUARANTINED_ACCOUNTS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_quarantined_accounts",
+        "Number of UserStaking accounts currently quarantined, skipped by the claims and finalizations"
+    )
+    .unwrap()
+});
+
+// Failed simulations, labeled by action and kind (skip, retry, alert - see preflight.rs)
+pub static PREFLIGHT_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "mrsablier_preflight_failures_total",
+        "Number of keeper transactions whose simulation failed",
+        &["action", "kind"]
+    )
+    .unwrap()
+});
+
+pub static RPC_RATE_LIMITED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "mrsablier_rpc_rate_limited_total",
+        "Number of RPC calls rejected by the provider rate limit (HTTP 429) and retried"
+    )
+    .unwrap()
+});
+
+pub static FEES_PAID_TODAY: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_fees_paid_today_lamports",
+        "Fees (base + priority) paid by the landed keeper transactions since the start of the UTC day, in lamports"
+    )
+    .unwrap()
+});
+
+// Referral rewards waiting to be distributed, in native units of the reward token
+pub static REFERRER_REWARD_VAULT_BALANCE: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_referrer_reward_vault_balance",
+        "Balance of the referrer reward token vault, in native units"
+    )
+    .unwrap()
+});
+
+pub static REFERRER_REWARD_VAULT_SECONDS_SINCE_DECREASE: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_referrer_reward_vault_seconds_since_decrease",
+        "Seconds since the referrer reward token vault balance last decreased (referral rewards paid out)"
+    )
+    .unwrap()
+});
+
+pub static USER_STAKING_ACCOUNTS_MISSING_STAKING_TYPE: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_user_staking_accounts_missing_staking_type",
+        "Number of UserStaking accounts waiting for their staking type to be defined before being indexed"
+    )
+    .unwrap()
+});
+
+// Slot of the last update received from the Geyser stream vs the RPC chain head
+pub static STREAM_SLOT: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_stream_slot",
+        "Slot of the last update received from the Geyser stream"
+    )
+    .unwrap()
+});
+
+pub static CHAIN_SLOT: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("mrsablier_chain_slot", "Chain head slot as seen by the RPC").unwrap()
+});
+
+pub static STREAM_SLOT_LAG: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_stream_slot_lag",
+        "Number of slots the Geyser stream is behind the RPC chain head"
+    )
+    .unwrap()
+});
+
+pub static SECONDS_SINCE_LAST_SLOT: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_seconds_since_last_slot",
+        "Seconds since the last slot update received from the Geyser stream"
+    )
+    .unwrap()
+});
+
+pub static PROTOCOL_PAUSED: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_protocol_paused",
+        "1 while the protocol is paused onchain (Cortex), the keeper transactions are suspended"
+    )
+    .unwrap()
+});
+
+pub static HEALTH_STATE: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "mrsablier_health_state",
+        "Keeper health combining the stream, evaluations, RPC, DB and confirmations signals (0: healthy, 1: degraded, 2: unhealthy)"
+    )
+    .unwrap()
+});
+
+// Time since the end of the current staking round while it is still unresolved, labeled by staking (0 until the round ends)
+pub static STAKING_ROUND_OVERDUE_SECONDS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "mrsablier_staking_round_overdue_seconds",
+        "Time since the end of the current staking round while it is still unresolved",
+        &["staking"]
+    )
+    .unwrap()
+});
+
+// Amounts staked across the indexed UserStaking accounts in native units, labeled by staking (adx, alp) and kind (locked, liquid)
+// The finalized locked stakes are not counted
+pub static STAKED_AMOUNT: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "mrsablier_staked_amount",
+        "Amount staked across the indexed UserStaking accounts, in native units",
+        &["staking", "kind"]
+    )
+    .unwrap()
+});
+
+// Number of entries of the indexes and caches, labeled by index
+pub static INDEX_SIZE: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "mrsablier_index_size",
+        "Number of entries of the keeper indexes and caches",
+        &["index"]
+    )
+    .unwrap()
+});
+
+// Time the shared maps locks are held by the keeper tasks, labeled by lock - a lock held long delays the resolves
+pub static LOCK_HOLD_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "mrsablier_lock_hold_seconds",
+        "Time the shared maps locks are held by the keeper tasks",
+        &["lock"],
+        vec![0.0001, 0.001, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 30.0]
+    )
+    .unwrap()
+});
+
+// Observes how long a lock is held once dropped - declared right after the guard, so both go out of scope together
+pub struct LockHoldTimer {
+    lock: &'static str,
+    acquired_at: Instant,
+}
+
+impl LockHoldTimer {
+    pub fn start(lock: &'static str) -> Self {
+        LockHoldTimer {
+            lock,
+            acquired_at: Instant::now(),
+        }
+    }
+}
+
+impl Drop for LockHoldTimer {
+    fn drop(&mut self) {
+        LOCK_HOLD_SECONDS
+            .with_label_values(&[self.lock])
+            .observe(self.acquired_at.elapsed().as_secs_f64());
+    }
+}
+
+// Yearly rewards per staked token, labeled by staking (adx, alp) and reward (reward, lm_reward)
+pub static STAKING_REWARD_PER_TOKEN_YEARLY: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "mrsablier_staking_reward_per_token_yearly",
+        "Yearly rewards per staked token, extrapolated from the last resolved round",
+        &["staking", "reward"]
+    )
+    .unwrap()
+});
+
+// Staking label of the actions done on a UserStaking account, from its staked token
+pub fn staking_label(staked_token_mint: &Pubkey) -> &'static str {
+    if *staked_token_mint == ADX_MINT {
+        "adx"
+    } else if *staked_token_mint == ALP_MINT {
+        "alp"
+    } else {
+        NO_STAKING_LABEL
+    }
+}
+
+// Register all metrics upfront so they are exposed before their first update
+pub fn register() {
+    LazyLock::force(&TRANSACTIONS_SENT);
+    LazyLock::force(&TRANSACTIONS_CONFIRMED);
+    LazyLock::force(&TRANSACTIONS_FAILED);
+    LazyLock::force(&TRANSACTIONS_DROPPED);
+    LazyLock::force(&TRANSACTIONS_REBROADCAST);
+    LazyLock::force(&PRIORITY_FEES_PAID);
+    LazyLock::force(&ACTIONS_ABANDONED);
+    LazyLock::force(&ACCOUNTS_QUARANTINED);
+    LazyLock::force(&QUARANTINED_ACCOUNTS);
+    LazyLock::force(&PREFLIGHT_FAILURES);
+    LazyLock::force(&RPC_RATE_LIMITED);
+    LazyLock::force(&FEES_PAID_TODAY);
+    LazyLock::force(&REFERRER_REWARD_VAULT_BALANCE);
+    LazyLock::force(&REFERRER_REWARD_VAULT_SECONDS_SINCE_DECREASE);
+    LazyLock::force(&USER_STAKING_ACCOUNTS_MISSING_STAKING_TYPE);
+    LazyLock::force(&STREAM_SLOT);
+    LazyLock::force(&CHAIN_SLOT);
+    LazyLock::force(&STREAM_SLOT_LAG);
+    LazyLock::force(&SECONDS_SINCE_LAST_SLOT);
+    LazyLock::force(&PROTOCOL_PAUSED);
+    LazyLock::force(&HEALTH_STATE);
+    LazyLock::force(&STAKING_ROUND_OVERDUE_SECONDS);
+    LazyLock::force(&STAKED_AMOUNT);
+    LazyLock::force(&INDEX_SIZE);
+    LazyLock::force(&LOCK_HOLD_SECONDS);
+    LazyLock::force(&STAKING_REWARD_PER_TOKEN_YEARLY);
+}
+
+// Prometheus text exposition of all registered metrics
+pub fn encode() -> String {
+    let mut buffer = vec![];
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        log::error!("Failed to encode metrics: {:?}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
@@ -0,0 +1,208 @@
+use {
+    crate::{
+        metrics::{
+            CHAIN_SLOT, INDEX_SIZE, REFERRER_REWARD_VAULT_BALANCE,
+            REFERRER_REWARD_VAULT_SECONDS_SINCE_DECREASE, SECONDS_SINCE_LAST_SLOT, STREAM_SLOT,
+            STREAM_SLOT_LAG,
+        },
+        rate_limiter::{rpc_call, throttle_rpc},
+        state_manager::StateManager,
+        token_accounts::reward_token_mint,
+        ActionRetriesThreadSafe, ChainClockThreadSafe, FinalizeLockedStakesCacheThreadSafe,
+        IndexedUserStakingAccountsThreadSafe, LastStreamSlotThreadSafe,
+        PendingTransactionsThreadSafe, UserStakingClaimCacheThreadSafe,
+    },
+    adrena_abi::{get_referrer_reward_token_vault_pda, StakingType},
+    solana_client::nonblocking::rpc_client::RpcClient,
+    std::{sync::atomic::Ordering, time::Duration},
+    tokio::time::interval,
+};
+
+const SLOT_LAG_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const REFERRER_REWARD_VAULT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const INDEX_SIZES_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+// Referral rewards are paid out continuously, a vault that never decreases for this long means the distribution stalled
+const REFERRER_REWARD_VAULT_MAX_STALL_SECONDS: i64 = 3 * 24 * 3_600;
+
+// Periodically compare the slot of the last update received from the stream with the RPC chain head,
+// and check that the stream slot updates are still flowing
+pub async fn monitor_slot_lag(
+    rpc_client: RpcClient,
+    last_stream_slot: LastStreamSlotThreadSafe,
+    chain_clock: ChainClockThreadSafe,
+    max_slot_lag: u64,
+) {
+    let mut check_interval = interval(SLOT_LAG_CHECK_INTERVAL);
+    loop {
+        check_interval.tick().await;
+
+        {
+            let chain_clock = chain_clock.read().await;
+            if chain_clock.slot != 0 {
+                SECONDS_SINCE_LAST_SLOT.set(chain_clock.seconds_since_last_slot());
+                if chain_clock.is_halted() {
+                    log::error!(
+                        "  <> No slot update for {}s (last slot: {}) - chain or stream halted",
+                        chain_clock.seconds_since_last_slot(),
+                        chain_clock.slot
+                    );
+                }
+            }
+        }
+
+        let stream_slot = last_stream_slot.load(Ordering::Relaxed);
+        if stream_slot == 0 {
+            // No update received yet
+            continue;
+        }
+
+        throttle_rpc().await;
+        let chain_slot = match rpc_client.get_slot().await {
+            Ok(chain_slot) => chain_slot,
+            Err(e) => {
+                log::warn!("   <> Failed to fetch chain head slot: {:?}", e);
+                continue;
+            }
+        };
+
+        let slot_lag = chain_slot.saturating_sub(stream_slot);
+        STREAM_SLOT.set(stream_slot as i64);
+        CHAIN_SLOT.set(chain_slot as i64);
+        STREAM_SLOT_LAG.set(slot_lag as i64);
+
+        if slot_lag > max_slot_lag {
+            log::error!(
+                "  <> Geyser stream is {} slots behind the chain head (stream: {}, chain: {})",
+                slot_lag,
+                stream_slot,
+                chain_slot
+            );
+        } else {
+            log::debug!("  <> Geyser stream slot lag: {}", slot_lag);
+        }
+    }
+}
+
+// Periodically check the referrer reward token vault: alert when the referral rewards pile up past the threshold
+// (in reward tokens) or when the vault hasn't been drawn from for too long
+pub async fn monitor_referrer_reward_vault(rpc_client: RpcClient, alert_threshold: Option<f64>) {
+    let vault = get_referrer_reward_token_vault_pda(&reward_token_mint()).0;
+    let mut check_interval = interval(REFERRER_REWARD_VAULT_CHECK_INTERVAL);
+    let mut previous_balance: Option<u64> = None;
+    let mut last_decrease_time = chrono::Utc::now().timestamp();
+    loop {
+        check_interval.tick().await;
+
+        let balance = match rpc_call(|| rpc_client.get_token_account_balance(&vault)).await {
+            Ok(balance) => balance,
+            Err(e) => {
+                log::warn!(
+                    "   <> Failed to fetch the referrer reward token vault balance: {:?}",
+                    e
+                );
+                continue;
+            }
+        };
+        let Ok(amount) = balance.amount.parse::<u64>() else {
+            log::warn!(
+                "   <> Invalid referrer reward token vault balance: {}",
+                balance.amount
+            );
+            continue;
+        };
+
+        let current_time = chrono::Utc::now().timestamp();
+        if previous_balance.is_some_and(|previous_balance| amount < previous_balance) {
+            last_decrease_time = current_time;
+        }
+        previous_balance = Some(amount);
+        let seconds_since_decrease = current_time - last_decrease_time;
+        REFERRER_REWARD_VAULT_BALANCE.set(amount as i64);
+        REFERRER_REWARD_VAULT_SECONDS_SINCE_DECREASE.set(seconds_since_decrease);
+
+        let ui_amount = balance.ui_amount.unwrap_or_default();
+        if let Some(alert_threshold) = alert_threshold.filter(|threshold| ui_amount > *threshold) {
+            log::error!(
+                "  <> Referrer reward token vault holds {} tokens (alert threshold: {})",
+                ui_amount,
+                alert_threshold
+            );
+        }
+        if amount != 0 && seconds_since_decrease > REFERRER_REWARD_VAULT_MAX_STALL_SECONDS {
+            log::error!(
+                "  <> Referrer reward token vault not drawn from for {}s ({} tokens) - referral rewards distribution stalled",
+                seconds_since_decrease,
+                ui_amount
+            );
+        }
+    }
+}
+
+// Periodically export the number of entries of the indexes and caches, to correlate with the lock hold times
+pub async fn monitor_index_sizes(
+    state_manager: StateManager,
+    indexed_user_staking_accounts: IndexedUserStakingAccountsThreadSafe,
+    claim_cache: UserStakingClaimCacheThreadSafe,
+    finalize_locked_stakes_cache: FinalizeLockedStakesCacheThreadSafe,
+    action_retries: ActionRetriesThreadSafe,
+    pending_transactions: PendingTransactionsThreadSafe,
+) {
+    let mut check_interval = interval(INDEX_SIZES_CHECK_INTERVAL);
+    loop {
+        check_interval.tick().await;
+
+        let Ok((
+            staking_accounts_count,
+            missing_staking_type_count,
+            next_resolve_times_count,
+            claim_queue_len,
+        )) = state_manager
+            .call(|state| {
+                (
+                    state.staking_accounts.len(),
+                    state.user_staking_accounts_missing_staking_type.len(),
+                    state.next_resolve_times.len(),
+                    state.claim_queue_len(),
+                )
+            })
+            .await
+        else {
+            // The state manager is gone, the keeper is going down
+            return;
+        };
+        let index_sizes = [
+            ("staking_accounts", staking_accounts_count),
+            ("user_staking_accounts", indexed_user_staking_accounts.len()),
+            (
+                "user_staking_accounts_lm",
+                indexed_user_staking_accounts.shard(StakingType::LM).len(),
+            ),
+            (
+                "user_staking_accounts_lp",
+                indexed_user_staking_accounts.shard(StakingType::LP).len(),
+            ),
+            (
+                "user_staking_accounts_missing_staking_type",
+                missing_staking_type_count,
+            ),
+            ("claim_cache", claim_cache.len()),
+            ("claim_queue", claim_queue_len),
+            (
+                "finalize_locked_stakes_cache",
+                finalize_locked_stakes_cache.len(),
+            ),
+            ("next_resolve_time_cache", next_resolve_times_count),
+            (
+                "action_retries",
+                action_retries.read().await.tracked_actions_count(),
+            ),
+            (
+                "pending_transactions",
+                pending_transactions.read().await.len(),
+            ),
+        ];
+        for (index, size) in index_sizes {
+            INDEX_SIZE.with_label_values(&[index]).set(size as i64);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use {
+    crate::{
+        reward_rates::staking_name, state_manager::StateManager,
+        update_caches::claim_jitter_seconds, FinalizeLockedStakesCacheThreadSafe,
+        UserStakingClaimCacheThreadSafe,
+    },
+    adrena_abi::Pubkey,
+    serde::Serialize,
+};
+
+// Schedule computed by the keeper from its caches, for the dashboards to show the next keeper run
+// Claims may also be forced earlier when the resolved rounds fill the Staking account (see FORCE_CLAIM_UNCLAIMED_ROUNDS)
+#[derive(Debug, Serialize)]
+pub struct NextActions {
+    pub resolves: Vec<NextResolve>,
+    pub claims: Vec<NextClaim>,
+    pub finalizations: Vec<NextFinalization>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NextResolve {
+    pub staking_account: String,
+    pub staking: Option<&'static str>,
+    pub next_resolve_time: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NextClaim {
+    pub user_staking_account: String,
+    pub next_claim_time: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NextFinalization {
+    pub user_staking_account: String,
+    pub stake_id: u64,
+    pub end_time: i64,
+}
+
+// All the scheduled actions, or only the ones of a UserStaking account (the resolves are shared by everyone), soonest first
+pub async fn next_actions(
+    state_manager: &StateManager,
+    claim_cache: &UserStakingClaimCacheThreadSafe,
+    finalize_locked_stakes_cache: &FinalizeLockedStakesCacheThreadSafe,
+    user_staking_account: Option<&Pubkey>,
+) -> NextActions {
+    // None if the state manager is gone, the keeper is going down
+    let mut resolves: Vec<NextResolve> = state_manager
+        .call(|state| state.next_resolve_times.clone())
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|(staking_account_key, next_resolve_time)| NextResolve {
+            staking_account: staking_account_key.to_string(),
+            staking: staking_name(staking_account_key),
+            next_resolve_time: *next_resolve_time,
+        })
+        .collect();
+    resolves.sort_by_key(|resolve| resolve.next_resolve_time);
+
+    let is_selected = |user_staking_account_key: &Pubkey| {
+        user_staking_account.map_or(true, |selected| selected == user_staking_account_key)
+    };
+
+    let mut claims: Vec<NextClaim> = claim_cache
+        .iter()
+        .filter(|entry| is_selected(entry.key()))
+        .filter_map(|entry| {
+            let next_claim_time = entry.value().next_claim_time()?;
+            Some(NextClaim {
+                user_staking_account: entry.key().to_string(),
+                next_claim_time: next_claim_time + claim_jitter_seconds(entry.key()),
+            })
+        })
+        .collect();
+    claims.sort_by_key(|claim| claim.next_claim_time);
+
+    let mut finalizations: Vec<NextFinalization> = finalize_locked_stakes_cache
+        .iter()
+        .filter(|entry| is_selected(entry.key()))
+        .flat_map(|entry| {
+            let user_staking_account_key = entry.key().to_string();
+            entry
+                .value()
+                .iter()
+                .map(|(stake_id, end_time)| NextFinalization {
+                    user_staking_account: user_staking_account_key.clone(),
+                    stake_id: *stake_id,
+                    end_time: *end_time,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    finalizations.sort_by_key(|finalization| finalization.end_time);
+
+    NextActions {
+        resolves,
+        claims,
+        finalizations,
+    }
+}
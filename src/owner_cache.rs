@@ -0,0 +1,95 @@
+use {
+    crate::{db::Db, health::record_db_outcome, metrics::INDEX_SIZE},
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::HashMap,
+        sync::{LazyLock, Mutex, RwLock},
+    },
+};
+
+// The whole UserStaking account -> owner mapping, bulk loaded from the DB and refreshed periodically (by the core loop)
+// The claims and finalizations read it instead of issuing a SELECT per account, the DB is only queried on a miss
+// Survives the reconnects, a failed refresh keeps serving the previous mapping
+static OWNER_CACHE: LazyLock<RwLock<HashMap<Pubkey, Pubkey>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+// Mappings learned by the keeper itself, waiting to be written back to the DB (flushed by the core loop)
+static LEARNED_OWNERS: LazyLock<Mutex<HashMap<Pubkey, Pubkey>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn cached_owner(user_staking_account_key: &Pubkey) -> Option<Pubkey> {
+    OWNER_CACHE
+        .read()
+        .unwrap()
+        .get(user_staking_account_key)
+        .copied()
+}
+
+// Mapping learned from a direct lookup (cache miss), kept until the next refresh replaces the whole map
+pub fn cache_owner(user_staking_account_key: &Pubkey, owner_pubkey: &Pubkey) {
+    OWNER_CACHE
+        .write()
+        .unwrap()
+        .insert(*user_staking_account_key, *owner_pubkey);
+}
+
+// Mapping observed onchain (e.g. a claim signed for this owner landed): served right away and written back to the DB,
+// so the table heals itself instead of depending entirely on the indexer
+pub fn learn_owner(user_staking_account_key: &Pubkey, owner_pubkey: &Pubkey) {
+    if cached_owner(user_staking_account_key) == Some(*owner_pubkey) {
+        return;
+    }
+    log::info!(
+        "  <> Learned owner {} of UserStaking account {}",
+        owner_pubkey,
+        user_staking_account_key
+    );
+    cache_owner(user_staking_account_key, owner_pubkey);
+    LEARNED_OWNERS
+        .lock()
+        .unwrap()
+        .insert(*user_staking_account_key, *owner_pubkey);
+}
+
+// Write the learned mappings to the DB - kept for the next flush if the write fails
+pub async fn flush_learned_owners(db: &Db) {
+    let learned_owners: Vec<(Pubkey, Pubkey)> =
+        std::mem::take(&mut *LEARNED_OWNERS.lock().unwrap())
+            .into_iter()
+            .collect();
+    if learned_owners.is_empty() {
+        return;
+    }
+
+    let result = db.insert_owner_pubkeys(&learned_owners).await;
+    record_db_outcome(result.is_ok());
+    if let Err(e) = result {
+        log::warn!("   <> Failed to write back the learned owners: {:?}", e);
+        LEARNED_OWNERS.lock().unwrap().extend(learned_owners);
+    }
+}
+
+// Reload the whole mapping from the DB
+pub async fn refresh_owner_cache(db: &Db) {
+    let owners = db.load_owner_pubkeys().await;
+    record_db_outcome(owners.is_ok());
+    match owners {
+        Ok(mut owners) => {
+            // Keep the learned mappings not written back yet, the DB doesn't have them
+            owners.extend(LEARNED_OWNERS.lock().unwrap().iter());
+            let owners_count = owners.len();
+            *OWNER_CACHE.write().unwrap() = owners;
+            INDEX_SIZE
+                .with_label_values(&["owner_cache"])
+                .set(owners_count as i64);
+            log::info!(
+                "  <> Owner mapping loaded: {} UserStaking accounts",
+                owners_count
+            );
+        }
+        Err(e) => log::warn!(
+            "   <> Failed to load the owner mapping, keeping the previous one: {:?}",
+            e
+        ),
+    }
+}
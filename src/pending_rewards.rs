@@ -0,0 +1,94 @@
+use {
+    crate::reward_rates::RATE_DECIMALS,
+    adrena_abi::{Staking, UserStaking},
+};
+
+// Rewards accrued by a UserStaking account since its stakes were last claimed (native units), estimated from the
+// rounds resolved in the Staking account - the program may differ slightly (rounds overlapping the claim time)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PendingRewards {
+    // Reward token (USDC unless changed)
+    pub rewards: u64,
+    // LM token (ADX)
+    pub lm_rewards: u64,
+}
+
+impl PendingRewards {
+    pub fn is_zero(&self) -> bool {
+        self.rewards == 0 && self.lm_rewards == 0
+    }
+}
+
+// Number of resolved rounds the oldest active stake hasn't claimed yet - the Staking account only keeps the last 32,
+// the rounds pushed out before the claim are lost for the stake
+pub fn unclaimed_resolved_rounds(
+    user_staking_account: &UserStaking,
+    staking_account: &Staking,
+) -> usize {
+    let liquid_stake = &user_staking_account.liquid_stake;
+    let oldest_claim_time = std::iter::once((liquid_stake.claim_time, liquid_stake.amount))
+        .chain(
+            user_staking_account
+                .locked_stakes
+                .iter()
+                .map(|locked_stake| (locked_stake.claim_time, locked_stake.amount)),
+        )
+        .filter(|(_, amount)| *amount != 0)
+        .map(|(claim_time, _)| claim_time)
+        .min();
+
+    oldest_claim_time.map_or(0, |oldest_claim_time| {
+        staking_account
+            .resolved_staking_rounds
+            .iter()
+            .filter(|round| round.start_time != 0 && round.start_time >= oldest_claim_time)
+            .count()
+    })
+}
+
+pub fn estimate_pending_rewards(
+    user_staking_account: &UserStaking,
+    staking_account: &Staking,
+) -> PendingRewards {
+    let rate_precision = 10u128.pow(RATE_DECIMALS as u32);
+    let resolved_rounds = || {
+        staking_account
+            .resolved_staking_rounds
+            .iter()
+            .filter(|round| round.start_time != 0)
+    };
+
+    // (claim time, amount weighted for the rewards, amount weighted for the LM rewards) of each active stake
+    let liquid_stake = &user_staking_account.liquid_stake;
+    let stakes = std::iter::once((
+        liquid_stake.claim_time,
+        liquid_stake.amount,
+        liquid_stake.amount,
+    ))
+    .chain(
+        user_staking_account
+            .locked_stakes
+            .iter()
+            .map(|locked_stake| {
+                (
+                    locked_stake.claim_time,
+                    locked_stake.amount_with_reward_multiplier,
+                    locked_stake.amount_with_lm_reward_multiplier,
+                )
+            }),
+    )
+    .filter(|(_, amount, _)| *amount != 0);
+
+    let (mut rewards, mut lm_rewards) = (0u128, 0u128);
+    for (claim_time, amount, lm_amount) in stakes {
+        for round in resolved_rounds().filter(|round| round.start_time >= claim_time) {
+            rewards += amount as u128 * round.rate as u128 / rate_precision;
+            lm_rewards += lm_amount as u128 * round.lm_rate as u128 / rate_precision;
+        }
+    }
+
+    PendingRewards {
+        rewards: rewards.min(u64::MAX as u128) as u64,
+        lm_rewards: lm_rewards.min(u64::MAX as u128) as u64,
+    }
+}
@@ -0,0 +1,124 @@
+use {
+    solana_sdk::{instruction::InstructionError, transaction::TransactionError},
+    std::fmt,
+};
+
+// Logged by anchor when a program error is raised
+const ANCHOR_ERROR_CODE_LOG: &str = "Error Code: ";
+// Anchor framework error codes (https://github.com/coral-xyz/anchor/blob/master/lang/src/error.rs)
+const ANCHOR_CONSTRAINT_ERRORS: std::ops::RangeInclusive<u32> = 2_000..=2_999;
+const ANCHOR_ACCOUNT_DISCRIMINATOR_NOT_FOUND: u32 = 3_001;
+const ANCHOR_ACCOUNT_OWNED_BY_WRONG_PROGRAM: u32 = 3_007;
+const ANCHOR_ACCOUNT_NOT_INITIALIZED: u32 = 3_012;
+// From here, the errors are defined by the program
+const ANCHOR_PROGRAM_ERRORS_START: u32 = 6_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreflightFailureKind {
+    // The action cannot succeed in the current state (e.g. account closed) - abandoned until the account changes
+    Skip,
+    // Transient (stale blockhash, state not propagated yet, timing) - retried with the usual backoff
+    Retry,
+    // Needs an operator (keeper out of SOL, constraint violation) - retried with the usual backoff, reported as an error
+    Alert,
+}
+
+impl PreflightFailureKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PreflightFailureKind::Skip => "skip",
+            PreflightFailureKind::Retry => "retry",
+            PreflightFailureKind::Alert => "alert",
+        }
+    }
+}
+
+// A failed simulation, carried as the handler error so the action retries can act on its kind
+#[derive(Debug)]
+pub struct PreflightFailure {
+    pub kind: PreflightFailureKind,
+    pub reason: String,
+    // Raised by the program on the accounts of the action (anchor constraint, program error) - the keeper side errors
+    // (payer out of SOL, blockhash) are not
+    pub program_error: bool,
+}
+
+impl fmt::Display for PreflightFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Simulation failed ({}): {}",
+            self.kind.as_str(),
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for PreflightFailure {}
+
+pub fn classify_simulation_failure(
+    error: &TransactionError,
+    simulation_logs: &[String],
+) -> PreflightFailure {
+    let kind = match error {
+        TransactionError::BlockhashNotFound | TransactionError::AlreadyProcessed => {
+            PreflightFailureKind::Retry
+        }
+        TransactionError::InsufficientFundsForFee
+        | TransactionError::InsufficientFundsForRent { .. }
+        | TransactionError::AccountNotFound => PreflightFailureKind::Alert,
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => match *code {
+            ANCHOR_ACCOUNT_DISCRIMINATOR_NOT_FOUND
+            | ANCHOR_ACCOUNT_OWNED_BY_WRONG_PROGRAM
+            | ANCHOR_ACCOUNT_NOT_INITIALIZED => PreflightFailureKind::Skip,
+            code if ANCHOR_CONSTRAINT_ERRORS.contains(&code) => PreflightFailureKind::Alert,
+            code if code >= ANCHOR_PROGRAM_ERRORS_START => PreflightFailureKind::Retry,
+            _ => PreflightFailureKind::Alert,
+        },
+        _ => PreflightFailureKind::Retry,
+    };
+
+    let program_error = matches!(
+        error,
+        TransactionError::InstructionError(_, InstructionError::Custom(_))
+    );
+
+    let reason = match find_anchor_error_code(simulation_logs) {
+        Some(error_code) => format!("{:?} ({})", error, error_code),
+        None => format!("{:?}", error),
+    };
+    PreflightFailure {
+        kind,
+        reason,
+        program_error,
+    }
+}
+
+// The transaction ran out of compute units - the action needs a higher CU limit, or to be split
+pub fn is_cu_exceeded(error: &TransactionError) -> bool {
+    matches!(
+        error,
+        TransactionError::InstructionError(_, InstructionError::ComputationalBudgetExceeded)
+    )
+}
+
+// A token account passed to the instruction doesn't exist (anymore) - e.g. an owner closed their reward token account
+pub fn is_missing_account_error(error: &TransactionError) -> bool {
+    matches!(
+        error,
+        TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(ANCHOR_ACCOUNT_NOT_INITIALIZED)
+                | InstructionError::UninitializedAccount
+                | InstructionError::InvalidAccountData
+        )
+    )
+}
+
+// Name of the anchor error raised in the simulation, if any
+fn find_anchor_error_code(simulation_logs: &[String]) -> Option<&str> {
+    simulation_logs.iter().find_map(|line| {
+        let (_, error_code) = line.split_once(ANCHOR_ERROR_CODE_LOG)?;
+        error_code.split('.').next()
+    })
+}
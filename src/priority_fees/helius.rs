@@ -0,0 +1,73 @@
+use {
+    super::PriorityFeeProvider,
+    crate::rate_limiter::rpc_call,
+    async_trait::async_trait,
+    solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::RpcRequest},
+    solana_sdk::pubkey::Pubkey,
+};
+
+// Helius getPriorityFeeEstimate for transactions write-locking the given accounts
+pub struct HeliusPriorityFeeProvider {
+    rpc_client: RpcClient,
+}
+
+impl HeliusPriorityFeeProvider {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            rpc_client: RpcClient::new(rpc_url),
+        }
+    }
+}
+
+// Helius priority levels are fixed percentiles: Min, Low (25th), Medium (50th), High (75th), VeryHigh (95th), UnsafeMax
+fn priority_level(percentile: u64) -> &'static str {
+    match percentile {
+        0 => "Min",
+        1..=2500 => "Low",
+        2501..=5000 => "Medium",
+        5001..=7500 => "High",
+        7501..=9500 => "VeryHigh",
+        _ => "UnsafeMax",
+    }
+}
+
+#[async_trait]
+impl PriorityFeeProvider for HeliusPriorityFeeProvider {
+    fn name(&self) -> &'static str {
+        "helius"
+    }
+
+    async fn priority_fee(
+        &self,
+        percentile: u64,
+        write_locked_accounts: &[Pubkey],
+    ) -> Result<u64, anyhow::Error> {
+        let account_keys: Vec<String> = write_locked_accounts
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        let params = serde_json::json!([{
+            "accountKeys": account_keys,
+            "options": {
+                "priorityLevel": priority_level(percentile),
+            },
+        }]);
+
+        let response: serde_json::Value = rpc_call(|| {
+            self.rpc_client.send(
+                RpcRequest::Custom {
+                    method: "getPriorityFeeEstimate",
+                },
+                params.clone(),
+            )
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch Helius priority fee estimate: {:?}", e))?;
+
+        // The estimate is returned as a float, in µLamports / cu
+        response["priorityFeeEstimate"]
+            .as_f64()
+            .map(|fee| fee.ceil() as u64)
+            .ok_or_else(|| anyhow::anyhow!("Invalid Helius priority fee estimate: {}", response))
+    }
+}
@@ -0,0 +1,189 @@
+pub mod helius;
+pub mod rpc;
+pub mod triton;
+
+pub use {helius::*, rpc::*, triton::*};
+
+use {
+    adrena_abi::pda::{
+        get_staking_lm_reward_token_vault_pda, get_staking_reward_token_vault_pda,
+        get_staking_staked_token_vault_pda,
+    },
+    anchor_client::anchor_lang::ToAccountMetas,
+    async_trait::async_trait,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::HashMap,
+        sync::{Arc, LazyLock, Mutex, OnceLock},
+        time::{Duration, Instant},
+    },
+};
+
+// Percentile (basis points) of the fees paid by the recent transactions write-locking the same accounts
+const WRITE_LOCKED_FEE_PERCENTILE: u64 = 3500; // 35th
+
+// The fees of a set of accounts are reused for this long (e.g. the batches of a claim, the finalizations of an account)
+const WRITE_LOCKED_FEE_TTL: Duration = Duration::from_secs(5);
+
+// Set once at startup - if never set, the transactions are priced on the fees fetched by the core loop
+static PRIORITY_FEES: OnceLock<PriorityFees> = OnceLock::new();
+
+// Recently fetched fees, per set of write-locked accounts
+static RECENT_WRITE_LOCKED_FEES: LazyLock<Mutex<HashMap<Vec<Pubkey>, (Instant, u64)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum PriorityFeeSource {
+    // getRecentPrioritizationFees percentiles (any RPC)
+    #[default]
+    Rpc,
+    // Helius getPriorityFeeEstimate
+    Helius,
+    // Triton getRecentPrioritizationFees with its percentile option
+    Triton,
+}
+
+// Where the compute unit prices come from - new providers only need an implementation and a PriorityFeeSource variant
+#[async_trait]
+pub trait PriorityFeeProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Whether the fees of the whole cluster can be fetched (no write-locked account given)
+    fn cluster_wide(&self) -> bool {
+        false
+    }
+
+    // Compute unit price (µLamports / cu) paid at the given percentile (basis points, 10_000 = 100th) by the recent
+    // transactions write-locking all the given accounts
+    async fn priority_fee(
+        &self,
+        percentile: u64,
+        write_locked_accounts: &[Pubkey],
+    ) -> Result<u64, anyhow::Error>;
+}
+
+struct PriorityFees {
+    provider: Arc<dyn PriorityFeeProvider>,
+    // Cap on the fetched compute unit price (µLamports / cu)
+    max_priority_fee: u64,
+}
+
+pub fn new_priority_fee_provider(
+    source: PriorityFeeSource,
+    rpc_url: String,
+) -> Arc<dyn PriorityFeeProvider> {
+    match source {
+        PriorityFeeSource::Rpc => Arc::new(RpcPriorityFeeProvider::new(rpc_url)),
+        PriorityFeeSource::Helius => Arc::new(HeliusPriorityFeeProvider::new(rpc_url)),
+        PriorityFeeSource::Triton => Arc::new(TritonPriorityFeeProvider::new(rpc_url)),
+    }
+}
+
+pub fn init_priority_fees(provider: Arc<dyn PriorityFeeProvider>, max_priority_fee: u64) {
+    log::info!("  <> Priority fees provider: {}", provider.name());
+    let _ = PRIORITY_FEES.set(PriorityFees {
+        provider,
+        max_priority_fee,
+    });
+}
+
+// Compute unit price paid at the given percentile by the transactions write-locking the accounts, capped by the max
+// priority fee - a spike in the fetched fees must not translate into absurd priority fees
+pub async fn fetch_priority_fee(
+    percentile: u64,
+    write_locked_accounts: &[Pubkey],
+) -> Result<u64, anyhow::Error> {
+    let priority_fees = PRIORITY_FEES
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("No priority fees provider"))?;
+    let priority_fee = priority_fees
+        .provider
+        .priority_fee(percentile, write_locked_accounts)
+        .await?;
+    if priority_fee > priority_fees.max_priority_fee {
+        log::warn!(
+            "  <> Fetched priority fee {} µLamports / cu capped to {}",
+            priority_fee,
+            priority_fees.max_priority_fee
+        );
+    }
+    Ok(priority_fee.min(priority_fees.max_priority_fee))
+}
+
+// Fee refreshed by the core loop, the fallback of the transactions and the price of the AUM updates: the whole cluster
+// when the provider allows it, the Staking accounts (write-locked by both the resolves and the claims) otherwise
+pub async fn fetch_reference_priority_fee(
+    percentile: u64,
+    staking_account_keys: &[Pubkey],
+) -> Result<u64, anyhow::Error> {
+    let cluster_wide = PRIORITY_FEES
+        .get()
+        .is_some_and(|priority_fees| priority_fees.provider.cluster_wide());
+    let write_locked_accounts = if cluster_wide {
+        &[]
+    } else {
+        staking_account_keys
+    };
+    fetch_priority_fee(percentile, write_locked_accounts).await
+}
+
+// Accounts an instruction write-locks among the ones shared by all the users of the staking (the Staking account and its
+// vaults) - the per user ones (UserStaking account, owner accounts...) are only contended by the keeper, and would make
+// each fetched fee good for a single user
+pub fn write_locked_accounts(accounts: &impl ToAccountMetas, staking: &Pubkey) -> Vec<Pubkey> {
+    let shared_accounts = [
+        *staking,
+        get_staking_staked_token_vault_pda(staking).0,
+        get_staking_reward_token_vault_pda(staking).0,
+        get_staking_lm_reward_token_vault_pda(staking).0,
+    ];
+    accounts
+        .to_account_metas(None)
+        .into_iter()
+        .filter(|account_meta| {
+            account_meta.is_writable && shared_accounts.contains(&account_meta.pubkey)
+        })
+        .map(|account_meta| account_meta.pubkey)
+        .collect()
+}
+
+// Compute unit price of a transaction, priced on the recent fees paid to write-lock the same shared accounts (the
+// Staking account, the vaults) - falls back to the given fee (fetched periodically by the core loop)
+pub async fn write_locked_priority_fee(
+    write_locked_accounts: &[Pubkey],
+    fallback_priority_fee: u64,
+) -> u64 {
+    let mut accounts_key = write_locked_accounts.to_vec();
+    accounts_key.sort();
+    accounts_key.dedup();
+    if let Some((fetched_at, priority_fee)) =
+        RECENT_WRITE_LOCKED_FEES.lock().unwrap().get(&accounts_key)
+    {
+        if fetched_at.elapsed() < WRITE_LOCKED_FEE_TTL {
+            return *priority_fee;
+        }
+    }
+
+    match fetch_priority_fee(WRITE_LOCKED_FEE_PERCENTILE, &accounts_key).await {
+        Ok(priority_fee) => {
+            log::debug!(
+                "  <> Priority fee for {} write-locked accounts: {} µLamports / cu (fallback: {})",
+                accounts_key.len(),
+                priority_fee,
+                fallback_priority_fee
+            );
+            let mut recent_write_locked_fees = RECENT_WRITE_LOCKED_FEES.lock().unwrap();
+            recent_write_locked_fees
+                .retain(|_, (fetched_at, _)| fetched_at.elapsed() < WRITE_LOCKED_FEE_TTL);
+            recent_write_locked_fees.insert(accounts_key, (Instant::now(), priority_fee));
+            priority_fee
+        }
+        Err(e) => {
+            log::warn!(
+                "   <> Failed to fetch the write-locked accounts priority fee, using the fallback one: {:?}",
+                e
+            );
+            fallback_priority_fee
+        }
+    }
+}
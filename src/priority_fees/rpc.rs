@@ -0,0 +1,74 @@
+use {
+    super::PriorityFeeProvider,
+    crate::rate_limiter::rpc_call,
+    async_trait::async_trait,
+    solana_client::{
+        nonblocking::rpc_client::RpcClient, rpc_request::RpcRequest,
+        rpc_response::RpcPrioritizationFee,
+    },
+    solana_sdk::pubkey::Pubkey,
+};
+
+// getRecentPrioritizationFees with a percentile, averaged over the returned slots
+pub struct RpcPriorityFeeProvider {
+    rpc_client: RpcClient,
+}
+
+impl RpcPriorityFeeProvider {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            rpc_client: RpcClient::new(rpc_url),
+        }
+    }
+}
+
+#[async_trait]
+impl PriorityFeeProvider for RpcPriorityFeeProvider {
+    fn name(&self) -> &'static str {
+        "rpc"
+    }
+
+    fn cluster_wide(&self) -> bool {
+        true
+    }
+
+    async fn priority_fee(
+        &self,
+        percentile: u64,
+        write_locked_accounts: &[Pubkey],
+    ) -> Result<u64, anyhow::Error> {
+        let accounts: Vec<String> = write_locked_accounts
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        let params = serde_json::json!([accounts, [percentile]]);
+
+        let recent_prioritization_fees: Vec<RpcPrioritizationFee> = rpc_call(|| {
+            self.rpc_client
+                .send(RpcRequest::GetRecentPrioritizationFees, params.clone())
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch mean priority fee: {:?}", e))?;
+
+        mean_prioritization_fee(&recent_prioritization_fees)
+    }
+}
+
+// Rounded up mean of the fees of the returned slots
+pub fn mean_prioritization_fee(
+    recent_prioritization_fees: &[RpcPrioritizationFee],
+) -> Result<u64, anyhow::Error> {
+    if recent_prioritization_fees.is_empty() {
+        return Err(anyhow::anyhow!("No prioritization fees retrieved"));
+    }
+
+    let sum: u64 = recent_prioritization_fees
+        .iter()
+        .map(|fee| fee.prioritization_fee)
+        .sum();
+
+    let mean = (sum + recent_prioritization_fees.len() as u64 - 1)
+        / recent_prioritization_fees.len() as u64;
+
+    Ok(mean)
+}
@@ -0,0 +1,52 @@
+use {
+    super::{mean_prioritization_fee, PriorityFeeProvider},
+    crate::rate_limiter::rpc_call,
+    async_trait::async_trait,
+    solana_client::{
+        nonblocking::rpc_client::RpcClient, rpc_request::RpcRequest,
+        rpc_response::RpcPrioritizationFee,
+    },
+    solana_sdk::pubkey::Pubkey,
+};
+
+// Triton getRecentPrioritizationFees: with the percentile option, each slot reports the fee paid at that percentile
+// (instead of the minimum one), averaged over the returned slots
+pub struct TritonPriorityFeeProvider {
+    rpc_client: RpcClient,
+}
+
+impl TritonPriorityFeeProvider {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            rpc_client: RpcClient::new(rpc_url),
+        }
+    }
+}
+
+#[async_trait]
+impl PriorityFeeProvider for TritonPriorityFeeProvider {
+    fn name(&self) -> &'static str {
+        "triton"
+    }
+
+    async fn priority_fee(
+        &self,
+        percentile: u64,
+        write_locked_accounts: &[Pubkey],
+    ) -> Result<u64, anyhow::Error> {
+        let accounts: Vec<String> = write_locked_accounts
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        let params = serde_json::json!([accounts, { "percentile": percentile }]);
+
+        let recent_prioritization_fees: Vec<RpcPrioritizationFee> = rpc_call(|| {
+            self.rpc_client
+                .send(RpcRequest::GetRecentPrioritizationFees, params.clone())
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch Triton priority fee: {:?}", e))?;
+
+        mean_prioritization_fee(&recent_prioritization_fees)
+    }
+}
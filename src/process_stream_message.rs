@@ -1,20 +1,36 @@
 use {
     crate::{
-        generate_accounts_filter_map,
+        db::{Db, ResolvedStakingRound},
+        events::{publish_event, KeeperEvent},
+        generate_subscribe_request,
+        logging::log_chatter,
+        maintenance::update_protocol_pause,
+        program_upgrade::record_program_data_update,
+        reward_rates::update_staking_reward_rates_metrics,
+        stake_transactions::learn_owners_from_transaction,
+        state_manager::StateManager,
+        tx_tracker::{confirm_transaction_from_stream, TransactionReceipt},
         update_caches::{
-            update_claim_cache_for_account, update_finalize_locked_stakes_cache_for_account,
+            schedule_forced_claims, update_claim_cache_for_account,
+            update_finalize_locked_stakes_cache_for_account,
             update_staking_round_next_resolve_time_cache_for_account,
         },
         update_indexes::{update_indexed_staking_accounts, update_indexed_user_staking_accounts},
-        FinalizeLockedStakesCacheThreadSafe, IndexedStakingAccountsThreadSafe,
-        IndexedUserStakingAccountsThreadSafe, StakingRoundNextResolveTimeCacheThreadSafe,
-        UserStakingClaimCacheThreadSafe,
+        warm_start::{forget_user_staking_account, store_user_staking_accounts},
+        webhooks::notify_round_resolved,
+        ActionRetriesThreadSafe, ChainClockThreadSafe, FinalizeLockedStakesCacheThreadSafe,
+        IndexedGenesisLockThreadSafe, IndexedUserStakingAccountsThreadSafe,
+        LastStreamSlotThreadSafe, PendingTransactionsThreadSafe, UserStakingClaimCacheThreadSafe,
     },
-    adrena_abi::{Staking, UserStaking},
+    adrena_abi::{AccountDeserialize, Cortex, GenesisLock, Staking, StakingType, UserStaking},
     futures::{channel::mpsc::SendError, Sink, SinkExt},
-    solana_sdk::pubkey::Pubkey,
-    yellowstone_grpc_proto::geyser::{
-        subscribe_update::UpdateOneof, SubscribeRequest, SubscribeRequestPing, SubscribeUpdate,
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+    std::sync::atomic::Ordering,
+    yellowstone_grpc_proto::{
+        geyser::{
+            subscribe_update::UpdateOneof, SubscribeRequest, SubscribeRequestPing, SubscribeUpdate,
+        },
+        prelude::CommitmentLevel,
     },
 };
 
@@ -33,11 +49,20 @@ pub enum UserStakingAccountUpdate {
 
 pub async fn process_stream_message<S>(
     message: Result<SubscribeUpdate, backoff::Error<anyhow::Error>>,
-    indexed_staking_accounts: &IndexedStakingAccountsThreadSafe,
+    state_manager: &StateManager,
     indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+    indexed_genesis_lock: &IndexedGenesisLockThreadSafe,
     claim_cache: &UserStakingClaimCacheThreadSafe,
     finalize_locked_stakes_cache: &FinalizeLockedStakesCacheThreadSafe,
-    staking_round_next_resolve_time_cache: &StakingRoundNextResolveTimeCacheThreadSafe,
+    last_stream_slot: &LastStreamSlotThreadSafe,
+    chain_clock: &ChainClockThreadSafe,
+    pending_transactions: &PendingTransactionsThreadSafe,
+    action_retries: &ActionRetriesThreadSafe,
+    keeper_pubkey: &Pubkey,
+    // Of the subscription, kept by the updated requests
+    commitment: Option<CommitmentLevel>,
+    // Not connected in the resolves role
+    db: Option<&Db>,
     subscribe_tx: &mut S,
 ) -> Result<(), backoff::Error<anyhow::Error>>
 where
@@ -49,17 +74,29 @@ where
         Ok(msg) => {
             match msg.update_oneof {
                 Some(UpdateOneof::Account(sua)) => {
+                    // Keep track of the stream progress (used to measure the slot lag)
+                    last_stream_slot.fetch_max(sua.slot, Ordering::Relaxed);
+                    let slot = sua.slot;
                     let account = sua.account.expect("Account should be defined");
                     let account_key = Pubkey::try_from(account.pubkey).expect("valid pubkey");
-                    let account_data = account.data.to_vec();
+                    let account_data = &account.data[..];
+                    // Transaction that modified the account (not provided by the WebSocket fallback)
+                    let txn_signature = account
+                        .txn_signature
+                        .as_deref()
+                        .and_then(|signature| Signature::try_from(signature).ok());
                     // Each loop iteration we check if we need to update the subscription request based on what previously happened
 
                     if msg.filters.contains(&"staking_create_update".to_owned()) {
+                        let previous_staking_account = state_manager
+                            .call(move |state| state.staking_accounts.get(&account_key).copied())
+                            .await
+                            .map_err(backoff::Error::permanent)?;
                         // Updates the indexed Staking accounts map
                         let update = update_indexed_staking_accounts(
                             &account_key,
-                            &account_data,
-                            indexed_staking_accounts,
+                            account_data,
+                            state_manager,
                         )
                         .await?;
 
@@ -68,14 +105,74 @@ where
                                 panic!("Staking account created in staking_create_update filter");
                             }
                             StakingAccountUpdate::Modified(updated_staking_account) => {
-                                log::info!("(scu) Staking account modified: {:#?}", account_key);
+                                log_chatter(
+                                    "(scu) Staking account modified",
+                                    format_args!("{}", account_key),
+                                );
                                 // Based on the updated Staking account, update the staking round next resolve time cache (if needed)
                                 update_staking_round_next_resolve_time_cache_for_account(
-                                    staking_round_next_resolve_time_cache,
+                                    state_manager,
                                     &account_key,
                                     &updated_staking_account,
                                 )
-                                .await;
+                                .await
+                                .map_err(backoff::Error::permanent)?;
+
+                                update_staking_reward_rates_metrics(
+                                    &account_key,
+                                    &updated_staking_account,
+                                );
+
+                                // A new current round means the previous one has been resolved, record it for analytics
+                                if let Some(previous_staking_account) = previous_staking_account {
+                                    let resolved_round_start_time =
+                                        previous_staking_account.current_staking_round.start_time;
+                                    if updated_staking_account.current_staking_round.start_time
+                                        != resolved_round_start_time
+                                    {
+                                        // One more resolved round to claim for the stakers of this side
+                                        schedule_forced_claims(
+                                            state_manager,
+                                            claim_cache,
+                                            indexed_user_staking_accounts
+                                                .shard(updated_staking_account.get_staking_type()),
+                                            &updated_staking_account,
+                                        )
+                                        .await
+                                        .map_err(backoff::Error::permanent)?;
+                                        if let Some((round_index, round)) = updated_staking_account
+                                            .resolved_staking_rounds
+                                            .iter()
+                                            .enumerate()
+                                            .find(|(_, round)| {
+                                                round.start_time == resolved_round_start_time
+                                            })
+                                        {
+                                            let resolved_staking_round = ResolvedStakingRound {
+                                                start_time: round.start_time,
+                                                end_time: updated_staking_account
+                                                    .current_staking_round
+                                                    .start_time,
+                                                rate: round.rate,
+                                                total_stake: round.total_stake,
+                                                total_claim: round.total_claim,
+                                                lm_rate: round.lm_rate,
+                                                lm_total_stake: round.lm_total_stake,
+                                                lm_total_claim: round.lm_total_claim,
+                                                resolved_slot: slot,
+                                            };
+                                            on_staking_round_resolved(
+                                                db,
+                                                &account_key,
+                                                updated_staking_account.get_staking_type(),
+                                                round_index as u64,
+                                                resolved_staking_round,
+                                                txn_signature.as_ref(),
+                                            )
+                                            .await;
+                                        }
+                                    }
+                                }
                             }
                             StakingAccountUpdate::Closed => {
                                 panic!("Staking account closed in staking_create_update filter");
@@ -83,6 +180,36 @@ where
                         }
                     }
 
+                    if msg.filters.contains(&"genesis_lock_update".to_owned()) {
+                        match GenesisLock::try_deserialize(&mut &account_data[..]) {
+                            Ok(genesis_lock) => {
+                                log::info!("(glu) Genesis lock modified: {:#?}", account_key);
+                                *indexed_genesis_lock.write().await = Some(genesis_lock);
+                            }
+                            Err(e) => {
+                                log::warn!("   <> Failed to deserialize the genesis lock: {:?}", e);
+                            }
+                        }
+                    }
+
+                    if msg.filters.contains(&"cortex_update".to_owned()) {
+                        match Cortex::try_deserialize(&mut &account_data[..]) {
+                            Ok(cortex) => update_protocol_pause(&cortex),
+                            Err(e) => {
+                                log::warn!("   <> Failed to deserialize the cortex: {:?}", e);
+                            }
+                        }
+                    }
+
+                    if msg.filters.contains(&"program_upgrade".to_owned())
+                        && record_program_data_update(account_data)
+                    {
+                        // Reconnecting re-fetches all the accounts with the new program
+                        return Err(backoff::Error::transient(anyhow::anyhow!(
+                            "Adrena program upgraded - resyncing"
+                        )));
+                    }
+
                     if msg
                         .filters
                         .contains(&"user_staking_create_update".to_owned())
@@ -90,8 +217,9 @@ where
                         // Updates the indexed UserStaking accounts map
                         let update = update_indexed_user_staking_accounts(
                             &account_key,
-                            &account_data,
+                            account_data,
                             indexed_user_staking_accounts,
+                            state_manager,
                         )
                         .await?;
 
@@ -103,55 +231,78 @@ where
                                     account_key
                                 );
 
-                                // Update the claim cache with the claim time of the oldest locked stake for the new UserStaking account
+                                // Update the claim cache with the claim times of the stakes of the new UserStaking account
                                 update_claim_cache_for_account(
+                                    state_manager,
                                     claim_cache,
                                     account_key,
                                     &new_user_staking_account,
                                 )
-                                .await;
+                                .await
+                                .map_err(backoff::Error::permanent)?;
 
                                 // Update the finalize locked stakes cache with the locked stakes for the new UserStaking account
                                 update_finalize_locked_stakes_cache_for_account(
                                     finalize_locked_stakes_cache,
                                     &account_key,
                                     &new_user_staking_account,
-                                )
-                                .await;
+                                );
+
+                                store_user_staking_accounts(
+                                    db,
+                                    &[(account_key, account_data.to_vec())],
+                                );
+                                publish_event(KeeperEvent::AccountIndexed {
+                                    user_staking_account: account_key.to_string(),
+                                    slot,
+                                });
 
                                 // We need to update the subscriptions request to include the new UserStaking account (for deletion filtering)
                                 subscriptions_update_required = true;
                             }
                             UserStakingAccountUpdate::Modified(user_staking_account) => {
-                                log::info!(
-                                    "(pcu) UserStaking account modified: {:#?}",
-                                    account_key
+                                log_chatter(
+                                    "(pcu) UserStaking account modified",
+                                    format_args!("{}", account_key),
                                 );
-                                // Update the claim cache with the claim time of the oldest locked stake for the modified UserStaking account
+                                // Update the claim cache with the claim times of the stakes of the modified UserStaking account
                                 update_claim_cache_for_account(
+                                    state_manager,
                                     claim_cache,
                                     account_key,
                                     &user_staking_account,
                                 )
-                                .await;
+                                .await
+                                .map_err(backoff::Error::permanent)?;
                                 // Update the finalize locked stakes cache
                                 update_finalize_locked_stakes_cache_for_account(
                                     finalize_locked_stakes_cache,
                                     &account_key,
                                     &user_staking_account,
-                                )
-                                .await;
+                                );
+                                // The account changed, the actions abandoned on it get a new chance
+                                action_retries.write().await.reset_account(&account_key);
+                                store_user_staking_accounts(
+                                    db,
+                                    &[(account_key, account_data.to_vec())],
+                                );
                             }
                             UserStakingAccountUpdate::MissingStakingType(_) => {
-                                log::info!(
-                                    "(pcu) UserStaking account missing staking type has been updated (did nothing): {:#?}",
-                                    account_key
+                                log_chatter(
+                                    "(pcu) UserStaking account missing staking type updated (did nothing)",
+                                    format_args!("{}", account_key),
                                 );
                             }
                             UserStakingAccountUpdate::Closed => {
                                 log::info!("(pcu) UserStaking account closed: {:#?}", account_key);
                                 // We need to remove the closed UserStaking account from the claim cache
-                                claim_cache.write().await.remove(&account_key);
+                                claim_cache.remove(&account_key);
+                                action_retries.write().await.reset_account(&account_key);
+                                forget_user_staking_account(db, &account_key);
+                                publish_event(KeeperEvent::AccountClosed {
+                                    user_staking_account: account_key.to_string(),
+                                    slot,
+                                });
                                 // We need to update the subscriptions request to remove the closed UserStaking account
                                 subscriptions_update_required = true;
                             }
@@ -162,8 +313,9 @@ where
                         // Updates the indexed UserStaking accounts map
                         let update = update_indexed_user_staking_accounts(
                             &account_key,
-                            &account_data,
+                            account_data,
                             indexed_user_staking_accounts,
+                            state_manager,
                         )
                         .await?;
 
@@ -177,12 +329,47 @@ where
                             UserStakingAccountUpdate::Closed
                             | UserStakingAccountUpdate::MissingStakingType(_) => {
                                 log::info!("(pc) UserStaking account closed: {:#?}", account_key);
+                                forget_user_staking_account(db, &account_key);
+                                publish_event(KeeperEvent::AccountClosed {
+                                    user_staking_account: account_key.to_string(),
+                                    slot,
+                                });
                                 // We need to update the subscriptions request to remove the closed UserStaking account
                                 subscriptions_update_required = true;
                             }
                         }
                     }
                 }
+                Some(UpdateOneof::Transaction(sut)) => {
+                    let transaction = sut.transaction.expect("Transaction should be defined");
+                    // Stakes of the users (stake_transactions filter) - a keeper transaction may match both filters
+                    if msg.filters.contains(&"stake_transactions".to_owned()) {
+                        learn_owners_from_transaction(&transaction);
+                    }
+                    // Transactions signed by the keeper (keeper_transactions filter)
+                    if msg.filters.contains(&"keeper_transactions".to_owned()) {
+                        let signature = Signature::try_from(transaction.signature.as_slice())
+                            .expect("valid signature");
+                        let (failed, receipt) = match transaction.meta {
+                            Some(meta) => (
+                                meta.err.is_some(),
+                                TransactionReceipt::from_stream_meta(&meta),
+                            ),
+                            None => (false, TransactionReceipt::default()),
+                        };
+                        confirm_transaction_from_stream(
+                            pending_transactions,
+                            &signature,
+                            failed,
+                            receipt,
+                        )
+                        .await;
+                    }
+                }
+                Some(UpdateOneof::Slot(sus)) => {
+                    last_stream_slot.fetch_max(sus.slot, Ordering::Relaxed);
+                    chain_clock.write().await.update(sus.slot);
+                }
                 Some(UpdateOneof::Ping(_)) => {
                     // This is necessary to keep load balancers that expect client pings alive. If your load balancer doesn't
                     // require periodic client pings then this is unnecessary
@@ -206,11 +393,14 @@ where
     // Update the subscriptions request if needed
     if subscriptions_update_required {
         log::info!("  <> Update subscriptions request");
-        let accounts_filter_map = generate_accounts_filter_map(indexed_user_staking_accounts).await;
-        let request = SubscribeRequest {
-            accounts: accounts_filter_map,
-            ..Default::default()
-        };
+        // The new request replaces the previous one, keep watching the keeper transactions
+        let request = generate_subscribe_request(
+            indexed_user_staking_accounts,
+            keeper_pubkey,
+            commitment,
+            None,
+        )
+        .await;
         subscribe_tx
             .send(request)
             .await
@@ -218,3 +408,36 @@ where
     }
     Ok(())
 }
+
+// Record the resolved round in the rounds history (not without DB, resolves role), then notify the downstream services
+async fn on_staking_round_resolved(
+    db: Option<&Db>,
+    staking_account_key: &Pubkey,
+    staking_type: StakingType,
+    round_index: u64,
+    resolved_staking_round: ResolvedStakingRound,
+    txn_signature: Option<&Signature>,
+) {
+    if let Some(db) = db {
+        if let Err(e) = db
+            .insert_resolved_staking_round(staking_account_key, &resolved_staking_round)
+            .await
+        {
+            log::warn!("   <> Failed to record resolved staking round: {:?}", e);
+        }
+    }
+
+    notify_round_resolved(
+        staking_account_key,
+        staking_type,
+        round_index,
+        resolved_staking_round.start_time,
+        resolved_staking_round.end_time,
+        resolved_staking_round.resolved_slot,
+        txn_signature,
+    );
+    publish_event(KeeperEvent::RoundResolved {
+        staking_account: staking_account_key.to_string(),
+        round: resolved_staking_round,
+    });
+}
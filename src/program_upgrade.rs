@@ -0,0 +1,95 @@
+use {
+    crate::rate_limiter::rpc_call,
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
+    solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcAccountInfoConfig},
+    solana_sdk::{bpf_loader_upgradeable::get_program_data_address, pubkey::Pubkey},
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+// UpgradeableLoaderState variant of the ProgramData accounts (bincode u32 tag)
+const PROGRAM_DATA_TAG: u32 = 3;
+// Tag and deployment slot - the rest of the account is the program itself, never fetched
+const PROGRAM_DATA_HEADER_LEN: usize = 12;
+
+// Slot of the last deployment of the Adrena program
+static PROGRAM_DEPLOY_SLOT: Mutex<Option<u64>> = Mutex::new(None);
+
+// Set when an upgrade is detected, the reconnection re-fetches all accounts and checks their layouts
+static RESYNC_PENDING: AtomicBool = AtomicBool::new(false);
+
+// The ProgramData account of the Adrena program, written by each deployment
+pub fn program_data_address() -> Pubkey {
+    get_program_data_address(&adrena_abi::ID)
+}
+
+// ProgramData account layout: tag (u32), deployment slot (u64), upgrade authority, then the program bytes
+fn deploy_slot(program_data: &[u8]) -> Option<u64> {
+    let tag = u32::from_le_bytes(program_data.get(0..4)?.try_into().ok()?);
+    if tag != PROGRAM_DATA_TAG {
+        return None;
+    }
+    Some(u64::from_le_bytes(
+        program_data
+            .get(4..PROGRAM_DATA_HEADER_LEN)?
+            .try_into()
+            .ok()?,
+    ))
+}
+
+pub async fn init_program_deploy_slot(rpc_client: &RpcClient) {
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        data_slice: Some(UiDataSliceConfig {
+            offset: 0,
+            length: PROGRAM_DATA_HEADER_LEN,
+        }),
+        ..Default::default()
+    };
+    let program_data = match rpc_call(|| {
+        rpc_client.get_account_with_config(&program_data_address(), config.clone())
+    })
+    .await
+    {
+        Ok(response) => response.value,
+        Err(e) => {
+            log::warn!(
+                "  <> Failed to fetch the Adrena ProgramData account: {:?}",
+                e
+            );
+            return;
+        }
+    };
+    let Some(slot) = program_data.and_then(|program_data| deploy_slot(&program_data.data)) else {
+        log::warn!("  <> Failed to decode the Adrena ProgramData account");
+        return;
+    };
+    log::info!("  <> Adrena program deployed at slot {}", slot);
+    *PROGRAM_DEPLOY_SLOT.lock().unwrap() = Some(slot);
+}
+
+// Called for each update of the ProgramData account - returns whether the program has been upgraded
+// (the authority changes also write the account, the deployment slot tells them apart)
+pub fn record_program_data_update(program_data: &[u8]) -> bool {
+    let Some(slot) = deploy_slot(program_data) else {
+        return false;
+    };
+    let previous_slot = PROGRAM_DEPLOY_SLOT.lock().unwrap().replace(slot);
+    if previous_slot == Some(slot) {
+        return false;
+    }
+
+    log::warn!(
+        "  <*> Adrena program upgraded (deployed at slot {}) - resyncing all the indexed accounts",
+        slot
+    );
+    RESYNC_PENDING.store(true, Ordering::Relaxed);
+    true
+}
+
+// Whether the indexes must be rebuilt from scratch (program upgraded since they were fetched)
+pub fn take_resync_pending() -> bool {
+    RESYNC_PENDING.swap(false, Ordering::Relaxed)
+}
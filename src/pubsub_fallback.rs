@@ -0,0 +1,200 @@
+use {
+    adrena_abi::{Discriminator, Staking, UserStaking, CORTEX_ID},
+    futures::{
+        channel::mpsc::{self, SendError},
+        sink,
+        stream::{self, BoxStream},
+        Sink, SinkExt, StreamExt,
+    },
+    solana_account_decoder::{UiAccount, UiAccountEncoding},
+    solana_client::{
+        nonblocking::pubsub_client::PubsubClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+        rpc_response::{Response, RpcKeyedAccount},
+    },
+    solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey},
+    std::str::FromStr,
+    yellowstone_grpc_proto::geyser::{
+        subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest, SubscribeUpdate,
+        SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdateSlot,
+    },
+};
+
+// Degraded mode used when the Geyser endpoint is down: the Staking/UserStaking accounts are watched through the
+// standard WebSocket programSubscribe (same discriminator filters), the Cortex account (protocol pause) through
+// accountSubscribe, and the notifications are converted to Geyser updates so that they go through the regular stream processing
+//
+// Limitations: UserStaking accounts closing are not notified (the closed account doesn't match the filters anymore),
+// and the keeper transactions are only confirmed by the RPC polling
+pub async fn subscribe_programs(
+    ws_endpoint: String,
+    commitment: CommitmentConfig,
+) -> Result<
+    (
+        impl Sink<SubscribeRequest, Error = SendError> + Send,
+        BoxStream<'static, Result<SubscribeUpdate, tonic::Status>>,
+    ),
+    backoff::Error<anyhow::Error>,
+> {
+    let pubsub_client = PubsubClient::new(&ws_endpoint)
+        .await
+        .map_err(|e| backoff::Error::transient(e.into()))?;
+    log::info!("  <> WebSocket PubSub client connected (degraded mode)");
+
+    let (update_tx, update_rx) = mpsc::unbounded();
+    // The subscriptions cannot be updated, the requests sent by the stream processing are dropped
+    let subscribe_tx = sink::drain().sink_map_err(|never| match never {});
+
+    // The PubSub streams borrow the client, a dedicated task owns it and forwards the notifications
+    tokio::spawn(async move {
+        let staking_subscription = pubsub_client
+            .program_subscribe(
+                &adrena_abi::ID,
+                Some(program_accounts_config(
+                    Staking::DISCRIMINATOR.to_vec(),
+                    commitment,
+                )),
+            )
+            .await;
+        let user_staking_subscription = pubsub_client
+            .program_subscribe(
+                &adrena_abi::ID,
+                Some(program_accounts_config(
+                    UserStaking::DISCRIMINATOR.to_vec(),
+                    commitment,
+                )),
+            )
+            .await;
+        let cortex_subscription = pubsub_client
+            .account_subscribe(
+                &CORTEX_ID,
+                Some(RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(commitment),
+                    ..Default::default()
+                }),
+            )
+            .await;
+        let slot_subscription = pubsub_client.slot_subscribe().await;
+
+        let (
+            Ok((staking_stream, _staking_unsubscribe)),
+            Ok((user_staking_stream, _user_staking_unsubscribe)),
+            Ok((cortex_stream, _cortex_unsubscribe)),
+            Ok((slot_stream, _slot_unsubscribe)),
+        ) = (
+            staking_subscription,
+            user_staking_subscription,
+            cortex_subscription,
+            slot_subscription,
+        )
+        else {
+            log::error!("  <> Failed to open the WebSocket PubSub subscriptions");
+            return;
+        };
+
+        let mut updates = stream::select_all([
+            staking_stream
+                .filter_map(|response| async move {
+                    to_account_update(response, "staking_create_update")
+                })
+                .boxed(),
+            user_staking_stream
+                .filter_map(|response| async move {
+                    to_account_update(response, "user_staking_create_update")
+                })
+                .boxed(),
+            cortex_stream
+                .filter_map(|response| async move {
+                    to_keyed_account_update(
+                        CORTEX_ID,
+                        response.value,
+                        response.context.slot,
+                        "cortex_update",
+                    )
+                })
+                .boxed(),
+            slot_stream
+                .map(|slot_info| SubscribeUpdate {
+                    filters: vec!["chain_head".to_owned()],
+                    update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                        slot: slot_info.slot,
+                        parent: Some(slot_info.parent),
+                        status: CommitmentLevel::Processed as i32,
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                })
+                .boxed(),
+        ]);
+
+        while let Some(update) = updates.next().await {
+            if update_tx.unbounded_send(Ok(update)).is_err() {
+                // The stream has been dropped (reconnecting)
+                break;
+            }
+        }
+        log::warn!("  <> WebSocket PubSub subscriptions closed");
+    });
+
+    Ok((subscribe_tx, update_rx.boxed()))
+}
+
+fn program_accounts_config(
+    discriminator: Vec<u8>,
+    commitment: CommitmentConfig,
+) -> RpcProgramAccountsConfig {
+    RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            discriminator,
+        ))]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(commitment),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn to_account_update(response: Response<RpcKeyedAccount>, filter: &str) -> Option<SubscribeUpdate> {
+    let pubkey = Pubkey::from_str(&response.value.pubkey).ok()?;
+    to_keyed_account_update(
+        pubkey,
+        response.value.account,
+        response.context.slot,
+        filter,
+    )
+}
+
+fn to_keyed_account_update(
+    pubkey: Pubkey,
+    account: UiAccount,
+    slot: u64,
+    filter: &str,
+) -> Option<SubscribeUpdate> {
+    let Some(account) = account.decode::<Account>() else {
+        log::warn!("   <> Failed to decode PubSub account {}", pubkey);
+        return None;
+    };
+
+    Some(SubscribeUpdate {
+        filters: vec![filter.to_owned()],
+        update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+            account: Some(SubscribeUpdateAccountInfo {
+                pubkey: pubkey.to_bytes().to_vec(),
+                lamports: account.lamports,
+                owner: account.owner.to_bytes().to_vec(),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                data: account.data,
+                ..Default::default()
+            }),
+            slot,
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,38 @@
+use {crate::metrics, prometheus::TEXT_FORMAT, std::time::Duration, tokio::time::interval};
+
+// Push the metrics to a Prometheus Pushgateway, for the deployments that can't be scraped (behind NAT)
+// Each push replaces the metrics of the group (job and instance), the last pushed values stay visible if the keeper dies
+pub async fn push_metrics(
+    pushgateway_url: String,
+    job: String,
+    instance: Option<String>,
+    push_interval: Duration,
+) {
+    let http_client = reqwest::Client::new();
+    let mut grouping_url = format!(
+        "{}/metrics/job/{}",
+        pushgateway_url.trim_end_matches('/'),
+        job
+    );
+    if let Some(instance) = instance {
+        grouping_url.push_str(&format!("/instance/{}", instance));
+    }
+
+    let mut push_ticker = interval(push_interval);
+    loop {
+        push_ticker.tick().await;
+        let result = http_client
+            .put(&grouping_url)
+            .header(reqwest::header::CONTENT_TYPE, TEXT_FORMAT)
+            .body(metrics::encode())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            log::warn!(
+                "  <> Failed to push the metrics to the Pushgateway: {:?}",
+                e
+            );
+        }
+    }
+}
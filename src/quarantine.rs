@@ -0,0 +1,248 @@
+use {
+    crate::{
+        action_retries::ActionId,
+        db::Db,
+        events::{publish_event, KeeperEvent},
+        health::record_db_outcome,
+        metrics::{ACCOUNTS_QUARANTINED, QUARANTINED_ACCOUNTS},
+        tx_tracker::KeeperAction,
+    },
+    serde::Serialize,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::HashMap,
+        sync::{LazyLock, Mutex, OnceLock, RwLock},
+    },
+};
+
+// Consecutive failed actions on a UserStaking account before it is quarantined, set once at startup (0 disables)
+static QUARANTINE_THRESHOLD: OnceLock<u32> = OnceLock::new();
+// Time after which a quarantined account is released on its own, set once at startup (0 keeps it until cleared)
+static QUARANTINE_EXPIRY_SECONDS: OnceLock<i64> = OnceLock::new();
+
+// Consecutive program errors of the claims and finalizations of each UserStaking account - unlike the action retries,
+// not reset when the account changes onchain, only when a transaction of the account lands
+static CONSECUTIVE_FAILURES: LazyLock<Mutex<HashMap<Pubkey, u32>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// UserStaking accounts skipped by the claims and finalizations, loaded from the DB at startup
+static QUARANTINED: LazyLock<RwLock<HashMap<Pubkey, QuarantinedAccount>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+// Accounts quarantined (Some) or released (None) since the last flush, waiting to be written to the DB (flushed by the core loop)
+static PENDING_QUARANTINED: LazyLock<Mutex<HashMap<Pubkey, Option<QuarantinedAccount>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedAccount {
+    pub failures: u32,
+    // The error of the failure that quarantined the account (bad ATA, program error...)
+    pub last_error: String,
+    pub quarantined_at: i64,
+}
+
+// A quarantined account as reported by the HTTP API
+#[derive(Debug, Serialize)]
+pub struct QuarantineEntry {
+    pub user_staking_account: String,
+    #[serde(flatten)]
+    pub account: QuarantinedAccount,
+}
+
+pub fn init_quarantine(threshold: u32, expiry_seconds: i64) {
+    QUARANTINE_THRESHOLD
+        .set(threshold)
+        .expect("Quarantine already initialized");
+    let _ = QUARANTINE_EXPIRY_SECONDS.set(expiry_seconds);
+}
+
+// An expired quarantine is released here, the account is picked by the next checks
+pub fn is_quarantined(user_staking_account_key: &Pubkey) -> bool {
+    let quarantined_at = match QUARANTINED.read().unwrap().get(user_staking_account_key) {
+        Some(account) => account.quarantined_at,
+        None => return false,
+    };
+    let expiry_seconds = *QUARANTINE_EXPIRY_SECONDS.get().unwrap_or(&0);
+    if expiry_seconds > 0 && chrono::Utc::now().timestamp() >= quarantined_at + expiry_seconds {
+        log::warn!(
+            "  <> Quarantine of UserStaking account {} expired",
+            user_staking_account_key
+        );
+        release_quarantine(user_staking_account_key);
+        return false;
+    }
+    true
+}
+
+pub fn quarantined_accounts() -> Vec<QuarantineEntry> {
+    let mut entries: Vec<QuarantineEntry> = QUARANTINED
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(key, account)| QuarantineEntry {
+            user_staking_account: key.to_string(),
+            account: account.clone(),
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.account.quarantined_at);
+    entries
+}
+
+// Only the actions done on behalf of the users count, the resolves and AUM updates are never quarantined
+fn is_user_action(action: KeeperAction) -> bool {
+    matches!(
+        action,
+        KeeperAction::ClaimStakes | KeeperAction::FinalizeLockedStake
+    )
+}
+
+fn clear_failures(user_staking_account_key: &Pubkey) {
+    CONSECUTIVE_FAILURES
+        .lock()
+        .unwrap()
+        .remove(user_staking_account_key);
+}
+
+// A transaction of the account landed (whatever its action): the failures counts of the account start over
+pub fn record_account_landed(user_staking_account_key: &Pubkey) {
+    clear_failures(user_staking_account_key);
+}
+
+// Only called for the program errors reported by the simulations (see action_retries.rs), the RPC, sending and DB
+// failures say nothing about the account
+pub fn record_account_failure(action_id: &ActionId, current_time: i64, error: &anyhow::Error) {
+    let threshold = *QUARANTINE_THRESHOLD.get().unwrap_or(&0);
+    let user_staking_account_key = &action_id.account;
+    if threshold == 0
+        || !is_user_action(action_id.action)
+        || is_quarantined(user_staking_account_key)
+    {
+        return;
+    }
+
+    let failures = {
+        let mut consecutive_failures = CONSECUTIVE_FAILURES.lock().unwrap();
+        let failures = consecutive_failures
+            .entry(*user_staking_account_key)
+            .or_insert(0);
+        *failures += 1;
+        *failures
+    };
+    if failures < threshold {
+        return;
+    }
+
+    clear_failures(user_staking_account_key);
+    let account = QuarantinedAccount {
+        failures,
+        last_error: error.to_string(),
+        quarantined_at: current_time,
+    };
+    log::error!(
+        "  <*> UserStaking account {} quarantined after {} consecutive failures, last error ({}): {}",
+        user_staking_account_key,
+        failures,
+        action_id.action.as_str(),
+        account.last_error
+    );
+    ACCOUNTS_QUARANTINED.inc();
+    publish_event(KeeperEvent::AccountQuarantined {
+        user_staking_account: user_staking_account_key.to_string(),
+        failures,
+        last_error: account.last_error.clone(),
+    });
+    PENDING_QUARANTINED
+        .lock()
+        .unwrap()
+        .insert(*user_staking_account_key, Some(account.clone()));
+    let mut quarantined = QUARANTINED.write().unwrap();
+    quarantined.insert(*user_staking_account_key, account);
+    QUARANTINED_ACCOUNTS.set(quarantined.len() as i64);
+}
+
+// Clear the quarantine of an account (support, once the underlying issue is fixed) - false if it wasn't quarantined
+// Its failures counts start over as well
+pub fn release_quarantine(user_staking_account_key: &Pubkey) -> bool {
+    clear_failures(user_staking_account_key);
+    let released = {
+        let mut quarantined = QUARANTINED.write().unwrap();
+        let released = quarantined.remove(user_staking_account_key).is_some();
+        QUARANTINED_ACCOUNTS.set(quarantined.len() as i64);
+        released
+    };
+    if released {
+        log::info!(
+            "  <> UserStaking account {} released from quarantine",
+            user_staking_account_key
+        );
+        PENDING_QUARANTINED
+            .lock()
+            .unwrap()
+            .insert(*user_staking_account_key, None);
+    }
+    released
+}
+
+// Write the quarantined and released accounts to the DB - kept for the next flush if the write fails
+pub async fn flush_quarantine(db: &Db) {
+    let pending = std::mem::take(&mut *PENDING_QUARANTINED.lock().unwrap());
+    if pending.is_empty() {
+        return;
+    }
+
+    let quarantined: Vec<(Pubkey, QuarantinedAccount)> = pending
+        .iter()
+        .filter_map(|(key, account)| Some((*key, account.clone()?)))
+        .collect();
+    let released: Vec<Pubkey> = pending
+        .iter()
+        .filter(|(_, account)| account.is_none())
+        .map(|(key, _)| *key)
+        .collect();
+    let mut result = Ok(());
+    if !quarantined.is_empty() {
+        result = db.upsert_quarantined_accounts(&quarantined).await;
+    }
+    if result.is_ok() && !released.is_empty() {
+        result = db.delete_quarantined_accounts(&released).await;
+    }
+    record_db_outcome(result.is_ok());
+    if let Err(e) = result {
+        log::warn!("   <> Failed to record the quarantined accounts: {:?}", e);
+        // The changes made since take precedence
+        let mut pending_quarantined = PENDING_QUARANTINED.lock().unwrap();
+        for (key, account) in pending {
+            pending_quarantined.entry(key).or_insert(account);
+        }
+    }
+}
+
+// Reload the quarantined accounts from the DB, so they stay skipped across restarts
+pub async fn load_quarantine(db: &Db) {
+    let accounts = db.load_quarantined_accounts().await;
+    record_db_outcome(accounts.is_ok());
+    match accounts {
+        Ok(mut accounts) => {
+            // Apply the changes not written yet, the DB doesn't have them
+            for (key, account) in PENDING_QUARANTINED.lock().unwrap().iter() {
+                match account {
+                    Some(account) => accounts.insert(*key, account.clone()),
+                    None => accounts.remove(key),
+                };
+            }
+            let quarantined_count = accounts.len();
+            *QUARANTINED.write().unwrap() = accounts;
+            QUARANTINED_ACCOUNTS.set(quarantined_count as i64);
+            if quarantined_count > 0 {
+                log::warn!(
+                    "  <*> {} UserStaking accounts quarantined (see /quarantine)",
+                    quarantined_count
+                );
+            }
+        }
+        Err(e) => log::warn!(
+            "   <> Failed to load the quarantined accounts, keeping the previous ones: {:?}",
+            e
+        ),
+    }
+}
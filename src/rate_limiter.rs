@@ -0,0 +1,150 @@
+use {
+    crate::{health::record_rpc_outcome, metrics::RPC_RATE_LIMITED},
+    rand::{thread_rng, Rng},
+    std::{
+        fmt::Debug,
+        future::Future,
+        sync::{LazyLock, OnceLock},
+        time::Duration,
+    },
+    tokio::{
+        sync::Mutex,
+        time::{sleep, sleep_until, Instant},
+    },
+};
+
+// Global token bucket shared by every RPC call (GPA scans, simulations, sends, priority fee fetches)
+// Initialized once at startup, if never initialized (or QPS set to 0) calls are not throttled
+static RPC_RATE_LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+// Calls rejected by the provider rate limit (HTTP 429) are retried after a jittered exponential delay, during which all RPC calls wait
+// The RPC client already retries the 429s a few times on its own (honoring Retry-After), the error only surfaces once these are exhausted
+const RATE_LIMITED_MAX_RETRIES: u32 = 4;
+const RATE_LIMITED_BASE_DELAY: Duration = Duration::from_secs(1);
+const RATE_LIMITED_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// Until when the RPC calls wait after a rate limited call
+static RATE_LIMITED_UNTIL: LazyLock<std::sync::Mutex<Option<Instant>>> =
+    LazyLock::new(|| std::sync::Mutex::new(None));
+
+pub struct RateLimiter {
+    qps: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(qps: u32, burst: u32) -> Self {
+        let burst = burst.max(1) as f64;
+        Self {
+            qps: qps as f64,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until a token is available and consume it
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.qps).min(self.burst);
+                bucket.last_refill = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.qps)
+            };
+            sleep(wait).await;
+        }
+    }
+}
+
+pub fn init_rpc_rate_limiter(qps: u32, burst: u32) {
+    if qps == 0 {
+        log::info!("  <> RPC rate limiter disabled");
+        return;
+    }
+    if RPC_RATE_LIMITER.set(RateLimiter::new(qps, burst)).is_err() {
+        log::warn!("  <> RPC rate limiter already initialized");
+        return;
+    }
+    log::info!(
+        "  <> RPC rate limiter initialized: {} QPS (burst {})",
+        qps,
+        burst
+    );
+}
+
+/// To be awaited before each RPC call
+pub async fn throttle_rpc() {
+    let rate_limited_until = *RATE_LIMITED_UNTIL.lock().unwrap();
+    if let Some(rate_limited_until) = rate_limited_until {
+        sleep_until(rate_limited_until).await;
+    }
+    if let Some(rate_limiter) = RPC_RATE_LIMITER.get() {
+        rate_limiter.acquire().await;
+    }
+}
+
+/// Throttle then run the RPC call, retrying it while the provider rate limits the keeper
+pub async fn rpc_call<T, E, F, Fut>(mut call: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Debug,
+{
+    let mut retries = 0;
+    loop {
+        throttle_rpc().await;
+        match call().await {
+            Err(e) if retries < RATE_LIMITED_MAX_RETRIES && is_rate_limited_error(&e) => {
+                retries += 1;
+                RPC_RATE_LIMITED.inc();
+                let delay = rate_limited_delay(retries);
+                log::warn!(
+                    "  <> RPC rate limited - pausing RPC calls for {:?} (retry {}/{})",
+                    delay,
+                    retries,
+                    RATE_LIMITED_MAX_RETRIES
+                );
+                pause_rpc_calls(delay);
+            }
+            result => {
+                record_rpc_outcome(result.is_ok());
+                return result;
+            }
+        }
+    }
+}
+
+pub fn is_rate_limited_error(error: &impl Debug) -> bool {
+    let error = format!("{:?}", error).to_lowercase();
+    error.contains("too many requests") || error.contains("rate limit")
+}
+
+// Exponential delay, jittered (+/- 50%) so the callers don't retry all at once
+fn rate_limited_delay(retries: u32) -> Duration {
+    let delay = RATE_LIMITED_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(retries - 1))
+        .min(RATE_LIMITED_MAX_DELAY);
+    delay.mul_f64(thread_rng().gen_range(0.5..1.5))
+}
+
+fn pause_rpc_calls(delay: Duration) {
+    let mut rate_limited_until = RATE_LIMITED_UNTIL.lock().unwrap();
+    let until = Instant::now() + delay;
+    *rate_limited_until = Some(rate_limited_until.map_or(until, |current| current.max(until)));
+}
@@ -0,0 +1,79 @@
+use {
+    crate::metrics::STAKING_REWARD_PER_TOKEN_YEARLY,
+    adrena_abi::{get_staking_pda, Staking, ADX_MINT, ALP_MINT},
+    serde::Serialize,
+    solana_sdk::pubkey::Pubkey,
+};
+
+// Onchain round rates are expressed with 9 decimals (reward native units per staked native unit)
+pub const RATE_DECIMALS: i32 = 9;
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 3600.0;
+
+// Yearly rewards per staked token, extrapolated from the last resolved round
+// The reward token is USDC and the LM reward token is ADX, staked ADX/ALP and rewards all have 6 decimals
+// Not APRs: in reward token per staked token, turning them into a % needs the prices of the staked and reward tokens
+#[derive(Debug, Clone, Serialize)]
+pub struct StakingRewardRates {
+    pub staking: &'static str,
+    pub round_start_time: i64,
+    pub round_duration_seconds: i64,
+    pub total_stake: u64,
+    pub reward_per_token_yearly: f64,
+    pub lm_reward_per_token_yearly: f64,
+}
+
+pub fn staking_name(staking_account_key: &Pubkey) -> Option<&'static str> {
+    if *staking_account_key == get_staking_pda(&ADX_MINT).0 {
+        Some("adx")
+    } else if *staking_account_key == get_staking_pda(&ALP_MINT).0 {
+        Some("alp")
+    } else {
+        None
+    }
+}
+
+pub fn compute_staking_reward_rates(
+    staking_account_key: &Pubkey,
+    staking_account: &Staking,
+) -> Option<StakingRewardRates> {
+    let staking = staking_name(staking_account_key)?;
+
+    let last_resolved_round = staking_account
+        .resolved_staking_rounds
+        .iter()
+        .filter(|round| round.start_time != 0)
+        .max_by_key(|round| round.start_time)?;
+    // The current round starts when the last one is resolved
+    let round_duration_seconds =
+        staking_account.current_staking_round.start_time - last_resolved_round.start_time;
+    if round_duration_seconds <= 0 {
+        return None;
+    }
+
+    let rounds_per_year = SECONDS_PER_YEAR / round_duration_seconds as f64;
+    let rate_to_yearly = |rate: u64| rate as f64 / 10f64.powi(RATE_DECIMALS) * rounds_per_year;
+
+    Some(StakingRewardRates {
+        staking,
+        round_start_time: last_resolved_round.start_time,
+        round_duration_seconds,
+        total_stake: last_resolved_round.total_stake,
+        reward_per_token_yearly: rate_to_yearly(last_resolved_round.rate),
+        lm_reward_per_token_yearly: rate_to_yearly(last_resolved_round.lm_rate),
+    })
+}
+
+// Called each time a Staking account is indexed or modified
+pub fn update_staking_reward_rates_metrics(
+    staking_account_key: &Pubkey,
+    staking_account: &Staking,
+) {
+    if let Some(rates) = compute_staking_reward_rates(staking_account_key, staking_account) {
+        STAKING_REWARD_PER_TOKEN_YEARLY
+            .with_label_values(&[rates.staking, "reward"])
+            .set(rates.reward_per_token_yearly);
+        STAKING_REWARD_PER_TOKEN_YEARLY
+            .with_label_values(&[rates.staking, "lm_reward"])
+            .set(rates.lm_reward_per_token_yearly);
+    }
+}
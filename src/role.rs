@@ -0,0 +1,35 @@
+use std::sync::OnceLock;
+
+// The workload can be split across instances: the claims need the DB (UserStaking account -> owner) and the UserStaking
+// index, the round resolution only needs the Staking accounts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Role {
+    // Claims, finalizations, round resolution and AUM updates
+    #[default]
+    All,
+    // Claims and finalizations of the UserStaking accounts
+    Claims,
+    // Round resolution and AUM updates, without DB
+    Resolves,
+}
+
+impl Role {
+    pub fn handles_claims(self) -> bool {
+        self != Role::Resolves
+    }
+
+    pub fn handles_resolves(self) -> bool {
+        self != Role::Claims
+    }
+}
+
+// Set once at startup
+static ROLE: OnceLock<Role> = OnceLock::new();
+
+pub fn init_role(role: Role) {
+    let _ = ROLE.set(role);
+}
+
+pub fn current_role() -> Role {
+    ROLE.get().copied().unwrap_or_default()
+}
@@ -0,0 +1,37 @@
+use {
+    std::{future::pending, time::Duration},
+    tokio::time::{interval, sleep, Interval},
+};
+
+// When a periodic task runs: a fixed interval, or a cron expression for predictable onchain activity windows
+pub enum TaskSchedule {
+    Interval(Interval),
+    // Evaluated in UTC
+    Cron(Box<cron::Schedule>),
+}
+
+impl TaskSchedule {
+    pub fn new(period: Duration, cron_schedule: Option<cron::Schedule>) -> Self {
+        match cron_schedule {
+            Some(cron_schedule) => TaskSchedule::Cron(Box::new(cron_schedule)),
+            None => TaskSchedule::Interval(interval(period)),
+        }
+    }
+
+    // Completes when the task is due - cancel safe (used in the core loop select)
+    pub async fn tick(&mut self) {
+        match self {
+            TaskSchedule::Interval(interval) => {
+                interval.tick().await;
+            }
+            TaskSchedule::Cron(cron_schedule) => {
+                let Some(next_run) = cron_schedule.upcoming(chrono::Utc).next() else {
+                    // The expression has no future occurrence, the task never runs again
+                    return pending().await;
+                };
+                let delay = (next_run - chrono::Utc::now()).to_std().unwrap_or_default();
+                sleep(delay).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use {
+    crate::owner_cache::learn_owner,
+    adrena_abi::{
+        get_staking_pda,
+        instruction::{AddLiquidStake, AddLockedStake},
+        Discriminator, ADX_MINT, ALP_MINT,
+    },
+    solana_sdk::pubkey::Pubkey,
+    yellowstone_grpc_proto::prelude::SubscribeUpdateTransactionInfo,
+};
+
+// Staking transactions of the users (stake_transactions filter): the UserStaking accounts are created by their first stake,
+// the owner signs it - learning it here closes the race with the indexer populating the DB before the first claim is due
+pub fn learn_owners_from_transaction(transaction_info: &SubscribeUpdateTransactionInfo) {
+    let Some(meta) = transaction_info.meta.as_ref() else {
+        return;
+    };
+    // The failed ones didn't create nor fund anything
+    if meta.err.is_some() {
+        return;
+    }
+    let Some(message) = transaction_info
+        .transaction
+        .as_ref()
+        .and_then(|transaction| transaction.message.as_ref())
+    else {
+        return;
+    };
+    let Some(header) = message.header.as_ref() else {
+        return;
+    };
+
+    // Static keys first, then the ones loaded from the address lookup tables (same order as the instructions account indexes)
+    let account_keys: Vec<Pubkey> = message
+        .account_keys
+        .iter()
+        .chain(meta.loaded_writable_addresses.iter())
+        .chain(meta.loaded_readonly_addresses.iter())
+        .filter_map(|key| Pubkey::try_from(key.as_slice()).ok())
+        .collect();
+    let signers =
+        &account_keys[..(header.num_required_signatures as usize).min(account_keys.len())];
+    let staking_account_keys = [get_staking_pda(&ADX_MINT).0, get_staking_pda(&ALP_MINT).0];
+
+    // Top level instructions only, the stakes done through another program (CPI) are left to the indexer
+    for instruction in message.instructions.iter() {
+        if account_keys.get(instruction.program_id_index as usize) != Some(&adrena_abi::ID) {
+            continue;
+        }
+        let is_stake = instruction
+            .data
+            .starts_with(&AddLiquidStake::DISCRIMINATOR[..])
+            || instruction
+                .data
+                .starts_with(&AddLockedStake::DISCRIMINATOR[..]);
+        if !is_stake {
+            continue;
+        }
+
+        let instruction_accounts: Vec<&Pubkey> = instruction
+            .accounts
+            .iter()
+            .filter_map(|index| account_keys.get(*index as usize))
+            .collect();
+        // Rather than relying on the accounts order of the instruction, find the signer whose UserStaking PDA it uses
+        for owner in signers.iter() {
+            for staking_account_key in staking_account_keys.iter() {
+                let user_staking_account_key = get_user_staking_pda(owner, staking_account_key);
+                if instruction_accounts.contains(&staking_account_key)
+                    && instruction_accounts.contains(&&user_staking_account_key)
+                {
+                    learn_owner(&user_staking_account_key, owner);
+                }
+            }
+        }
+    }
+}
+
+// Seeds of the UserStaking accounts in the Adrena program
+pub fn get_user_staking_pda(owner: &Pubkey, staking_account_key: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"user_staking",
+            owner.as_ref(),
+            staking_account_key.as_ref(),
+        ],
+        &adrena_abi::ID,
+    )
+    .0
+}
@@ -0,0 +1,74 @@
+use {
+    crate::{
+        metrics::{staking_label, STAKED_AMOUNT},
+        IndexedUserStakingAccountsThreadSafe,
+    },
+    adrena_abi::{StakingType, UserStaking, ADX_MINT, ALP_MINT},
+};
+
+// (staking, locked amount, liquid amount) of a UserStaking account - the finalized locked stakes are left out
+fn staked_amounts(user_staking_account: &UserStaking) -> (&'static str, u64, u64) {
+    let staked_token_mint = match user_staking_account.get_staking_type() {
+        StakingType::LM => ADX_MINT,
+        StakingType::LP => ALP_MINT,
+    };
+    let locked_amount = user_staking_account
+        .locked_stakes
+        .iter()
+        .filter(|locked_stake| locked_stake.resolved == 0)
+        .map(|locked_stake| locked_stake.amount)
+        .sum();
+    (
+        staking_label(&staked_token_mint),
+        locked_amount,
+        user_staking_account.liquid_stake.amount,
+    )
+}
+
+// A UserStaking account was created (previous None), modified or closed (current None) in the index: the staked
+// amounts gauges move by the difference, without going through the whole index
+pub fn update_staked_amounts(previous: Option<&UserStaking>, current: Option<&UserStaking>) {
+    if let Some(previous) = previous {
+        let (staking, locked_amount, liquid_amount) = staked_amounts(previous);
+        STAKED_AMOUNT
+            .with_label_values(&[staking, "locked"])
+            .sub(locked_amount as i64);
+        STAKED_AMOUNT
+            .with_label_values(&[staking, "liquid"])
+            .sub(liquid_amount as i64);
+    }
+    if let Some(current) = current {
+        let (staking, locked_amount, liquid_amount) = staked_amounts(current);
+        STAKED_AMOUNT
+            .with_label_values(&[staking, "locked"])
+            .add(locked_amount as i64);
+        STAKED_AMOUNT
+            .with_label_values(&[staking, "liquid"])
+            .add(liquid_amount as i64);
+    }
+}
+
+// Sum the staked amounts of the whole index, after it was (re)built from the RPC, the state snapshot or the DB
+pub fn compute_staked_amounts(
+    indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+) {
+    // One staking side per shard of the index
+    for (staking_type, staked_token_mint) in
+        [(StakingType::LM, ADX_MINT), (StakingType::LP, ALP_MINT)]
+    {
+        let (mut locked_total, mut liquid_total) = (0u64, 0u64);
+        for entry in indexed_user_staking_accounts.shard(staking_type).iter() {
+            let (_, locked_amount, liquid_amount) = staked_amounts(entry.value());
+            locked_total += locked_amount;
+            liquid_total += liquid_amount;
+        }
+
+        let staking = staking_label(&staked_token_mint);
+        STAKED_AMOUNT
+            .with_label_values(&[staking, "locked"])
+            .set(locked_total as i64);
+        STAKED_AMOUNT
+            .with_label_values(&[staking, "liquid"])
+            .set(liquid_total as i64);
+    }
+}
@@ -0,0 +1,382 @@
+use {
+    adrena_abi::{
+        get_staking_pda, Custody, Staking, StakingType, ADX_MINT, ALP_MINT,
+        ROUND_MIN_DURATION_SECONDS,
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        cmp::Reverse,
+        collections::{BinaryHeap, HashMap, HashSet},
+    },
+    tokio::sync::{mpsc, oneshot},
+};
+
+// Indexes and caches owned by the state manager task: the keeper tasks (stream processing, resolves, claims, API,
+// monitoring) no longer take locks on them, they send commands applied one at a time - no lock ordering to get right,
+// and the state transitions below only depend on the sequence of commands
+#[derive(Debug, Default)]
+pub struct KeeperState {
+    // The indexed Staking accounts (the top level ADX and ALP staking "config" accounts)
+    pub staking_accounts: HashMap<Pubkey, Staking>,
+    // The indexed custodies - not directly observed, needed for the AUM update instructions
+    pub custodies: HashMap<Pubkey, Custody>,
+    // UserStaking accounts without staking type defined yet - promoted to the index as soon as an update sets it
+    pub user_staking_accounts_missing_staking_type: HashSet<Pubkey>,
+    // Time of the next resolve of each Staking account - only modified through schedule_resolve / reschedule_resolve
+    pub next_resolve_times: HashMap<Pubkey, i64>,
+    // The same resolve times, soonest first - the entries superseded by a later schedule are dropped when popped
+    resolve_queue: BinaryHeap<Reverse<(i64, Pubkey)>>,
+    // Earliest time the claim of each queued UserStaking account is checked (jitter included) - only modified through
+    // schedule_claim / pop_due_claims / clear_claims
+    pub next_claim_times: HashMap<Pubkey, i64>,
+    // The same claim times, soonest first - the entries superseded by an earlier schedule are dropped when popped
+    claim_queue: BinaryHeap<Reverse<(i64, Pubkey)>>,
+}
+
+impl KeeperState {
+    pub fn schedule_resolve(&mut self, staking_account_key: Pubkey, next_resolve_time: i64) {
+        self.next_resolve_times
+            .insert(staking_account_key, next_resolve_time);
+        self.resolve_queue
+            .push(Reverse((next_resolve_time, staking_account_key)));
+    }
+
+    // Move the next resolve of a Staking account, unless a Staking account update rescheduled it since it was read at
+    // scheduled_time (the update knows better) - returns whether it was moved
+    pub fn reschedule_resolve(
+        &mut self,
+        staking_account_key: &Pubkey,
+        scheduled_time: i64,
+        next_resolve_time: i64,
+    ) -> bool {
+        match self.next_resolve_times.get_mut(staking_account_key) {
+            Some(current_time) if *current_time == scheduled_time => {
+                *current_time = next_resolve_time;
+                self.resolve_queue
+                    .push(Reverse((next_resolve_time, *staking_account_key)));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Take the resolves due at current_time out of the queue, with the end time of the current round of their Staking
+    // account (if indexed) - the caller puts them back with reschedule_resolve, whatever the outcome
+    pub fn pop_due_resolves(&mut self, current_time: i64) -> Vec<(Pubkey, i64, Option<i64>)> {
+        let mut due_resolves: Vec<(Pubkey, i64, Option<i64>)> = vec![];
+        while let Some(Reverse((next_resolve_time, staking_account_key))) =
+            self.resolve_queue.peek().copied()
+        {
+            if next_resolve_time > current_time {
+                break;
+            }
+            self.resolve_queue.pop();
+            // Superseded by a later schedule (or popped twice)
+            if self.next_resolve_times.get(&staking_account_key) != Some(&next_resolve_time)
+                || due_resolves
+                    .iter()
+                    .any(|(key, _, _)| *key == staking_account_key)
+            {
+                continue;
+            }
+            due_resolves.push((
+                staking_account_key,
+                next_resolve_time,
+                self.round_end_time(&staking_account_key),
+            ));
+        }
+        due_resolves
+    }
+
+    // Check the claim of a UserStaking account at claim_time, unless it's already checked sooner (the check queues the
+    // due time again if it's too early) - the account updates leave the queue as is when their due time doesn't change
+    pub fn schedule_claim(&mut self, user_staking_account_key: Pubkey, claim_time: i64) {
+        if self
+            .next_claim_times
+            .get(&user_staking_account_key)
+            .is_some_and(|next_claim_time| *next_claim_time <= claim_time)
+        {
+            return;
+        }
+        self.next_claim_times
+            .insert(user_staking_account_key, claim_time);
+        self.claim_queue
+            .push(Reverse((claim_time, user_staking_account_key)));
+    }
+
+    // Take the UserStaking accounts queued up to current_time out of the queue, each once - the caller queues them again
+    // if needed
+    pub fn pop_due_claims(&mut self, current_time: i64) -> Vec<Pubkey> {
+        let mut due_claims: Vec<Pubkey> = vec![];
+        while let Some(Reverse((claim_time, user_staking_account_key))) =
+            self.claim_queue.peek().copied()
+        {
+            if claim_time > current_time {
+                break;
+            }
+            self.claim_queue.pop();
+            // Superseded by an earlier schedule (already popped)
+            if self.next_claim_times.get(&user_staking_account_key) != Some(&claim_time) {
+                continue;
+            }
+            self.next_claim_times.remove(&user_staking_account_key);
+            due_claims.push(user_staking_account_key);
+        }
+        due_claims
+    }
+
+    // Entries of the claim queue, the superseded ones included
+    pub fn claim_queue_len(&self) -> usize {
+        self.claim_queue.len()
+    }
+
+    // The claim queue is rebuilt from the claim cache
+    pub fn clear_claims(&mut self) {
+        self.next_claim_times.clear();
+        self.claim_queue.clear();
+    }
+
+    // The Staking account of a staking side (LM: ADX, LP: ALP), if indexed
+    pub fn staking_account(&self, staking_type: StakingType) -> Option<Staking> {
+        let staked_token_mint = match staking_type {
+            StakingType::LM => ADX_MINT,
+            StakingType::LP => ALP_MINT,
+        };
+        self.staking_accounts
+            .get(&get_staking_pda(&staked_token_mint).0)
+            .copied()
+    }
+
+    // End of the current round of a Staking account (the earliest it can be resolved)
+    pub fn round_end_time(&self, staking_account_key: &Pubkey) -> Option<i64> {
+        self.staking_accounts
+            .get(staking_account_key)
+            .map(|staking_account| {
+                staking_account.current_staking_round.start_time + ROUND_MIN_DURATION_SECONDS
+            })
+    }
+
+    // Returns the number of UserStaking accounts missing their staking type
+    pub fn add_missing_staking_type(&mut self, user_staking_account_key: &Pubkey) -> usize {
+        self.user_staking_accounts_missing_staking_type
+            .insert(*user_staking_account_key);
+        self.user_staking_accounts_missing_staking_type.len()
+    }
+
+    // Returns whether the account was missing its staking type, and the number of accounts still missing it
+    pub fn remove_missing_staking_type(
+        &mut self,
+        user_staking_account_key: &Pubkey,
+    ) -> (bool, usize) {
+        let removed = self
+            .user_staking_accounts_missing_staking_type
+            .remove(user_staking_account_key);
+        (
+            removed,
+            self.user_staking_accounts_missing_staking_type.len(),
+        )
+    }
+}
+
+type StateCommand = Box<dyn FnOnce(&mut KeeperState) + Send>;
+
+// Handle to the state manager task, cloned by each task accessing the state
+#[derive(Clone)]
+pub struct StateManager {
+    commands: mpsc::UnboundedSender<StateCommand>,
+}
+
+impl StateManager {
+    // Spawn the task owning the state - it runs until the last handle is dropped
+    pub fn spawn() -> Self {
+        let (commands, mut receiver) = mpsc::unbounded_channel::<StateCommand>();
+        tokio::spawn(async move {
+            let mut state = KeeperState::default();
+            while let Some(command) = receiver.recv().await {
+                command(&mut state);
+            }
+        });
+        StateManager { commands }
+    }
+
+    // Apply the command to the state (after the ones already sent by the other tasks) and return its result
+    // The commands are plain functions: nothing is awaited while the state is borrowed
+    // Fails once the task is gone (a command panicked): the state is lost, the callers give up instead of panicking too
+    pub async fn call<R: Send + 'static>(
+        &self,
+        command: impl FnOnce(&mut KeeperState) -> R + Send + 'static,
+    ) -> anyhow::Result<R> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.commands
+            .send(Box::new(move |state| {
+                let _ = reply_tx.send(command(state));
+            }))
+            .map_err(|_| anyhow::anyhow!("State manager task stopped"))?;
+        reply_rx
+            .await
+            .map_err(|_| anyhow::anyhow!("State manager task stopped"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pop_due_resolves_returns_the_due_resolves_soonest_first() {
+        let mut state = KeeperState::default();
+        let (first, second, later) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        state.schedule_resolve(second, 200);
+        state.schedule_resolve(later, 400);
+        state.schedule_resolve(first, 100);
+
+        let due_resolves = state.pop_due_resolves(300);
+        assert_eq!(due_resolves, vec![(first, 100, None), (second, 200, None)]);
+        // Popped until rescheduled
+        assert!(state.pop_due_resolves(300).is_empty());
+        assert_eq!(state.pop_due_resolves(400), vec![(later, 400, None)]);
+    }
+
+    #[test]
+    fn pop_due_resolves_skips_the_superseded_schedules() {
+        let mut state = KeeperState::default();
+        let staking_account_key = Pubkey::new_unique();
+        state.schedule_resolve(staking_account_key, 100);
+        // A Staking account update moved it later
+        state.schedule_resolve(staking_account_key, 500);
+        // Then earlier again: a single entry is returned for the account
+        state.schedule_resolve(staking_account_key, 200);
+        state.schedule_resolve(staking_account_key, 200);
+
+        assert!(state.pop_due_resolves(150).is_empty());
+        assert_eq!(
+            state.pop_due_resolves(600),
+            vec![(staking_account_key, 200, None)]
+        );
+        assert!(state.pop_due_resolves(600).is_empty());
+    }
+
+    #[test]
+    fn reschedule_resolve_puts_the_resolve_back_in_the_queue() {
+        let mut state = KeeperState::default();
+        let staking_account_key = Pubkey::new_unique();
+        state.schedule_resolve(staking_account_key, 100);
+        assert_eq!(
+            state.pop_due_resolves(100),
+            vec![(staking_account_key, 100, None)]
+        );
+
+        assert!(state.reschedule_resolve(&staking_account_key, 100, 300));
+        assert_eq!(
+            state.next_resolve_times.get(&staking_account_key),
+            Some(&300)
+        );
+        assert!(state.pop_due_resolves(200).is_empty());
+        assert_eq!(
+            state.pop_due_resolves(300),
+            vec![(staking_account_key, 300, None)]
+        );
+    }
+
+    #[test]
+    fn reschedule_resolve_keeps_the_schedule_of_a_later_update() {
+        let mut state = KeeperState::default();
+        let staking_account_key = Pubkey::new_unique();
+        state.schedule_resolve(staking_account_key, 100);
+        state.pop_due_resolves(100);
+        // The Staking account update arrived while the resolve was being sent
+        state.schedule_resolve(staking_account_key, 1_000);
+
+        assert!(!state.reschedule_resolve(&staking_account_key, 100, 300));
+        assert_eq!(
+            state.next_resolve_times.get(&staking_account_key),
+            Some(&1_000)
+        );
+        assert!(state.pop_due_resolves(300).is_empty());
+        assert_eq!(
+            state.pop_due_resolves(1_000),
+            vec![(staking_account_key, 1_000, None)]
+        );
+    }
+
+    #[test]
+    fn reschedule_resolve_ignores_an_unknown_staking_account() {
+        let mut state = KeeperState::default();
+        assert!(!state.reschedule_resolve(&Pubkey::new_unique(), 100, 300));
+        assert!(state.next_resolve_times.is_empty());
+        assert!(state.pop_due_resolves(i64::MAX).is_empty());
+    }
+
+    #[test]
+    fn schedule_claim_keeps_a_single_entry_per_unchanged_due_time() {
+        let mut state = KeeperState::default();
+        let user_staking_account_key = Pubkey::new_unique();
+        // Every UserStaking account update schedules its due time again
+        for _ in 0..100 {
+            state.schedule_claim(user_staking_account_key, 500);
+        }
+        assert_eq!(state.claim_queue_len(), 1);
+
+        // A later schedule waits for the earlier check, an earlier one supersedes it
+        state.schedule_claim(user_staking_account_key, 800);
+        state.schedule_claim(user_staking_account_key, 300);
+        assert_eq!(state.claim_queue_len(), 2);
+        assert!(state.pop_due_claims(200).is_empty());
+        assert_eq!(state.pop_due_claims(600), vec![user_staking_account_key]);
+        assert_eq!(state.claim_queue_len(), 0);
+        assert!(state.next_claim_times.is_empty());
+    }
+
+    #[test]
+    fn pop_due_claims_returns_the_due_claims_soonest_first() {
+        let mut state = KeeperState::default();
+        let (first, second, later) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        state.schedule_claim(second, 200);
+        state.schedule_claim(later, 400);
+        state.schedule_claim(first, 100);
+
+        assert_eq!(state.pop_due_claims(300), vec![first, second]);
+        // Popped claims are queued again by their check
+        state.schedule_claim(first, 350);
+        assert_eq!(state.pop_due_claims(400), vec![first, later]);
+
+        state.schedule_claim(first, 500);
+        state.clear_claims();
+        assert!(state.pop_due_claims(i64::MAX).is_empty());
+    }
+
+    #[tokio::test]
+    async fn call_fails_once_the_state_manager_task_stopped() {
+        let state_manager = StateManager::spawn();
+        let staking_account_key = Pubkey::new_unique();
+        state_manager
+            .call(move |state| state.schedule_resolve(staking_account_key, 100))
+            .await
+            .unwrap();
+        assert_eq!(
+            state_manager
+                .call(|state| state.next_resolve_times.len())
+                .await
+                .unwrap(),
+            1
+        );
+
+        // A panicking command takes the task down
+        assert!(state_manager
+            .call(|_| -> usize { panic!("command failed") })
+            .await
+            .is_err());
+        assert!(state_manager
+            .call(|state| state.next_resolve_times.len())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,321 @@
+use {
+    crate::{
+        rate_limiter::throttle_rpc,
+        state_manager::StateManager,
+        update_caches::{
+            update_claim_cache, update_finalize_locked_stakes_cache,
+            update_staking_round_next_resolve_time_cache, ClaimTimes,
+        },
+        user_staking_index::UserStakingIndex,
+        user_staking_layout::decode_user_staking,
+        FinalizeLockedStakesCacheThreadSafe, IndexedUserStakingAccountsThreadSafe,
+        UserStakingClaimCacheThreadSafe,
+    },
+    adrena_abi::{AccountDeserialize, Discriminator, Staking, UserStaking},
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    dashmap::DashMap,
+    serde::{Deserialize, Serialize},
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
+    solana_client::{
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+    std::{
+        collections::{HashMap, HashSet},
+        str::FromStr,
+        sync::Arc,
+    },
+};
+
+// The stream replays the changes since the snapshot slot (from_slot), the Geyser endpoints only keep a few minutes of updates
+const MAX_STATE_SNAPSHOT_AGE_SECONDS: i64 = 600;
+// getMultipleAccounts limit
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+// Keeper state at a given slot: the raw Staking/UserStaking accounts data (base64) and the caches derived from them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    // Slot fetched right before the accounts, the snapshot contains all changes up to (at least) this slot
+    pub slot: u64,
+    pub created_at: i64,
+    pub staking_accounts: HashMap<String, String>,
+    pub user_staking_accounts: HashMap<String, String>,
+    pub claim_cache: HashMap<String, ClaimTimes>,
+    pub finalize_locked_stakes_cache: HashMap<String, HashMap<u64, i64>>,
+    pub staking_round_next_resolve_time_cache: HashMap<String, i64>,
+}
+
+// export-state subcommand: fetch the accounts, rebuild the indexes and caches like the keeper does at startup and dump them
+pub async fn export_state(
+    endpoint: String,
+    commitment: CommitmentConfig,
+    output_path: &str,
+) -> anyhow::Result<()> {
+    let rpc_client = RpcClient::new_with_commitment(endpoint, commitment);
+
+    throttle_rpc().await;
+    let slot = rpc_client.get_slot().await?;
+    log::info!("  <> Exporting keeper state at slot {}", slot);
+
+    let staking_accounts =
+        fetch_program_accounts_data(&rpc_client, Staking::DISCRIMINATOR, commitment).await?;
+    let user_staking_accounts =
+        fetch_program_accounts_data(&rpc_client, UserStaking::DISCRIMINATOR, commitment).await?;
+
+    let state_manager = StateManager::spawn();
+    let mut indexed_staking_accounts: HashMap<Pubkey, Staking> = HashMap::new();
+    for (key, data) in staking_accounts.iter() {
+        indexed_staking_accounts.insert(*key, Staking::try_deserialize(&mut &data[..])?);
+    }
+    state_manager
+        .call(move |state| state.staking_accounts.extend(indexed_staking_accounts))
+        .await?;
+    let indexed_user_staking_accounts: IndexedUserStakingAccountsThreadSafe =
+        Arc::new(UserStakingIndex::default());
+    for (key, data) in user_staking_accounts.iter() {
+        let user_staking_account = decode_user_staking(&data)?;
+        // Same as the keeper: the accounts without staking type are not indexed
+        if user_staking_account.staking_type != 0 {
+            indexed_user_staking_accounts.insert(*key, user_staking_account);
+        }
+    }
+
+    update_staking_round_next_resolve_time_cache(&state_manager).await?;
+    let claim_cache: UserStakingClaimCacheThreadSafe = Arc::new(DashMap::new());
+    update_claim_cache(&state_manager, &claim_cache, &indexed_user_staking_accounts).await?;
+    let finalize_locked_stakes_cache: FinalizeLockedStakesCacheThreadSafe =
+        Arc::new(DashMap::new());
+    update_finalize_locked_stakes_cache(
+        &finalize_locked_stakes_cache,
+        &indexed_user_staking_accounts,
+    );
+
+    let snapshot = StateSnapshot {
+        slot,
+        created_at: chrono::Utc::now().timestamp(),
+        staking_accounts: staking_accounts
+            .iter()
+            .map(|(key, data)| (key.to_string(), BASE64.encode(data)))
+            .collect(),
+        user_staking_accounts: user_staking_accounts
+            .iter()
+            .filter(|(key, _)| indexed_user_staking_accounts.contains_key(key))
+            .map(|(key, data)| (key.to_string(), BASE64.encode(data)))
+            .collect(),
+        claim_cache: claim_cache
+            .iter()
+            .map(|entry| (entry.key().to_string(), *entry.value()))
+            .collect(),
+        finalize_locked_stakes_cache: finalize_locked_stakes_cache
+            .iter()
+            .map(|entry| (entry.key().to_string(), entry.value().clone()))
+            .collect(),
+        staking_round_next_resolve_time_cache: state_manager
+            .call(|state| state.next_resolve_times.clone())
+            .await?
+            .iter()
+            .map(|(key, next_resolve_time)| (key.to_string(), *next_resolve_time))
+            .collect(),
+    };
+
+    std::fs::write(output_path, serde_json::to_string_pretty(&snapshot)?)?;
+    log::info!(
+        "  <> Keeper state exported to {} ({} Staking accounts, {} UserStaking accounts)",
+        output_path,
+        snapshot.staking_accounts.len(),
+        snapshot.user_staking_accounts.len()
+    );
+    Ok(())
+}
+
+// Seed the indexes from a snapshot and only fetch what changed since its slot (see index_user_staking_accounts_delta)
+// The caches are then rebuilt from the indexes as usual
+// Returns the snapshot slot: the accounts modified since are only current once the stream replayed the updates from it
+pub async fn load_state_snapshot(
+    path: &str,
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    state_manager: &StateManager,
+    indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+) -> anyhow::Result<u64> {
+    let snapshot: StateSnapshot = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let snapshot_age = chrono::Utc::now().timestamp() - snapshot.created_at;
+    if snapshot_age > MAX_STATE_SNAPSHOT_AGE_SECONDS {
+        return Err(anyhow::anyhow!(
+            "State snapshot is too old ({}s, max {}s)",
+            snapshot_age,
+            MAX_STATE_SNAPSHOT_AGE_SECONDS
+        ));
+    }
+    log::info!(
+        "  <> Loading state snapshot from slot {} ({} UserStaking accounts)",
+        snapshot.slot,
+        snapshot.user_staking_accounts.len()
+    );
+
+    let mut user_staking_accounts: HashMap<Pubkey, UserStaking> = HashMap::new();
+    for (key, data) in snapshot.user_staking_accounts.iter() {
+        let data = BASE64.decode(data)?;
+        user_staking_accounts.insert(Pubkey::from_str(key)?, decode_user_staking(&data)?);
+    }
+
+    index_user_staking_accounts_delta(
+        user_staking_accounts,
+        rpc_client,
+        commitment,
+        state_manager,
+        indexed_user_staking_accounts,
+    )
+    .await?;
+    Ok(snapshot.slot)
+}
+
+// UserStaking accounts created and closed since the indexed state was saved
+pub struct UserStakingAccountsDelta {
+    // Only the indexed ones (staking type defined), with their full data
+    pub fetched: Vec<(Pubkey, Vec<u8>)>,
+    pub closed: Vec<Pubkey>,
+}
+
+// Index previously saved UserStaking accounts and only fetch what changed since:
+// - the Staking accounts are refetched (a handful of accounts)
+// - the UserStaking accounts are listed without their data, the new ones are fetched and the closed ones dropped
+// The accounts modified since are not refetched: the callers refresh them (e.g. stream replay from the snapshot slot)
+pub async fn index_user_staking_accounts_delta(
+    mut user_staking_accounts: HashMap<Pubkey, UserStaking>,
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    state_manager: &StateManager,
+    indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+) -> anyhow::Result<UserStakingAccountsDelta> {
+    index_staking_accounts(rpc_client, commitment, state_manager).await?;
+
+    // Delta of the UserStaking accounts since the saved state
+    let current_user_staking_accounts_keys: HashSet<Pubkey> =
+        fetch_program_accounts_keys(rpc_client, UserStaking::DISCRIMINATOR, commitment).await?;
+    let closed_user_staking_accounts_keys: Vec<Pubkey> = user_staking_accounts
+        .keys()
+        .filter(|key| !current_user_staking_accounts_keys.contains(key))
+        .copied()
+        .collect();
+    for key in closed_user_staking_accounts_keys.iter() {
+        user_staking_accounts.remove(key);
+    }
+
+    let new_user_staking_accounts_keys: Vec<Pubkey> = current_user_staking_accounts_keys
+        .into_iter()
+        .filter(|key| !user_staking_accounts.contains_key(key))
+        .collect();
+    let mut fetched_user_staking_accounts: Vec<(Pubkey, Vec<u8>)> = vec![];
+    for keys in new_user_staking_accounts_keys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        throttle_rpc().await;
+        let accounts = rpc_client
+            .get_multiple_accounts_with_commitment(keys, commitment)
+            .await?
+            .value;
+        for (key, account) in keys.iter().zip(accounts) {
+            let Some(account) = account else {
+                continue;
+            };
+            let user_staking_account = decode_user_staking(&account.data)?;
+            // Same as the keeper: the accounts without staking type are not indexed
+            if user_staking_account.staking_type != 0 {
+                user_staking_accounts.insert(*key, user_staking_account);
+                fetched_user_staking_accounts.push((*key, account.data));
+            }
+        }
+    }
+    log::info!(
+        "  <> UserStaking accounts delta: {} fetched, {} closed",
+        new_user_staking_accounts_keys.len(),
+        closed_user_staking_accounts_keys.len()
+    );
+
+    indexed_user_staking_accounts.extend(user_staking_accounts);
+    Ok(UserStakingAccountsDelta {
+        fetched: fetched_user_staking_accounts,
+        closed: closed_user_staking_accounts_keys,
+    })
+}
+
+// Fetch and index the Staking accounts (a handful of accounts)
+pub async fn index_staking_accounts(
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    state_manager: &StateManager,
+) -> anyhow::Result<()> {
+    let mut staking_accounts: HashMap<Pubkey, Staking> = HashMap::new();
+    for (key, data) in
+        fetch_program_accounts_data(rpc_client, Staking::DISCRIMINATOR, commitment).await?
+    {
+        staking_accounts.insert(key, Staking::try_deserialize(&mut &data[..])?);
+    }
+    state_manager
+        .call(move |state| state.staking_accounts.extend(staking_accounts))
+        .await?;
+    Ok(())
+}
+
+// Keys of the program accounts matching the discriminator, without their data
+async fn fetch_program_accounts_keys(
+    rpc_client: &RpcClient,
+    discriminator: &[u8],
+    commitment: CommitmentConfig,
+) -> anyhow::Result<HashSet<Pubkey>> {
+    throttle_rpc().await;
+    let accounts = rpc_client
+        .get_program_accounts_with_config(
+            &adrena_abi::ID,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    0,
+                    discriminator.to_vec(),
+                ))]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: Some(UiDataSliceConfig {
+                        offset: 0,
+                        length: 0,
+                    }),
+                    commitment: Some(commitment),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(accounts.into_iter().map(|(key, _)| key).collect())
+}
+
+async fn fetch_program_accounts_data(
+    rpc_client: &RpcClient,
+    discriminator: &[u8],
+    commitment: CommitmentConfig,
+) -> anyhow::Result<Vec<(Pubkey, Vec<u8>)>> {
+    throttle_rpc().await;
+    let accounts = rpc_client
+        .get_program_accounts_with_config(
+            &adrena_abi::ID,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    0,
+                    discriminator.to_vec(),
+                ))]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(commitment),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(accounts
+        .into_iter()
+        .map(|(key, account)| (key, account.data))
+        .collect())
+}
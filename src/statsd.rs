@@ -0,0 +1,113 @@
+use {
+    prometheus::proto::{Metric, MetricType},
+    std::{collections::HashMap, time::Duration},
+    tokio::{net::UdpSocket, time::interval},
+};
+
+// Stay below the usual MTU, the lines of a flush are split across datagrams
+const MAX_DATAGRAM_SIZE: usize = 1_400;
+
+// Emit the keeper metrics to a StatsD agent (Datadog DogStatsD tags), read from the same registry as /metrics
+// - counters: the increase since the previous flush (count)
+// - gauges: the current value (gauge)
+// - histograms (latencies): the increase of the observations count and sum since the previous flush (count), e.g.
+//   mrsablier_lock_hold_seconds.count and mrsablier_lock_hold_seconds.sum
+pub async fn export_statsd(statsd_addr: String, flush_interval: Duration) {
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(e) => {
+            log::error!("  <> Failed to bind the StatsD socket: {:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = socket.connect(&statsd_addr).await {
+        log::error!(
+            "  <> Failed to resolve the StatsD agent {}: {:?}",
+            statsd_addr,
+            e
+        );
+        return;
+    }
+
+    // Cumulative values of the previous flush, per metric line (name and tags)
+    let mut previous_values: HashMap<String, f64> = HashMap::new();
+    let mut flush_ticker = interval(flush_interval);
+    loop {
+        flush_ticker.tick().await;
+
+        let mut lines: Vec<String> = vec![];
+        for metric_family in prometheus::gather() {
+            let name = metric_family.get_name();
+            for metric in metric_family.get_metric() {
+                let tags = statsd_tags(metric);
+                match metric_family.get_field_type() {
+                    MetricType::COUNTER => {
+                        let key = format!("{}{}", name, tags);
+                        let value = metric.get_counter().get_value();
+                        let delta = value - previous_values.insert(key, value).unwrap_or(0.0);
+                        lines.push(format!("{}:{}|c{}", name, delta, tags));
+                    }
+                    MetricType::GAUGE => {
+                        lines.push(format!(
+                            "{}:{}|g{}",
+                            name,
+                            metric.get_gauge().get_value(),
+                            tags
+                        ));
+                    }
+                    MetricType::HISTOGRAM => {
+                        let histogram = metric.get_histogram();
+                        for (suffix, value) in [
+                            ("count", histogram.get_sample_count() as f64),
+                            ("sum", histogram.get_sample_sum()),
+                        ] {
+                            let key = format!("{}.{}{}", name, suffix, tags);
+                            let delta = value - previous_values.insert(key, value).unwrap_or(0.0);
+                            lines.push(format!("{}.{}:{}|c{}", name, suffix, delta, tags));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for datagram in datagrams(&lines) {
+            if let Err(e) = socket.send(datagram.as_bytes()).await {
+                log::debug!("  <> Failed to send the metrics to StatsD: {:?}", e);
+            }
+        }
+    }
+}
+
+// DogStatsD tags from the Prometheus labels, e.g. |#action:claim_stakes,staking:adx
+fn statsd_tags(metric: &Metric) -> String {
+    let labels: Vec<String> = metric
+        .get_label()
+        .iter()
+        .map(|label| format!("{}:{}", label.get_name(), label.get_value()))
+        .collect();
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("|#{}", labels.join(","))
+    }
+}
+
+// Pack the lines in newline separated datagrams
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams: Vec<String> = vec![];
+    let mut current = String::new();
+    for line in lines {
+        if !current.is_empty() && current.len() + 1 + line.len() > MAX_DATAGRAM_SIZE {
+            datagrams.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        datagrams.push(current);
+    }
+    datagrams
+}
@@ -0,0 +1,169 @@
+use {
+    crate::rate_limiter::rpc_call,
+    adrena_abi::{
+        ADX_MINT, ALP_MINT, SPL_ASSOCIATED_TOKEN_PROGRAM_ID, SPL_TOKEN_PROGRAM_ID, USDC_MINT,
+    },
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{pubkey, pubkey::Pubkey},
+    std::{
+        collections::HashMap,
+        sync::{LazyLock, Mutex, OnceLock, RwLock},
+    },
+};
+
+pub const SPL_TOKEN_2022_PROGRAM_ID: Pubkey =
+    pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+// Token program owning each mint (classic SPL Token or Token-2022), read from the mint accounts at startup
+// The mints not resolved (RPC error) are assumed classic SPL Token
+static MINT_TOKEN_PROGRAMS: LazyLock<RwLock<HashMap<Pubkey, Pubkey>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+// Mint of the staking rewards (fee redistribution), set once at startup - USDC unless the protocol changes it
+static REWARD_TOKEN_MINT: OnceLock<Pubkey> = OnceLock::new();
+
+// The owners' reward (USDC by default) and lm (ADX) associated token accounts - They are deterministic, derived once per owner
+static OWNER_TOKEN_ACCOUNTS: LazyLock<Mutex<HashMap<Pubkey, OwnerTokenAccounts>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy)]
+pub struct OwnerTokenAccounts {
+    pub reward_token_account: Pubkey,
+    pub lm_token_account: Pubkey,
+    // Both token accounts were seen onchain, the claims don't need to create them
+    pub exist: bool,
+}
+
+pub fn init_reward_token_mint(mint: Option<Pubkey>) {
+    if let Some(mint) = mint {
+        log::info!("  <> Reward token mint: {}", mint);
+        REWARD_TOKEN_MINT.set(mint).ok();
+    }
+}
+
+pub fn reward_token_mint() -> Pubkey {
+    REWARD_TOKEN_MINT.get().copied().unwrap_or(USDC_MINT)
+}
+
+// Resolve the token program of the staking mints, so the protocol can migrate the reward or LM mint to Token-2022
+pub async fn init_mint_token_programs(rpc_client: &RpcClient) {
+    let mints = [reward_token_mint(), ADX_MINT, ALP_MINT];
+    match rpc_call(|| rpc_client.get_multiple_accounts(&mints)).await {
+        Ok(accounts) => {
+            let mut mint_token_programs = MINT_TOKEN_PROGRAMS.write().unwrap();
+            for (mint, account) in mints.iter().zip(accounts) {
+                match account {
+                    Some(account)
+                        if account.owner == SPL_TOKEN_PROGRAM_ID
+                            || account.owner == SPL_TOKEN_2022_PROGRAM_ID =>
+                    {
+                        if account.owner == SPL_TOKEN_2022_PROGRAM_ID {
+                            log::info!("  <> Mint {} is a Token-2022 mint", mint);
+                        }
+                        mint_token_programs.insert(*mint, account.owner);
+                    }
+                    _ => log::warn!(
+                        "   <> Mint {} not owned by a token program - assuming SPL Token",
+                        mint
+                    ),
+                }
+            }
+        }
+        Err(e) => log::warn!(
+            "   <> Failed to fetch the mints, assuming SPL Token: {:?}",
+            e
+        ),
+    }
+
+    // The staking instructions take a single token program for the reward and LM transfers
+    if token_program_id(&reward_token_mint()) != token_program_id(&ADX_MINT) {
+        log::warn!(
+            "  <*> The reward and LM mints are owned by different token programs - the staking instructions use the reward one"
+        );
+    }
+}
+
+pub fn token_program_id(mint: &Pubkey) -> Pubkey {
+    MINT_TOKEN_PROGRAMS
+        .read()
+        .unwrap()
+        .get(mint)
+        .copied()
+        .unwrap_or(SPL_TOKEN_PROGRAM_ID)
+}
+
+// Token program passed to the staking instructions (resolve, claim, finalize), which move the reward tokens
+pub fn staking_token_program_id() -> Pubkey {
+    token_program_id(&reward_token_mint())
+}
+
+// The associated token account address depends on the token program of the mint
+fn derive_associated_token_account(owner_pubkey: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            &owner_pubkey.to_bytes(),
+            &token_program_id(mint).to_bytes(),
+            &mint.to_bytes(),
+        ],
+        &SPL_ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+pub fn get_owner_token_accounts(owner_pubkey: &Pubkey) -> OwnerTokenAccounts {
+    *OWNER_TOKEN_ACCOUNTS
+        .lock()
+        .unwrap()
+        .entry(*owner_pubkey)
+        .or_insert_with(|| OwnerTokenAccounts {
+            reward_token_account: derive_associated_token_account(
+                owner_pubkey,
+                &reward_token_mint(),
+            ),
+            lm_token_account: derive_associated_token_account(owner_pubkey, &ADX_MINT),
+            exist: false,
+        })
+}
+
+// Check (once per owner) whether the owner's token accounts exist onchain
+// On RPC error they are assumed missing, the claim creates them (idempotent)
+pub async fn validate_owner_token_accounts(
+    rpc_client: &RpcClient,
+    owner_pubkey: &Pubkey,
+) -> OwnerTokenAccounts {
+    let mut owner_token_accounts = get_owner_token_accounts(owner_pubkey);
+    if owner_token_accounts.exist {
+        return owner_token_accounts;
+    }
+
+    let token_accounts_keys = [
+        owner_token_accounts.reward_token_account,
+        owner_token_accounts.lm_token_account,
+    ];
+    match rpc_call(|| rpc_client.get_multiple_accounts(&token_accounts_keys)).await {
+        Ok(accounts) => {
+            if accounts.iter().all(Option::is_some) {
+                owner_token_accounts.exist = true;
+                OWNER_TOKEN_ACCOUNTS
+                    .lock()
+                    .unwrap()
+                    .insert(*owner_pubkey, owner_token_accounts);
+            }
+        }
+        Err(e) => {
+            log::warn!(
+                "   <> Failed to fetch the token accounts of {}: {:?}",
+                owner_pubkey,
+                e
+            );
+        }
+    }
+    owner_token_accounts
+}
+
+// The owner may have closed a token account since it was validated, check again on the next claim
+pub fn invalidate_owner_token_accounts(owner_pubkey: &Pubkey) {
+    if let Some(owner_token_accounts) = OWNER_TOKEN_ACCOUNTS.lock().unwrap().get_mut(owner_pubkey) {
+        owner_token_accounts.exist = false;
+    }
+}
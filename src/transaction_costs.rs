@@ -0,0 +1,67 @@
+use {
+    crate::{db::Db, health::record_db_outcome, tx_tracker::KeeperAction},
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+};
+
+// Costs are only recorded when a DB is configured, otherwise they would pile up
+static TRANSACTION_COSTS_RECORDING: AtomicBool = AtomicBool::new(false);
+
+// Landed keeper transactions waiting to be written to the DB (flushed by the core loop)
+static PENDING_TRANSACTION_COSTS: LazyLock<Mutex<Vec<TransactionCost>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
+// What a landed keeper transaction actually cost
+#[derive(Debug, Clone)]
+pub struct TransactionCost {
+    pub signature: Signature,
+    pub action: KeeperAction,
+    // The main account targeted by the action (Staking, UserStaking or Pool account)
+    pub account: Pubkey,
+    // Total fee paid (base + priority) as charged onchain, plus the tip of the submission backend
+    pub fee_lamports: u64,
+    pub landed_at: i64,
+}
+
+pub fn enable_transaction_costs_recording() {
+    TRANSACTION_COSTS_RECORDING.store(true, Ordering::Relaxed);
+}
+
+pub fn record_transaction_cost(transaction_cost: TransactionCost) {
+    if TRANSACTION_COSTS_RECORDING.load(Ordering::Relaxed) {
+        PENDING_TRANSACTION_COSTS
+            .lock()
+            .unwrap()
+            .push(transaction_cost);
+    }
+}
+
+fn take_transaction_costs() -> Vec<TransactionCost> {
+    std::mem::take(&mut *PENDING_TRANSACTION_COSTS.lock().unwrap())
+}
+
+// The DB write failed, the costs are written with the next flush
+fn requeue_transaction_costs(transaction_costs: Vec<TransactionCost>) {
+    PENDING_TRANSACTION_COSTS
+        .lock()
+        .unwrap()
+        .extend(transaction_costs);
+}
+
+// Write the recorded costs to the DB
+pub async fn flush_transaction_costs(db: &Db) {
+    let transaction_costs = take_transaction_costs();
+    if transaction_costs.is_empty() {
+        return;
+    }
+
+    let result = db.insert_transaction_costs(&transaction_costs).await;
+    record_db_outcome(result.is_ok());
+    if let Err(e) = result {
+        log::warn!("   <> Failed to record the transaction costs: {:?}", e);
+        requeue_transaction_costs(transaction_costs);
+    }
+}
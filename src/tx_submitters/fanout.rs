@@ -0,0 +1,53 @@
+use {
+    super::{send_transaction, TxSubmitter},
+    async_trait::async_trait,
+    futures::future::join_all,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{signature::Signature, transaction::Transaction},
+};
+
+// sendTransaction on several RPCs at once (different leaders connectivity), successful if any of them accepts it
+pub struct FanoutTxSubmitter {
+    rpc_clients: Vec<RpcClient>,
+}
+
+impl FanoutTxSubmitter {
+    pub fn new(rpc_urls: Vec<String>) -> Self {
+        Self {
+            rpc_clients: rpc_urls.into_iter().map(RpcClient::new).collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl TxSubmitter for FanoutTxSubmitter {
+    fn name(&self) -> &'static str {
+        "fanout"
+    }
+
+    async fn submit(&self, transaction: &Transaction) -> Result<Signature, anyhow::Error> {
+        let results = join_all(
+            self.rpc_clients
+                .iter()
+                .map(|rpc_client| send_transaction(rpc_client, transaction)),
+        )
+        .await;
+
+        let mut last_error = None;
+        for (rpc_client, result) in self.rpc_clients.iter().zip(results) {
+            match result {
+                Ok(signature) => return Ok(signature),
+                Err(e) => {
+                    log::debug!(
+                        "   <> Failed to send TX {} through {}: {:?}",
+                        transaction.signatures[0],
+                        rpc_client.url(),
+                        e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No RPC to send the transaction to")))
+    }
+}
@@ -0,0 +1,98 @@
+use {
+    super::{submit_http_client, TxSubmitter},
+    async_trait::async_trait,
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    rand::seq::SliceRandom,
+    solana_sdk::{
+        instruction::Instruction, pubkey, pubkey::Pubkey, signature::Signature, system_instruction,
+        transaction::Transaction,
+    },
+    std::str::FromStr,
+};
+
+// Helius Sender tip accounts (mainnet) - one is picked at random for each transaction to spread the write locks
+const HELIUS_SENDER_TIP_ACCOUNTS: [Pubkey; 10] = [
+    pubkey!("4ACfpUFoaSD9bfPdeu6DBt89gB6ENTeHBXCAi87NhDEE"),
+    pubkey!("D2L6yPZ2FmmmTKPgzaMKdhu6EWZcTpLy1Vhx8uvZe7NZ"),
+    pubkey!("9bnz4RShgq1hAnLnZbP8kbgBg1kEmcJBYQq3gQbmnSta"),
+    pubkey!("5VY91ws6B2hMmBFRsXkoAAdsPHBJwRfBht4DXox3xkwn"),
+    pubkey!("2nyhqdwKcJZR2vcqCyrYsaPVdAGFoJuiVqe7sJ4eDcTq"),
+    pubkey!("2q5pghRs6arqVjRvT5gfgWfWcHWmw1ZuCzphgd5KfWGJ"),
+    pubkey!("wyvPkWjVZz1M8fHQnMMCDTQDbkManefNNhweYk5WkcF"),
+    pubkey!("3KCKozbAaF75qEU33jtzozcJ29yJuaLJTy2jFdzUY8bT"),
+    pubkey!("4vieeGHPYPG2MmyPRcYjdiDmmhN3ww7hsFNap8pVN3Ey"),
+    pubkey!("4TQLFNWK8AovT1gFvda5jfw2oJeRMKEmw7aH6MGBJ3or"),
+];
+
+// Helius Sender: the transaction is sent at once to the leaders through staked connections and to Jito, requires a tip
+// and a priority fee - for the transactions that must land within a few slots (e.g. the resolves at the round boundaries)
+pub struct HeliusSenderTxSubmitter {
+    http_client: reqwest::Client,
+    sender_url: String,
+    tip_lamports: u64,
+}
+
+impl HeliusSenderTxSubmitter {
+    pub fn new(sender_url: String, tip_lamports: u64) -> Self {
+        Self {
+            http_client: submit_http_client(),
+            sender_url,
+            tip_lamports,
+        }
+    }
+}
+
+#[async_trait]
+impl TxSubmitter for HeliusSenderTxSubmitter {
+    fn name(&self) -> &'static str {
+        "helius-sender"
+    }
+
+    fn tip_instruction(&self, payer: &Pubkey) -> Option<Instruction> {
+        let tip_account = HELIUS_SENDER_TIP_ACCOUNTS.choose(&mut rand::thread_rng())?;
+        Some(system_instruction::transfer(
+            payer,
+            tip_account,
+            self.tip_lamports,
+        ))
+    }
+
+    fn tip_lamports(&self) -> u64 {
+        self.tip_lamports
+    }
+
+    async fn submit(&self, transaction: &Transaction) -> Result<Signature, anyhow::Error> {
+        let encoded_transaction = BASE64.encode(bincode::serialize(transaction)?);
+        // Sender only accepts the transactions skipping the preflight and the RPC retries
+        let response: serde_json::Value = self
+            .http_client
+            .post(&self.sender_url)
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "sendTransaction",
+                "params": [
+                    encoded_transaction,
+                    { "encoding": "base64", "skipPreflight": true, "maxRetries": 0 },
+                ],
+            }))
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        if let Some(error) = response.get("error") {
+            return Err(anyhow::anyhow!(
+                "Helius Sender sendTransaction failed: {}",
+                error
+            ));
+        }
+        let signature = response["result"].as_str().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid Helius Sender sendTransaction response: {}",
+                response
+            )
+        })?;
+        Ok(Signature::from_str(signature)?)
+    }
+}
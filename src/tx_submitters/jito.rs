@@ -0,0 +1,85 @@
+use {
+    super::{submit_http_client, TxSubmitter},
+    async_trait::async_trait,
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    rand::seq::SliceRandom,
+    solana_sdk::{
+        instruction::Instruction, pubkey, pubkey::Pubkey, signature::Signature, system_instruction,
+        transaction::Transaction,
+    },
+    std::str::FromStr,
+};
+
+// Jito tip accounts (mainnet) - one is picked at random for each transaction to spread the write locks
+const JITO_TIP_ACCOUNTS: [Pubkey; 8] = [
+    pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
+    pubkey!("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"),
+    pubkey!("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY"),
+    pubkey!("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49"),
+    pubkey!("DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh"),
+    pubkey!("ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt"),
+    pubkey!("DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL"),
+    pubkey!("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT"),
+];
+
+// Jito block engine sendTransaction: forwarded to the leaders as a single transaction bundle, requires a tip
+pub struct JitoTxSubmitter {
+    http_client: reqwest::Client,
+    block_engine_url: String,
+    tip_lamports: u64,
+}
+
+impl JitoTxSubmitter {
+    pub fn new(block_engine_url: String, tip_lamports: u64) -> Self {
+        Self {
+            http_client: submit_http_client(),
+            block_engine_url,
+            tip_lamports,
+        }
+    }
+}
+
+#[async_trait]
+impl TxSubmitter for JitoTxSubmitter {
+    fn name(&self) -> &'static str {
+        "jito"
+    }
+
+    fn tip_instruction(&self, payer: &Pubkey) -> Option<Instruction> {
+        let tip_account = JITO_TIP_ACCOUNTS.choose(&mut rand::thread_rng())?;
+        Some(system_instruction::transfer(
+            payer,
+            tip_account,
+            self.tip_lamports,
+        ))
+    }
+
+    fn tip_lamports(&self) -> u64 {
+        self.tip_lamports
+    }
+
+    async fn submit(&self, transaction: &Transaction) -> Result<Signature, anyhow::Error> {
+        let encoded_transaction = BASE64.encode(bincode::serialize(transaction)?);
+        let response: serde_json::Value = self
+            .http_client
+            .post(format!("{}/api/v1/transactions", self.block_engine_url))
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "sendTransaction",
+                "params": [encoded_transaction, { "encoding": "base64" }],
+            }))
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        if let Some(error) = response.get("error") {
+            return Err(anyhow::anyhow!("Jito sendTransaction failed: {}", error));
+        }
+        let signature = response["result"].as_str().ok_or_else(|| {
+            anyhow::anyhow!("Invalid Jito sendTransaction response: {}", response)
+        })?;
+        Ok(Signature::from_str(signature)?)
+    }
+}
@@ -0,0 +1,149 @@
+pub mod fanout;
+pub mod helius_sender;
+pub mod jito;
+pub mod rpc;
+
+pub use {fanout::*, helius_sender::*, jito::*, rpc::*};
+
+use {
+    crate::{rate_limiter::rpc_call, tx_tracker::KeeperAction},
+    async_trait::async_trait,
+    solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig},
+    solana_sdk::{
+        instruction::Instruction, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+    },
+    std::{
+        collections::HashMap,
+        sync::{Arc, OnceLock},
+        time::Duration,
+    },
+};
+
+// The HTTP backends give up on a request after this long, the transaction is rebroadcast anyway
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Set once at startup - if never set, the transactions are sent through the RPC client of the handlers
+static TX_SUBMITTERS: OnceLock<TxSubmitters> = OnceLock::new();
+
+struct TxSubmitters {
+    default: Arc<dyn TxSubmitter>,
+    // The actions sent through another backend than the default one (e.g. the resolves through Helius Sender)
+    per_action: HashMap<KeeperAction, Arc<dyn TxSubmitter>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum TxSubmitterBackend {
+    // sendTransaction on the endpoint
+    #[default]
+    Rpc,
+    // sendTransaction on the endpoint and every --submit-endpoint at once
+    Fanout,
+    // Jito block engine sendTransaction, with a tip
+    Jito,
+    // Helius Sender (staked connections and Jito), with a tip
+    HeliusSender,
+}
+
+// How the transactions built by the handlers are delivered - new backends only need an implementation and a
+// TxSubmitterBackend variant
+#[async_trait]
+pub trait TxSubmitter: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Instruction the backend requires in the transactions it delivers (e.g. a tip transfer), added by the handlers
+    fn tip_instruction(&self, _payer: &Pubkey) -> Option<Instruction> {
+        None
+    }
+
+    // Lamports transferred by the tip instruction, counted in the fees paid once the transaction lands
+    fn tip_lamports(&self) -> u64 {
+        0
+    }
+
+    // Deliver a signed transaction - the keeper tracks its outcome and rebroadcasts it, no retries expected here
+    async fn submit(&self, transaction: &Transaction) -> Result<Signature, anyhow::Error>;
+}
+
+pub fn init_tx_submitters(
+    default: Arc<dyn TxSubmitter>,
+    per_action: HashMap<KeeperAction, Arc<dyn TxSubmitter>>,
+) {
+    log::info!("  <> Transaction submission backend: {}", default.name());
+    for (action, tx_submitter) in per_action.iter() {
+        log::info!(
+            "  <> Transaction submission backend of {}: {}",
+            action.as_str(),
+            tx_submitter.name()
+        );
+    }
+    let _ = TX_SUBMITTERS.set(TxSubmitters {
+        default,
+        per_action,
+    });
+}
+
+fn tx_submitter(action: KeeperAction) -> Option<&'static Arc<dyn TxSubmitter>> {
+    TX_SUBMITTERS.get().map(|tx_submitters| {
+        tx_submitters
+            .per_action
+            .get(&action)
+            .unwrap_or(&tx_submitters.default)
+    })
+}
+
+pub fn tip_instruction(action: KeeperAction, payer: &Pubkey) -> Option<Instruction> {
+    tx_submitter(action).and_then(|tx_submitter| tx_submitter.tip_instruction(payer))
+}
+
+pub fn tip_lamports(action: KeeperAction) -> u64 {
+    tx_submitter(action).map_or(0, |tx_submitter| tx_submitter.tip_lamports())
+}
+
+// Whether the transactions of both actions are delivered by the same backend (they can share a transaction)
+pub fn same_tx_submitter(action: KeeperAction, other_action: KeeperAction) -> bool {
+    match (tx_submitter(action), tx_submitter(other_action)) {
+        (Some(tx_submitter), Some(other_tx_submitter)) => {
+            Arc::ptr_eq(tx_submitter, other_tx_submitter)
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// Send a keeper transaction through the backend of its action
+pub async fn submit_transaction(
+    action: KeeperAction,
+    rpc_client: &RpcClient,
+    transaction: &Transaction,
+) -> Result<Signature, anyhow::Error> {
+    match tx_submitter(action) {
+        Some(tx_submitter) => tx_submitter.submit(transaction).await,
+        None => send_transaction(rpc_client, transaction).await,
+    }
+}
+
+// Client of the HTTP backends - like reqwest::Client::new, only fails if the TLS backend can't be initialized
+fn submit_http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(SUBMIT_TIMEOUT)
+        .build()
+        .expect("Failed to build the transaction submission HTTP client")
+}
+
+// The keeper transactions skip the preflight (simulated beforehand when needed) and the RPC retries (rebroadcast by the keeper)
+async fn send_transaction(
+    rpc_client: &RpcClient,
+    transaction: &Transaction,
+) -> Result<Signature, anyhow::Error> {
+    Ok(rpc_call(|| {
+        rpc_client.send_transaction_with_config(
+            transaction,
+            RpcSendTransactionConfig {
+                skip_preflight: true,
+                max_retries: Some(0),
+                ..Default::default()
+            },
+        )
+    })
+    .await?)
+}
@@ -0,0 +1,30 @@
+use {
+    super::{send_transaction, TxSubmitter},
+    async_trait::async_trait,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{signature::Signature, transaction::Transaction},
+};
+
+// sendTransaction on a single RPC
+pub struct RpcTxSubmitter {
+    rpc_client: RpcClient,
+}
+
+impl RpcTxSubmitter {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            rpc_client: RpcClient::new(rpc_url),
+        }
+    }
+}
+
+#[async_trait]
+impl TxSubmitter for RpcTxSubmitter {
+    fn name(&self) -> &'static str {
+        "rpc"
+    }
+
+    async fn submit(&self, transaction: &Transaction) -> Result<Signature, anyhow::Error> {
+        send_transaction(&self.rpc_client, transaction).await
+    }
+}
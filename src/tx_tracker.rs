@@ -0,0 +1,818 @@
+use {
+    crate::{
+        action_log::record_action_outcome,
+        action_retries::ActionId,
+        blockhash_cache::is_blockhash_expired,
+        compute_units::{record_cu_exceeded, record_units_consumed},
+        events::{publish_event, KeeperEvent},
+        fee_budget::record_fee_paid,
+        fee_escalation::{record_transaction_dropped, record_transaction_landed},
+        health::{record_landed_transaction, record_sent_transaction},
+        instruction_packer::record_failed_pack,
+        maintenance::are_transactions_suspended,
+        metrics::{
+            LockHoldTimer, NO_STAKING_LABEL, PRIORITY_FEES_PAID, TRANSACTIONS_CONFIRMED,
+            TRANSACTIONS_DROPPED, TRANSACTIONS_FAILED, TRANSACTIONS_REBROADCAST, TRANSACTIONS_SENT,
+        },
+        owner_cache::learn_owner,
+        preflight::is_cu_exceeded,
+        quarantine::record_account_landed,
+        rate_limiter::{throttle_rpc, RateLimiter},
+        transaction_costs::{record_transaction_cost, TransactionCost},
+        tx_submitters::tip_lamports,
+        webhooks::{claimed_amounts, notify_claim},
+        PendingTransactionsThreadSafe,
+    },
+    futures::{
+        channel::mpsc::{self, UnboundedSender},
+        stream::FuturesUnordered,
+        StreamExt,
+    },
+    solana_client::{
+        nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+        rpc_config::{RpcSendTransactionConfig, RpcSignatureSubscribeConfig, RpcTransactionConfig},
+        rpc_response::{ProcessedSignatureResult, RpcSignatureResult},
+    },
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::{Transaction, TransactionError},
+    },
+    solana_transaction_status::{UiTransactionEncoding, UiTransactionTokenBalance},
+    std::{
+        sync::{LazyLock, Mutex, OnceLock},
+        time::Duration,
+    },
+    tokio::time::{interval, timeout},
+    yellowstone_grpc_proto::prelude::{
+        TokenBalance as StreamTokenBalance, TransactionStatusMeta as StreamTransactionStatusMeta,
+    },
+};
+
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+// Max number of signatures accepted by getSignatureStatuses
+const MAX_SIGNATURE_STATUSES_PER_CALL: usize = 256;
+// Past this delay without any status, the transaction blockhash has expired and it will never land
+// (used when the blockhash validity of the transaction is unknown)
+const TRANSACTION_DROPPED_TIMEOUT_SECONDS: i64 = 90;
+// Keeper transactions are confirmed through the Geyser stream, the RPC is only polled for the ones not seen in time
+const STREAM_CONFIRMATION_GRACE_PERIOD_SECONDS: i64 = 30;
+
+// Max number of keeper transactions waiting for confirmation, set once at startup - if never set (or 0) there is no limit
+static MAX_IN_FLIGHT_TRANSACTIONS: OnceLock<usize> = OnceLock::new();
+
+// Feeds the signatureSubscribe task with the sent transactions - None while the task isn't running (disabled or reconnecting)
+static SIGNATURE_SUBSCRIPTIONS: LazyLock<Mutex<Option<UnboundedSender<Signature>>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeeperAction {
+    ResolveStakingRound,
+    ClaimStakes,
+    FinalizeLockedStake,
+    UpdatePoolAum,
+}
+
+impl KeeperAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeeperAction::ResolveStakingRound => "resolve_staking_round",
+            KeeperAction::ClaimStakes => "claim_stakes",
+            KeeperAction::FinalizeLockedStake => "finalize_locked_stake",
+            KeeperAction::UpdatePoolAum => "update_pool_aum",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SentTransaction {
+    pub signature: Signature,
+    pub action: KeeperAction,
+    // The main account targeted by the action (Staking, UserStaking or Pool account)
+    pub account: Pubkey,
+    // The locked stake of a finalization
+    pub locked_stake_id: Option<u64>,
+    // Priority fee paid once the transaction lands (compute unit price * compute unit limit)
+    pub priority_fee_lamports: u64,
+    // Tip of the submission backend (e.g. Jito), only transferred when the transaction succeeds
+    pub tip_lamports: u64,
+    pub compute_unit_limit: u32,
+    pub sent_at: i64,
+    // Wallet owning the UserStaking account, for the actions done on behalf of a user
+    pub owner: Option<Pubkey>,
+    // Staking label of the metrics (see metrics.rs)
+    pub staking: &'static str,
+    // Last block height at which the transaction can land (from its blockhash)
+    pub last_valid_block_height: Option<u64>,
+    // The signed transaction, rebroadcast as is while it's pending and its blockhash is valid
+    pub transaction: Option<Transaction>,
+    // UserStaking account claimed in the same transaction as a resolve (its owner is the owner above)
+    pub combined_claim: Option<Pubkey>,
+    // The other actions (and their staking label) packed in the same transaction, sharing its outcome
+    pub packed: Vec<(ActionId, &'static str)>,
+}
+
+impl SentTransaction {
+    pub fn new(
+        signature: Signature,
+        action: KeeperAction,
+        account: Pubkey,
+        compute_unit_price: u64,
+        compute_unit_limit: u32,
+    ) -> Self {
+        Self {
+            signature,
+            action,
+            account,
+            locked_stake_id: None,
+            // compute unit price is in µLamports
+            priority_fee_lamports: compute_unit_price * compute_unit_limit as u64 / 1_000_000,
+            tip_lamports: tip_lamports(action),
+            compute_unit_limit,
+            sent_at: chrono::Utc::now().timestamp(),
+            owner: None,
+            staking: NO_STAKING_LABEL,
+            last_valid_block_height: None,
+            transaction: None,
+            combined_claim: None,
+            packed: vec![],
+        }
+    }
+
+    pub fn with_transaction(mut self, transaction: Transaction) -> Self {
+        self.transaction = Some(transaction);
+        self
+    }
+
+    pub fn with_last_valid_block_height(mut self, last_valid_block_height: u64) -> Self {
+        self.last_valid_block_height = Some(last_valid_block_height);
+        self
+    }
+
+    // The transaction can no longer land (its blockhash expired)
+    pub fn is_expired(&self, current_time: i64) -> bool {
+        match self.last_valid_block_height {
+            Some(last_valid_block_height) => is_blockhash_expired(last_valid_block_height),
+            None => current_time - self.sent_at > TRANSACTION_DROPPED_TIMEOUT_SECONDS,
+        }
+    }
+
+    pub fn with_owner(mut self, owner: Pubkey) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    pub fn with_combined_claim(mut self, user_staking_account_key: Pubkey, owner: Pubkey) -> Self {
+        self.combined_claim = Some(user_staking_account_key);
+        self.owner = Some(owner);
+        self
+    }
+
+    pub fn with_staking(mut self, staking: &'static str) -> Self {
+        self.staking = staking;
+        self
+    }
+
+    pub fn with_locked_stake_id(mut self, locked_stake_id: Option<u64>) -> Self {
+        self.locked_stake_id = locked_stake_id;
+        self
+    }
+
+    pub fn with_packed(mut self, packed: Vec<(ActionId, &'static str)>) -> Self {
+        self.packed = packed;
+        self
+    }
+
+    pub fn action_id(&self) -> ActionId {
+        ActionId {
+            action: self.action,
+            account: self.account,
+            locked_stake_id: self.locked_stake_id,
+        }
+    }
+
+    // The action of the transaction, then the ones packed with it
+    fn actions(&self) -> impl Iterator<Item = (KeeperAction, &Pubkey)> {
+        std::iter::once((self.action, &self.account)).chain(
+            self.packed
+                .iter()
+                .map(|(action_id, _)| (action_id.action, &action_id.account)),
+        )
+    }
+}
+
+// What we need from a landed transaction
+#[derive(Debug, Default)]
+pub struct TransactionReceipt {
+    pub units_consumed: Option<u64>,
+    // Total fee paid (base + priority)
+    pub fee: Option<u64>,
+    pub token_balance_changes: Vec<TokenBalanceChange>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenBalanceChange {
+    pub owner: String,
+    pub mint: String,
+    pub amount: i128,
+}
+
+struct TokenBalance {
+    account_index: u32,
+    mint: String,
+    owner: String,
+    amount: u64,
+}
+
+impl TransactionReceipt {
+    pub fn from_stream_meta(meta: &StreamTransactionStatusMeta) -> Self {
+        let to_token_balances = |balances: &[StreamTokenBalance]| -> Vec<TokenBalance> {
+            balances
+                .iter()
+                .map(|balance| TokenBalance {
+                    account_index: balance.account_index,
+                    mint: balance.mint.clone(),
+                    owner: balance.owner.clone(),
+                    amount: balance
+                        .ui_token_amount
+                        .as_ref()
+                        .and_then(|amount| amount.amount.parse().ok())
+                        .unwrap_or(0),
+                })
+                .collect()
+        };
+
+        Self {
+            units_consumed: meta.compute_units_consumed,
+            fee: Some(meta.fee),
+            token_balance_changes: token_balance_changes(
+                &to_token_balances(&meta.pre_token_balances),
+                &to_token_balances(&meta.post_token_balances),
+            ),
+        }
+    }
+
+    fn from_rpc_token_balances(
+        units_consumed: Option<u64>,
+        fee: u64,
+        pre_token_balances: Vec<UiTransactionTokenBalance>,
+        post_token_balances: Vec<UiTransactionTokenBalance>,
+    ) -> Self {
+        let to_token_balances = |balances: Vec<UiTransactionTokenBalance>| -> Vec<TokenBalance> {
+            balances
+                .into_iter()
+                .map(|balance| TokenBalance {
+                    account_index: balance.account_index as u32,
+                    mint: balance.mint,
+                    owner: Option::<String>::from(balance.owner).unwrap_or_default(),
+                    amount: balance.ui_token_amount.amount.parse().unwrap_or(0),
+                })
+                .collect()
+        };
+
+        Self {
+            units_consumed,
+            fee: Some(fee),
+            token_balance_changes: token_balance_changes(
+                &to_token_balances(pre_token_balances),
+                &to_token_balances(post_token_balances),
+            ),
+        }
+    }
+}
+
+// Token accounts created by the transaction have no pre balance
+fn token_balance_changes(
+    pre_token_balances: &[TokenBalance],
+    post_token_balances: &[TokenBalance],
+) -> Vec<TokenBalanceChange> {
+    post_token_balances
+        .iter()
+        .map(|post| {
+            let pre_amount = pre_token_balances
+                .iter()
+                .find(|pre| pre.account_index == post.account_index)
+                .map_or(0, |pre| pre.amount);
+            TokenBalanceChange {
+                owner: post.owner.clone(),
+                mint: post.mint.clone(),
+                amount: post.amount as i128 - pre_amount as i128,
+            }
+        })
+        .filter(|change| change.amount != 0)
+        .collect()
+}
+
+enum TransactionOutcome {
+    Confirmed,
+    Failed,
+    Dropped,
+}
+
+pub fn init_in_flight_limit(max_in_flight_transactions: usize) {
+    let _ = MAX_IN_FLIGHT_TRANSACTIONS.set(max_in_flight_transactions);
+}
+
+// Whether a new transaction can be sent without exceeding the in-flight limit
+// When it can't, the due actions are left in the caches and picked up by a later loop
+pub async fn has_in_flight_capacity(pending_transactions: &PendingTransactionsThreadSafe) -> bool {
+    match MAX_IN_FLIGHT_TRANSACTIONS.get() {
+        Some(&max_in_flight_transactions) if max_in_flight_transactions != 0 => {
+            pending_transactions.read().await.len() < max_in_flight_transactions
+        }
+        _ => true,
+    }
+}
+
+// Whether a transaction of the action (alone or packed) is waiting for confirmation - not sent again meanwhile
+pub async fn is_pending(
+    pending_transactions: &PendingTransactionsThreadSafe,
+    action_id: &ActionId,
+) -> bool {
+    pending_transactions
+        .read()
+        .await
+        .values()
+        .any(|sent_transaction| {
+            sent_transaction.action_id() == *action_id
+                || sent_transaction
+                    .packed
+                    .iter()
+                    .any(|(packed_action_id, _)| packed_action_id == action_id)
+        })
+}
+
+// Record a sent transaction, its outcome will be checked by the confirmation task
+pub async fn track_transaction(
+    pending_transactions: &PendingTransactionsThreadSafe,
+    sent_transaction: SentTransaction,
+) {
+    let sent_transaction_signature = sent_transaction.signature;
+    record_sent_transaction();
+    TRANSACTIONS_SENT
+        .with_label_values(&[sent_transaction.action.as_str(), sent_transaction.staking])
+        .inc();
+    pending_transactions
+        .write()
+        .await
+        .insert(sent_transaction.signature, sent_transaction);
+
+    if let Some(signature_subscriptions) = SIGNATURE_SUBSCRIPTIONS.lock().unwrap().as_ref() {
+        let _ = signature_subscriptions.unbounded_send(sent_transaction_signature);
+    }
+}
+
+// Periodically send the pending transactions again (same signed transaction, so it can only land once)
+// through every endpoint, until they land or their blockhash expires - improves the landing rate under congestion
+// Throttled on their own (rebroadcast_qps, 0 disables), the RPC calls of the keeper don't wait behind them
+pub async fn rebroadcast_pending_transactions(
+    rpc_clients: Vec<RpcClient>,
+    pending_transactions: PendingTransactionsThreadSafe,
+    rebroadcast_interval: Duration,
+    rebroadcast_qps: u32,
+) {
+    let rate_limiter = (rebroadcast_qps != 0).then(|| RateLimiter::new(rebroadcast_qps, 1));
+    let mut rebroadcast_ticker = interval(rebroadcast_interval);
+    loop {
+        rebroadcast_ticker.tick().await;
+        if are_transactions_suspended() {
+            continue;
+        }
+
+        let current_time = chrono::Utc::now().timestamp();
+        let transactions: Vec<(KeeperAction, &'static str, Transaction)> = pending_transactions
+            .read()
+            .await
+            .values()
+            .filter(|sent_transaction| {
+                current_time - sent_transaction.sent_at >= rebroadcast_interval.as_secs() as i64
+                    && sent_transaction.last_valid_block_height.is_some_and(
+                        |last_valid_block_height| !is_blockhash_expired(last_valid_block_height),
+                    )
+            })
+            .filter_map(|sent_transaction| {
+                sent_transaction.transaction.clone().map(|transaction| {
+                    (
+                        sent_transaction.action,
+                        sent_transaction.staking,
+                        transaction,
+                    )
+                })
+            })
+            .collect();
+
+        for (action, staking, transaction) in transactions {
+            for rpc_client in &rpc_clients {
+                if let Some(rate_limiter) = rate_limiter.as_ref() {
+                    rate_limiter.acquire().await;
+                }
+                if let Err(e) = rpc_client
+                    .send_transaction_with_config(
+                        &transaction,
+                        RpcSendTransactionConfig {
+                            skip_preflight: true,
+                            max_retries: Some(0),
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                {
+                    log::debug!(
+                        "   <> Failed to rebroadcast {} TX {} through {}: {:?}",
+                        action.as_str(),
+                        transaction.signatures[0],
+                        rpc_client.url(),
+                        e
+                    );
+                }
+            }
+            TRANSACTIONS_REBROADCAST
+                .with_label_values(&[action.as_str(), staking])
+                .inc();
+        }
+    }
+}
+
+// Confirm the keeper transactions through the WebSocket signatureSubscribe, notified within a slot or two of landing
+// The RPC polling and the Geyser stream keep running, the first to see the outcome records it
+pub async fn confirm_pending_transactions_with_signature_subscribe(
+    ws_endpoint: String,
+    rpc_client: RpcClient,
+    pending_transactions: PendingTransactionsThreadSafe,
+) {
+    let pubsub_client = match PubsubClient::new(&ws_endpoint).await {
+        Ok(pubsub_client) => pubsub_client,
+        Err(e) => {
+            log::error!(
+                "  <> Failed to connect the signatureSubscribe WebSocket client: {:?}",
+                e
+            );
+            return;
+        }
+    };
+    log::info!("  <> signatureSubscribe confirmations enabled");
+
+    let (signatures_tx, mut signatures_rx) = mpsc::unbounded::<Signature>();
+    *SIGNATURE_SUBSCRIPTIONS.lock().unwrap() = Some(signatures_tx);
+
+    let mut signature_notifications = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            Some(signature) = signatures_rx.next() => {
+                signature_notifications.push(wait_for_signature_notification(&pubsub_client, signature));
+            }
+            Some((signature, result)) = signature_notifications.next(), if !signature_notifications.is_empty() => {
+                // No notification in time: left to the RPC polling
+                let Some(result) = result else {
+                    continue;
+                };
+                let Some(sent_transaction) = pending_transactions.write().await.remove(&signature) else {
+                    // Already confirmed through the stream or the polling
+                    continue;
+                };
+
+                match result {
+                    Ok(()) => {
+                        record_outcome(&sent_transaction, &TransactionOutcome::Confirmed);
+                        if let Some(receipt) = fetch_transaction_receipt(&rpc_client, &signature).await {
+                            on_transaction_confirmed(&sent_transaction, &receipt);
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "   <> {} TX {} failed: {:?}",
+                            sent_transaction.action.as_str(),
+                            signature,
+                            e
+                        );
+                        record_outcome(&sent_transaction, &TransactionOutcome::Failed);
+                        if is_cu_exceeded(&e) {
+                            record_cu_exceeded(sent_transaction.action);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Outcome of the transaction, or None if it isn't notified before its blockhash expires
+async fn wait_for_signature_notification(
+    pubsub_client: &PubsubClient,
+    signature: Signature,
+) -> (Signature, Option<Result<(), TransactionError>>) {
+    let subscription = pubsub_client
+        .signature_subscribe(
+            &signature,
+            Some(RpcSignatureSubscribeConfig {
+                commitment: Some(CommitmentConfig::confirmed()),
+                enable_received_notification: Some(false),
+            }),
+        )
+        .await;
+    let (mut notifications, unsubscribe) = match subscription {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            log::warn!(
+                "   <> Failed to subscribe to signature {}: {:?}",
+                signature,
+                e
+            );
+            return (signature, None);
+        }
+    };
+
+    let notification = timeout(
+        Duration::from_secs(TRANSACTION_DROPPED_TIMEOUT_SECONDS as u64),
+        notifications.next(),
+    )
+    .await;
+    drop(notifications);
+    unsubscribe().await;
+
+    let result = match notification {
+        Ok(Some(response)) => match response.value {
+            RpcSignatureResult::ProcessedSignature(ProcessedSignatureResult { err: None }) => {
+                Some(Ok(()))
+            }
+            RpcSignatureResult::ProcessedSignature(ProcessedSignatureResult { err: Some(e) }) => {
+                Some(Err(e))
+            }
+            RpcSignatureResult::ReceivedSignature(_) => None,
+        },
+        Ok(None) | Err(_) => None,
+    };
+    (signature, result)
+}
+
+// Periodically check the status of the pending transactions not seen in the stream and record their outcome
+pub async fn poll_pending_transactions(
+    rpc_client: RpcClient,
+    pending_transactions: PendingTransactionsThreadSafe,
+) {
+    let mut poll_interval = interval(CONFIRMATION_POLL_INTERVAL);
+    loop {
+        poll_interval.tick().await;
+
+        let current_time = chrono::Utc::now().timestamp();
+        let signatures: Vec<Signature> = pending_transactions
+            .read()
+            .await
+            .values()
+            .filter(|sent_transaction| {
+                current_time - sent_transaction.sent_at > STREAM_CONFIRMATION_GRACE_PERIOD_SECONDS
+            })
+            .map(|sent_transaction| sent_transaction.signature)
+            .collect();
+
+        // One call per chunk of signatures, keeps the RPC usage flat whatever the number of transactions in flight
+        let mut statuses = Vec::with_capacity(signatures.len());
+        for signatures_chunk in signatures.chunks(MAX_SIGNATURE_STATUSES_PER_CALL) {
+            throttle_rpc().await;
+            match rpc_client.get_signature_statuses(signatures_chunk).await {
+                Ok(response) => statuses.extend(signatures_chunk.iter().copied().zip(
+                    response.value.into_iter().map(|status| {
+                        status
+                            .filter(|status| {
+                                status.satisfies_commitment(CommitmentConfig::confirmed())
+                            })
+                            .map(|status| status.status)
+                    }),
+                )),
+                Err(e) => {
+                    log::warn!("   <> Failed to fetch signature statuses: {:?}", e);
+                }
+            }
+        }
+
+        for (signature, status) in statuses {
+            let current_time = chrono::Utc::now().timestamp();
+            let mut pending_transactions = pending_transactions.write().await;
+            let _pending_transactions_hold = LockHoldTimer::start("pending_transactions");
+            let Some(sent_transaction) = pending_transactions.get(&signature) else {
+                continue;
+            };
+
+            let outcome = match status {
+                Some(Ok(())) => TransactionOutcome::Confirmed,
+                Some(Err(e)) => {
+                    log::warn!(
+                        "   <> {} TX {} failed: {:?}",
+                        sent_transaction.action.as_str(),
+                        signature,
+                        e
+                    );
+                    if is_cu_exceeded(&e) {
+                        record_cu_exceeded(sent_transaction.action);
+                    }
+                    TransactionOutcome::Failed
+                }
+                None if sent_transaction.is_expired(current_time) => {
+                    log::warn!(
+                        "   <> {} TX {} dropped",
+                        sent_transaction.action.as_str(),
+                        signature
+                    );
+                    TransactionOutcome::Dropped
+                }
+                None => continue,
+            };
+
+            let Some(sent_transaction) = pending_transactions.remove(&signature) else {
+                continue;
+            };
+            drop(pending_transactions);
+
+            record_outcome(&sent_transaction, &outcome);
+
+            if let TransactionOutcome::Confirmed = outcome {
+                if let Some(receipt) = fetch_transaction_receipt(&rpc_client, &signature).await {
+                    on_transaction_confirmed(&sent_transaction, &receipt);
+                }
+            }
+        }
+    }
+}
+
+// Called for each keeper transaction seen in the Geyser stream
+pub async fn confirm_transaction_from_stream(
+    pending_transactions: &PendingTransactionsThreadSafe,
+    signature: &Signature,
+    failed: bool,
+    receipt: TransactionReceipt,
+) {
+    let Some(sent_transaction) = pending_transactions.write().await.remove(signature) else {
+        // Not sent by this process, or already handled
+        return;
+    };
+
+    if failed {
+        log::warn!(
+            "   <> {} TX {} failed",
+            sent_transaction.action.as_str(),
+            signature
+        );
+        record_outcome(&sent_transaction, &TransactionOutcome::Failed);
+        // The stream only tells the transaction failed: it used up its whole limit
+        if receipt.units_consumed.is_some_and(|units_consumed| {
+            units_consumed >= sent_transaction.compute_unit_limit as u64
+        }) {
+            record_cu_exceeded(sent_transaction.action);
+        }
+    } else {
+        record_outcome(&sent_transaction, &TransactionOutcome::Confirmed);
+        on_transaction_confirmed(&sent_transaction, &receipt);
+    }
+}
+
+fn on_transaction_confirmed(sent_transaction: &SentTransaction, receipt: &TransactionReceipt) {
+    // Feed the adaptive CU limits with the actual consumption of the landed transaction
+    // (not representative of any of the actions when several went in the transaction)
+    if let Some(units_consumed) = receipt
+        .units_consumed
+        .filter(|_| sent_transaction.combined_claim.is_none() && sent_transaction.packed.is_empty())
+    {
+        record_units_consumed(sent_transaction.action, units_consumed);
+    }
+
+    if let Some(fee) = receipt.fee {
+        record_transaction_cost(TransactionCost {
+            signature: sent_transaction.signature,
+            action: sent_transaction.action,
+            account: sent_transaction.account,
+            fee_lamports: fee + sent_transaction.tip_lamports,
+            landed_at: chrono::Utc::now().timestamp(),
+        });
+    }
+
+    match (sent_transaction.action, sent_transaction.owner) {
+        (KeeperAction::ClaimStakes, Some(owner)) => {
+            on_claim_confirmed(&sent_transaction.account, &owner, sent_transaction, receipt);
+        }
+        (KeeperAction::ResolveStakingRound, Some(owner)) => {
+            if let Some(user_staking_account_key) = sent_transaction.combined_claim {
+                on_claim_confirmed(&user_staking_account_key, &owner, sent_transaction, receipt);
+            }
+        }
+        _ => {}
+    }
+
+    for (action, account) in sent_transaction.actions() {
+        if action == KeeperAction::FinalizeLockedStake {
+            publish_event(KeeperEvent::StakeFinalized {
+                user_staking_account: account.to_string(),
+                signature: sent_transaction.signature.to_string(),
+            });
+        }
+    }
+}
+
+fn on_claim_confirmed(
+    user_staking_account_key: &Pubkey,
+    owner: &Pubkey,
+    sent_transaction: &SentTransaction,
+    receipt: &TransactionReceipt,
+) {
+    // The program checked the owner of the UserStaking account
+    learn_owner(user_staking_account_key, owner);
+    notify_claim(
+        owner,
+        user_staking_account_key,
+        &sent_transaction.signature,
+        receipt,
+    );
+    publish_event(KeeperEvent::ClaimExecuted {
+        user_staking_account: user_staking_account_key.to_string(),
+        wallet: owner.to_string(),
+        signature: sent_transaction.signature.to_string(),
+        claimed_amounts: claimed_amounts(owner, receipt),
+    });
+}
+
+fn record_outcome(sent_transaction: &SentTransaction, outcome: &TransactionOutcome) {
+    let labels = [sent_transaction.action.as_str(), sent_transaction.staking];
+    match outcome {
+        TransactionOutcome::Confirmed => {
+            record_action_outcome(sent_transaction, "confirmed");
+            record_landed_transaction();
+            for (action, account) in sent_transaction.actions() {
+                record_transaction_landed(action, account);
+                record_account_landed(account);
+            }
+            if let Some(user_staking_account_key) = sent_transaction.combined_claim {
+                record_account_landed(&user_staking_account_key);
+            }
+            TRANSACTIONS_CONFIRMED.with_label_values(&labels).inc();
+            PRIORITY_FEES_PAID
+                .with_label_values(&labels)
+                .inc_by(sent_transaction.priority_fee_lamports);
+            record_fee_paid(
+                sent_transaction.priority_fee_lamports,
+                sent_transaction.tip_lamports,
+            );
+        }
+        TransactionOutcome::Failed => {
+            record_action_outcome(sent_transaction, "failed");
+            // Landed transactions pay their fees even when failing
+            record_landed_transaction();
+            for (action, account) in sent_transaction.actions() {
+                record_transaction_landed(action, account);
+            }
+            // The failing action of the pack is unknown: the failure is recorded against each of them, sent on their own next
+            if !sent_transaction.packed.is_empty() {
+                record_failed_pack(
+                    std::iter::once((sent_transaction.action_id(), sent_transaction.staking))
+                        .chain(sent_transaction.packed.iter().copied())
+                        .collect(),
+                );
+            }
+            TRANSACTIONS_FAILED.with_label_values(&labels).inc();
+            PRIORITY_FEES_PAID
+                .with_label_values(&labels)
+                .inc_by(sent_transaction.priority_fee_lamports);
+            // The tip transfer is reverted with the rest of the transaction
+            record_fee_paid(sent_transaction.priority_fee_lamports, 0);
+        }
+        TransactionOutcome::Dropped => {
+            record_action_outcome(sent_transaction, "dropped");
+            for (action, account) in sent_transaction.actions() {
+                record_transaction_dropped(action, account);
+            }
+            TRANSACTIONS_DROPPED.with_label_values(&labels).inc();
+        }
+    }
+}
+
+async fn fetch_transaction_receipt(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+) -> Option<TransactionReceipt> {
+    throttle_rpc().await;
+    let transaction = match rpc_client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await
+    {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            log::warn!("   <> Failed to fetch transaction {}: {:?}", signature, e);
+            return None;
+        }
+    };
+
+    let meta = transaction.transaction.meta?;
+    Some(TransactionReceipt::from_rpc_token_balances(
+        meta.compute_units_consumed.into(),
+        meta.fee,
+        Option::<Vec<UiTransactionTokenBalance>>::from(meta.pre_token_balances).unwrap_or_default(),
+        Option::<Vec<UiTransactionTokenBalance>>::from(meta.post_token_balances)
+            .unwrap_or_default(),
+    ))
+}
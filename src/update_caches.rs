@@ -1,147 +1,357 @@
 use {
     crate::{
-        FinalizeLockedStakesCacheThreadSafe, IndexedStakingAccountsThreadSafe,
-        IndexedUserStakingAccountsThreadSafe, StakingRoundNextResolveTimeCacheThreadSafe,
-        UserStakingClaimCacheThreadSafe,
+        action_retries::{ActionAttempts, ActionStatus},
+        pending_rewards::unclaimed_resolved_rounds,
+        state_manager::StateManager,
+        FinalizeLockedStakesCacheThreadSafe, IndexedUserStakingAccountsThreadSafe,
+        UserStakingClaimCacheThreadSafe, AUTO_CLAIM_THRESHOLD_SECONDS,
+        FORCE_CLAIM_UNCLAIMED_ROUNDS,
     },
     adrena_abi::{Pubkey, Staking, UserStaking, ROUND_MIN_DURATION_SECONDS},
+    dashmap::DashMap,
     rand::{thread_rng, Rng},
-    std::{cmp::min, collections::HashMap},
+    serde::{Deserialize, Serialize},
+    std::{collections::HashMap, sync::OnceLock},
 };
 
-pub async fn update_staking_round_next_resolve_time_cache_for_account(
-    staking_round_next_resolve_time_cache: &StakingRoundNextResolveTimeCacheThreadSafe,
-    staking_account_key: &Pubkey,
-    staking_account: &Staking,
+// Auto claim thresholds of the locked and liquid stakes, set once at startup - AUTO_CLAIM_THRESHOLD_SECONDS if never set
+static LOCKED_CLAIM_THRESHOLD_SECONDS: OnceLock<i64> = OnceLock::new();
+static LIQUID_CLAIM_THRESHOLD_SECONDS: OnceLock<i64> = OnceLock::new();
+
+pub fn init_claim_thresholds(
+    locked_claim_threshold_seconds: i64,
+    liquid_claim_threshold_seconds: i64,
 ) {
-    let current_time = chrono::Utc::now().timestamp();
+    let _ = LOCKED_CLAIM_THRESHOLD_SECONDS.set(locked_claim_threshold_seconds);
+    let _ = LIQUID_CLAIM_THRESHOLD_SECONDS.set(liquid_claim_threshold_seconds);
+}
+
+fn locked_claim_threshold_seconds() -> i64 {
+    *LOCKED_CLAIM_THRESHOLD_SECONDS
+        .get()
+        .unwrap_or(&AUTO_CLAIM_THRESHOLD_SECONDS)
+}
+
+fn liquid_claim_threshold_seconds() -> i64 {
+    *LIQUID_CLAIM_THRESHOLD_SECONDS
+        .get()
+        .unwrap_or(&AUTO_CLAIM_THRESHOLD_SECONDS)
+}
+
+// Claim times of the oldest locked stake and of the liquid stake of a UserStaking account - None if there is no such stake
+// A claim collects both, but each kind of stake has its own threshold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaimTimes {
+    pub locked: Option<i64>,
+    pub liquid: Option<i64>,
+}
+
+impl ClaimTimes {
+    pub fn of(user_staking_account: &UserStaking) -> Self {
+        let locked = user_staking_account
+            .locked_stakes
+            .iter()
+            .filter(|stake| stake.amount != 0)
+            .map(|stake| stake.claim_time)
+            .min();
+        let liquid = (user_staking_account.liquid_stake.amount != 0)
+            .then_some(user_staking_account.liquid_stake.claim_time);
+        Self { locked, liquid }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locked.is_none() && self.liquid.is_none()
+    }
+
+    // Oldest claim time across both kinds of stakes
+    pub fn oldest(&self) -> Option<i64> {
+        self.locked.into_iter().chain(self.liquid).min()
+    }
+
+    // Time at which the first of the two thresholds is reached (before the jitter)
+    pub fn next_claim_time(&self) -> Option<i64> {
+        let locked = self
+            .locked
+            .map(|claim_time| claim_time + locked_claim_threshold_seconds());
+        let liquid = self
+            .liquid
+            .map(|claim_time| claim_time + liquid_claim_threshold_seconds());
+        locked.into_iter().chain(liquid).min()
+    }
+
+    // As if both kinds of stakes were claimed at claim_time
+    pub fn claimed_at(self, claim_time: i64) -> Self {
+        Self {
+            locked: self.locked.map(|_| claim_time),
+            liquid: self.liquid.map(|_| claim_time),
+        }
+    }
+
+    // Postpone the next claim to next_claim_time, whatever the kind of stakes
+    pub fn due_at(self, next_claim_time: i64) -> Self {
+        Self {
+            locked: self
+                .locked
+                .map(|_| next_claim_time - locked_claim_threshold_seconds()),
+            liquid: self
+                .liquid
+                .map(|_| next_claim_time - liquid_claim_threshold_seconds()),
+        }
+    }
+}
+
+// Claims are spread over this window past the auto claim threshold, set once at startup - no jitter if never set (or 0)
+static CLAIM_JITTER_WINDOW_SECONDS: OnceLock<i64> = OnceLock::new();
+
+pub fn init_claim_jitter_window(claim_jitter_window_seconds: i64) {
+    let _ = CLAIM_JITTER_WINDOW_SECONDS.set(claim_jitter_window_seconds);
+}
+
+// Deterministic per account delay added to the auto claim threshold, so the users who staked together are not all claimed
+// in the same minute - pubkeys are uniformly distributed, their first bytes are as good as a hash
+pub fn claim_jitter_seconds(user_staking_account_key: &Pubkey) -> i64 {
+    match CLAIM_JITTER_WINDOW_SECONDS.get() {
+        Some(&window) if window > 0 => {
+            let key_bytes = user_staking_account_key.to_bytes();
+            let key_prefix =
+                u64::from_le_bytes(key_bytes[..8].try_into().expect("32 bytes pubkey"));
+            (key_prefix % window as u64) as i64
+        }
+        _ => 0,
+    }
+}
+
+// Time at which the claim of a UserStaking account is due, None without stakes - the claim checks only pop the due
+// accounts from the claim queue of the state manager instead of going through the whole claim cache
+pub fn claim_due_time(user_staking_account_key: &Pubkey, claim_times: &ClaimTimes) -> Option<i64> {
+    Some(claim_times.next_claim_time()? + claim_jitter_seconds(user_staking_account_key))
+}
+
+pub async fn schedule_claims(
+    state_manager: &StateManager,
+    claims: Vec<(Pubkey, i64)>,
+) -> anyhow::Result<()> {
+    if claims.is_empty() {
+        return Ok(());
+    }
+    state_manager
+        .call(move |state| {
+            for (user_staking_account_key, claim_time) in claims {
+                state.schedule_claim(user_staking_account_key, claim_time);
+            }
+        })
+        .await
+}
+
+pub async fn schedule_claim(
+    state_manager: &StateManager,
+    user_staking_account_key: Pubkey,
+    claim_time: i64,
+) -> anyhow::Result<()> {
+    schedule_claims(state_manager, vec![(user_staking_account_key, claim_time)]).await
+}
+
+// A claim failed: popped again once its backoff expires, the abandoned ones wait for an account update
+pub async fn requeue_failed_claim(
+    state_manager: &StateManager,
+    user_staking_account_key: &Pubkey,
+    attempts: &ActionAttempts,
+) -> anyhow::Result<()> {
+    if attempts.status != ActionStatus::Retrying {
+        return Ok(());
+    }
+    schedule_claim(
+        state_manager,
+        *user_staking_account_key,
+        attempts.next_attempt_time,
+    )
+    .await
+}
+
+// Change the claim times of a cached UserStaking account and queue its new due time - false if it isn't in the cache
+pub async fn reschedule_claim(
+    state_manager: &StateManager,
+    claim_cache: &UserStakingClaimCacheThreadSafe,
+    user_staking_account_key: &Pubkey,
+    reschedule: impl FnOnce(ClaimTimes) -> ClaimTimes,
+) -> anyhow::Result<bool> {
+    let due_time = {
+        let Some(mut claim_times) = claim_cache.get_mut(user_staking_account_key) else {
+            return Ok(false);
+        };
+        *claim_times = reschedule(*claim_times);
+        claim_due_time(user_staking_account_key, &claim_times)
+    };
+    if let Some(due_time) = due_time {
+        schedule_claim(state_manager, *user_staking_account_key, due_time).await?;
+    }
+    Ok(true)
+}
+
+pub async fn pop_due_claims(
+    state_manager: &StateManager,
+    current_time: i64,
+) -> anyhow::Result<Vec<Pubkey>> {
+    state_manager
+        .call(move |state| state.pop_due_claims(current_time))
+        .await
+}
+
+// A round of the Staking account was resolved: queue the UserStaking accounts of its side with too many unclaimed
+// resolved rounds, their claim is forced before the threshold (once per round, see process_claim_stakes)
+pub async fn schedule_forced_claims(
+    state_manager: &StateManager,
+    claim_cache: &UserStakingClaimCacheThreadSafe,
+    user_staking_accounts: &DashMap<Pubkey, UserStaking>,
+    staking_account: &Staking,
+) -> anyhow::Result<()> {
+    let mut forced_claims: Vec<(Pubkey, i64)> = vec![];
+    for entry in user_staking_accounts.iter() {
+        if unclaimed_resolved_rounds(entry.value(), staking_account) < FORCE_CLAIM_UNCLAIMED_ROUNDS
+        {
+            continue;
+        }
+        let last_claim_time = claim_cache
+            .get(entry.key())
+            .and_then(|claim_times| claim_times.oldest());
+        if let Some(last_claim_time) = last_claim_time {
+            forced_claims.push((*entry.key(), last_claim_time + ROUND_MIN_DURATION_SECONDS));
+        }
+    }
+    if !forced_claims.is_empty() {
+        log::info!(
+            "  <> {} UserStaking accounts queued for a forced claim (unclaimed resolved rounds)",
+            forced_claims.len()
+        );
+    }
+    schedule_claims(state_manager, forced_claims).await
+}
+
+fn next_resolve_time(staking_account: &Staking, current_time: i64) -> i64 {
     // How long has the current staking round been running for?
     let round_current_duration = current_time - staking_account.current_staking_round.start_time;
     // If the current round has been running for longer than the minimum duration, set the next resolve time to be now
-    let next_resolve_time = if round_current_duration >= ROUND_MIN_DURATION_SECONDS {
+    if round_current_duration >= ROUND_MIN_DURATION_SECONDS {
         current_time
     } else {
         staking_account.current_staking_round.start_time
             + ROUND_MIN_DURATION_SECONDS
             + thread_rng().gen_range(600..1800) // adding random + 10 to 60 min, to prevent staggering all claims together, and also for taking into account the slight delay in round execution
-    };
+    }
+}
 
-    staking_round_next_resolve_time_cache
-        .write()
+pub async fn update_staking_round_next_resolve_time_cache_for_account(
+    state_manager: &StateManager,
+    staking_account_key: &Pubkey,
+    staking_account: &Staking,
+) -> anyhow::Result<()> {
+    let next_resolve_time = next_resolve_time(staking_account, chrono::Utc::now().timestamp());
+    let staking_account_key = *staking_account_key;
+    state_manager
+        .call(move |state| state.schedule_resolve(staking_account_key, next_resolve_time))
         .await
-        .insert(*staking_account_key, next_resolve_time);
 }
 
 pub async fn update_staking_round_next_resolve_time_cache(
-    staking_round_next_resolve_time_cache: &StakingRoundNextResolveTimeCacheThreadSafe,
-    indexed_staking_accounts: &IndexedStakingAccountsThreadSafe,
-) {
+    state_manager: &StateManager,
+) -> anyhow::Result<()> {
+    let current_time = chrono::Utc::now().timestamp();
     // For each Staking account, look at when the current staking round ends and set the next resolve time cache to be that time + the minimum round duration (6h)
-    for (staking_account_key, staking_account) in indexed_staking_accounts.read().await.iter() {
-        update_staking_round_next_resolve_time_cache_for_account(
-            staking_round_next_resolve_time_cache,
-            staking_account_key,
-            staking_account,
-        )
-        .await;
-    }
+    state_manager
+        .call(move |state| {
+            let next_resolve_times: Vec<(Pubkey, i64)> = state
+                .staking_accounts
+                .iter()
+                .map(|(staking_account_key, staking_account)| {
+                    (
+                        *staking_account_key,
+                        next_resolve_time(staking_account, current_time),
+                    )
+                })
+                .collect();
+            for (staking_account_key, next_resolve_time) in next_resolve_times {
+                state.schedule_resolve(staking_account_key, next_resolve_time);
+            }
+        })
+        .await
 }
 
-// Update the claim cache with the claim time of the oldest locked stake for each user staking account
+// Update the claim cache with the claim times of the oldest locked stake and of the liquid stake for each user staking account
+// The claim queue is rebuilt along
 pub async fn update_claim_cache(
+    state_manager: &StateManager,
     claim_cache: &UserStakingClaimCacheThreadSafe,
     indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
-) {
-    for (user_staking_account_key, user_staking_account) in
-        indexed_user_staking_accounts.read().await.iter()
-    {
-        update_claim_cache_for_account(
-            claim_cache,
-            *user_staking_account_key,
-            user_staking_account,
-        )
-        .await;
+) -> anyhow::Result<()> {
+    let mut claims: Vec<(Pubkey, i64)> = vec![];
+    for entry in indexed_user_staking_accounts.iter() {
+        claims.extend(cache_claim_times(claim_cache, *entry.key(), entry.value()));
     }
+    state_manager
+        .call(move |state| {
+            state.clear_claims();
+            for (user_staking_account_key, due_time) in claims {
+                state.schedule_claim(user_staking_account_key, due_time);
+            }
+        })
+        .await
 }
 
-/// Update the claim cache with the claim time of the oldest locked stake for a given UserStaking account
+/// Update the claim cache with the claim times of the oldest locked stake and of the liquid stake for a given UserStaking account
 pub async fn update_claim_cache_for_account(
+    state_manager: &StateManager,
     claim_cache: &UserStakingClaimCacheThreadSafe,
     account_key: Pubkey,
     user_staking_account: &UserStaking,
-) {
-    let has_locked_stakes = user_staking_account
-        .locked_stakes
-        .iter()
-        .any(|stake| stake.amount != 0);
-    let has_liquid_stake = user_staking_account.liquid_stake.amount != 0;
-
-    let oldest_claim_time_locked = if has_locked_stakes {
-        user_staking_account
-            .locked_stakes
-            .iter()
-            .filter(|stake| stake.amount != 0)
-            .map(|stake| stake.claim_time)
-            .min()
-    } else {
-        None
-    };
-
-    let oldest_claim_time_liquid = if has_liquid_stake {
-        Some(user_staking_account.liquid_stake.claim_time)
-    } else {
-        None
-    };
-
-    let oldest_claim_time = match (oldest_claim_time_locked, oldest_claim_time_liquid) {
-        (Some(locked), Some(liquid)) => Some(min(locked, liquid)),
-        (Some(locked), None) => Some(locked),
-        (None, Some(liquid)) => Some(liquid),
-        (None, None) => None,
-    };
+) -> anyhow::Result<()> {
+    match cache_claim_times(claim_cache, account_key, user_staking_account) {
+        Some(due_time) => schedule_claim(state_manager, account_key, due_time).await,
+        None => Ok(()),
+    }
+}
 
-    claim_cache
-        .write()
-        .await
-        .insert(account_key, oldest_claim_time);
+// Returns the due time of the claim, None once all the stakes were withdrawn
+fn cache_claim_times(
+    claim_cache: &UserStakingClaimCacheThreadSafe,
+    account_key: Pubkey,
+    user_staking_account: &UserStaking,
+) -> Option<i64> {
+    let claim_times = ClaimTimes::of(user_staking_account);
+    if claim_times.is_empty() {
+        // All the stakes were withdrawn: the account leaves the cache until new stakes appear (account update)
+        claim_cache.remove(&account_key);
+        return None;
+    }
+    claim_cache.insert(account_key, claim_times);
+    claim_due_time(&account_key, &claim_times)
 }
 
-pub async fn update_finalize_locked_stakes_cache(
+pub fn update_finalize_locked_stakes_cache(
     finalize_locked_stakes_cache: &FinalizeLockedStakesCacheThreadSafe,
     indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
 ) {
-    for (user_staking_account_key, user_staking_account) in
-        indexed_user_staking_accounts.read().await.iter()
-    {
+    for entry in indexed_user_staking_accounts.iter() {
         update_finalize_locked_stakes_cache_for_account(
             finalize_locked_stakes_cache,
-            user_staking_account_key,
-            user_staking_account,
-        )
-        .await;
+            entry.key(),
+            entry.value(),
+        );
     }
 }
 
-pub async fn update_finalize_locked_stakes_cache_for_account(
+pub fn update_finalize_locked_stakes_cache_for_account(
     finalize_locked_stakes_cache: &FinalizeLockedStakesCacheThreadSafe,
     user_staking_account_key: &Pubkey,
     user_staking_account: &UserStaking,
 ) {
     // Remove all elements from the cache (in order to not reprocess the same finalize stake multiple times)
-    finalize_locked_stakes_cache
-        .write()
-        .await
+    let mut locked_stakes = finalize_locked_stakes_cache
         .entry(*user_staking_account_key)
         .or_insert_with(HashMap::new);
     //
     for ls in user_staking_account.locked_stakes.iter() {
         if ls.amount != 0 && ls.resolved == 0 {
-            finalize_locked_stakes_cache
-                .write()
-                .await
-                .entry(*user_staking_account_key)
-                .or_insert_with(HashMap::new)
-                .insert(ls.id, ls.end_time);
+            locked_stakes.insert(ls.id, ls.end_time);
         }
     }
 }
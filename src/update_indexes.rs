@@ -1,12 +1,218 @@
 use {
     crate::{
+        metrics::USER_STAKING_ACCOUNTS_MISSING_STAKING_TYPE,
         process_stream_message::{StakingAccountUpdate, UserStakingAccountUpdate},
-        IndexedStakingAccountsThreadSafe, IndexedUserStakingAccountsThreadSafe,
+        rate_limiter::rpc_call,
+        staked_amounts::update_staked_amounts,
+        state_manager::StateManager,
+        user_staking_layout::decode_user_staking,
+        IndexedUserStakingAccountsThreadSafe, STAKING_ACCOUNT_SIZE, USER_STAKING_ACCOUNT_SIZE,
     },
-    adrena_abi::{AccountDeserialize, Staking, UserStaking},
-    solana_sdk::pubkey::Pubkey,
+    adrena_abi::{AccountDeserialize, Discriminator, Staking, UserStaking},
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
+    solana_client::{
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_sdk::{account::Account, pubkey::Pubkey},
+    std::{mem::offset_of, time::Duration},
 };
 
+// Attempts per startup getProgramAccounts chunk before giving up (and reconnecting)
+const PROGRAM_ACCOUNTS_CHUNK_MAX_ATTEMPTS: u32 = 3;
+const PROGRAM_ACCOUNTS_CHUNK_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+// The startup scan of the UserStaking accounts is split by staking type (0: not defined yet, 1: LM, 2: LP)
+const USER_STAKING_TYPE_CHUNKS: [u8; 3] = [0, 1, 2];
+
+// Startup scan of the Staking accounts
+pub async fn fetch_staking_accounts(
+    rpc_client: &RpcClient,
+) -> anyhow::Result<Vec<(Pubkey, Staking)>> {
+    let accounts = get_program_accounts_with_retries(
+        rpc_client,
+        RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, Staking::DISCRIMINATOR.to_vec())),
+                RpcFilterType::DataSize(STAKING_ACCOUNT_SIZE),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        "Staking",
+    )
+    .await?;
+
+    let mut staking_accounts = Vec::with_capacity(accounts.len());
+    for (key, account) in accounts {
+        staking_accounts.push((key, Staking::try_deserialize(&mut &account.data[..])?));
+    }
+    Ok(staking_accounts)
+}
+
+// Startup scan of the UserStaking accounts, only requesting the bytes the keeper needs (dataSlice)
+// The missing bytes are zeroed when deserializing - the handlers only use the fetched fields, and the stream updates bring the full accounts
+// One request per staking type, each retried on its own - a failing chunk doesn't restart the whole scan
+pub async fn fetch_user_staking_accounts_data_sliced(
+    rpc_client: &RpcClient,
+) -> anyhow::Result<Vec<(Pubkey, Vec<u8>)>> {
+    // The keeper only reads the UserStaking fields up to the locked stakes (staking type, liquid stake, locked stakes claim/end times)
+    let indexed_data_len = 8
+        + offset_of!(UserStaking, locked_stakes)
+        + field_size(|user_staking: &UserStaking| &user_staking.locked_stakes);
+    let staking_type_offset = 8 + offset_of!(UserStaking, staking_type);
+
+    let mut user_staking_accounts = vec![];
+    for staking_type in USER_STAKING_TYPE_CHUNKS {
+        let accounts = get_program_accounts_with_retries(
+            rpc_client,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                        0,
+                        UserStaking::DISCRIMINATOR.to_vec(),
+                    )),
+                    RpcFilterType::DataSize(USER_STAKING_ACCOUNT_SIZE),
+                    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                        staking_type_offset,
+                        vec![staking_type],
+                    )),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: Some(UiDataSliceConfig {
+                        offset: 0,
+                        length: indexed_data_len,
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            &format!("UserStaking (staking type {})", staking_type),
+        )
+        .await?;
+        log::info!(
+            "  <> # of UserStaking accounts with staking type {}: {}",
+            staking_type,
+            accounts.len()
+        );
+        user_staking_accounts.extend(
+            accounts
+                .into_iter()
+                .map(|(key, account)| (key, account.data)),
+        );
+    }
+    Ok(user_staking_accounts)
+}
+
+// Number of program accounts with the discriminator, with or without the expected size (no data fetched)
+async fn count_program_accounts(
+    rpc_client: &RpcClient,
+    discriminator: &[u8],
+    data_size: Option<u64>,
+    chunk_name: &str,
+) -> anyhow::Result<usize> {
+    let mut filters = vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+        0,
+        discriminator.to_vec(),
+    ))];
+    filters.extend(data_size.map(RpcFilterType::DataSize));
+
+    let accounts = get_program_accounts_with_retries(
+        rpc_client,
+        RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice: Some(UiDataSliceConfig {
+                    offset: 0,
+                    length: 0,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        chunk_name,
+    )
+    .await?;
+    Ok(accounts.len())
+}
+
+// After a program upgrade: the accounts whose size no longer matches the ABI are silently left out of the indexes
+pub async fn check_account_layouts(rpc_client: &RpcClient) -> anyhow::Result<()> {
+    for (name, discriminator, account_size) in [
+        ("Staking", &Staking::DISCRIMINATOR[..], STAKING_ACCOUNT_SIZE),
+        (
+            "UserStaking",
+            &UserStaking::DISCRIMINATOR[..],
+            USER_STAKING_ACCOUNT_SIZE,
+        ),
+    ] {
+        let all_accounts = count_program_accounts(rpc_client, discriminator, None, name).await?;
+        let decodable_accounts =
+            count_program_accounts(rpc_client, discriminator, Some(account_size), name).await?;
+        if decodable_accounts != all_accounts {
+            log::error!(
+                "  <*> {} of {} {} accounts don't have the expected size ({} bytes) - the account layout may have changed, check adrena-abi",
+                all_accounts - decodable_accounts,
+                all_accounts,
+                name,
+                account_size
+            );
+        } else {
+            log::info!(
+                "  <> {} accounts layout check passed ({} accounts)",
+                name,
+                all_accounts
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn get_program_accounts_with_retries(
+    rpc_client: &RpcClient,
+    config: RpcProgramAccountsConfig,
+    chunk_name: &str,
+) -> anyhow::Result<Vec<(Pubkey, Account)>> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        // The rate limited attempts are retried apart
+        match rpc_call(|| {
+            rpc_client.get_program_accounts_with_config(&adrena_abi::ID, config.clone())
+        })
+        .await
+        {
+            Ok(accounts) => return Ok(accounts),
+            Err(e) if attempts < PROGRAM_ACCOUNTS_CHUNK_MAX_ATTEMPTS => {
+                log::warn!(
+                    "  <> Fetching the {} accounts failed (attempt {}/{}): {:?} - Retrying...",
+                    chunk_name,
+                    attempts,
+                    PROGRAM_ACCOUNTS_CHUNK_MAX_ATTEMPTS,
+                    e
+                );
+                tokio::time::sleep(PROGRAM_ACCOUNTS_CHUNK_RETRY_DELAY * attempts).await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// Deserialize UserStaking data fetched with a dataSlice (or full), the missing bytes are zeroed
+pub fn deserialize_user_staking_sliced(data: &[u8]) -> anyhow::Result<UserStaking> {
+    decode_user_staking(data)
+}
+
+fn field_size<T, F>(_field: fn(&T) -> &F) -> usize {
+    std::mem::size_of::<F>()
+}
+
 // Updates the indexed Staking accounts map based on the received account data.
 // - Creates a new entry if the Staking account is not indexed.
 // - Deletes the entry if the account data is empty (Staking account closed).
@@ -16,21 +222,30 @@ use {
 pub async fn update_indexed_staking_accounts(
     staking_account_key: &Pubkey,
     staking_account_data: &[u8],
-    indexed_staking_accounts: &IndexedStakingAccountsThreadSafe,
+    state_manager: &StateManager,
 ) -> Result<StakingAccountUpdate, backoff::Error<anyhow::Error>> {
-    let mut staking_accounts = indexed_staking_accounts.write().await;
+    let staking_account_key = *staking_account_key;
 
     if staking_account_data.is_empty() {
-        staking_accounts.remove(staking_account_key);
+        state_manager
+            .call(move |state| state.staking_accounts.remove(&staking_account_key))
+            .await
+            .map_err(backoff::Error::permanent)?;
         return Ok(StakingAccountUpdate::Closed);
     }
 
     let staking_account = Staking::try_deserialize(&mut &staking_account_data[..])
         .map_err(|e| backoff::Error::transient(e.into()))?;
 
-    let is_new_staking_account = staking_accounts
-        .insert(*staking_account_key, staking_account)
-        .is_none();
+    let is_new_staking_account = state_manager
+        .call(move |state| {
+            state
+                .staking_accounts
+                .insert(staking_account_key, staking_account)
+                .is_none()
+        })
+        .await
+        .map_err(backoff::Error::permanent)?;
 
     if is_new_staking_account {
         Ok(StakingAccountUpdate::Created(staking_account))
@@ -43,32 +258,60 @@ pub async fn update_indexed_staking_accounts(
 // - Creates a new entry if the UserStaking account is not indexed.
 // - Deletes the entry if the account data is empty (UserStaking account closed).
 // - Updates the entry if the UserStaking account is indexed and data is non-empty (UserStaking account modified).
+// - Keeps the UserStaking accounts without staking type aside, they are created in the index once their type is set.
 //
 // Returns an enum with the update type and the UserStaking account if it was created or modified
 pub async fn update_indexed_user_staking_accounts(
     user_staking_account_key: &Pubkey,
     user_staking_account_data: &[u8],
     indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+    state_manager: &StateManager,
 ) -> Result<UserStakingAccountUpdate, backoff::Error<anyhow::Error>> {
-    let mut user_staking_accounts = indexed_user_staking_accounts.write().await;
+    let key = *user_staking_account_key;
 
     if user_staking_account_data.is_empty() {
-        user_staking_accounts.remove(user_staking_account_key);
+        if let Some((_, previous)) = indexed_user_staking_accounts.remove(user_staking_account_key)
+        {
+            update_staked_amounts(Some(&previous), None);
+        }
+        let (_, missing_staking_type_count) = state_manager
+            .call(move |state| state.remove_missing_staking_type(&key))
+            .await
+            .map_err(backoff::Error::permanent)?;
+        USER_STAKING_ACCOUNTS_MISSING_STAKING_TYPE.set(missing_staking_type_count as i64);
         return Ok(UserStakingAccountUpdate::Closed);
     }
 
-    let user_staking_account = UserStaking::try_deserialize(&mut &user_staking_account_data[..])
-        .map_err(|e| backoff::Error::transient(e.into()))?;
+    let user_staking_account =
+        decode_user_staking(user_staking_account_data).map_err(backoff::Error::transient)?;
 
     if user_staking_account.staking_type == 0 {
+        let missing_staking_type_count = state_manager
+            .call(move |state| state.add_missing_staking_type(&key))
+            .await
+            .map_err(backoff::Error::permanent)?;
+        USER_STAKING_ACCOUNTS_MISSING_STAKING_TYPE.set(missing_staking_type_count as i64);
         return Ok(UserStakingAccountUpdate::MissingStakingType(
             user_staking_account,
         ));
     }
 
-    let is_new_user_staking_account = user_staking_accounts
-        .insert(*user_staking_account_key, user_staking_account)
-        .is_none();
+    let (was_missing_staking_type, missing_staking_type_count) = state_manager
+        .call(move |state| state.remove_missing_staking_type(&key))
+        .await
+        .map_err(backoff::Error::permanent)?;
+    if was_missing_staking_type {
+        log::info!(
+            "  <> UserStaking account {} staking type defined - promoted to the index",
+            user_staking_account_key
+        );
+        USER_STAKING_ACCOUNTS_MISSING_STAKING_TYPE.set(missing_staking_type_count as i64);
+    }
+
+    let previous =
+        indexed_user_staking_accounts.insert(*user_staking_account_key, user_staking_account);
+    update_staked_amounts(previous.as_ref(), Some(&user_staking_account));
+    let is_new_user_staking_account = previous.is_none();
 
     if is_new_user_staking_account {
         Ok(UserStakingAccountUpdate::Created(user_staking_account))
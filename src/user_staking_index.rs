@@ -0,0 +1,86 @@
+use {
+    adrena_abi::{StakingType, UserStaking},
+    dashmap::{
+        mapref::{multiple::RefMulti, one::Ref},
+        DashMap,
+    },
+    solana_sdk::pubkey::Pubkey,
+};
+
+// The indexed UserStaking accounts, one sharded map per staking type (LM: ADX stakers, LP: ALP stakers) - the work done
+// for one staking side only walks its own map, and the whole index stays usable as a single map
+// Only the accounts with their staking type defined are indexed
+#[derive(Debug, Default)]
+pub struct UserStakingIndex {
+    lm: DashMap<Pubkey, UserStaking>,
+    lp: DashMap<Pubkey, UserStaking>,
+}
+
+impl UserStakingIndex {
+    pub fn shard(&self, staking_type: StakingType) -> &DashMap<Pubkey, UserStaking> {
+        match staking_type {
+            StakingType::LM => &self.lm,
+            StakingType::LP => &self.lp,
+        }
+    }
+
+    pub fn get(&self, user_staking_account_key: &Pubkey) -> Option<Ref<'_, Pubkey, UserStaking>> {
+        self.lm
+            .get(user_staking_account_key)
+            .or_else(|| self.lp.get(user_staking_account_key))
+    }
+
+    pub fn contains_key(&self, user_staking_account_key: &Pubkey) -> bool {
+        self.lm.contains_key(user_staking_account_key)
+            || self.lp.contains_key(user_staking_account_key)
+    }
+
+    // Returns the previous account, whichever shard it was in
+    pub fn insert(
+        &self,
+        user_staking_account_key: Pubkey,
+        user_staking_account: UserStaking,
+    ) -> Option<UserStaking> {
+        let (shard, other_shard) = match user_staking_account.get_staking_type() {
+            StakingType::LM => (&self.lm, &self.lp),
+            StakingType::LP => (&self.lp, &self.lm),
+        };
+        // The staking type is set once by the program, this only guards against a stale entry in the other shard
+        let moved = other_shard
+            .remove(&user_staking_account_key)
+            .map(|(_, previous)| previous);
+        shard
+            .insert(user_staking_account_key, user_staking_account)
+            .or(moved)
+    }
+
+    pub fn remove(&self, user_staking_account_key: &Pubkey) -> Option<(Pubkey, UserStaking)> {
+        self.lm
+            .remove(user_staking_account_key)
+            .or_else(|| self.lp.remove(user_staking_account_key))
+    }
+
+    pub fn extend(&self, user_staking_accounts: impl IntoIterator<Item = (Pubkey, UserStaking)>) {
+        for (user_staking_account_key, user_staking_account) in user_staking_accounts {
+            self.insert(user_staking_account_key, user_staking_account);
+        }
+    }
+
+    pub fn clear(&self) {
+        self.lm.clear();
+        self.lp.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lm.len() + self.lp.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lm.is_empty() && self.lp.is_empty()
+    }
+
+    // Both shards, LM first
+    pub fn iter(&self) -> impl Iterator<Item = RefMulti<'_, Pubkey, UserStaking>> {
+        self.lm.iter().chain(self.lp.iter())
+    }
+}
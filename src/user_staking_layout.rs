@@ -0,0 +1,15 @@
+use {
+    crate::USER_STAKING_ACCOUNT_SIZE,
+    adrena_abi::{AccountDeserialize, UserStaking},
+};
+
+// Decode UserStaking data, fetched in full or with a dataSlice (the missing bytes are zeroed)
+pub fn decode_user_staking(account_data: &[u8]) -> anyhow::Result<UserStaking> {
+    let account_size = USER_STAKING_ACCOUNT_SIZE as usize;
+    if account_data.len() >= account_size {
+        return Ok(UserStaking::try_deserialize(&mut &account_data[..])?);
+    }
+    let mut account_data = account_data.to_vec();
+    account_data.resize(account_size, 0);
+    Ok(UserStaking::try_deserialize(&mut &account_data[..])?)
+}
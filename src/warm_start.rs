@@ -0,0 +1,257 @@
+use {
+    crate::{
+        db::Db,
+        handler_error,
+        health::record_db_outcome,
+        state_manager::StateManager,
+        state_snapshot::{index_staking_accounts, index_user_staking_accounts_delta},
+        update_indexes::deserialize_user_staking_sliced,
+        IndexedUserStakingAccountsThreadSafe,
+    },
+    adrena_abi::UserStaking,
+    solana_client::nonblocking::rpc_client::RpcClient,
+    solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey},
+    std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            LazyLock, Mutex, OnceLock,
+        },
+    },
+};
+
+// Same as the state snapshots: the stream replays the changes since the checkpoint slot, the Geyser endpoints only keep a few minutes of updates
+const MAX_WARM_START_STATE_AGE_SECONDS: i64 = 600;
+
+// UserStaking accounts written (Some) or deleted (None) by the stream updates, waiting to be stored (flushed by the core loop)
+// Only the last change of an account is kept
+static PENDING_USER_STAKING_WRITES: LazyLock<Mutex<HashMap<Pubkey, Option<Vec<u8>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Set when the UserStaking accounts of a full scan could not be stored (or the closed ones pruned): the stored accounts
+// miss changes the stream won't replay, no checkpoint is saved until the next full scan is stored
+static SCANNED_ACCOUNTS_STORE_FAILED: AtomicBool = AtomicBool::new(false);
+
+// Set once at startup - If enabled, the indexed UserStaking accounts are stored in the DB as updates arrive
+static WARM_START: OnceLock<bool> = OnceLock::new();
+
+// Set once at startup - Longest downtime after which the stored accounts are indexed as is, without fetching the new and closed ones (0 disables)
+static MAX_RESUME_DOWNTIME_SECONDS: OnceLock<i64> = OnceLock::new();
+
+pub fn init_warm_start(enabled: bool, max_resume_downtime_seconds: i64) {
+    let _ = WARM_START.set(enabled);
+    let _ = MAX_RESUME_DOWNTIME_SECONDS.set(max_resume_downtime_seconds);
+}
+
+pub fn is_warm_start_enabled() -> bool {
+    WARM_START.get().copied().unwrap_or(false)
+}
+
+// Whether the stored UserStaking accounts are indexed as is after this downtime (--max-resume-downtime-seconds), the
+// stream replaying the changes since the checkpoint - after a longer one the new and closed accounts are fetched first
+fn can_resume(downtime: i64) -> bool {
+    let max_resume_downtime_seconds = MAX_RESUME_DOWNTIME_SECONDS.get().copied().unwrap_or(0);
+    max_resume_downtime_seconds != 0 && downtime <= max_resume_downtime_seconds
+}
+
+// Save the highest slot processed from the stream - the UserStaking accounts updated up to it must be stored already
+// (see flush_user_staking_accounts)
+// Not fatal, the next restart resumes from an older slot or reconciles with the chain
+pub async fn save_stream_checkpoint(db: Option<&Db>, slot: u64) {
+    let Some(db) = db.filter(|_| is_warm_start_enabled()) else {
+        return;
+    };
+    // Nothing processed yet
+    if slot == 0 {
+        return;
+    }
+    // Logged once by store_scanned_user_staking_accounts
+    if SCANNED_ACCOUNTS_STORE_FAILED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Err(e) = db
+        .upsert_stream_checkpoint(slot, chrono::Utc::now().timestamp())
+        .await
+    {
+        log::warn!("   <> Failed to save the stream checkpoint: {:?}", e);
+    }
+}
+
+// Seed the indexes from the UserStaking accounts stored in the DB, then only fetch what changed since (see index_user_staking_accounts_delta)
+// Returns the stream checkpoint slot: the stored accounts contain all the changes up to it, the accounts modified since
+// are only current once the stream replayed the updates from it
+// After a brief downtime (see can_resume), only the Staking accounts are fetched
+pub async fn warm_start_indexes(
+    db: &Db,
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    state_manager: &StateManager,
+    indexed_user_staking_accounts: &IndexedUserStakingAccountsThreadSafe,
+) -> anyhow::Result<u64> {
+    let Some((checkpoint_slot, saved_at)) =
+        db.load_stream_checkpoint().await.map_err(handler_error)?
+    else {
+        return Err(anyhow::anyhow!("No stream checkpoint stored in the DB"));
+    };
+    let downtime = chrono::Utc::now().timestamp() - saved_at;
+    if downtime > MAX_WARM_START_STATE_AGE_SECONDS {
+        return Err(anyhow::anyhow!(
+            "Stream checkpoint stored in the DB is too old ({}s, max {}s)",
+            downtime,
+            MAX_WARM_START_STATE_AGE_SECONDS
+        ));
+    }
+    let stored_user_staking_accounts = db
+        .load_user_staking_accounts()
+        .await
+        .map_err(handler_error)?;
+    let Some(last_update) = stored_user_staking_accounts
+        .iter()
+        .map(|stored| stored.updated_at)
+        .max()
+    else {
+        return Err(anyhow::anyhow!("No UserStaking accounts stored in the DB"));
+    };
+    log::info!(
+        "  <> Warm start from the DB ({} UserStaking accounts, last update {}s ago, checkpoint at slot {} saved {}s ago)",
+        stored_user_staking_accounts.len(),
+        chrono::Utc::now().timestamp() - last_update,
+        checkpoint_slot,
+        downtime
+    );
+
+    let mut user_staking_accounts: HashMap<Pubkey, UserStaking> =
+        HashMap::with_capacity(stored_user_staking_accounts.len());
+    for stored in stored_user_staking_accounts.iter() {
+        user_staking_accounts.insert(stored.key, deserialize_user_staking_sliced(&stored.data)?);
+    }
+
+    if can_resume(downtime) {
+        index_staking_accounts(rpc_client, commitment, state_manager).await?;
+        indexed_user_staking_accounts.extend(user_staking_accounts);
+        log::info!(
+            "  <> Resuming from slot {}, the stream replays the changes since",
+            checkpoint_slot
+        );
+        return Ok(checkpoint_slot);
+    }
+
+    let delta = index_user_staking_accounts_delta(
+        user_staking_accounts,
+        rpc_client,
+        commitment,
+        state_manager,
+        indexed_user_staking_accounts,
+    )
+    .await?;
+
+    // Keep the DB in sync with the delta, the next warm start doesn't fetch it again
+    store_user_staking_accounts(Some(db), &delta.fetched);
+    for key in delta.closed.iter() {
+        forget_user_staking_account(Some(db), key);
+    }
+    Ok(checkpoint_slot)
+}
+
+// After a full scan: the stored UserStaking accounts are replaced by the scanned ones
+pub async fn store_scanned_user_staking_accounts(
+    db: &Db,
+    user_staking_accounts: &[(Pubkey, Vec<u8>)],
+) {
+    if !is_warm_start_enabled() {
+        return;
+    }
+    // Older than the scan
+    PENDING_USER_STAKING_WRITES.lock().unwrap().clear();
+    let scan_time = chrono::Utc::now().timestamp();
+    if let Err(e) = db
+        .upsert_user_staking_accounts(user_staking_accounts, scan_time)
+        .await
+    {
+        log::warn!(
+            "   <> Failed to store the UserStaking accounts - no stream checkpoint until the next full scan is stored: {:?}",
+            e
+        );
+        SCANNED_ACCOUNTS_STORE_FAILED.store(true, Ordering::Relaxed);
+        return;
+    }
+    if let Err(e) = db.prune_user_staking_accounts(scan_time).await {
+        log::warn!(
+            "   <> Failed to prune the stored UserStaking accounts - no stream checkpoint until the next full scan is stored: {:?}",
+            e
+        );
+        SCANNED_ACCOUNTS_STORE_FAILED.store(true, Ordering::Relaxed);
+        return;
+    }
+    SCANNED_ACCOUNTS_STORE_FAILED.store(false, Ordering::Relaxed);
+}
+
+// Queued, the stream updates don't wait for the DB (see flush_user_staking_accounts)
+// No DB (resolves role) means nothing to store
+pub fn store_user_staking_accounts(db: Option<&Db>, user_staking_accounts: &[(Pubkey, Vec<u8>)]) {
+    if db.is_none() || !is_warm_start_enabled() {
+        return;
+    }
+    let mut pending_writes = PENDING_USER_STAKING_WRITES.lock().unwrap();
+    for (key, data) in user_staking_accounts.iter() {
+        pending_writes.insert(*key, Some(data.clone()));
+    }
+}
+
+pub fn forget_user_staking_account(db: Option<&Db>, user_staking_account_key: &Pubkey) {
+    if db.is_none() || !is_warm_start_enabled() {
+        return;
+    }
+    PENDING_USER_STAKING_WRITES
+        .lock()
+        .unwrap()
+        .insert(*user_staking_account_key, None);
+}
+
+// Store the queued UserStaking accounts changes in one go - returns false if some are still pending
+// Not fatal, the keeper keeps running - the failed ones are retried on the next flush, unless changed again since
+pub async fn flush_user_staking_accounts(db: &Db) -> bool {
+    let pending_writes = std::mem::take(&mut *PENDING_USER_STAKING_WRITES.lock().unwrap());
+    if pending_writes.is_empty() {
+        return true;
+    }
+
+    let mut upserts: Vec<(Pubkey, Vec<u8>)> = vec![];
+    let mut failed: HashMap<Pubkey, Option<Vec<u8>>> = HashMap::new();
+    for (key, data) in pending_writes {
+        match data {
+            Some(data) => upserts.push((key, data)),
+            None => {
+                let result = db.delete_user_staking_account(&key).await;
+                record_db_outcome(result.is_ok());
+                if let Err(e) = result {
+                    log::warn!(
+                        "   <> Failed to delete the stored UserStaking account {}: {:?}",
+                        key,
+                        e
+                    );
+                    failed.insert(key, None);
+                }
+            }
+        }
+    }
+    if !upserts.is_empty() {
+        let result = db
+            .upsert_user_staking_accounts(&upserts, chrono::Utc::now().timestamp())
+            .await;
+        record_db_outcome(result.is_ok());
+        if let Err(e) = result {
+            log::warn!("   <> Failed to store the UserStaking accounts: {:?}", e);
+            failed.extend(upserts.into_iter().map(|(key, data)| (key, Some(data))));
+        }
+    }
+
+    if failed.is_empty() {
+        return true;
+    }
+    let mut pending_writes = PENDING_USER_STAKING_WRITES.lock().unwrap();
+    for (key, data) in failed {
+        pending_writes.entry(key).or_insert(data);
+    }
+    false
+}
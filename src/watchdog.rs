@@ -0,0 +1,43 @@
+use {
+    crate::health::{evaluate_health, HealthState},
+    sd_notify::NotifyState,
+    std::time::Duration,
+    tokio::time::interval,
+};
+
+// The indexes are loaded and the core loop is starting - no-op when not run by systemd
+pub fn notify_ready() {
+    if let Err(e) = sd_notify::notify(false, &[NotifyState::Ready]) {
+        log::warn!("  <> Failed to notify systemd: {:?}", e);
+    }
+}
+
+// Ping the systemd watchdog (WatchdogSec=) as long as the keeper is not unhealthy, so a wedged keeper gets restarted
+pub async fn run_systemd_watchdog() {
+    let mut watchdog_usec = 0;
+    if !sd_notify::watchdog_enabled(false, &mut watchdog_usec) {
+        return;
+    }
+    log::info!(
+        "  <> systemd watchdog enabled ({}s)",
+        watchdog_usec / 1_000_000
+    );
+
+    // Twice per watchdog period, as recommended by systemd
+    let mut ping_interval = interval(Duration::from_micros(watchdog_usec / 2));
+    loop {
+        ping_interval.tick().await;
+
+        let health = evaluate_health();
+        if health.state == HealthState::Unhealthy {
+            log::error!(
+                "  <> Keeper unhealthy ({}) - skipping the watchdog ping",
+                health.reasons.join(", ")
+            );
+            continue;
+        }
+        if let Err(e) = sd_notify::notify(false, &[NotifyState::Watchdog]) {
+            log::warn!("  <> Failed to ping the systemd watchdog: {:?}", e);
+        }
+    }
+}
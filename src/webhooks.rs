@@ -0,0 +1,154 @@
+use {
+    crate::tx_tracker::TransactionReceipt,
+    adrena_abi::StakingType,
+    serde::Serialize,
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+    std::{
+        sync::{LazyLock, OnceLock},
+        time::Duration,
+    },
+};
+
+// A hanging endpoint must not pile up the notification tasks (one per claim / round)
+const WEBHOOK_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+static CLAIM_WEBHOOK_URL: OnceLock<String> = OnceLock::new();
+static ROUND_WEBHOOK_URL: OnceLock<String> = OnceLock::new();
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .connect_timeout(WEBHOOK_CONNECT_TIMEOUT)
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .expect("Failed to build the webhooks HTTP client")
+});
+
+#[derive(Debug, Serialize)]
+struct ClaimNotification {
+    wallet: String,
+    user_staking_account: String,
+    signature: String,
+    claimed_amounts: Vec<ClaimedAmount>,
+}
+
+#[derive(Debug, Serialize)]
+struct RoundResolvedNotification {
+    staking_account: String,
+    // "lm" (ADX) or "lp" (ALP)
+    staking_side: &'static str,
+    // Index of the round in the resolved rounds of the Staking account
+    round_index: u64,
+    start_time: i64,
+    end_time: i64,
+    resolved_slot: u64,
+    // Transaction that resolved the round, unknown when the update came through the WebSocket fallback
+    signature: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClaimedAmount {
+    pub mint: String,
+    // Native units
+    pub amount: u64,
+}
+
+// Tokens received by the wallet in a landed claim transaction
+pub fn claimed_amounts(owner: &Pubkey, receipt: &TransactionReceipt) -> Vec<ClaimedAmount> {
+    let wallet = owner.to_string();
+    receipt
+        .token_balance_changes
+        .iter()
+        .filter(|change| change.owner == wallet && change.amount > 0)
+        .map(|change| ClaimedAmount {
+            mint: change.mint.clone(),
+            amount: change.amount as u64,
+        })
+        .collect()
+}
+
+pub fn init_claim_webhook(url: Option<String>) {
+    if let Some(url) = url {
+        log::info!("  <> Claim webhook enabled");
+        CLAIM_WEBHOOK_URL.set(url).ok();
+    }
+}
+
+pub fn init_round_webhook(url: Option<String>) {
+    if let Some(url) = url {
+        log::info!("  <> Round webhook enabled");
+        ROUND_WEBHOOK_URL.set(url).ok();
+    }
+}
+
+// POST the claimed amounts to the webhook once an auto-claim has landed (fire and forget)
+pub fn notify_claim(
+    owner: &Pubkey,
+    user_staking_account: &Pubkey,
+    signature: &Signature,
+    receipt: &TransactionReceipt,
+) {
+    let Some(url) = CLAIM_WEBHOOK_URL.get() else {
+        return;
+    };
+
+    let notification = ClaimNotification {
+        claimed_amounts: claimed_amounts(owner, receipt),
+        wallet: owner.to_string(),
+        user_staking_account: user_staking_account.to_string(),
+        signature: signature.to_string(),
+    };
+
+    let description = format!("Claim webhook for TX {}", notification.signature);
+    post_notification(url, notification, description);
+}
+
+// POST the resolved round once its Staking account update is received (fire and forget)
+#[allow(clippy::too_many_arguments)]
+pub fn notify_round_resolved(
+    staking_account: &Pubkey,
+    staking_type: StakingType,
+    round_index: u64,
+    start_time: i64,
+    end_time: i64,
+    resolved_slot: u64,
+    signature: Option<&Signature>,
+) {
+    let Some(url) = ROUND_WEBHOOK_URL.get() else {
+        return;
+    };
+
+    let notification = RoundResolvedNotification {
+        staking_account: staking_account.to_string(),
+        staking_side: match staking_type {
+            StakingType::LM => "lm",
+            StakingType::LP => "lp",
+        },
+        round_index,
+        start_time,
+        end_time,
+        resolved_slot,
+        signature: signature.map(Signature::to_string),
+    };
+
+    let description = format!(
+        "Round webhook for {} (round start {})",
+        notification.staking_account, notification.start_time
+    );
+    post_notification(url, notification, description);
+}
+
+fn post_notification<T: Serialize + Send + 'static>(
+    url: &'static str,
+    notification: T,
+    description: String,
+) {
+    tokio::spawn(async move {
+        match HTTP_CLIENT.post(url).json(&notification).send().await {
+            Ok(response) if !response.status().is_success() => {
+                log::warn!("   <> {} returned {}", description, response.status())
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("   <> {} failed: {:?}", description, e),
+        }
+    });
+}